@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use mugraph_core::{error::ErrorCode, types::Response};
+use onlyerror::Error;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum Error {
+    /// The node answered with `Response::Error`.
+    #[error("{code}: {reason}")]
+    Rejected {
+        code: ErrorCode,
+        reason: String,
+        retryable: bool,
+        details: BTreeMap<String, String>,
+    },
+
+    /// The request never reached the node, or its answer never came back.
+    #[error("Transport error: {reason}")]
+    Transport {
+        reason: String,
+        /// Nothing was sent, so the request is safe to repeat
+        connect: bool,
+        timeout: bool,
+    },
+
+    /// The node answered with something other than a `Response`.
+    #[error("HTTP {status}: {body}")]
+    Http { status: u16, body: String },
+
+    #[error("Unexpected response to {method}: {reason}")]
+    UnexpectedResponse {
+        method: &'static str,
+        reason: String,
+    },
+
+    /// A blind signature did not come with a valid proof that the node
+    /// signed it with its published key.
+    #[error("Invalid DLEQ proof on signature {index}: {reason}")]
+    InvalidProof { index: usize, reason: String },
+
+    #[error("Invalid node URL: {reason}")]
+    InvalidUrl { reason: String },
+}
+
+impl Error {
+    /// Code the node refused the request with, if it did.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Rejected { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Whether sending the same request again may succeed. Timeouts are
+    /// not retryable: the node may have applied the request.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Rejected { retryable, .. } => *retryable,
+            Self::Transport { connect, .. } => *connect,
+            _ => false,
+        }
+    }
+
+    /// Delay the node asked for before the next attempt.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Rejected { details, .. } => details
+                .get("retry_after_secs")
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn unexpected(
+        method: &'static str,
+        response: &Response,
+    ) -> Self {
+        Self::UnexpectedResponse {
+            method,
+            reason: format!("{response:?}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Transport {
+            reason: e.to_string(),
+            connect: e.is_connect(),
+            timeout: e.is_timeout(),
+        }
+    }
+}
+
+impl From<Response> for Error {
+    /// Convert a `Response::Error`; anything else is not an error and maps
+    /// to [`Error::UnexpectedResponse`].
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Error {
+                reason,
+                code,
+                retryable,
+                details,
+            } => Self::Rejected {
+                code,
+                reason,
+                retryable,
+                details,
+            },
+            other => Self::unexpected("rpc", &other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mugraph_core::error::Error as CoreError;
+
+    use super::*;
+
+    #[test]
+    fn rejections_keep_the_node_code_and_retry_hint() {
+        let error = Error::from(Response::from(CoreError::RateLimited {
+            scope: "method".to_string(),
+            retry_after_secs: 3,
+        }));
+
+        assert_eq!(error.code(), Some(ErrorCode::RateLimited));
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn timeouts_are_not_retried() {
+        let error = Error::Transport {
+            reason: "timed out".to_string(),
+            connect: false,
+            timeout: true,
+        };
+
+        assert_eq!(error.code(), None);
+        assert!(!error.is_retryable());
+    }
+}
@@ -0,0 +1,522 @@
+//! Typed async client for a Mugraph node.
+//!
+//! Each method sends one [`Request`] variant to `/rpc` and unwraps the
+//! matching [`Response`]. Blind signatures are checked against their DLEQ
+//! proofs before they are returned, and refusals come back as
+//! [`Error::Rejected`] carrying the node's [`ErrorCode`](mugraph_core::error::ErrorCode).
+//! Every request declares [`ProtocolVersion::CURRENT`], so a node that
+//! cannot serve it refuses with `UNSUPPORTED_VERSION` instead of
+//! misreading it.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mugraph_core::types::{
+    AssetName, BatchRequest, BlindSignature, DepositRequest, DepositResponse,
+    Note, PROTOCOL_VERSION_HEADER, PolicyId, ProtocolInfo, ProtocolVersion,
+    PublicKey, Refresh, Request, Response, SignedNodeInfo, TransferAckPayload,
+    TransferInitPayload, TransferNoticePayload, TransferStatusPayload,
+    TransferStatusQueryPayload, WithdrawRequest, WithdrawResponse,
+    WithdrawalStatus, WithdrawalStatusRequest, WithdrawalStatusResponse,
+    XNodeEnvelope,
+};
+pub use reqwest::Url;
+use tokio::sync::OnceCell;
+
+mod error;
+mod verify;
+
+pub use error::*;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often a failed request is sent again, and how long to wait between
+/// attempts. Only errors for which [`Error::is_retryable`] holds are
+/// retried; a `Retry-After` hint from the node overrides the backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts including the first one
+    pub max_attempts: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Send every request once.
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        base_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Node identity returned by `public_key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub delegate_pk: PublicKey,
+    pub cardano_script_address: Option<String>,
+    /// Versions and features the node serves
+    pub protocol: ProtocolInfo,
+}
+
+pub struct ClientBuilder {
+    base: Url,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+    delegate: Option<PublicKey>,
+}
+
+impl ClientBuilder {
+    /// Limit on a whole request, from connecting to reading the body.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Verify signatures against this key instead of the one the node
+    /// reports on first use.
+    pub fn delegate(mut self, delegate: PublicKey) -> Self {
+        self.delegate = Some(delegate);
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let join = |path: &str| {
+            self.base.join(path).map_err(|e| Error::InvalidUrl {
+                reason: e.to_string(),
+            })
+        };
+        let rpc_url = join("/rpc")?;
+        let health_url = join("/health")?;
+
+        let http = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .build()?;
+
+        Ok(Client {
+            http,
+            rpc_url,
+            health_url,
+            retry: self.retry,
+            delegate: Arc::new(OnceCell::new_with(self.delegate)),
+        })
+    }
+}
+
+/// Connection to one node; cheap to clone.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    rpc_url: Url,
+    health_url: Url,
+    retry: RetryPolicy,
+    delegate: Arc<OnceCell<PublicKey>>,
+}
+
+impl Client {
+    /// Client with the default timeouts and [`RetryPolicy`].
+    pub fn new(base: Url) -> Result<Self> {
+        Self::builder(base).build()
+    }
+
+    pub fn builder(base: Url) -> ClientBuilder {
+        ClientBuilder {
+            base,
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            delegate: None,
+        }
+    }
+
+    pub async fn health(&self) -> Result<()> {
+        let res = self.http.get(self.health_url.clone()).send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Error::Http {
+                status: status.as_u16(),
+                body: res.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn info(&self) -> Result<NodeInfo> {
+        match self.rpc(&Request::Info).await? {
+            Response::Info {
+                delegate_pk,
+                cardano_script_address,
+                protocol,
+            } => Ok(NodeInfo {
+                delegate_pk,
+                cardano_script_address,
+                protocol,
+            }),
+            other => Err(Error::unexpected("info", &other)),
+        }
+    }
+
+    /// The node's signed information document, checked against the
+    /// delegate key and its expiry. Keep the signature to re-check it
+    /// offline.
+    pub async fn node_info(&self) -> Result<SignedNodeInfo> {
+        let info = match self.rpc(&Request::NodeInfo).await? {
+            Response::NodeInfo(info) => info,
+            other => return Err(Error::unexpected("node_info", &other)),
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        info.verify(&self.delegate().await?, now).map_err(|e| {
+            Error::InvalidProof {
+                index: 0,
+                reason: e.to_string(),
+            }
+        })?;
+
+        Ok(*info)
+    }
+
+    /// Key the node signs with: the one given to the builder, or the one it
+    /// reported the first time it was asked.
+    pub async fn delegate(&self) -> Result<PublicKey> {
+        self.delegate
+            .get_or_try_init(|| async { Ok(self.info().await?.delegate_pk) })
+            .await
+            .copied()
+    }
+
+    /// Mint a note (dev mode only).
+    pub async fn emit(
+        &self,
+        policy_id: PolicyId,
+        asset_name: AssetName,
+        amount: u64,
+    ) -> Result<Note> {
+        let request = Request::Emit {
+            policy_id,
+            asset_name,
+            amount,
+        };
+        let response = self.rpc(&request).await?;
+        match self.verified(&request, response).await? {
+            Response::Emit(note) => Ok(*note),
+            other => Err(Error::unexpected("emit", &other)),
+        }
+    }
+
+    pub async fn refresh(
+        &self,
+        refresh: &Refresh,
+    ) -> Result<Vec<BlindSignature>> {
+        let request = Request::Refresh(refresh.clone());
+        let response = self.rpc(&request).await?;
+        match self.verified(&request, response).await? {
+            Response::Transaction { outputs } => Ok(outputs),
+            other => Err(Error::unexpected("refresh", &other)),
+        }
+    }
+
+    pub async fn deposit(
+        &self,
+        deposit: &DepositRequest,
+    ) -> Result<DepositResponse> {
+        let request = Request::Deposit(deposit.clone());
+        let response = self.rpc(&request).await?;
+        match self.verified(&request, response).await? {
+            Response::Deposit {
+                signatures,
+                deposit_ref,
+            } => Ok(DepositResponse {
+                signatures,
+                deposit_ref,
+            }),
+            other => Err(Error::unexpected("deposit", &other)),
+        }
+    }
+
+    pub async fn withdraw(
+        &self,
+        withdraw: &WithdrawRequest,
+    ) -> Result<WithdrawResponse> {
+        let request = Request::Withdraw(withdraw.clone());
+        let response = self.rpc(&request).await?;
+        match self.verified(&request, response).await? {
+            Response::Withdraw {
+                signed_tx_cbor,
+                tx_hash,
+                change_notes,
+            } => Ok(WithdrawResponse {
+                signed_tx_cbor,
+                tx_hash,
+                change_notes,
+            }),
+            other => Err(Error::unexpected("withdraw", &other)),
+        }
+    }
+
+    /// Where the withdrawal `tx_hash` stands. A pending or failed one is
+    /// still being settled by the node and may complete later.
+    pub async fn withdrawal_status(
+        &self,
+        tx_hash: &str,
+    ) -> Result<WithdrawalStatusResponse> {
+        let request = Request::WithdrawalStatus(WithdrawalStatusRequest {
+            tx_hash: tx_hash.to_string(),
+        });
+        match self.rpc(&request).await? {
+            Response::WithdrawalStatus {
+                tx_hash,
+                status,
+                updated_at,
+                resubmissions,
+                invalid_hereafter,
+                refund_notes,
+            } => Ok(WithdrawalStatusResponse {
+                tx_hash,
+                status,
+                updated_at,
+                resubmissions,
+                invalid_hereafter,
+                refund_notes,
+            }),
+            other => Err(Error::unexpected("withdrawal_status", &other)),
+        }
+    }
+
+    /// Refund notes for the withdrawal `tx_hash`, checked against the
+    /// `refund_outputs` its request carried; `None` until the node has
+    /// refunded it. Unblind them as change notes.
+    pub async fn withdrawal_refund(
+        &self,
+        tx_hash: &str,
+        refund_outputs: &[BlindSignature],
+    ) -> Result<Option<Vec<BlindSignature>>> {
+        let status = self.withdrawal_status(tx_hash).await?;
+        if status.status != WithdrawalStatus::Refunded {
+            return Ok(None);
+        }
+
+        verify::check_signatures(
+            "withdrawal_status",
+            &self.delegate().await?,
+            &verify::output_points(refund_outputs)?,
+            &status.refund_notes,
+        )?;
+        Ok(Some(status.refund_notes))
+    }
+
+    /// Open a cross-node transfer; returns whether the node accepted it.
+    pub async fn transfer_create(
+        &self,
+        envelope: &XNodeEnvelope<TransferInitPayload>,
+    ) -> Result<bool> {
+        let request = Request::CrossNodeTransferCreate(envelope.clone());
+        match self.rpc(&request).await? {
+            Response::CrossNodeTransferCreate { accepted, .. } => Ok(accepted),
+            other => {
+                Err(Error::unexpected("cross_node_transfer_create", &other))
+            }
+        }
+    }
+
+    pub async fn transfer_notify(
+        &self,
+        envelope: &XNodeEnvelope<TransferNoticePayload>,
+    ) -> Result<bool> {
+        let request = Request::CrossNodeTransferNotify(envelope.clone());
+        match self.rpc(&request).await? {
+            Response::CrossNodeTransferNotify { accepted } => Ok(accepted),
+            other => {
+                Err(Error::unexpected("cross_node_transfer_notify", &other))
+            }
+        }
+    }
+
+    pub async fn transfer_status(
+        &self,
+        envelope: &XNodeEnvelope<TransferStatusQueryPayload>,
+    ) -> Result<XNodeEnvelope<TransferStatusPayload>> {
+        let request = Request::CrossNodeTransferStatus(envelope.clone());
+        match self.rpc(&request).await? {
+            Response::CrossNodeTransferStatus(status) => Ok(*status),
+            other => {
+                Err(Error::unexpected("cross_node_transfer_status", &other))
+            }
+        }
+    }
+
+    pub async fn transfer_ack(
+        &self,
+        envelope: &XNodeEnvelope<TransferAckPayload>,
+    ) -> Result<bool> {
+        let request = Request::CrossNodeTransferAck(envelope.clone());
+        match self.rpc(&request).await? {
+            Response::CrossNodeTransferAck { accepted } => Ok(accepted),
+            other => Err(Error::unexpected("cross_node_transfer_ack", &other)),
+        }
+    }
+
+    /// Send `requests` in one round trip. The outer error covers the batch
+    /// as a whole; each request gets its own result, in order, with its
+    /// signatures verified like a single call's.
+    pub async fn batch(
+        &self,
+        requests: Vec<Request>,
+        atomic: bool,
+    ) -> Result<Vec<Result<Response>>> {
+        let request = Request::Batch(BatchRequest {
+            requests: requests.clone(),
+            atomic,
+        });
+        let responses = match self.rpc(&request).await? {
+            Response::Batch { responses } => responses,
+            other => return Err(Error::unexpected("batch", &other)),
+        };
+        if responses.len() != requests.len() {
+            return Err(Error::UnexpectedResponse {
+                method: "batch",
+                reason: format!(
+                    "expected {} responses, got {}",
+                    requests.len(),
+                    responses.len()
+                ),
+            });
+        }
+
+        let mut results = Vec::with_capacity(responses.len());
+        for (request, response) in requests.iter().zip(responses) {
+            let result = match response {
+                Response::Error { .. } => Err(Error::from(response)),
+                response => self.verified(request, response).await,
+            };
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    /// Send `request` as is, retrying per the [`RetryPolicy`]. A
+    /// `Response::Error` comes back as [`Error::Rejected`]; signatures are
+    /// not verified.
+    pub async fn rpc(&self, request: &Request) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            match self.send(request).await {
+                Err(e)
+                    if e.is_retryable()
+                        && attempt < self.retry.max_attempts =>
+                {
+                    let delay = e
+                        .retry_after()
+                        .unwrap_or_else(|| self.retry.backoff(attempt));
+                    tracing::debug!(attempt, ?delay, error = %e, "retrying request");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(&self, request: &Request) -> Result<Response> {
+        let res = self
+            .http
+            .post(self.rpc_url.clone())
+            .header(
+                PROTOCOL_VERSION_HEADER,
+                ProtocolVersion::CURRENT.to_string(),
+            )
+            .json(request)
+            .send()
+            .await?;
+        let status = res.status();
+        let body = res.bytes().await?;
+
+        // Failed operations still carry a `Response::Error` body
+        match serde_json::from_slice(&body) {
+            Ok(response @ Response::Error { .. }) => Err(response.into()),
+            Ok(response) if status.is_success() => Ok(response),
+            _ => Err(Error::Http {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            }),
+        }
+    }
+
+    /// Check the blind signatures in `response` against the points
+    /// `request` asked the node to sign.
+    async fn verified(
+        &self,
+        request: &Request,
+        response: Response,
+    ) -> Result<Response> {
+        let (method, points, signatures) = match (request, &response) {
+            (Request::Emit { .. }, Response::Emit(note)) => {
+                verify::check_note(&self.delegate().await?, note)?;
+                return Ok(response);
+            }
+            (Request::Refresh(refresh), Response::Transaction { outputs }) => {
+                ("refresh", verify::refresh_points(refresh)?, outputs)
+            }
+            (
+                Request::Deposit(deposit),
+                Response::Deposit { signatures, .. },
+            ) => (
+                "deposit",
+                verify::output_points(&deposit.outputs)?,
+                signatures,
+            ),
+            (
+                Request::Withdraw(withdraw),
+                Response::Withdraw { change_notes, .. },
+            ) => (
+                "withdraw",
+                verify::output_points(&withdraw.change_outputs)?,
+                change_notes,
+            ),
+            _ => return Ok(response),
+        };
+
+        verify::check_signatures(
+            method,
+            &self.delegate().await?,
+            &points,
+            signatures,
+        )?;
+        Ok(response)
+    }
+}
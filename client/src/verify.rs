@@ -0,0 +1,194 @@
+//! DLEQ checks on the blind signatures a node returns: each one must be
+//! the blinded point we sent, multiplied by the node's published key.
+
+use mugraph_core::{
+    crypto::{self, G, Point},
+    types::{BlindSignature, Note, PublicKey, Refresh},
+};
+
+use crate::error::{Error, Result};
+
+/// Points the node signs for the outputs of `refresh`, in output order.
+pub(crate) fn refresh_points(refresh: &Refresh) -> Result<Vec<Point>> {
+    let mut points = Vec::new();
+
+    for (i, atom) in refresh.atoms.iter().enumerate() {
+        if !refresh.is_output(i) {
+            continue;
+        }
+
+        let point = match refresh.blinded_points.get(points.len()) {
+            Some(point) => {
+                point.to_point().map_err(|e| Error::InvalidProof {
+                    index: points.len(),
+                    reason: e.to_string(),
+                })?
+            }
+            None => crypto::hash_to_curve(
+                atom.commitment(&refresh.asset_ids).as_ref(),
+            ),
+        };
+        points.push(point);
+    }
+
+    Ok(points)
+}
+
+/// Points carried in the `signature` field of blinded deposit or change
+/// outputs.
+pub(crate) fn output_points(outputs: &[BlindSignature]) -> Result<Vec<Point>> {
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| {
+            output
+                .signature
+                .0
+                .to_point()
+                .map_err(|e| Error::InvalidProof {
+                    index,
+                    reason: e.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// Check `signatures` against the points they answer, one for one.
+pub(crate) fn check_signatures(
+    method: &'static str,
+    delegate: &PublicKey,
+    points: &[Point],
+    signatures: &[BlindSignature],
+) -> Result<()> {
+    if points.len() != signatures.len() {
+        return Err(Error::UnexpectedResponse {
+            method,
+            reason: format!(
+                "expected {} signatures, got {}",
+                points.len(),
+                signatures.len()
+            ),
+        });
+    }
+
+    for (index, (point, signed)) in points.iter().zip(signatures).enumerate() {
+        let valid = crypto::verify_dleq_signature(
+            delegate,
+            point,
+            &signed.signature,
+            &signed.proof,
+        )
+        .map_err(|e| Error::InvalidProof {
+            index,
+            reason: e.to_string(),
+        })?;
+
+        if !valid {
+            return Err(Error::InvalidProof {
+                index,
+                reason: "proof does not match the delegate key".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Check an emitted note: its signature, and the proof over the blinded
+/// point it was issued from.
+pub(crate) fn check_note(delegate: &PublicKey, note: &Note) -> Result<()> {
+    let invalid = |reason: String| Error::InvalidProof { index: 0, reason };
+
+    let Some(dleq) = note.dleq else {
+        return Err(invalid("note carries no DLEQ proof".to_string()));
+    };
+
+    let commitment = note.commitment();
+    let r = dleq.blinding_factor.to_scalar();
+    let delegate_point =
+        delegate.to_point().map_err(|e| invalid(e.to_string()))?;
+    let signature = note
+        .signature
+        .to_point()
+        .map_err(|e| invalid(e.to_string()))?;
+
+    let blinded = crypto::hash_to_curve(commitment.as_ref()) + G * r;
+    let signed = signature + delegate_point * r;
+    let valid = crypto::verify_dleq(delegate, &blinded, &signed, &dleq.proof)
+        .map_err(|e| invalid(e.to_string()))?
+        && crypto::verify(delegate, commitment.as_ref(), note.signature)
+            .map_err(|e| invalid(e.to_string()))?;
+
+    if !valid {
+        return Err(invalid(
+            "note is not signed by the delegate key".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mugraph_core::types::{Hash, Keypair, Signature};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn signatures_from_another_key_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let node = Keypair::random(&mut rng);
+        let impostor = Keypair::random(&mut rng);
+
+        let point = crypto::hash_to_curve(b"output");
+        let honest = crypto::sign_blinded(&mut rng, &node.secret_key, &point);
+        let forged =
+            crypto::sign_blinded(&mut rng, &impostor.secret_key, &point);
+
+        check_signatures("refresh", &node.public_key, &[point], &[honest])
+            .unwrap();
+        assert!(matches!(
+            check_signatures("refresh", &node.public_key, &[point], &[forged]),
+            Err(Error::InvalidProof { index: 0, .. })
+        ));
+        assert!(matches!(
+            check_signatures("refresh", &node.public_key, &[point], &[]),
+            Err(Error::UnexpectedResponse { .. })
+        ));
+    }
+
+    #[test]
+    fn emitted_notes_must_carry_a_matching_proof() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let node = Keypair::random(&mut rng);
+        let mut note = Note {
+            delegate: node.public_key,
+            policy_id: Default::default(),
+            asset_name: Default::default(),
+            nonce: Hash::random(&mut rng),
+            amount: 5,
+            signature: Signature::default(),
+            dleq: None,
+        };
+        assert!(check_note(&node.public_key, &note).is_err());
+
+        let blind = crypto::blind_note(&mut rng, &note);
+        let signed =
+            crypto::sign_blinded(&mut rng, &node.secret_key, &blind.point);
+        note.signature = crypto::unblind_signature(
+            &signed.signature,
+            &blind.factor,
+            &node.public_key,
+        )
+        .unwrap();
+        note.dleq = Some(mugraph_core::types::DleqProofWithBlinding {
+            proof: signed.proof,
+            blinding_factor: blind.factor.into(),
+        });
+        check_note(&node.public_key, &note).unwrap();
+
+        note.amount += 1;
+        assert!(check_note(&node.public_key, &note).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::{future::Future, path::Path, sync::OnceLock, time::Duration};
+
+use mugraph_client::{Client, Error, RetryPolicy, Url};
+use mugraph_core::{
+    builder::RefreshBuilder,
+    crypto,
+    error::ErrorCode,
+    types::{
+        Feature, Keypair, Note, ProtocolVersion, Refresh, Request, Signature,
+    },
+};
+use mugraph_node::{config::Config, routes::router};
+use rand::{SeedableRng, rngs::StdRng};
+use tempfile::TempDir;
+
+fn env_lock() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+fn test_config() -> Config {
+    Config::test_server()
+}
+
+async fn with_db_path<T, Fut>(path: &Path, f: impl FnOnce() -> Fut) -> T
+where
+    Fut: Future<Output = T>,
+{
+    let _guard = env_lock().lock().await;
+    let previous = std::env::var_os("MUGRAPH_DB_PATH");
+    // SAFETY: tests in this file serialize environment mutation through a
+    // process-wide mutex and hold it across the async startup call.
+    unsafe {
+        std::env::set_var("MUGRAPH_DB_PATH", path);
+    }
+    let result = f().await;
+    match previous {
+        Some(value) => unsafe { std::env::set_var("MUGRAPH_DB_PATH", value) },
+        None => unsafe { std::env::remove_var("MUGRAPH_DB_PATH") },
+    }
+    result
+}
+
+/// Serve a dev-mode node on a loopback port; returns its base URL and key.
+async fn spawn_node(dir: &TempDir, config: Config) -> (Url, Keypair) {
+    let keypair = config.keypair().unwrap();
+    let app = with_db_path(&dir.path().join("client.redb"), || async {
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{addr}").parse().unwrap(), keypair)
+}
+
+/// Refresh `note` into two outputs, blinding both with fresh factors.
+fn split(note: &Note, rng: &mut StdRng) -> (Refresh, Vec<crypto::Scalar>) {
+    let mut refresh = RefreshBuilder::new()
+        .input(note.clone())
+        .output(note.policy_id, note.asset_name, note.amount / 2)
+        .output(
+            note.policy_id,
+            note.asset_name,
+            note.amount - note.amount / 2,
+        )
+        .build()
+        .unwrap();
+
+    let mut factors = Vec::new();
+    for (i, atom) in refresh.atoms.iter().enumerate() {
+        if refresh.is_output(i) {
+            let commitment = atom.commitment(&refresh.asset_ids);
+            let blinded = crypto::blind(rng, commitment.as_ref());
+            factors.push(blinded.factor);
+            refresh.blinded_points.push(Signature::from(blinded.point));
+        }
+    }
+
+    (refresh, factors)
+}
+
+fn spend(note: &Note) -> Request {
+    Request::Refresh(
+        RefreshBuilder::new()
+            .input(note.clone())
+            .output(note.policy_id, note.asset_name, note.amount)
+            .build()
+            .unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn info_reports_the_node_delegate() {
+    let dir = TempDir::new().unwrap();
+    let (url, keypair) = spawn_node(&dir, test_config()).await;
+    let client = Client::new(url).unwrap();
+
+    client.health().await.unwrap();
+    let info = client.info().await.unwrap();
+    assert_eq!(info.delegate_pk, keypair.public_key);
+    assert_eq!(info.cardano_script_address, None);
+    assert_eq!(info.protocol.version, ProtocolVersion::CURRENT);
+    assert!(info.protocol.supports(Feature::Batch));
+    assert_eq!(client.delegate().await.unwrap(), keypair.public_key);
+}
+
+#[tokio::test]
+async fn node_info_document_is_verified_against_the_delegate() {
+    let dir = TempDir::new().unwrap();
+    let (url, keypair) = spawn_node(&dir, test_config()).await;
+
+    let info = Client::new(url.clone()).unwrap().node_info().await.unwrap();
+    let document = info.document().unwrap();
+    assert_eq!(document.delegate_pk, keypair.public_key);
+    assert_eq!(document.node_id, "node://local");
+    assert!(document.expires_at > document.issued_at);
+
+    let impostor = Keypair::random(&mut StdRng::seed_from_u64(9));
+    let pinned = Client::builder(url)
+        .delegate(impostor.public_key)
+        .build()
+        .unwrap();
+    let err = pinned.node_info().await.unwrap_err();
+    assert!(matches!(err, Error::InvalidProof { .. }), "{err:?}");
+}
+
+#[tokio::test]
+async fn emitted_and_refreshed_signatures_are_verified() {
+    let dir = TempDir::new().unwrap();
+    let (url, keypair) = spawn_node(&dir, test_config()).await;
+    let client = Client::new(url).unwrap();
+    let mut rng = StdRng::seed_from_u64(1);
+
+    let note = client
+        .emit(Default::default(), Default::default(), 100)
+        .await
+        .unwrap();
+    assert_eq!(note.amount, 100);
+
+    let (refresh, factors) = split(&note, &mut rng);
+    let outputs = client.refresh(&refresh).await.unwrap();
+    assert_eq!(outputs.len(), 2);
+
+    let commitments = refresh
+        .atoms
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| refresh.is_output(*i))
+        .map(|(_, atom)| atom.commitment(&refresh.asset_ids));
+    for ((signed, factor), commitment) in
+        outputs.iter().zip(&factors).zip(commitments)
+    {
+        let signature = crypto::unblind_signature(
+            &signed.signature,
+            factor,
+            &keypair.public_key,
+        )
+        .unwrap();
+        assert!(
+            crypto::verify(&keypair.public_key, commitment.as_ref(), signature)
+                .unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn signatures_under_another_key_are_refused() {
+    let dir = TempDir::new().unwrap();
+    let (url, _) = spawn_node(&dir, test_config()).await;
+    let impostor = Keypair::random(&mut StdRng::seed_from_u64(9));
+    let client = Client::builder(url)
+        .delegate(impostor.public_key)
+        .build()
+        .unwrap();
+
+    let err = client
+        .emit(Default::default(), Default::default(), 5)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidProof { .. }), "{err:?}");
+}
+
+#[tokio::test]
+async fn node_refusals_are_structured_errors() {
+    let dir = TempDir::new().unwrap();
+    let (url, _) = spawn_node(&dir, test_config()).await;
+    let client = Client::new(url).unwrap();
+
+    let note = client
+        .emit(Default::default(), Default::default(), 10)
+        .await
+        .unwrap();
+    let Request::Refresh(refresh) = spend(&note) else {
+        unreachable!()
+    };
+    client.refresh(&refresh).await.unwrap();
+
+    let err = client.refresh(&refresh).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::AlreadySpent));
+    assert!(!err.is_retryable());
+    let Error::Rejected { details, .. } = err else {
+        panic!("expected a rejection, got {err:?}");
+    };
+    assert_eq!(details["signature"], note.signature.to_string());
+}
+
+#[tokio::test]
+async fn atomic_batches_report_each_request_in_order() {
+    let dir = TempDir::new().unwrap();
+    let (url, _) = spawn_node(&dir, test_config()).await;
+    let client = Client::new(url).unwrap();
+
+    let note = client
+        .emit(Default::default(), Default::default(), 10)
+        .await
+        .unwrap();
+
+    let results = client
+        .batch(vec![spend(&note), spend(&note)], true)
+        .await
+        .unwrap();
+    let codes: Vec<_> = results
+        .iter()
+        .map(|result| result.as_ref().err().and_then(Error::code))
+        .collect();
+    assert_eq!(
+        codes,
+        [Some(ErrorCode::BatchAborted), Some(ErrorCode::AlreadySpent)]
+    );
+
+    // The rolled-back spend left the note unspent
+    let results = client
+        .batch(vec![spend(&note), Request::Info], false)
+        .await
+        .unwrap();
+    assert!(results.iter().all(Result::is_ok), "{results:?}");
+}
+
+#[tokio::test]
+async fn rate_limited_requests_are_retried_after_the_node_hint() {
+    let dir = TempDir::new().unwrap();
+    let mut config = test_config();
+    if let Config::Server {
+        rpc_method_rate_limits,
+        ..
+    } = &mut config
+    {
+        *rpc_method_rate_limits = vec!["public_key=1".parse().unwrap()];
+    }
+    let (url, _) = spawn_node(&dir, config).await;
+
+    let once = Client::builder(url.clone())
+        .retry(RetryPolicy::NONE)
+        .build()
+        .unwrap();
+    // One token per second with a burst of two
+    let mut limited = None;
+    for _ in 0..3 {
+        if let Err(e) = once.info().await {
+            limited = Some(e);
+        }
+    }
+    let limited = limited.expect("third request must be limited");
+    assert_eq!(limited.code(), Some(ErrorCode::RateLimited));
+    assert!(limited.is_retryable());
+    assert!(limited.retry_after().is_some());
+
+    let patient = Client::new(url).unwrap();
+    patient.info().await.unwrap();
+}
+
+#[tokio::test]
+async fn refunds_of_unknown_withdrawals_are_not_found() {
+    let dir = TempDir::new().unwrap();
+    let (url, _) = spawn_node(&dir, test_config()).await;
+    let client = Client::new(url).unwrap();
+
+    let err = client
+        .withdrawal_refund(&"ab".repeat(32), &[])
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::WithdrawalNotFound));
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn unreachable_nodes_fail_with_a_retryable_transport_error() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url: Url = format!("http://{}", listener.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    drop(listener);
+
+    let client = Client::builder(url)
+        .retry(RetryPolicy {
+            max_attempts: 2,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+        })
+        .build()
+        .unwrap();
+
+    let err = client.info().await.unwrap_err();
+    assert!(
+        matches!(err, Error::Transport { connect: true, .. }),
+        "{err:?}"
+    );
+    assert!(err.is_retryable());
+}
@@ -8,6 +8,7 @@ use crate::{
 
 pub const HTC_SEP: &[u8] = b"mugraph_v0_htc";
 pub const DLEQ_SEP: &[u8] = b"mugraph_v0_dleq";
+pub const SIG_SEP: &[u8] = b"mugraph_v0_sig";
 
 pub type Point = curve25519_dalek::ristretto::RistrettoPoint;
 pub type Scalar = curve25519_dalek::scalar::Scalar;
@@ -139,6 +140,55 @@ fn dleq_challenge(
     )
 }
 
+/// Sign `message` with `secret_key`. Unlike [`sign_blinded`], the
+/// challenge covers the message itself, so only the key holder can produce
+/// it for a chosen message.
+pub fn sign_message<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    secret_key: &SecretKey,
+    message: &[u8],
+) -> SchnorrSignature {
+    let k = Hash::random(rng).to_scalar();
+    let r = G * k;
+
+    let challenge = message_challenge(&secret_key.public(), &r, message);
+    let response = k + challenge * secret_key.to_scalar();
+
+    SchnorrSignature {
+        challenge: challenge.into(),
+        response: response.into(),
+    }
+}
+
+pub fn verify_message(
+    public_key: &PublicKey,
+    message: &[u8],
+    signature: &SchnorrSignature,
+) -> Result<bool> {
+    let e = signature.challenge.to_scalar();
+    let z = signature.response.to_scalar();
+
+    let r = (G * z) - (public_key.to_point()? * e);
+
+    Ok(e == message_challenge(public_key, &r, message))
+}
+
+fn message_challenge(
+    public_key: &PublicKey,
+    r: &Point,
+    message: &[u8],
+) -> Scalar {
+    hash_to_scalar_with_domain(
+        SIG_SEP,
+        &[
+            G.compress().as_bytes(),
+            public_key.as_ref(),
+            r.compress().as_bytes(),
+            message,
+        ],
+    )
+}
+
 pub fn verify(
     public_key: &PublicKey,
     message: &[u8],
@@ -333,4 +383,22 @@ mod tests {
             &bad_proof
         )?);
     }
+
+    #[proptest(cases = 200)]
+    fn test_message_signature_binds_key_and_message(
+        #[strategy(rng())] mut rng: StdRng,
+        a: Keypair,
+        b: Keypair,
+        msg: Vec<u8>,
+        other: Vec<u8>,
+    ) {
+        let sig = sign_message(&mut rng, &a.secret_key, &msg);
+
+        prop_assert!(verify_message(&a.public_key, &msg, &sig)?);
+        prop_assert_eq!(verify_message(&b.public_key, &msg, &sig)?, a == b);
+        prop_assert_eq!(
+            verify_message(&a.public_key, &other, &sig)?,
+            msg == other
+        );
+    }
 }
@@ -1,6 +1,7 @@
-use std::{io::ErrorKind, sync::PoisonError};
+use std::{collections::BTreeMap, io::ErrorKind, sync::PoisonError};
 
 use onlyerror::Error;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
@@ -72,12 +73,36 @@ pub enum Error {
     #[error("Unbalanced transaction, expected {pre:?}, got {post:?}")]
     UnbalancedTransaction { pre: Vec<u128>, post: Vec<u128> },
 
+    /// A Plutus script in a Cardano transaction failed local evaluation or
+    /// needs more execution units than its redeemer declares.
+    #[error("Script evaluation failed: {reason}")]
+    ScriptEvaluationFailed {
+        input_index: Option<u32>,
+        reason: String,
+    },
+
     #[error("Invalid blinding factor")]
     InvalidBlindingFactor,
 
     #[error("Invalid Transaction: {reason}")]
     InvalidOperation { reason: String },
 
+    /// Events after the requested cursor have been pruned from the event
+    /// log; `oldest_cursor` is the oldest one still kept.
+    #[error("Event cursor expired, the oldest kept event is {oldest_cursor}")]
+    CursorExpired { oldest_cursor: u64 },
+
+    #[error("Rate limited ({scope}), retry after {retry_after_secs}s")]
+    RateLimited {
+        scope: String,
+        retry_after_secs: u64,
+    },
+
+    /// A request refused with a specific [`ErrorCode`], such as an xnode
+    /// envelope failing authentication.
+    #[error("{code}: {reason}")]
+    Rejected { code: ErrorCode, reason: String },
+
     #[error("Multiple errors happened at once: {errors:?}")]
     Multiple { errors: Vec<Error> },
 
@@ -85,6 +110,240 @@ pub enum Error {
     Other,
 }
 
+/// Stable, machine-readable classification of an [`Error`], carried in
+/// `Response::Error` so clients never have to parse `reason`.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Arbitrary,
+    JsonSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidInput,
+    InvalidSignature,
+    InvalidKey,
+    InvalidHash,
+    InvalidAtom,
+    InvalidBlindingFactor,
+    InvalidOperation,
+    MalformedJson,
+    UnbalancedTransaction,
+    InsufficientFunds,
+    ScriptEvaluationFailed,
+    AlreadySpent,
+    UnsupportedVersion,
+    UnsupportedMessageType,
+    SchemaValidationFailed,
+    UnknownKeyId,
+    AuthzDenied,
+    MethodDisabled,
+    ReplayDetected,
+    IdempotencyConflict,
+    TransferAlreadyExists,
+    TransferNotFound,
+    WithdrawalNotFound,
+    CursorExpired,
+    PayloadTooLarge,
+    RateLimited,
+    BatchAborted,
+    ProviderUnavailable,
+    StorageError,
+    #[default]
+    InternalError,
+}
+
+impl ErrorCode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidInput => "INVALID_INPUT",
+            Self::InvalidSignature => "INVALID_SIGNATURE",
+            Self::InvalidKey => "INVALID_KEY",
+            Self::InvalidHash => "INVALID_HASH",
+            Self::InvalidAtom => "INVALID_ATOM",
+            Self::InvalidBlindingFactor => "INVALID_BLINDING_FACTOR",
+            Self::InvalidOperation => "INVALID_OPERATION",
+            Self::MalformedJson => "MALFORMED_JSON",
+            Self::UnbalancedTransaction => "UNBALANCED_TRANSACTION",
+            Self::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            Self::ScriptEvaluationFailed => "SCRIPT_EVALUATION_FAILED",
+            Self::AlreadySpent => "ALREADY_SPENT",
+            Self::UnsupportedVersion => "UNSUPPORTED_VERSION",
+            Self::UnsupportedMessageType => "UNSUPPORTED_MESSAGE_TYPE",
+            Self::SchemaValidationFailed => "SCHEMA_VALIDATION_FAILED",
+            Self::UnknownKeyId => "UNKNOWN_KEY_ID",
+            Self::AuthzDenied => "AUTHZ_DENIED",
+            Self::MethodDisabled => "METHOD_DISABLED",
+            Self::ReplayDetected => "REPLAY_DETECTED",
+            Self::IdempotencyConflict => "IDEMPOTENCY_CONFLICT",
+            Self::TransferAlreadyExists => "TRANSFER_ALREADY_EXISTS",
+            Self::TransferNotFound => "TRANSFER_NOT_FOUND",
+            Self::WithdrawalNotFound => "WITHDRAWAL_NOT_FOUND",
+            Self::CursorExpired => "CURSOR_EXPIRED",
+            Self::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::BatchAborted => "BATCH_ABORTED",
+            Self::ProviderUnavailable => "PROVIDER_UNAVAILABLE",
+            Self::StorageError => "STORAGE_ERROR",
+            Self::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    /// HTTP status a node answers with when a request fails with this code.
+    pub const fn http_status(self) -> u16 {
+        match self {
+            Self::InvalidInput
+            | Self::InvalidSignature
+            | Self::InvalidKey
+            | Self::InvalidHash
+            | Self::InvalidAtom
+            | Self::InvalidBlindingFactor
+            | Self::InvalidOperation
+            | Self::MalformedJson
+            | Self::UnsupportedVersion
+            | Self::UnsupportedMessageType
+            | Self::SchemaValidationFailed => 400,
+            Self::UnknownKeyId => 401,
+            Self::AuthzDenied | Self::MethodDisabled => 403,
+            Self::TransferNotFound | Self::WithdrawalNotFound => 404,
+            Self::CursorExpired => 410,
+            Self::AlreadySpent
+            | Self::ReplayDetected
+            | Self::IdempotencyConflict
+            | Self::TransferAlreadyExists
+            | Self::BatchAborted => 409,
+            Self::PayloadTooLarge => 413,
+            Self::UnbalancedTransaction
+            | Self::InsufficientFunds
+            | Self::ScriptEvaluationFailed => 422,
+            Self::RateLimited => 429,
+            Self::InternalError => 500,
+            Self::ProviderUnavailable => 502,
+            Self::StorageError => 503,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub const fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited | Self::ProviderUnavailable | Self::StorageError
+        )
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::ServerError { .. }
+            | Self::Internal { .. }
+            | Self::SimulationError { .. }
+            | Self::Other => ErrorCode::InternalError,
+            Self::NetworkError { .. } => ErrorCode::ProviderUnavailable,
+            // Injected faults stand in for storage failures
+            Self::StorageError { .. } | Self::SimulatedError { .. } => {
+                ErrorCode::StorageError
+            }
+            Self::InvalidInput { .. } => ErrorCode::InvalidInput,
+            Self::UnsupportedVersion { .. } => ErrorCode::UnsupportedVersion,
+            Self::UnsupportedMessageType { .. } => {
+                ErrorCode::UnsupportedMessageType
+            }
+            Self::InsufficientFunds { .. } => ErrorCode::InsufficientFunds,
+            Self::AlreadySpent { .. } => ErrorCode::AlreadySpent,
+            Self::InvalidSignature { .. } => ErrorCode::InvalidSignature,
+            Self::InvalidKey { .. } => ErrorCode::InvalidKey,
+            Self::InvalidHash { .. } => ErrorCode::InvalidHash,
+            Self::InvalidAtom { .. } => ErrorCode::InvalidAtom,
+            Self::JsonError { .. } => ErrorCode::MalformedJson,
+            Self::UnbalancedTransaction { .. } => {
+                ErrorCode::UnbalancedTransaction
+            }
+            Self::ScriptEvaluationFailed { .. } => {
+                ErrorCode::ScriptEvaluationFailed
+            }
+            Self::InvalidBlindingFactor => ErrorCode::InvalidBlindingFactor,
+            Self::InvalidOperation { .. } => ErrorCode::InvalidOperation,
+            Self::CursorExpired { .. } => ErrorCode::CursorExpired,
+            Self::RateLimited { .. } => ErrorCode::RateLimited,
+            Self::Rejected { code, .. } => *code,
+            Self::Multiple { errors } => {
+                errors.first().map_or(ErrorCode::InternalError, Error::code)
+            }
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.code().is_retryable()
+    }
+
+    /// Structured fields a client may act on, e.g. the spent signature.
+    pub fn details(&self) -> BTreeMap<String, String> {
+        let fields: Vec<(&str, String)> = match self {
+            Self::StorageError { kind, .. } => vec![("kind", kind.clone())],
+            Self::UnsupportedVersion { version } => {
+                vec![("version", version.clone())]
+            }
+            Self::UnsupportedMessageType { message_type } => {
+                vec![("message_type", message_type.clone())]
+            }
+            Self::InsufficientFunds {
+                policy_id,
+                asset_name,
+                expected,
+                got,
+            } => vec![
+                ("policy_id", policy_id.to_string()),
+                ("asset_name", asset_name.to_string()),
+                ("expected", expected.to_string()),
+                ("got", got.to_string()),
+            ],
+            Self::AlreadySpent { signature }
+            | Self::InvalidSignature { signature, .. } => {
+                vec![("signature", signature.to_string())]
+            }
+            Self::ScriptEvaluationFailed {
+                input_index: Some(index),
+                ..
+            } => vec![("input_index", index.to_string())],
+            Self::CursorExpired { oldest_cursor } => {
+                vec![("oldest_cursor", oldest_cursor.to_string())]
+            }
+            Self::RateLimited {
+                scope,
+                retry_after_secs,
+            } => vec![
+                ("scope", scope.clone()),
+                ("retry_after_secs", retry_after_secs.to_string()),
+            ],
+            Self::Multiple { errors } => {
+                return errors.first().map(Error::details).unwrap_or_default();
+            }
+            _ => Vec::new(),
+        };
+
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         let reason = e.to_string();
@@ -192,7 +451,8 @@ impl From<crate::types::XNodeProtocolError> for Error {
                 }
             }
             crate::types::XNodeProtocolErrorCode::SchemaValidationFailed => {
-                Self::InvalidInput {
+                Self::Rejected {
+                    code: ErrorCode::SchemaValidationFailed,
                     reason: value.detail,
                 }
             }
@@ -5,6 +5,7 @@ use core::{
 
 use proptest::prelude::*;
 use rand::RngCore;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
@@ -29,6 +30,8 @@ pub const ASSET_ID_BYTES_SIZE: usize = POLICY_ID_SIZE + 4 + ASSET_NAME_MAX_SIZE;
 #[repr(transparent)]
 pub struct PolicyId(#[serde(with = "muhex::serde")] pub [u8; POLICY_ID_SIZE]);
 
+impl_hex_schema!(PolicyId, 56, "28-byte Cardano policy id, hex encoded");
+
 impl Arbitrary for PolicyId {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
@@ -167,6 +170,20 @@ impl AssetName {
     }
 }
 
+impl JsonSchema for AssetName {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "AssetName".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "maxLength": ASSET_NAME_MAX_SIZE,
+            "description": "UTF-8 Cardano asset name of at most 32 bytes",
+        })
+    }
+}
+
 impl Arbitrary for AssetName {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
@@ -225,6 +242,7 @@ impl core::fmt::Debug for AssetName {
     }
 }
 
+/// A Cardano asset: minting policy plus asset name.
 #[derive(
     Debug,
     Clone,
@@ -238,6 +256,7 @@ impl core::fmt::Debug for AssetName {
     test_strategy::Arbitrary,
     PartialOrd,
     Ord,
+    JsonSchema,
 )]
 pub struct Asset {
     pub policy_id: PolicyId,
@@ -1,5 +1,7 @@
 use redb::{Key, Value};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use test_strategy::Arbitrary;
 
 use crate::types::{TransferChainState, TransferCreditState};
 
@@ -40,14 +42,21 @@ pub struct DepositRecord {
 }
 
 /// Withdrawal status for tracking state machine
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Arbitrary, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum WithdrawalStatus {
     /// Withdrawal is pending (notes burned, not yet submitted)
     Pending,
-    /// Withdrawal completed successfully
+    /// Withdrawal completed successfully: submitted, or seen on chain
     Completed,
-    /// Withdrawal failed (submission failed after notes burned)
+    /// Withdrawal failed (submission failed after notes burned); retried
+    /// until the chain shows it landed or can no longer land
     Failed,
+    /// Withdrawal can no longer land and the node signed the refund
+    /// outputs the request carried
+    Refunded,
 }
 
 /// Withdrawal record for idempotent signing
@@ -338,6 +347,10 @@ impl WithdrawalRecord {
     pub fn failed() -> Self {
         Self::new(WithdrawalStatus::Failed)
     }
+
+    pub fn refunded() -> Self {
+        Self::new(WithdrawalStatus::Refunded)
+    }
 }
 
 /// UTxO identifier (tx_hash + index)
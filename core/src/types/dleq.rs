@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
@@ -18,6 +19,7 @@ use crate::types::{Blinded, Hash, Signature};
     PartialOrd,
     Ord,
     ::core::hash::Hash,
+    JsonSchema,
 )]
 pub struct DleqProof {
     #[serde(rename = "e")]
@@ -39,6 +41,7 @@ pub struct DleqProof {
     PartialOrd,
     Ord,
     ::core::hash::Hash,
+    JsonSchema,
 )]
 pub struct DleqProofWithBlinding {
     #[serde(flatten)]
@@ -47,6 +50,7 @@ pub struct DleqProofWithBlinding {
     pub blinding_factor: Hash,
 }
 
+/// The delegate's signature on a blinded output, with proof it used its key.
 #[derive(
     Debug,
     Default,
@@ -60,10 +64,36 @@ pub struct DleqProofWithBlinding {
     PartialOrd,
     Ord,
     ::core::hash::Hash,
+    JsonSchema,
 )]
 pub struct BlindSignature {
+    /// Unblind with the factor used for the request to get the note signature
     #[serde(rename = "c")]
     pub signature: Blinded<Signature>,
     #[serde(rename = "p")]
     pub proof: DleqProof,
 }
+
+/// Schnorr signature by a node key over a message, domain-separated from
+/// DLEQ proofs so a blind signing request cannot be replayed as one.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Arbitrary,
+    PartialOrd,
+    Ord,
+    ::core::hash::Hash,
+    JsonSchema,
+)]
+pub struct SchnorrSignature {
+    #[serde(rename = "e")]
+    pub challenge: Hash,
+    #[serde(rename = "z")]
+    pub response: Hash,
+}
@@ -0,0 +1,138 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use test_strategy::Arbitrary;
+
+use crate::types::TransferStatusPayload;
+
+/// What an event is about; subscribers filter on it.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventSubject {
+    /// Deposit UTxO at the script address
+    Deposit {
+        /// Transaction hash (lowercase hex)
+        tx_hash: String,
+        index: u16,
+    },
+    /// Withdrawal transaction
+    Withdrawal {
+        /// Transaction hash (lowercase hex)
+        tx_hash: String,
+    },
+    /// Cross-node transfer
+    Transfer { transfer_id: String },
+}
+
+/// State change carried by a [`NodeEvent`].
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// Deposit accepted and notes issued
+    DepositRecorded { block_height: u64 },
+    /// Deposit gained confirmations but is not yet final
+    DepositConfirming { confirmations: u64, required: u64 },
+    /// Deposit reached the configured confirmation depth
+    DepositConfirmed { confirmations: u64 },
+    /// Deposit passed its expiry before being confirmed
+    DepositExpired,
+    /// Deposit left the script address or fell below the minimum value
+    DepositInvalidated,
+    /// Deposit consumed by a withdrawal
+    DepositSpent { withdrawal_tx_hash: String },
+    /// Notes burned; the transaction is about to be submitted
+    WithdrawalPending,
+    /// Transaction accepted by the chain provider
+    WithdrawalSubmitted,
+    /// Submission failed; the withdrawal can be retried
+    WithdrawalFailed,
+    /// Transaction can no longer land; refund notes were signed instead
+    WithdrawalRefunded,
+    /// Cross-node transfer moved to a new status
+    TransferStatus(TransferStatusPayload),
+}
+
+/// Entry in the node's persisted event log.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct NodeEvent {
+    /// Position in the log; resume a subscription after this event by
+    /// passing it back as the cursor
+    pub cursor: u64,
+    /// Unix timestamp when the event was recorded
+    pub created_at: u64,
+    pub subject: EventSubject,
+    pub event: EventKind,
+}
+
+/// One page of the event log.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct EventPage {
+    /// Matching events, oldest first
+    pub events: Vec<NodeEvent>,
+    /// Cursor to resume from; it also advances past events that did not
+    /// match the filter
+    pub next: u64,
+}
+
+impl EventSubject {
+    pub fn deposit(tx_hash: &[u8; 32], index: u16) -> Self {
+        Self::Deposit {
+            tx_hash: muhex::encode(tx_hash),
+            index,
+        }
+    }
+
+    pub fn withdrawal(tx_hash: &str) -> Self {
+        Self::Withdrawal {
+            tx_hash: tx_hash.to_ascii_lowercase(),
+        }
+    }
+
+    pub fn transfer(transfer_id: &str) -> Self {
+        Self::Transfer {
+            transfer_id: transfer_id.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prop_assert_eq;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn prop_node_event_serde_roundtrip(event: NodeEvent) {
+        let json = serde_json::to_string(&event).unwrap();
+        let decoded: NodeEvent = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(decoded, event);
+    }
+
+    #[test]
+    fn events_are_tagged_by_kind_and_type() {
+        let event = NodeEvent {
+            cursor: 7,
+            created_at: 1,
+            subject: EventSubject::withdrawal("ABCD"),
+            event: EventKind::WithdrawalSubmitted,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "cursor": 7,
+                "created_at": 1,
+                "subject": { "kind": "withdrawal", "tx_hash": "abcd" },
+                "event": { "type": "withdrawal_submitted" },
+            })
+        );
+    }
+}
@@ -26,6 +26,8 @@ use crate::crypto::Scalar;
 #[repr(transparent)]
 pub struct Hash(#[serde(with = "muhex::serde")] pub [u8; 32]);
 
+impl_hex_schema!(Hash, 64, "32-byte hash, hex encoded");
+
 impl Arbitrary for Hash {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
@@ -1,9 +1,34 @@
+/// JSON schema for a fixed-size byte array serialised as a hex string of
+/// `$hex_len` characters.
+macro_rules! impl_hex_schema {
+    ($ty:ident, $hex_len:literal, $description:literal) => {
+        impl schemars::JsonSchema for $ty {
+            fn schema_name() -> std::borrow::Cow<'static, str> {
+                stringify!($ty).into()
+            }
+
+            fn json_schema(
+                _: &mut schemars::SchemaGenerator,
+            ) -> schemars::Schema {
+                schemars::json_schema!({
+                    "type": "string",
+                    "pattern": concat!("^[0-9a-fA-F]{", $hex_len, "}$"),
+                    "description": $description,
+                })
+            }
+        }
+    };
+}
+
 mod asset;
 mod cardano;
 mod dleq;
+mod event;
 mod hash;
 mod keypair;
+mod node_info;
 mod note;
+mod protocol;
 mod public_key;
 mod refresh;
 mod request;
@@ -13,6 +38,7 @@ mod signature;
 mod xnode;
 
 pub use self::{
-    asset::*, cardano::*, dleq::*, hash::*, keypair::*, note::*, public_key::*,
-    refresh::*, request::*, response::*, secret_key::*, signature::*, xnode::*,
+    asset::*, cardano::*, dleq::*, event::*, hash::*, keypair::*, node_info::*,
+    note::*, protocol::*, public_key::*, refresh::*, request::*, response::*,
+    secret_key::*, signature::*, xnode::*,
 };
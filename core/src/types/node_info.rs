@@ -0,0 +1,307 @@
+//! Node information document: everything a wallet needs to decide whether
+//! and how to use a node, signed with its delegate key so it can be pinned
+//! and checked offline.
+
+use std::collections::BTreeMap;
+
+use rand::prelude::{CryptoRng, RngCore};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use test_strategy::Arbitrary;
+
+use crate::{
+    crypto,
+    error::{Error, ErrorCode, Result},
+    types::{Keypair, ProtocolInfo, PublicKey, SchnorrSignature},
+};
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct NodeInfoDocument {
+    /// Cross-node identity, e.g. `node://operator`
+    pub node_id: String,
+    /// Key notes are currently signed with
+    pub delegate_pk: PublicKey,
+    pub keysets: Vec<Keyset>,
+    pub protocol: ProtocolInfo,
+    pub cardano: CardanoInfo,
+    pub deposits: DepositTerms,
+    pub withdrawals: WithdrawalTerms,
+    pub limits: RpcLimits,
+    /// Trusted, unrevoked cross-node peers
+    pub peers: Vec<PeerInfo>,
+    /// How to reach the operator, e.g. `mailto:` or `https:` URIs
+    pub contact: Vec<String>,
+    /// Unix seconds the document was signed at
+    pub issued_at: u64,
+    /// Unix seconds after which the document must be fetched again
+    pub expires_at: u64,
+}
+
+/// A key the node signs notes with.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct Keyset {
+    pub public_key: PublicKey,
+    /// Signs new notes; inactive keysets are only honoured for spending
+    pub active: bool,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct CardanoInfo {
+    /// `mainnet`, `preprod` or `preview`
+    pub network: String,
+    /// Deposit script address, once the node has set up its wallet
+    pub script_address: Option<String>,
+    /// Deposit validator hash (hex)
+    pub script_hash: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct DepositTerms {
+    /// Smallest deposit accepted, in lovelace
+    pub min_value: u64,
+    /// Blocks a deposit must be buried under before it can be claimed
+    pub confirm_depth: u64,
+    /// Blocks after which an unclaimed deposit may be reclaimed
+    pub expiration_blocks: u64,
+    /// Native assets are accepted alongside lovelace
+    pub native_assets: bool,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct WithdrawalTerms {
+    /// Largest transaction fee the node will sign, in lovelace
+    pub max_fee: u64,
+    /// Allowed difference from the fee the node computes, in percent
+    pub fee_tolerance_pct: u8,
+    /// Largest withdrawal transaction, in bytes
+    pub max_tx_size: u64,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct RpcLimits {
+    /// Largest request body, in bytes
+    pub max_body_bytes: u64,
+    /// Sustained requests per second per client IP (0 means unlimited)
+    pub rate_limit: u32,
+    /// Per-method requests per second per client IP
+    pub method_rate_limits: BTreeMap<String, u32>,
+    /// Requests one `batch` may carry (0 means batches are disabled)
+    pub max_batch_size: u64,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct PeerInfo {
+    pub node_id: String,
+    pub endpoint: String,
+}
+
+/// A [`NodeInfoDocument`] with the delegate's signature over its JSON
+/// encoding. The document travels as the exact text that was signed, so
+/// checking it never depends on how a JSON library re-encodes it.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct SignedNodeInfo {
+    /// The signed [`NodeInfoDocument`], as JSON text
+    pub document: String,
+    pub signature: SchnorrSignature,
+}
+
+impl SignedNodeInfo {
+    /// Sign `document` with `keypair`, which must be its delegate key.
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        keypair: &Keypair,
+        document: NodeInfoDocument,
+    ) -> Result<Self> {
+        if document.delegate_pk != keypair.public_key {
+            return Err(Error::InvalidKey {
+                reason: "node info must be signed by its delegate key"
+                    .to_string(),
+            });
+        }
+
+        let document = serde_json::to_string(&document)?;
+        let signature =
+            crypto::sign_message(rng, &keypair.secret_key, document.as_bytes());
+
+        Ok(Self {
+            document,
+            signature,
+        })
+    }
+
+    /// The document, without checking its signature.
+    pub fn document(&self) -> Result<NodeInfoDocument> {
+        Ok(serde_json::from_str(&self.document)?)
+    }
+
+    /// Check the document was signed by `delegate` and is still valid at
+    /// `now` (Unix seconds), and return it.
+    pub fn verify(
+        &self,
+        delegate: &PublicKey,
+        now: u64,
+    ) -> Result<NodeInfoDocument> {
+        let document = self.document()?;
+        if document.delegate_pk != *delegate {
+            return Err(Error::InvalidKey {
+                reason: format!(
+                    "node info names delegate {}, expected {delegate}",
+                    document.delegate_pk
+                ),
+            });
+        }
+
+        let message = self.document.as_bytes();
+        if !crypto::verify_message(delegate, message, &self.signature)? {
+            return Err(Error::Rejected {
+                code: ErrorCode::InvalidSignature,
+                reason: "node info signature does not match its delegate"
+                    .to_string(),
+            });
+        }
+
+        if now >= document.expires_at {
+            return Err(Error::InvalidInput {
+                reason: format!("node info expired at {}", document.expires_at),
+            });
+        }
+
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    fn document(delegate_pk: PublicKey) -> NodeInfoDocument {
+        NodeInfoDocument {
+            node_id: "node://a".to_string(),
+            delegate_pk,
+            keysets: vec![Keyset {
+                public_key: delegate_pk,
+                active: true,
+            }],
+            protocol: ProtocolInfo::current(),
+            cardano: CardanoInfo {
+                network: "preprod".to_string(),
+                script_address: None,
+                script_hash: None,
+            },
+            deposits: DepositTerms {
+                min_value: 1_000_000,
+                confirm_depth: 15,
+                expiration_blocks: 1440,
+                native_assets: true,
+            },
+            withdrawals: WithdrawalTerms {
+                max_fee: 2_000_000,
+                fee_tolerance_pct: 5,
+                max_tx_size: 16_384,
+            },
+            limits: RpcLimits {
+                max_body_bytes: 1024,
+                rate_limit: 50,
+                method_rate_limits: BTreeMap::new(),
+                max_batch_size: 100,
+            },
+            peers: Vec::new(),
+            contact: vec!["mailto:ops@example.com".to_string()],
+            issued_at: 100,
+            expires_at: 200,
+        }
+    }
+
+    #[test]
+    fn signed_documents_verify_until_they_expire() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let node = Keypair::random(&mut rng);
+        let signed =
+            SignedNodeInfo::sign(&mut rng, &node, document(node.public_key))
+                .unwrap();
+
+        assert_eq!(
+            signed.verify(&node.public_key, 150).unwrap(),
+            document(node.public_key)
+        );
+        assert!(matches!(
+            signed.verify(&node.public_key, 200),
+            Err(Error::InvalidInput { .. })
+        ));
+
+        // The signature survives a JSON round trip
+        let decoded: SignedNodeInfo =
+            serde_json::from_str(&serde_json::to_string(&signed).unwrap())
+                .unwrap();
+        decoded.verify(&node.public_key, 150).unwrap();
+    }
+
+    #[test]
+    fn signatures_cover_the_document_text() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let node = Keypair::random(&mut rng);
+        let mut signed =
+            SignedNodeInfo::sign(&mut rng, &node, document(node.public_key))
+                .unwrap();
+
+        // The same document, encoded differently, is not what was signed
+        let value: serde_json::Value =
+            serde_json::from_str(&signed.document).unwrap();
+        signed.document = serde_json::to_string_pretty(&value).unwrap();
+        assert_eq!(signed.document().unwrap(), document(node.public_key));
+        assert_eq!(
+            signed.verify(&node.public_key, 150).unwrap_err().code(),
+            ErrorCode::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn tampered_or_foreign_documents_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let node = Keypair::random(&mut rng);
+        let impostor = Keypair::random(&mut rng);
+
+        let mut signed =
+            SignedNodeInfo::sign(&mut rng, &node, document(node.public_key))
+                .unwrap();
+        assert_eq!(
+            signed.verify(&impostor.public_key, 150).unwrap_err().code(),
+            ErrorCode::InvalidKey
+        );
+
+        let mut tampered = document(node.public_key);
+        tampered.withdrawals.max_fee += 1;
+        signed.document = serde_json::to_string(&tampered).unwrap();
+        assert_eq!(
+            signed.verify(&node.public_key, 150).unwrap_err().code(),
+            ErrorCode::InvalidSignature
+        );
+
+        assert!(
+            SignedNodeInfo::sign(
+                &mut rng,
+                &impostor,
+                document(node.public_key)
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,9 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::types::*;
 
 pub const COMMITMENT_INPUT_SIZE: usize = 136;
 
+/// A bearer note: value signed by a delegate that only its holder can spend.
 #[derive(
     Debug,
     Default,
@@ -14,6 +16,7 @@ pub const COMMITMENT_INPUT_SIZE: usize = 136;
     Deserialize,
     Hash,
     test_strategy::Arbitrary,
+    JsonSchema,
 )]
 pub struct Note {
     pub amount: u64,
@@ -22,6 +25,7 @@ pub struct Note {
     pub asset_name: AssetName,
     pub nonce: Hash,
     pub signature: Signature,
+    /// Present on notes emitted by the delegate, absent after a refresh
     #[serde(default)]
     pub dleq: Option<DleqProofWithBlinding>,
 }
@@ -0,0 +1,374 @@
+//! Client protocol versions and the features each one introduced.
+//!
+//! Clients declare the version they speak in the
+//! [`PROTOCOL_VERSION_HEADER`] of every request. The node serves any minor
+//! version of its own major from [`ProtocolVersion::MIN_SUPPORTED`] up,
+//! answers newer minors at [`ProtocolVersion::CURRENT`], and refuses a
+//! request that uses a feature newer than the version it declares, rather
+//! than silently dropping fields the client did not know it was sending.
+
+use std::{fmt, str::FromStr};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use test_strategy::Arbitrary;
+
+use crate::{
+    error::{Error, ErrorCode},
+    types::Request,
+};
+
+/// Header carrying the client's protocol version on requests, and the
+/// version the node answered in on responses.
+pub const PROTOCOL_VERSION_HEADER: &str = "mugraph-protocol-version";
+
+/// `major.minor`; minors within a major only ever add features.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Arbitrary,
+)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    /// Requests as they were before versioning
+    pub const V1_0: Self = Self::new(1, 0);
+    /// Client-blinded refresh outputs, batches, error codes and events
+    pub const V1_1: Self = Self::new(1, 1);
+    /// Signed node information documents
+    pub const V1_2: Self = Self::new(1, 2);
+    /// Withdrawal status queries
+    pub const V1_3: Self = Self::new(1, 3);
+    /// Refund outputs on withdrawals
+    pub const V1_4: Self = Self::new(1, 4);
+
+    /// Version this build speaks.
+    pub const CURRENT: Self = Self::V1_4;
+    /// Oldest version this build still serves.
+    pub const MIN_SUPPORTED: Self = Self::V1_0;
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// Version to answer a request in, given the one its client declared.
+    ///
+    /// Requests without a version predate negotiation and are served at
+    /// [`Self::CURRENT`], as they always were. A declared version from
+    /// another major, or older than [`Self::MIN_SUPPORTED`], is refused; a
+    /// newer minor is answered at [`Self::CURRENT`].
+    pub fn negotiate(
+        declared: Option<Self>,
+        request: &Request,
+    ) -> Result<Self, Error> {
+        let Some(declared) = declared else {
+            return Ok(Self::CURRENT);
+        };
+
+        if declared.major != Self::CURRENT.major
+            || declared < Self::MIN_SUPPORTED
+        {
+            return Err(Error::UnsupportedVersion {
+                version: declared.to_string(),
+            });
+        }
+
+        if let Some(feature) = request.newest_feature()
+            && feature.since() > declared
+        {
+            return Err(Error::Rejected {
+                code: ErrorCode::UnsupportedVersion,
+                reason: format!(
+                    "{feature} requires protocol {}, request declared {declared}",
+                    feature.since()
+                ),
+            });
+        }
+
+        Ok(declared.min(Self::CURRENT))
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::UnsupportedVersion {
+            version: value.to_string(),
+        };
+
+        let (major, minor) = value.split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Serialize for ProtocolVersion {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProtocolVersion {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for ProtocolVersion {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "ProtocolVersion".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "pattern": "^[0-9]+\\.[0-9]+$",
+            "description": "Protocol version as `major.minor`",
+        })
+    }
+}
+
+/// Optional capability a node advertises in `Info`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Arbitrary,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Refresh outputs carry points the client blinded (`b`)
+    BlindedPoints,
+    /// `batch` requests
+    Batch,
+    /// Error responses carry a stable `code`
+    ErrorCodes,
+    /// `/v1/events` and its stream
+    Events,
+    /// `node_info` and `/.well-known/mugraph-node.json`
+    NodeInfo,
+    /// `withdrawal_status` and `GET /v1/withdrawals/{tx_hash}`
+    WithdrawalStatus,
+    /// Withdrawals carry `refund_outputs` signed if they cannot land
+    WithdrawalRefunds,
+}
+
+impl Feature {
+    pub const ALL: &[Self] = &[
+        Self::BlindedPoints,
+        Self::Batch,
+        Self::ErrorCodes,
+        Self::Events,
+        Self::NodeInfo,
+        Self::WithdrawalStatus,
+        Self::WithdrawalRefunds,
+    ];
+
+    /// First protocol version with this feature.
+    pub fn since(self) -> ProtocolVersion {
+        match self {
+            Self::BlindedPoints
+            | Self::Batch
+            | Self::ErrorCodes
+            | Self::Events => ProtocolVersion::V1_1,
+            Self::NodeInfo => ProtocolVersion::V1_2,
+            Self::WithdrawalStatus => ProtocolVersion::V1_3,
+            Self::WithdrawalRefunds => ProtocolVersion::V1_4,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::BlindedPoints => "blinded_points",
+            Self::Batch => "batch",
+            Self::ErrorCodes => "error_codes",
+            Self::Events => "events",
+            Self::NodeInfo => "node_info",
+            Self::WithdrawalStatus => "withdrawal_status",
+            Self::WithdrawalRefunds => "withdrawal_refunds",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Versions and features a node supports, reported by `Info`.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Arbitrary, JsonSchema,
+)]
+pub struct ProtocolInfo {
+    /// Newest version the node speaks
+    pub version: ProtocolVersion,
+    /// Oldest version the node still serves
+    pub min_version: ProtocolVersion,
+    pub features: Vec<Feature>,
+}
+
+impl ProtocolInfo {
+    /// What this build supports, with every feature enabled.
+    pub fn current() -> Self {
+        Self {
+            version: ProtocolVersion::CURRENT,
+            min_version: ProtocolVersion::MIN_SUPPORTED,
+            features: Feature::ALL.to_vec(),
+        }
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+impl Default for ProtocolInfo {
+    /// A node that predates negotiation: 1.0 and nothing else.
+    fn default() -> Self {
+        Self {
+            version: ProtocolVersion::V1_0,
+            min_version: ProtocolVersion::V1_0,
+            features: Vec::new(),
+        }
+    }
+}
+
+impl Request {
+    /// Newest feature this request relies on, if any.
+    pub fn newest_feature(&self) -> Option<Feature> {
+        match self {
+            Self::NodeInfo => Some(Feature::NodeInfo),
+            Self::WithdrawalStatus(_) => Some(Feature::WithdrawalStatus),
+            Self::Batch(_) => Some(Feature::Batch),
+            Self::Refresh(refresh) if !refresh.blinded_points.is_empty() => {
+                Some(Feature::BlindedPoints)
+            }
+            Self::Withdraw(withdraw) if !withdraw.refund_outputs.is_empty() => {
+                Some(Feature::WithdrawalRefunds)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        BatchRequest, BlindSignature, Refresh, Signature, WithdrawRequest,
+    };
+
+    fn blinded_refresh() -> Request {
+        Request::Refresh(Refresh {
+            blinded_points: vec![Signature::default()],
+            ..Refresh::default()
+        })
+    }
+
+    #[test]
+    fn versions_round_trip_as_strings() {
+        let version: ProtocolVersion = "1.1".parse().unwrap();
+        assert_eq!(version, ProtocolVersion::V1_1);
+        assert_eq!(serde_json::to_string(&version).unwrap(), "\"1.1\"");
+        assert!("1".parse::<ProtocolVersion>().is_err());
+        assert!("one.zero".parse::<ProtocolVersion>().is_err());
+    }
+
+    #[test]
+    fn undeclared_versions_are_served_as_before() {
+        assert_eq!(
+            ProtocolVersion::negotiate(None, &blinded_refresh()).unwrap(),
+            ProtocolVersion::CURRENT
+        );
+    }
+
+    #[test]
+    fn other_majors_are_rejected() {
+        let err = ProtocolVersion::negotiate(
+            Some(ProtocolVersion::new(2, 0)),
+            &Request::Info,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnsupportedVersion);
+        assert_eq!(err.details()["version"], "2.0");
+    }
+
+    #[test]
+    fn newer_minors_are_answered_at_the_current_version() {
+        assert_eq!(
+            ProtocolVersion::negotiate(
+                Some(ProtocolVersion::new(1, 7)),
+                &Request::Info
+            )
+            .unwrap(),
+            ProtocolVersion::CURRENT
+        );
+    }
+
+    #[test]
+    fn features_newer_than_the_declared_version_are_rejected() {
+        let old = Some(ProtocolVersion::V1_0);
+
+        ProtocolVersion::negotiate(old, &Request::Refresh(Refresh::default()))
+            .unwrap();
+        for request in [
+            blinded_refresh(),
+            Request::Batch(BatchRequest {
+                requests: Vec::new(),
+                atomic: false,
+            }),
+        ] {
+            let err = ProtocolVersion::negotiate(old, &request).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::UnsupportedVersion);
+        }
+
+        ProtocolVersion::negotiate(
+            Some(ProtocolVersion::V1_1),
+            &blinded_refresh(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn refund_outputs_need_protocol_1_4() {
+        let withdraw = |refund_outputs| {
+            Request::Withdraw(WithdrawRequest {
+                notes: vec![BlindSignature::default()],
+                change_outputs: Vec::new(),
+                refund_outputs,
+                tx_cbor: String::new(),
+                tx_hash: String::new(),
+            })
+        };
+
+        let old = Some(ProtocolVersion::V1_3);
+        ProtocolVersion::negotiate(old, &withdraw(Vec::new())).unwrap();
+        let err = ProtocolVersion::negotiate(
+            old,
+            &withdraw(vec![BlindSignature::default()]),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnsupportedVersion);
+
+        ProtocolVersion::negotiate(
+            Some(ProtocolVersion::V1_4),
+            &withdraw(vec![BlindSignature::default()]),
+        )
+        .unwrap();
+    }
+}
@@ -25,6 +25,8 @@ use crate::{crypto::Scalar, error::Error};
 #[repr(transparent)]
 pub struct PublicKey(#[serde(with = "muhex::serde")] pub [u8; 32]);
 
+impl_hex_schema!(PublicKey, 64, "Compressed Ristretto point, hex encoded");
+
 impl Arbitrary for PublicKey {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
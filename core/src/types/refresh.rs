@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::{COMMITMENT_INPUT_SIZE, PublicKey};
@@ -24,12 +25,17 @@ pub const DATA_SIZE: usize = 256 * MAX_ATOMS;
     test_strategy::Arbitrary,
     PartialOrd,
     Ord,
+    JsonSchema,
 )]
 pub struct Atom {
+    /// Delegate that signed (or will sign) this note
     pub delegate: PublicKey,
+    /// Index into [`Refresh::asset_ids`]
     pub asset_id: u32,
     pub amount: u64,
+    /// Random value that makes otherwise identical notes distinct
     pub nonce: Hash,
+    /// For inputs, index into [`Refresh::signatures`]; absent on outputs
     pub signature: Option<u32>,
 }
 
@@ -53,6 +59,8 @@ impl Atom {
     }
 }
 
+/// Spends input notes and asks the delegate to blind-sign outputs of the
+/// same total value per asset.
 #[derive(
     Debug,
     Default,
@@ -65,14 +73,18 @@ impl Atom {
     test_strategy::Arbitrary,
     PartialOrd,
     Ord,
+    JsonSchema,
 )]
 pub struct Refresh {
+    /// Bit `i` is set when `atoms[i]` is an input being spent
     #[serde(rename = "m")]
     pub input_mask: BitSet32,
     #[serde(rename = "a")]
     pub atoms: Vec<Atom>,
+    /// Asset catalog that atoms reference by index
     #[serde(rename = "a_")]
     pub asset_ids: Vec<Asset>,
+    /// Signatures of the input atoms
     #[serde(rename = "s")]
     pub signatures: Vec<Signature>,
     #[serde(rename = "b", default, skip_serializing_if = "Vec::is_empty")]
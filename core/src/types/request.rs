@@ -1,13 +1,15 @@
+use proptest::prelude::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
 use crate::types::{
     AssetName, BlindSignature, PolicyId, Refresh, TransferAckPayload,
     TransferInitPayload, TransferNoticePayload, TransferStatusQueryPayload,
-    XNodeEnvelope,
+    WithdrawalStatus, XNodeEnvelope,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 #[serde(tag = "m", content = "p")]
 pub enum Request {
     #[serde(rename = "refresh")]
@@ -32,11 +34,33 @@ pub enum Request {
     CrossNodeTransferStatus(XNodeEnvelope<TransferStatusQueryPayload>),
     #[serde(rename = "cross_node_transfer_ack")]
     CrossNodeTransferAck(XNodeEnvelope<TransferAckPayload>),
+    #[serde(rename = "batch")]
+    Batch(BatchRequest),
+    #[serde(rename = "node_info")]
+    NodeInfo,
+    #[serde(rename = "withdrawal_status")]
+    WithdrawalStatus(WithdrawalStatusRequest),
+}
+
+/// Several requests sent in one round trip, answered in order by
+/// `Response::Batch`
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
+pub struct BatchRequest {
+    /// Requests to run, in order; batches cannot be nested
+    #[strategy(proptest::collection::vec(
+        any::<Refresh>().prop_map(Request::Refresh),
+        0..4,
+    ))]
+    pub requests: Vec<Request>,
+    /// Commit every refresh in one transaction or none of them; atomic
+    /// batches may only carry refreshes
+    #[serde(default)]
+    pub atomic: bool,
 }
 
 /// Deposit request from user
 /// User sends funds to script address and provides proof of deposit
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 pub struct DepositRequest {
     /// UTxO reference (tx_hash + index) at the script address
     pub utxo: UtxoReference,
@@ -53,7 +77,7 @@ pub struct DepositRequest {
 }
 
 /// UTxO reference for deposits
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 pub struct UtxoReference {
     /// Transaction hash (hex encoded)
     pub tx_hash: String,
@@ -63,7 +87,7 @@ pub struct UtxoReference {
 
 /// Withdrawal request from user
 /// User provides unsigned transaction spending script UTxOs
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 pub struct WithdrawRequest {
     /// Notes to burn (blinded inputs)
     pub notes: Vec<BlindSignature>,
@@ -71,14 +95,27 @@ pub struct WithdrawRequest {
     /// to the script address. These are matched to script outputs by count and
     /// transaction output order.
     pub change_outputs: Vec<BlindSignature>,
+    /// Blinded outputs the node signs in place of the burned notes, one per
+    /// note, if the transaction can no longer land. Without them a
+    /// withdrawal that expires or loses its inputs stays failed. Only their
+    /// number is checked; their value is trusted like `change_outputs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refund_outputs: Vec<BlindSignature>,
     /// Unsigned transaction CBOR (hex encoded)
     pub tx_cbor: String,
     /// Transaction hash (expected)
     pub tx_hash: String,
 }
 
+/// Query for the state of a withdrawal the node has burned notes for
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
+pub struct WithdrawalStatusRequest {
+    /// Transaction hash (hex encoded)
+    pub tx_hash: String,
+}
+
 /// Deposit response
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 pub struct DepositResponse {
     /// Blind signatures for the outputs
     pub signatures: Vec<BlindSignature>,
@@ -87,7 +124,7 @@ pub struct DepositResponse {
 }
 
 /// Withdrawal response
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 pub struct WithdrawResponse {
     /// Fully signed transaction CBOR (hex encoded)
     pub signed_tx_cbor: String,
@@ -97,6 +134,22 @@ pub struct WithdrawResponse {
     pub change_notes: Vec<BlindSignature>,
 }
 
+/// Withdrawal status response
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
+pub struct WithdrawalStatusResponse {
+    /// Transaction hash
+    pub tx_hash: String,
+    pub status: WithdrawalStatus,
+    /// Unix timestamp of the last status change
+    pub updated_at: u64,
+    /// Times the node has submitted the transaction again
+    pub resubmissions: u32,
+    /// Slot from which the transaction can no longer be included
+    pub invalid_hereafter: Option<u64>,
+    /// Signed refund outputs, once the withdrawal is refunded
+    pub refund_notes: Vec<BlindSignature>,
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prop_assert_eq;
@@ -171,6 +224,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Confirmed,
                 tx_hash: "abcd".to_string(),
@@ -209,6 +263,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferInitPayload {
                 asset: "lovelace".to_string(),
                 amount: "10".to_string(),
@@ -230,6 +285,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: None,
+            trace_context: None,
             payload: TransferStatusQueryPayload {
                 query_type: TransferQueryType::Current,
             },
@@ -248,6 +304,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferAckPayload {
                 ack_for_message_id: "mid-c".to_string(),
                 ack_status: TransferAckStatus::Processed,
@@ -267,4 +324,24 @@ mod tests {
         assert_eq!(a["m"], "cross_node_transfer_ack");
         assert_eq!(a["p"]["payload"]["ack_status"], "processed");
     }
+
+    #[test]
+    fn test_legacy_payloads_still_parse() {
+        // A 1.0 refresh, before outputs could carry blinded points
+        let legacy = r#"{"m":"refresh","p":{"m":0,"a":[],"a_":[],"s":[]}}"#;
+        let request: Request = serde_json::from_str(legacy).unwrap();
+        let Request::Refresh(refresh) = &request else {
+            panic!("expected refresh");
+        };
+        assert!(refresh.blinded_points.is_empty());
+        assert_eq!(request.newest_feature(), None);
+        assert_eq!(serde_json::to_string(&request).unwrap(), legacy);
+
+        // Batches before `atomic` existed
+        let batch: Request = serde_json::from_str(
+            r#"{"m":"batch","p":{"requests":[{"m":"public_key"}]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(batch, Request::Batch(b) if !b.atomic));
+    }
 }
@@ -1,9 +1,16 @@
+use std::collections::BTreeMap;
+
+use proptest::prelude::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
-use crate::types::*;
+use crate::{
+    error::{Error, ErrorCode},
+    types::*,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary)]
+#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, JsonSchema)]
 #[serde(tag = "m", content = "r")]
 pub enum Response {
     #[serde(rename = "refresh")]
@@ -17,6 +24,9 @@ pub enum Response {
         delegate_pk: PublicKey,
         /// Cardano script address for deposits
         cardano_script_address: Option<String>,
+        /// Missing from nodes that predate version negotiation
+        #[serde(default)]
+        protocol: ProtocolInfo,
     },
     #[serde(rename = "emit")]
     Emit(Box<Note>),
@@ -46,8 +56,71 @@ pub enum Response {
     CrossNodeTransferStatus(Box<XNodeEnvelope<TransferStatusPayload>>),
     #[serde(rename = "cross_node_transfer_ack")]
     CrossNodeTransferAck { accepted: bool },
+    #[serde(rename = "batch")]
+    Batch {
+        /// One response per request, in request order
+        #[strategy(proptest::collection::vec(
+            any::<Vec<BlindSignature>>()
+                .prop_map(|outputs| Response::Transaction { outputs }),
+            0..4,
+        ))]
+        responses: Vec<Response>,
+    },
+    #[serde(rename = "node_info")]
+    NodeInfo(Box<SignedNodeInfo>),
+    #[serde(rename = "withdrawal_status")]
+    WithdrawalStatus {
+        /// Transaction hash
+        tx_hash: String,
+        status: WithdrawalStatus,
+        /// Unix timestamp of the last status change
+        updated_at: u64,
+        /// Times the node has submitted the transaction again after the
+        /// request that burned its notes
+        resubmissions: u32,
+        /// Slot from which the transaction can no longer be included, if
+        /// it has a TTL
+        invalid_hereafter: Option<u64>,
+        /// Blind signatures over the request's refund outputs, once the
+        /// withdrawal is refunded
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        refund_notes: Vec<BlindSignature>,
+    },
     #[serde(rename = "error")]
-    Error { reason: String },
+    Error {
+        /// Human-readable message; not meant to be matched on
+        reason: String,
+        /// Missing from nodes that predate error codes
+        #[serde(default)]
+        code: ErrorCode,
+        #[serde(default)]
+        retryable: bool,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        details: BTreeMap<String, String>,
+    },
+}
+
+impl Response {
+    /// An error response that does not originate from an [`Error`].
+    pub fn error(code: ErrorCode, reason: impl Into<String>) -> Self {
+        Self::Error {
+            reason: reason.into(),
+            code,
+            retryable: code.is_retryable(),
+            details: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<Error> for Response {
+    fn from(error: Error) -> Self {
+        Self::Error {
+            reason: error.to_string(),
+            code: error.code(),
+            retryable: error.is_retryable(),
+            details: error.details(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -55,10 +128,14 @@ mod tests {
     use proptest::prop_assert_eq;
     use test_strategy::proptest;
 
-    use crate::types::{
-        Response, TransferChainState, TransferCreditState,
-        TransferSettlementState, TransferStatusPayload, XNodeAuth,
-        XNodeEnvelope, XNodeMessageType,
+    use crate::{
+        error::{Error, ErrorCode},
+        types::{
+            Feature, ProtocolInfo, ProtocolVersion, PublicKey, Response,
+            Signature, TransferChainState, TransferCreditState,
+            TransferSettlementState, TransferStatusPayload, XNodeAuth,
+            XNodeEnvelope, XNodeMessageType,
+        },
     };
 
     #[proptest]
@@ -84,6 +161,7 @@ mod tests {
                 destination_node_id: "node://b".to_string(),
                 sent_at: "2026-02-26T18:00:00Z".to_string(),
                 expires_at: None,
+                trace_context: None,
                 payload: TransferStatusPayload {
                     source_state: "confirming".to_string(),
                     destination_state: "credit_eligible".to_string(),
@@ -107,6 +185,35 @@ mod tests {
         assert_eq!(value["r"]["payload"]["credit_state"], "eligible");
     }
 
+    #[test]
+    fn test_error_response_carries_code_and_stays_compatible() {
+        let response = Response::from(Error::AlreadySpent {
+            signature: Signature::default(),
+        });
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["m"], "error");
+        assert_eq!(value["r"]["code"], "ALREADY_SPENT");
+        assert_eq!(value["r"]["retryable"], false);
+        assert_eq!(
+            value["r"]["details"]["signature"],
+            Signature::default().to_string()
+        );
+        assert!(value["r"]["reason"].as_str().unwrap().contains("spent"));
+
+        // Responses from older nodes only carry `reason`
+        let legacy: Response =
+            serde_json::from_str(r#"{"m":"error","r":{"reason":"boom"}}"#)
+                .unwrap();
+        assert!(matches!(
+            legacy,
+            Response::Error {
+                code: ErrorCode::InternalError,
+                retryable: false,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_cross_node_response_contract_shapes() {
         let create = Response::CrossNodeTransferCreate {
@@ -127,4 +234,30 @@ mod tests {
         assert_eq!(a["m"], "cross_node_transfer_ack");
         assert_eq!(a["r"]["accepted"], true);
     }
+
+    #[test]
+    fn test_info_advertises_protocol_and_reads_legacy_payloads() {
+        let response = Response::Info {
+            delegate_pk: PublicKey::default(),
+            cardano_script_address: None,
+            protocol: ProtocolInfo::current(),
+        };
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["r"]["protocol"]["version"], "1.4");
+        assert_eq!(value["r"]["protocol"]["min_version"], "1.0");
+        assert_eq!(value["r"]["protocol"]["features"][1], "batch");
+
+        // Nodes before negotiation answer without `protocol`
+        let legacy = format!(
+            r#"{{"m":"public_key","r":{{"delegate_pk":"{}","cardano_script_address":null}}}}"#,
+            PublicKey::default()
+        );
+        let Response::Info { protocol, .. } =
+            serde_json::from_str(&legacy).unwrap()
+        else {
+            panic!("expected info");
+        };
+        assert_eq!(protocol.version, ProtocolVersion::V1_0);
+        assert!(!protocol.supports(Feature::Batch));
+    }
 }
@@ -4,6 +4,7 @@ use core::{
 };
 
 use curve25519_dalek::ristretto::CompressedRistretto;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
@@ -25,6 +26,7 @@ use crate::{
     Serialize,
     Deserialize,
     Arbitrary,
+    JsonSchema,
 )]
 #[repr(transparent)]
 #[serde(transparent)]
@@ -47,6 +49,8 @@ pub struct Blinded<T>(pub T);
 #[serde(transparent)]
 pub struct Signature(#[serde(with = "muhex::serde")] pub [u8; 32]);
 
+impl_hex_schema!(Signature, 64, "Compressed Ristretto point, hex encoded");
+
 impl Signature {
     #[inline]
     pub const fn zero() -> Self {
@@ -1,7 +1,10 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use test_strategy::Arbitrary;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum XNodeMessageType {
     TransferInit,
@@ -11,14 +14,19 @@ pub enum XNodeMessageType {
     TransferAck,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct XNodeAuth {
     pub alg: String,
     pub kid: String,
     pub sig: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
+#[schemars(rename = "XNodeEnvelope_for_{T}")]
 pub struct XNodeEnvelope<T> {
     pub m: String,
     pub version: String,
@@ -31,11 +39,17 @@ pub struct XNodeEnvelope<T> {
     pub destination_node_id: String,
     pub sent_at: String,
     pub expires_at: Option<String>,
+    /// W3C `traceparent` of the sender's span, so one trace can follow a
+    /// transfer across nodes. Covered by the auth signature when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<String>,
     pub payload: T,
     pub auth: XNodeAuth,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct TransferInitPayload {
     pub asset: String,
     pub amount: String,
@@ -43,7 +57,9 @@ pub struct TransferInitPayload {
     pub source_intent_hash: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferNoticeStage {
     Submitted,
@@ -51,26 +67,34 @@ pub enum TransferNoticeStage {
     Finalized,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct TransferNoticePayload {
     pub notice_stage: TransferNoticeStage,
     pub tx_hash: String,
     pub confirmations: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferQueryType {
     Current,
     History,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct TransferStatusQueryPayload {
     pub query_type: TransferQueryType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferSettlementState {
     NotSubmitted,
@@ -81,7 +105,9 @@ pub enum TransferSettlementState {
     ManualReview,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferChainState {
     Unknown,
@@ -91,7 +117,9 @@ pub enum TransferChainState {
     Invalidated,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferCreditState {
     None,
@@ -101,7 +129,9 @@ pub enum TransferCreditState {
     Reversed,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct TransferStatusPayload {
     pub source_state: String,
     pub destination_state: String,
@@ -113,7 +143,9 @@ pub struct TransferStatusPayload {
     pub updated_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum TransferAckStatus {
     Processed,
@@ -122,7 +154,9 @@ pub enum TransferAckStatus {
     Rejected,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Arbitrary, PartialEq, Eq, JsonSchema,
+)]
 pub struct TransferAckPayload {
     pub ack_for_message_id: String,
     pub ack_status: TransferAckStatus,
@@ -285,6 +319,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferInitPayload {
                 asset: "lovelace".to_string(),
                 amount: "1".to_string(),
@@ -318,6 +353,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: None,
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Submitted,
                 tx_hash: "abcd".to_string(),
@@ -379,6 +415,7 @@ mod tests {
                 destination_node_id: "node://b".to_string(),
                 sent_at: "2026-02-26T18:00:00Z".to_string(),
                 expires_at: None,
+                trace_context: None,
                 payload: (),
                 auth: XNodeAuth {
                     alg: "Ed25519".to_string(),
@@ -410,6 +447,7 @@ mod tests {
                 destination_node_id: "node://b".to_string(),
                 sent_at: "2026-02-26T18:00:00Z".to_string(),
                 expires_at: None,
+                trace_context: None,
                 payload: (),
                 auth: XNodeAuth {
                     alg: "Ed25519".to_string(),
@@ -14,6 +14,7 @@ macro_rules! impl_bitset {
                 serde::Deserialize,
                 Hash,
                 test_strategy::Arbitrary,
+                schemars::JsonSchema,
             )]
             #[serde(transparent)]
             #[repr(transparent)]
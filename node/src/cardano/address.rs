@@ -16,7 +16,8 @@ pub fn compute_script_hash(cbor: &[u8]) -> Vec<u8> {
 }
 
 /// Build script address from hash and network
-/// Uses Shelley address format directly instead of bech32
+/// Uses Shelley address format directly instead of bech32: an enterprise
+/// address (header type 7) whose payment credential is the script hash
 pub fn build_script_address(
     script_hash: &[u8],
     network: &str,
@@ -24,7 +25,7 @@ pub fn build_script_address(
     let network = CardanoNetwork::parse(network)
         .map_err(|e| color_eyre::eyre::eyre!(e.to_string()))?;
 
-    let header: u8 = 0xF0 | network.address_network_id();
+    let header: u8 = 0x70 | network.address_network_id();
     let mut address_bytes = vec![header];
     address_bytes.extend_from_slice(script_hash);
 
@@ -0,0 +1,567 @@
+//! Local projection of the outputs at the node's script address.
+//!
+//! The indexer polls the provider for the tip and, once a block has been
+//! added since the last sync, the script address's UTxOs. The snapshot is
+//! compared with the index in a read transaction, so the write transaction
+//! only touches the outputs that changed. Outputs that disappear are
+//! marked spent rather than dropped, so spent-by information survives
+//! until the spend can no longer be rolled back. Rollbacks are detected by
+//! comparing the tip's hash with the one recorded for that height; outputs
+//! created and spends seen above the fork are undone before the next
+//! snapshot is applied.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mugraph_core::{error::Error, types::UtxoRef};
+use redb::{ReadableTable, ReadableTableMetadata, Table};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    database::{
+        CARDANO_WALLET, CHAIN_INDEX, CHAIN_INDEX_BLOCKS, Database, SCRIPT_UTXOS,
+    },
+    provider::{ChainTip, Provider, UtxoInfo},
+    supervisor::Shutdown,
+    telemetry::{record_chain_index_rollback, record_chain_index_tip},
+    tx_ids::parse_utxo_ref,
+};
+
+/// Blocks past which Cardano does not roll back (the security parameter
+/// `k`); block hashes and spent outputs older than this are pruned
+const ROLLBACK_LIMIT: u64 = 2_160;
+
+/// Readers trust the index for this many poll intervals after a sync
+const FRESH_INTERVALS: u32 = 3;
+
+const STATE_KEY: &str = "state";
+
+/// An output at the script address as last seen by the indexer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedUtxo {
+    pub utxo: UtxoInfo,
+    /// Transaction the node submitted spending this output
+    pub spent_by: Option<String>,
+    /// Tip height at which the output was first missing from the chain
+    pub spent_at: Option<u64>,
+}
+
+impl IndexedUtxo {
+    pub fn is_unspent(&self) -> bool {
+        self.spent_at.is_none()
+    }
+}
+
+/// How far the index has caught up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexState {
+    pub script_address: String,
+    pub tip: ChainTip,
+    /// Unix seconds after which readers go back to the provider
+    pub fresh_until: u64,
+}
+
+/// Background worker following the chain for the script address
+#[derive(Clone)]
+pub struct ChainIndex {
+    database: Arc<Database>,
+    provider: Provider,
+    poll_interval: Duration,
+}
+
+impl ChainIndex {
+    pub fn new(
+        database: Arc<Database>,
+        provider: Provider,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            database,
+            provider,
+            poll_interval,
+        }
+    }
+
+    /// Poll until `shutdown` fires
+    pub async fn run(self, shutdown: Shutdown) {
+        tracing::info!(
+            "Starting chain index, polling every {:?}",
+            self.poll_interval
+        );
+
+        let mut interval = tokio::time::interval(self.poll_interval);
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+
+            if let Err(e) = self.sync().await {
+                tracing::error!("Error syncing chain index: {}", e);
+            }
+        }
+
+        tracing::info!("Chain index stopped");
+    }
+
+    /// Bring the projection up to the provider's current tip. Returns
+    /// `None` while no Cardano wallet exists to follow.
+    pub async fn sync(&self) -> Result<Option<IndexState>, Error> {
+        let (script_address, previous) = {
+            let read_tx = self.database.read()?;
+            let wallets = read_tx.open_table(CARDANO_WALLET)?;
+            let script_address = match wallets.get("wallet")? {
+                Some(wallet) => wallet.value().script_address,
+                None => return Ok(None),
+            };
+            let meta = read_tx.open_table(CHAIN_INDEX)?;
+            let previous = match meta.get(STATE_KEY)? {
+                Some(bytes) => Some(decode::<IndexState>(bytes.value())?),
+                None => None,
+            };
+            (script_address, previous)
+        };
+
+        let tip =
+            self.provider
+                .get_tip()
+                .await
+                .map_err(|e| Error::NetworkError {
+                    reason: format!("Failed to get chain tip: {}", e),
+                })?;
+        let state = IndexState {
+            script_address,
+            fresh_until: unix_now()
+                + (self.poll_interval * FRESH_INTERVALS).as_secs(),
+            tip,
+        };
+
+        let same_address = previous.as_ref().is_some_and(|previous| {
+            previous.script_address == state.script_address
+        });
+        // Outputs only change with a new block
+        if same_address
+            && previous.as_ref().is_some_and(|previous| {
+                previous.tip.block_height == state.tip.block_height
+                    && previous.tip.hash == state.tip.hash
+            })
+        {
+            let write_tx = self.database.write()?;
+            write_tx
+                .open_table(CHAIN_INDEX)?
+                .insert(STATE_KEY, encode(&state)?.as_slice())?;
+            write_tx.commit()?;
+            return Ok(Some(state));
+        }
+
+        let utxos = self
+            .provider
+            .get_address_utxos(&state.script_address)
+            .await
+            .map_err(|e| Error::NetworkError {
+                reason: format!("Failed to fetch script address UTxOs: {}", e),
+            })?;
+        let height = state.tip.block_height;
+        let horizon = height.saturating_sub(ROLLBACK_LIMIT);
+        let delta = same_address
+            .then(|| Delta::between(&self.database, &utxos, horizon))
+            .transpose()?;
+
+        let write_tx = self.database.write()?;
+        {
+            let mut meta = write_tx.open_table(CHAIN_INDEX)?;
+            let mut blocks = write_tx.open_table(CHAIN_INDEX_BLOCKS)?;
+            let mut outputs = write_tx.open_table(SCRIPT_UTXOS)?;
+
+            if previous.is_some() && !same_address {
+                tracing::warn!(
+                    "script address changed, rebuilding the chain index"
+                );
+                outputs.retain(|_, _| false)?;
+                blocks.retain(|_, _| false)?;
+            }
+
+            let fork = rollback_point(&blocks, &state.tip)?;
+            if let Some(fork) = fork {
+                tracing::warn!(
+                    fork,
+                    tip = state.tip.block_height,
+                    "chain rolled back, undoing the index above the fork"
+                );
+                record_chain_index_rollback();
+                roll_back(&mut outputs, &mut blocks, fork)?;
+            }
+
+            blocks.insert(height, state.tip.hash.as_str())?;
+            blocks.retain(|h, _| h >= horizon)?;
+
+            // A rollback rewrote outputs the delta was taken against
+            match delta.filter(|_| fork.is_none()) {
+                Some(delta) => delta.apply(&mut outputs, height)?,
+                None => {
+                    apply_snapshot(&mut outputs, utxos, height)?;
+                    outputs.retain(|_, bytes| {
+                        decode::<IndexedUtxo>(bytes)
+                            .map(|indexed| {
+                                indexed
+                                    .spent_at
+                                    .is_none_or(|spent| spent >= horizon)
+                            })
+                            .unwrap_or(true)
+                    })?;
+                }
+            }
+
+            meta.insert(STATE_KEY, encode(&state)?.as_slice())?;
+            record_chain_index_tip(height, outputs.len()?);
+        }
+        write_tx.commit()?;
+
+        Ok(Some(state))
+    }
+}
+
+/// How a snapshot of the script address differs from the index
+#[derive(Default)]
+struct Delta {
+    /// Outputs that are new, changed, or back after their spend was
+    /// rolled back
+    upserts: Vec<(UtxoRef, UtxoInfo)>,
+    /// Unspent outputs missing from the snapshot
+    spent: Vec<UtxoRef>,
+    /// Outputs spent before the rollback horizon
+    pruned: Vec<UtxoRef>,
+}
+
+impl Delta {
+    /// Compare `utxos` with the index in a read transaction, leaving the
+    /// index open to writers meanwhile
+    fn between(
+        database: &Database,
+        utxos: &[UtxoInfo],
+        horizon: u64,
+    ) -> Result<Self, Error> {
+        let mut current = snapshot_refs(utxos.iter().cloned());
+        let mut delta = Self::default();
+
+        let read_tx = database.read()?;
+        let outputs = read_tx.open_table(SCRIPT_UTXOS)?;
+        for row in outputs.iter()? {
+            let (key, bytes) = row?;
+            let utxo_ref = key.value();
+            let indexed = decode::<IndexedUtxo>(bytes.value())?;
+
+            match current.remove(&utxo_ref) {
+                Some(utxo)
+                    if !indexed.is_unspent()
+                        || refreshed(&indexed.utxo, utxo.clone())
+                            != indexed.utxo =>
+                {
+                    delta.upserts.push((utxo_ref, utxo));
+                }
+                Some(_) => {}
+                None if indexed.is_unspent() => delta.spent.push(utxo_ref),
+                None if indexed.spent_at.is_some_and(|at| at < horizon) => {
+                    delta.pruned.push(utxo_ref)
+                }
+                None => {}
+            }
+        }
+        delta.upserts.extend(current);
+
+        Ok(delta)
+    }
+
+    /// Write the delta, re-reading each output so that spends recorded
+    /// since it was taken are kept
+    fn apply(
+        self,
+        outputs: &mut Table<'_, UtxoRef, &'static [u8]>,
+        height: u64,
+    ) -> Result<(), Error> {
+        for (utxo_ref, utxo) in self.upserts {
+            let indexed = match outputs.get(&utxo_ref)? {
+                Some(bytes) => {
+                    let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+                    indexed.utxo = refreshed(&indexed.utxo, utxo);
+                    indexed.spent_at = None;
+                    indexed
+                }
+                None => IndexedUtxo {
+                    utxo,
+                    spent_by: None,
+                    spent_at: None,
+                },
+            };
+            outputs.insert(utxo_ref, encode(&indexed)?.as_slice())?;
+        }
+
+        for utxo_ref in self.spent {
+            let Some(bytes) = outputs.get(&utxo_ref)? else {
+                continue;
+            };
+            let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+            drop(bytes);
+            if indexed.is_unspent() {
+                indexed.spent_at = Some(height);
+                outputs.insert(utxo_ref, encode(&indexed)?.as_slice())?;
+            }
+        }
+
+        for utxo_ref in self.pruned {
+            outputs.remove(&utxo_ref)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The index state, if the indexer has synced recently enough for readers
+/// to rely on it instead of the provider
+pub fn fresh_state(database: &Database) -> Result<Option<IndexState>, Error> {
+    let read_tx = database.read()?;
+    let table = read_tx.open_table(CHAIN_INDEX)?;
+    let Some(bytes) = table.get(STATE_KEY)? else {
+        return Ok(None);
+    };
+
+    let state = decode::<IndexState>(bytes.value())?;
+    Ok((state.fresh_until > unix_now()).then_some(state))
+}
+
+/// The indexed output at `utxo_ref`, spent or not
+pub fn indexed_utxo(
+    database: &Database,
+    utxo_ref: &UtxoRef,
+) -> Result<Option<IndexedUtxo>, Error> {
+    let read_tx = database.read()?;
+    let table = read_tx.open_table(SCRIPT_UTXOS)?;
+    table
+        .get(utxo_ref)?
+        .map(|bytes| decode(bytes.value()))
+        .transpose()
+}
+
+/// Every output still unspent at the script address
+pub fn unspent_utxos(database: &Database) -> Result<Vec<UtxoInfo>, Error> {
+    let read_tx = database.read()?;
+    let table = read_tx.open_table(SCRIPT_UTXOS)?;
+
+    let mut unspent = Vec::new();
+    for row in table.iter()? {
+        let (_, bytes) = row?;
+        let indexed = decode::<IndexedUtxo>(bytes.value())?;
+        if indexed.is_unspent() {
+            unspent.push(indexed.utxo);
+        }
+    }
+
+    Ok(unspent)
+}
+
+/// Note that the node submitted `tx_hash` spending `inputs`. Inputs outside
+/// the index are ignored.
+pub fn record_spends(
+    database: &Database,
+    inputs: &[UtxoRef],
+    tx_hash: &str,
+) -> Result<(), Error> {
+    let write_tx = database.write()?;
+    {
+        let mut table = write_tx.open_table(SCRIPT_UTXOS)?;
+        for input in inputs {
+            let Some(bytes) = table.get(input)? else {
+                continue;
+            };
+            let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+            drop(bytes);
+
+            indexed.spent_by = Some(tx_hash.to_string());
+            table.insert(input, encode(&indexed)?.as_slice())?;
+        }
+    }
+    write_tx.commit()
+}
+
+/// Fill in the datum and height of the unspent indexed output at
+/// `utxo_ref` from `utxo`, the provider's answer for it
+pub fn complete_utxo(
+    database: &Database,
+    utxo_ref: &UtxoRef,
+    utxo: &UtxoInfo,
+) -> Result<(), Error> {
+    let write_tx = database.write()?;
+    {
+        let mut table = write_tx.open_table(SCRIPT_UTXOS)?;
+        let Some(bytes) = table.get(utxo_ref)? else {
+            return Ok(());
+        };
+        let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+        drop(bytes);
+        if !indexed.is_unspent() {
+            return Ok(());
+        }
+
+        indexed.utxo = refreshed(&indexed.utxo, utxo.clone());
+        table.insert(utxo_ref, encode(&indexed)?.as_slice())?;
+    }
+    write_tx.commit()
+}
+
+/// The highest block still on the chain, if `tip` shows the chain rolled
+/// back past blocks the index has seen. Polling only reveals the tip, so a
+/// changed block at the tip's height is taken to fork just below it.
+fn rollback_point(
+    blocks: &impl ReadableTable<u64, &'static str>,
+    tip: &ChainTip,
+) -> Result<Option<u64>, Error> {
+    let Some((last, _)) = blocks.last()? else {
+        return Ok(None);
+    };
+    let last = last.value();
+    let height = tip.block_height;
+
+    Ok(match blocks.get(height)? {
+        Some(hash) if hash.value() != tip.hash => {
+            Some(height.saturating_sub(1))
+        }
+        Some(_) if last > height => Some(height),
+        None if last > height => Some(height.saturating_sub(1)),
+        _ => None,
+    })
+}
+
+/// Drop outputs created above `fork` and revive outputs spent above it
+fn roll_back(
+    outputs: &mut Table<'_, UtxoRef, &'static [u8]>,
+    blocks: &mut Table<'_, u64, &'static str>,
+    fork: u64,
+) -> Result<(), Error> {
+    blocks.retain(|height, _| height <= fork)?;
+
+    let mut revived = Vec::new();
+    for row in outputs.iter()? {
+        let (key, bytes) = row?;
+        let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+        if indexed.spent_at.is_some_and(|spent| spent > fork) {
+            indexed.spent_at = None;
+            revived.push((key.value(), indexed));
+        }
+    }
+    for (key, indexed) in revived {
+        outputs.insert(key, encode(&indexed)?.as_slice())?;
+    }
+
+    outputs.retain(|_, bytes| {
+        decode::<IndexedUtxo>(bytes)
+            .map(|indexed| {
+                indexed
+                    .utxo
+                    .block_height
+                    .is_none_or(|height| height <= fork)
+            })
+            .unwrap_or(true)
+    })?;
+
+    Ok(())
+}
+
+/// Reconcile the index with the outputs the provider reports at `height`:
+/// new outputs are added, missing ones are marked spent, and spent ones
+/// that are back (their spend was rolled back) are revived
+fn apply_snapshot(
+    outputs: &mut Table<'_, UtxoRef, &'static [u8]>,
+    utxos: Vec<UtxoInfo>,
+    height: u64,
+) -> Result<(), Error> {
+    let mut current = snapshot_refs(utxos);
+
+    let mut updates = Vec::new();
+    for row in outputs.iter()? {
+        let (key, bytes) = row?;
+        let utxo_ref = key.value();
+        let mut indexed = decode::<IndexedUtxo>(bytes.value())?;
+
+        match current.remove(&utxo_ref) {
+            Some(utxo) => {
+                indexed.utxo = refreshed(&indexed.utxo, utxo);
+                indexed.spent_at = None;
+            }
+            None if indexed.is_unspent() => indexed.spent_at = Some(height),
+            None => continue,
+        }
+        updates.push((utxo_ref, indexed));
+    }
+
+    updates.extend(current.into_iter().map(|(utxo_ref, utxo)| {
+        (
+            utxo_ref,
+            IndexedUtxo {
+                utxo,
+                spent_by: None,
+                spent_at: None,
+            },
+        )
+    }));
+    for (utxo_ref, indexed) in updates {
+        outputs.insert(utxo_ref, encode(&indexed)?.as_slice())?;
+    }
+
+    Ok(())
+}
+
+/// `utxos` by reference, skipping malformed ones
+fn snapshot_refs(
+    utxos: impl IntoIterator<Item = UtxoInfo>,
+) -> HashMap<UtxoRef, UtxoInfo> {
+    let mut refs = HashMap::new();
+    for utxo in utxos {
+        match parse_utxo_ref(&utxo.tx_hash, utxo.output_index) {
+            Ok(utxo_ref) => {
+                refs.insert(utxo_ref, utxo);
+            }
+            Err(e) => tracing::warn!(
+                "skipping UTxO {}#{} with a malformed reference: {:?}",
+                utxo.tx_hash,
+                utxo.output_index,
+                e
+            ),
+        }
+    }
+    refs
+}
+
+/// `utxo` as newly reported, keeping the datum and height `indexed` already
+/// knows when the snapshot leaves them out: address listings from some
+/// backends carry neither
+fn refreshed(indexed: &UtxoInfo, mut utxo: UtxoInfo) -> UtxoInfo {
+    if utxo.datum.is_none() && utxo.datum_hash == indexed.datum_hash {
+        utxo.datum.clone_from(&indexed.datum);
+        utxo.inline_datum = indexed.inline_datum;
+    }
+    utxo.block_height = utxo.block_height.or(indexed.block_height);
+    utxo
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(value).map_err(|e| Error::Internal {
+        reason: format!("failed to encode chain index entry: {e}"),
+    })
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(bytes).map_err(|e| Error::Internal {
+        reason: format!("failed to decode chain index entry: {e}"),
+    })
+}
@@ -1,6 +1,15 @@
-use std::net::SocketAddr;
+use std::{
+    ffi::OsString,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
 
-use clap::Parser;
+use clap::{
+    ArgMatches, CommandFactory, FromArgMatches, Parser, error::ErrorKind,
+    parser::ValueSource,
+};
 use color_eyre::eyre::Result;
 use mugraph_core::{
     error::Error,
@@ -8,14 +17,129 @@ use mugraph_core::{
 };
 use rand::{Rng, SeedableRng, rng};
 use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    network::CardanoNetwork,
+    provider::{CachePolicy, EMULATOR_PROVIDER},
+    routes::RPC_METHODS,
+};
+
+mod file;
+
+pub use file::{
+    ArchiveSection, CardanoSection, ConfigFile, DepositSection, EventsSection,
+    InfoSection, MetricsSection, RpcSection, Seed, ServerSection, TlsSection,
+    TracingSection, WithdrawSection, XNodeSection,
+};
+
+/// Per-client request rate for one RPC method, written `method=per_sec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodRateLimit {
+    pub method: String,
+    pub per_sec: u32,
+}
 
-use crate::network::CardanoNetwork;
+impl FromStr for MethodRateLimit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| Error::InvalidInput { reason };
+
+        let (method, per_sec) = s.split_once('=').ok_or_else(|| {
+            invalid(format!("expected method=per_sec, got {s:?}"))
+        })?;
+        let method = method.trim();
+        if !RPC_METHODS.contains(&method) {
+            return Err(invalid(format!(
+                "unknown RPC method {method:?}, expected one of {}",
+                RPC_METHODS.join(", ")
+            )));
+        }
+        let per_sec = per_sec
+            .trim()
+            .parse()
+            .map_err(|e| invalid(format!("invalid rate for {method}: {e}")))?;
+
+        Ok(Self {
+            method: method.to_string(),
+            per_sec,
+        })
+    }
+}
+
+impl fmt::Display for MethodRateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.method, self.per_sec)
+    }
+}
+
+/// An extra Cardano provider backend, written `provider[:api_key][@url]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderBackend {
+    pub provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_url: Option<String>,
+}
+
+impl ProviderBackend {
+    fn validate(&self) -> Result<(), String> {
+        if !file::KNOWN_PROVIDERS.contains(&self.provider.as_str()) {
+            return Err(format!(
+                "unknown provider {:?}, expected one of {}",
+                self.provider,
+                file::KNOWN_PROVIDERS.join(", ")
+            ));
+        }
+
+        if let Some(url) = &self.provider_url
+            && reqwest::Url::parse(url).is_err()
+        {
+            return Err(format!("{url:?} is not a valid URL"));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ProviderBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (head, provider_url) = match s.trim().split_once('@') {
+            Some((head, url)) => (head, Some(url.to_string())),
+            None => (s.trim(), None),
+        };
+        let (provider, api_key) = match head.split_once(':') {
+            Some((provider, api_key)) => (provider, Some(api_key.to_string())),
+            None => (head, None),
+        };
+
+        let backend = Self {
+            provider: provider.to_string(),
+            api_key,
+            provider_url,
+        };
+        backend
+            .validate()
+            .map_err(|reason| Error::InvalidInput { reason })?;
+
+        Ok(backend)
+    }
+}
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Parser)]
 pub enum Config {
     #[command(about)]
     Server {
+        /// TOML config file layered underneath env vars and flags
+        #[clap(long = "config", env = "MUGRAPH_CONFIG")]
+        config_file: Option<String>,
+
         #[clap(short, long, default_value = "0.0.0.0:9999")]
         addr: SocketAddr,
 
@@ -29,18 +153,74 @@ pub enum Config {
         #[clap(long, env = "CARDANO_NETWORK", default_value = "preprod")]
         cardano_network: String,
 
-        /// Cardano provider (blockfrost, maestro)
+        /// Cardano provider (blockfrost, maestro, koios, ogmios, or emulator
+        /// in dev mode)
         #[clap(long, env = "CARDANO_PROVIDER", default_value = "blockfrost")]
         cardano_provider: String,
 
-        /// Cardano provider API key
+        /// Cardano provider API key (optional for koios)
         #[clap(long, env = "CARDANO_API_KEY")]
         cardano_api_key: Option<String>,
 
-        /// Cardano provider URL (optional, for custom endpoints)
+        /// Cardano provider URL (optional, for custom endpoints). For ogmios
+        /// it is the Kupo URL; for the emulator it names the ledger
+        /// instance, e.g. `emulator://alice`
         #[clap(long, env = "CARDANO_PROVIDER_URL")]
         cardano_provider_url: Option<String>,
 
+        /// Ogmios WebSocket URL used alongside Kupo by the ogmios provider
+        #[clap(long, env = "CARDANO_OGMIOS_URL")]
+        cardano_ogmios_url: Option<String>,
+
+        /// Extra provider backends for failover and quorum checks, as
+        /// comma-separated `provider[:api_key][@url]` entries. Ogmios
+        /// backends share --cardano-ogmios-url
+        #[clap(long, env = "CARDANO_BACKENDS", value_delimiter = ',')]
+        cardano_backends: Vec<ProviderBackend>,
+
+        /// Backends that must give the same answer for deposit UTxOs and
+        /// transaction block heights
+        #[clap(long, env = "CARDANO_QUORUM", default_value = "1")]
+        cardano_quorum: usize,
+
+        /// Seconds to cache transaction heights buried at least the deposit
+        /// confirmation depth, which can no longer change (0 disables)
+        #[clap(
+            long,
+            env = "CARDANO_CACHE_IMMUTABLE_SECS",
+            default_value = "3600"
+        )]
+        cardano_cache_immutable_secs: u64,
+
+        /// Seconds to cache the tip, unspent outputs and answers that are
+        /// not yet settled (0 disables)
+        #[clap(long, env = "CARDANO_CACHE_VOLATILE_SECS", default_value = "5")]
+        cardano_cache_volatile_secs: u64,
+
+        /// Seconds to cache "not found" answers (0 disables)
+        #[clap(long, env = "CARDANO_CACHE_NEGATIVE_SECS", default_value = "2")]
+        cardano_cache_negative_secs: u64,
+
+        /// Provider requests allowed per minute. Background calls are shed
+        /// before critical ones once three quarters are used (unlimited when
+        /// unset)
+        #[clap(long, env = "CARDANO_REQUEST_BUDGET")]
+        cardano_request_budget: Option<u32>,
+
+        /// Seconds between chain index polls of the script address (0
+        /// disables the index, so routes query the provider directly)
+        #[clap(long, env = "CARDANO_INDEX_POLL_SECS", default_value = "20")]
+        cardano_index_poll_secs: u64,
+
+        /// Seconds between blocks on the emulated ledger (0 produces blocks
+        /// only on demand)
+        #[clap(
+            long,
+            env = "CARDANO_EMULATOR_BLOCK_SECS",
+            default_value = "20"
+        )]
+        cardano_emulator_block_secs: u64,
+
         /// Optional Cardano payment signing key to import (hex encoded)
         #[clap(long, env = "CARDANO_PAYMENT_SK")]
         cardano_payment_sk: Option<String>,
@@ -81,12 +261,129 @@ pub enum Config {
         #[clap(long, env = "FEE_TOLERANCE_PCT", default_value = "5")]
         fee_tolerance_pct: u8,
 
+        /// Seconds between passes that settle pending and failed
+        /// withdrawals against the chain (0 disables them)
+        #[clap(long, env = "WITHDRAWAL_RECONCILE_SECS", default_value = "30")]
+        withdrawal_reconcile_secs: u64,
+
         /// Dev mode: skip Cardano chain dependencies (wallet, deposit monitor, reconciler)
         #[clap(long, env = "DEV_MODE", default_value = "false")]
         dev_mode: bool,
+
+        /// Archive spent note signatures older than this many seconds into
+        /// immutable segment files (disabled when unset)
+        #[clap(long, env = "SPENT_ARCHIVE_AFTER_SECS")]
+        spent_archive_after_secs: Option<u64>,
+
+        /// Seconds between spent-set archive passes
+        #[clap(
+            long,
+            env = "SPENT_ARCHIVE_INTERVAL_SECS",
+            default_value = "3600",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        spent_archive_interval_secs: u64,
+
+        /// Seconds events are kept in the event log before they are pruned
+        /// (0 keeps every event)
+        #[clap(long, env = "EVENT_RETENTION_SECS", default_value = "604800")]
+        event_retention_secs: u64,
+
+        /// Event streams open at once across all clients (0 leaves them
+        /// unlimited)
+        #[clap(long, env = "EVENT_MAX_STREAMS", default_value = "256")]
+        event_max_streams: usize,
+
+        /// Serve `/metrics` on this separate admin address instead of the
+        /// main listener
+        #[clap(long, env = "METRICS_ADDR")]
+        metrics_addr: Option<SocketAddr>,
+
+        /// Asset units counted under their own `asset` label in deposit and
+        /// withdrawal metrics, as comma-separated units. Every other unit is
+        /// counted as `other`
+        #[clap(
+            long,
+            env = "METRICS_ASSETS",
+            value_delimiter = ',',
+            default_value = "lovelace"
+        )]
+        metrics_assets: Vec<String>,
+
+        /// OTLP/HTTP collector base URL for span export (disabled when unset)
+        #[clap(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+        otlp_endpoint: Option<String>,
+
+        /// Largest accepted `/rpc` request body in bytes (default: 2 MiB)
+        #[clap(long, env = "RPC_MAX_BODY_BYTES", default_value = "2097152")]
+        rpc_max_body_bytes: usize,
+
+        /// Sustained `/rpc` requests per second allowed from one client IP,
+        /// with bursts of twice that (0 disables)
+        #[clap(long, env = "RPC_RATE_LIMIT", default_value = "50")]
+        rpc_rate_limit: u32,
+
+        /// Per-client rates for individual RPC methods, as comma-separated
+        /// `method=per_sec` pairs (0 disables a method's limit)
+        #[clap(
+            long,
+            env = "RPC_METHOD_RATE_LIMITS",
+            value_delimiter = ',',
+            default_value = "refresh=20,deposit=2,withdraw=2"
+        )]
+        rpc_method_rate_limits: Vec<MethodRateLimit>,
+
+        /// Deposit and withdraw requests processed at once across all
+        /// clients (0 disables)
+        #[clap(
+            long,
+            env = "RPC_MAX_CONCURRENT_EXPENSIVE",
+            default_value = "16"
+        )]
+        rpc_max_concurrent_expensive: usize,
+
+        /// Requests one `batch` call may carry (0 disables batches)
+        #[clap(long, env = "RPC_MAX_BATCH_SIZE", default_value = "100")]
+        rpc_max_batch_size: usize,
+
+        /// Reverse proxies whose `X-Forwarded-For` header names the client,
+        /// as comma-separated IP addresses. Without them, every client
+        /// behind a proxy shares the proxy's rate limits.
+        #[clap(long, env = "RPC_TRUSTED_PROXIES", value_delimiter = ',')]
+        rpc_trusted_proxies: Vec<IpAddr>,
+
+        /// PEM certificate chain for serving HTTPS (requires --tls-key-file)
+        #[clap(long, env = "TLS_CERT_FILE")]
+        tls_cert_file: Option<String>,
+
+        /// PEM private key for --tls-cert-file
+        #[clap(long, env = "TLS_KEY_FILE")]
+        tls_key_file: Option<String>,
+
+        /// PEM CA bundle for mutual TLS on xnode requests: their client
+        /// certificate must chain to it and name the origin node as a URI SAN
+        /// (requires --tls-cert-file)
+        #[clap(long, env = "XNODE_CLIENT_CA_FILE")]
+        xnode_client_ca_file: Option<String>,
+
+        /// Seconds a signed node info document stays valid
+        #[clap(long, env = "NODE_INFO_TTL_SECS", default_value = "3600")]
+        node_info_ttl_secs: u64,
+
+        /// Ways to reach the operator, published in the node info document
+        /// as comma-separated URIs (e.g. `mailto:ops@example.com`)
+        #[clap(long, env = "OPERATOR_CONTACT", value_delimiter = ',')]
+        operator_contact: Vec<String>,
     },
     #[command(about)]
     GenerateKey,
+    /// Print the effective server configuration with secrets redacted
+    #[command(about)]
+    CheckConfig {
+        /// Flags exactly as they would be passed to `server`
+        #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 }
 
 impl Default for Config {
@@ -97,7 +394,539 @@ impl Default for Config {
 
 impl Config {
     pub fn new() -> Self {
-        Self::parse()
+        Self::try_load_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Dev-mode server on preprod with a fixed seed and every background
+    /// worker and rate limit turned off, for tests. Change individual
+    /// settings through `if let Config::Server { .. } = &mut config`.
+    pub fn test_server() -> Self {
+        Self::Server {
+            config_file: None,
+            addr: "127.0.0.1:9999".parse().expect("valid socket address"),
+            seed: Some(42),
+            secret_key: None,
+            cardano_network: "preprod".to_string(),
+            cardano_provider: "blockfrost".to_string(),
+            cardano_api_key: None,
+            cardano_provider_url: None,
+            cardano_payment_sk: None,
+            xnode_peer_registry_file: None,
+            xnode_node_id: "node://local".to_string(),
+            deposit_confirm_depth: 15,
+            deposit_expiration_blocks: 1440,
+            min_deposit_value: Some(1_000_000),
+            max_tx_size: 16_384,
+            max_withdrawal_fee: 2_000_000,
+            fee_tolerance_pct: 5,
+            dev_mode: true,
+            spent_archive_after_secs: None,
+            spent_archive_interval_secs: 3600,
+            event_retention_secs: 0,
+            event_max_streams: 0,
+            metrics_addr: None,
+            metrics_assets: vec!["lovelace".to_string()],
+            otlp_endpoint: None,
+            rpc_max_body_bytes: 2 * 1024 * 1024,
+            rpc_rate_limit: 0,
+            rpc_method_rate_limits: Vec::new(),
+            rpc_max_concurrent_expensive: 0,
+            tls_cert_file: None,
+            tls_key_file: None,
+            xnode_client_ca_file: None,
+            rpc_max_batch_size: 100,
+            rpc_trusted_proxies: Vec::new(),
+            node_info_ttl_secs: 3600,
+            operator_contact: Vec::new(),
+            cardano_emulator_block_secs: 20,
+            cardano_ogmios_url: None,
+            cardano_backends: Vec::new(),
+            cardano_quorum: 1,
+            cardano_cache_immutable_secs: 3600,
+            cardano_cache_volatile_secs: 5,
+            cardano_cache_negative_secs: 2,
+            cardano_request_budget: None,
+            cardano_index_poll_secs: 0,
+            withdrawal_reconcile_secs: 0,
+        }
+    }
+
+    /// Parse `args` like [`Parser::try_parse_from`], then fill every setting
+    /// that was not given as a flag or env var from the `--config` file.
+    ///
+    /// Precedence, highest first: flags, env vars, config file, defaults.
+    /// Settings that need one another are checked once merged, so a flag
+    /// may pair with a key from the file.
+    pub fn try_load_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args)?;
+        let mut config = Self::from_arg_matches(&matches)?;
+
+        if let Some(("server", server_matches)) = matches.subcommand() {
+            if let Some(path) = config.config_file() {
+                let file = ConfigFile::load(&path).map_err(|e| {
+                    Self::command()
+                        .error(ErrorKind::InvalidValue, e.to_string())
+                })?;
+                config.apply_file(server_matches, file);
+            }
+
+            config
+                .effective_file()
+                .validate_requirements()
+                .map_err(|e| {
+                    Self::command().error(
+                        ErrorKind::MissingRequiredArgument,
+                        e.to_string(),
+                    )
+                })?;
+        }
+
+        Ok(config)
+    }
+
+    /// Resolve the server settings a `check-config` invocation refers to.
+    pub fn resolve_check_config(&self) -> Result<Self, clap::Error> {
+        match self {
+            Self::CheckConfig { args } => Self::try_load_from(
+                ["mugraph-node", "server"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(args.iter().cloned()),
+            ),
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn apply_file(&mut self, matches: &ArgMatches, file: ConfigFile) {
+        let Self::Server {
+            config_file: _,
+            addr,
+            seed,
+            secret_key,
+            cardano_network,
+            cardano_provider,
+            cardano_api_key,
+            cardano_provider_url,
+            cardano_ogmios_url,
+            cardano_backends,
+            cardano_quorum,
+            cardano_cache_immutable_secs,
+            cardano_cache_volatile_secs,
+            cardano_cache_negative_secs,
+            cardano_request_budget,
+            cardano_index_poll_secs,
+            cardano_emulator_block_secs,
+            cardano_payment_sk,
+            xnode_peer_registry_file,
+            xnode_node_id,
+            deposit_confirm_depth,
+            deposit_expiration_blocks,
+            min_deposit_value,
+            max_tx_size,
+            max_withdrawal_fee,
+            fee_tolerance_pct,
+            withdrawal_reconcile_secs,
+            dev_mode,
+            spent_archive_after_secs,
+            spent_archive_interval_secs,
+            event_retention_secs,
+            event_max_streams,
+            metrics_addr,
+            metrics_assets,
+            otlp_endpoint,
+            rpc_max_body_bytes,
+            rpc_rate_limit,
+            rpc_method_rate_limits,
+            rpc_max_concurrent_expensive,
+            rpc_max_batch_size,
+            rpc_trusted_proxies,
+            tls_cert_file,
+            tls_key_file,
+            xnode_client_ca_file,
+            node_info_ttl_secs,
+            operator_contact,
+        } = self
+        else {
+            return;
+        };
+
+        fn layer<T>(
+            matches: &ArgMatches,
+            id: &str,
+            target: &mut T,
+            value: Option<T>,
+        ) {
+            let explicit = matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            );
+            if !explicit && let Some(value) = value {
+                *target = value;
+            }
+        }
+
+        let ConfigFile {
+            server,
+            cardano,
+            deposit,
+            withdraw,
+            xnode,
+            archive,
+            events,
+            metrics,
+            tracing,
+            rpc,
+            tls,
+            info,
+        } = file;
+
+        layer(matches, "addr", addr, server.addr);
+        layer(
+            matches,
+            "seed",
+            seed,
+            server.seed.and_then(Seed::value).map(Some),
+        );
+        layer(
+            matches,
+            "secret_key",
+            secret_key,
+            server.secret_key.map(Some),
+        );
+        layer(matches, "dev_mode", dev_mode, server.dev_mode);
+        layer(matches, "cardano_network", cardano_network, cardano.network);
+        layer(
+            matches,
+            "cardano_provider",
+            cardano_provider,
+            cardano.provider,
+        );
+        layer(
+            matches,
+            "cardano_api_key",
+            cardano_api_key,
+            cardano.api_key.map(Some),
+        );
+        layer(
+            matches,
+            "cardano_provider_url",
+            cardano_provider_url,
+            cardano.provider_url.map(Some),
+        );
+        layer(
+            matches,
+            "cardano_ogmios_url",
+            cardano_ogmios_url,
+            cardano.ogmios_url.map(Some),
+        );
+        layer(
+            matches,
+            "cardano_backends",
+            cardano_backends,
+            cardano.backends,
+        );
+        layer(matches, "cardano_quorum", cardano_quorum, cardano.quorum);
+        layer(
+            matches,
+            "cardano_cache_immutable_secs",
+            cardano_cache_immutable_secs,
+            cardano.cache_immutable_secs,
+        );
+        layer(
+            matches,
+            "cardano_cache_volatile_secs",
+            cardano_cache_volatile_secs,
+            cardano.cache_volatile_secs,
+        );
+        layer(
+            matches,
+            "cardano_cache_negative_secs",
+            cardano_cache_negative_secs,
+            cardano.cache_negative_secs,
+        );
+        layer(
+            matches,
+            "cardano_request_budget",
+            cardano_request_budget,
+            cardano.request_budget.map(Some),
+        );
+        layer(
+            matches,
+            "cardano_index_poll_secs",
+            cardano_index_poll_secs,
+            cardano.index_poll_secs,
+        );
+        layer(
+            matches,
+            "cardano_emulator_block_secs",
+            cardano_emulator_block_secs,
+            cardano.emulator_block_secs,
+        );
+        layer(
+            matches,
+            "cardano_payment_sk",
+            cardano_payment_sk,
+            cardano.payment_sk.map(Some),
+        );
+        layer(
+            matches,
+            "xnode_peer_registry_file",
+            xnode_peer_registry_file,
+            xnode.peer_registry_file.map(Some),
+        );
+        layer(matches, "xnode_node_id", xnode_node_id, xnode.node_id);
+        layer(
+            matches,
+            "deposit_confirm_depth",
+            deposit_confirm_depth,
+            deposit.confirm_depth,
+        );
+        layer(
+            matches,
+            "deposit_expiration_blocks",
+            deposit_expiration_blocks,
+            deposit.expiration_blocks,
+        );
+        layer(
+            matches,
+            "min_deposit_value",
+            min_deposit_value,
+            deposit.min_value.map(Some),
+        );
+        layer(matches, "max_tx_size", max_tx_size, withdraw.max_tx_size);
+        layer(
+            matches,
+            "max_withdrawal_fee",
+            max_withdrawal_fee,
+            withdraw.max_fee,
+        );
+        layer(
+            matches,
+            "fee_tolerance_pct",
+            fee_tolerance_pct,
+            withdraw.fee_tolerance_pct,
+        );
+        layer(
+            matches,
+            "withdrawal_reconcile_secs",
+            withdrawal_reconcile_secs,
+            withdraw.reconcile_secs,
+        );
+        layer(
+            matches,
+            "spent_archive_after_secs",
+            spent_archive_after_secs,
+            archive.spent_after_secs.map(Some),
+        );
+        layer(
+            matches,
+            "spent_archive_interval_secs",
+            spent_archive_interval_secs,
+            archive.interval_secs,
+        );
+        layer(
+            matches,
+            "event_retention_secs",
+            event_retention_secs,
+            events.retention_secs,
+        );
+        layer(
+            matches,
+            "event_max_streams",
+            event_max_streams,
+            events.max_streams,
+        );
+        layer(
+            matches,
+            "metrics_addr",
+            metrics_addr,
+            metrics.addr.map(Some),
+        );
+        layer(matches, "metrics_assets", metrics_assets, metrics.assets);
+        layer(
+            matches,
+            "otlp_endpoint",
+            otlp_endpoint,
+            tracing.otlp_endpoint.map(Some),
+        );
+        layer(
+            matches,
+            "rpc_max_body_bytes",
+            rpc_max_body_bytes,
+            rpc.max_body_bytes,
+        );
+        layer(matches, "rpc_rate_limit", rpc_rate_limit, rpc.rate_limit);
+        layer(
+            matches,
+            "rpc_method_rate_limits",
+            rpc_method_rate_limits,
+            rpc.method_rate_limits.map(|limits| {
+                limits
+                    .into_iter()
+                    .map(|(method, per_sec)| MethodRateLimit {
+                        method,
+                        per_sec,
+                    })
+                    .collect()
+            }),
+        );
+        layer(
+            matches,
+            "rpc_max_concurrent_expensive",
+            rpc_max_concurrent_expensive,
+            rpc.max_concurrent_expensive,
+        );
+        layer(
+            matches,
+            "rpc_max_batch_size",
+            rpc_max_batch_size,
+            rpc.max_batch_size,
+        );
+        layer(
+            matches,
+            "rpc_trusted_proxies",
+            rpc_trusted_proxies,
+            rpc.trusted_proxies,
+        );
+        layer(
+            matches,
+            "tls_cert_file",
+            tls_cert_file,
+            tls.cert_file.map(Some),
+        );
+        layer(
+            matches,
+            "tls_key_file",
+            tls_key_file,
+            tls.key_file.map(Some),
+        );
+        layer(
+            matches,
+            "xnode_client_ca_file",
+            xnode_client_ca_file,
+            xnode.client_ca_file.map(Some),
+        );
+        layer(
+            matches,
+            "node_info_ttl_secs",
+            node_info_ttl_secs,
+            info.ttl_secs,
+        );
+        layer(matches, "operator_contact", operator_contact, info.contact);
+    }
+
+    /// The effective server settings in config file form.
+    ///
+    /// Secrets are included verbatim; call [`ConfigFile::redacted`] before
+    /// displaying the result.
+    pub fn effective_file(&self) -> ConfigFile {
+        let Self::Server {
+            addr,
+            seed,
+            secret_key,
+            dev_mode,
+            ..
+        } = self
+        else {
+            return ConfigFile::default();
+        };
+
+        ConfigFile {
+            server: ServerSection {
+                addr: Some(*addr),
+                seed: seed.map(Seed::Value),
+                secret_key: secret_key.clone(),
+                dev_mode: Some(*dev_mode),
+            },
+            cardano: CardanoSection {
+                network: Some(self.network()),
+                provider: Some(self.provider_type()),
+                api_key: self.provider_api_key_opt(),
+                provider_url: self.provider_url(),
+                ogmios_url: self.ogmios_url(),
+                backends: Some(self.provider_backends()),
+                quorum: Some(self.provider_quorum()),
+                cache_immutable_secs: Some(
+                    self.provider_cache().immutable.as_secs(),
+                ),
+                cache_volatile_secs: Some(
+                    self.provider_cache().volatile.as_secs(),
+                ),
+                cache_negative_secs: Some(
+                    self.provider_cache().negative.as_secs(),
+                ),
+                request_budget: self.provider_cache().budget_per_minute,
+                index_poll_secs: Some(self.index_poll_secs()),
+                emulator_block_secs: Some(self.emulator_block_secs()),
+                payment_sk: self.payment_sk(),
+            },
+            deposit: DepositSection {
+                confirm_depth: Some(self.deposit_confirm_depth()),
+                expiration_blocks: Some(self.deposit_expiration_blocks()),
+                min_value: Some(self.min_deposit_value()),
+            },
+            withdraw: WithdrawSection {
+                max_tx_size: Some(self.max_tx_size()),
+                max_fee: Some(self.max_withdrawal_fee()),
+                fee_tolerance_pct: Some(self.fee_tolerance_pct()),
+                reconcile_secs: Some(self.withdrawal_reconcile_secs()),
+            },
+            xnode: XNodeSection {
+                peer_registry_file: self.xnode_peer_registry_file(),
+                node_id: Some(self.xnode_node_id()),
+                client_ca_file: self.xnode_client_ca_file(),
+            },
+            archive: ArchiveSection {
+                spent_after_secs: self.spent_archive_after_secs(),
+                interval_secs: Some(self.spent_archive_interval_secs()),
+            },
+            events: EventsSection {
+                retention_secs: Some(self.event_retention_secs()),
+                max_streams: Some(self.event_max_streams()),
+            },
+            metrics: MetricsSection {
+                addr: self.metrics_addr(),
+                assets: Some(self.metrics_assets()),
+            },
+            tracing: TracingSection {
+                otlp_endpoint: self.otlp_endpoint(),
+            },
+            rpc: RpcSection {
+                max_body_bytes: Some(self.rpc_max_body_bytes()),
+                rate_limit: Some(self.rpc_rate_limit()),
+                method_rate_limits: Some(
+                    self.rpc_method_rate_limits()
+                        .into_iter()
+                        .map(|limit| (limit.method, limit.per_sec))
+                        .collect(),
+                ),
+                max_concurrent_expensive: Some(
+                    self.rpc_max_concurrent_expensive(),
+                ),
+                max_batch_size: Some(self.rpc_max_batch_size()),
+                trusted_proxies: Some(self.rpc_trusted_proxies()),
+            },
+            tls: TlsSection {
+                cert_file: self.tls_cert_file(),
+                key_file: self.tls_key_file(),
+            },
+            info: InfoSection {
+                ttl_secs: Some(self.node_info_ttl_secs()),
+                contact: Some(self.operator_contact()),
+            },
+        }
+    }
+
+    /// Get the config file path, if one was given
+    pub fn config_file(&self) -> Option<String> {
+        match self {
+            Self::Server { config_file, .. } => config_file.clone(),
+            _ => None,
+        }
     }
 
     /// Get the Cardano network
@@ -149,6 +978,15 @@ impl Config {
         }
     }
 
+    fn provider_api_key_opt(&self) -> Option<String> {
+        match self {
+            Self::Server {
+                cardano_api_key, ..
+            } => cardano_api_key.clone(),
+            _ => None,
+        }
+    }
+
     /// Get the provider URL
     pub fn provider_url(&self) -> Option<String> {
         match self {
@@ -160,6 +998,83 @@ impl Config {
         }
     }
 
+    /// Get the Ogmios URL for the ogmios provider
+    pub fn ogmios_url(&self) -> Option<String> {
+        match self {
+            Self::Server {
+                cardano_ogmios_url, ..
+            } => cardano_ogmios_url.clone(),
+            _ => None,
+        }
+    }
+
+    /// Get the extra provider backends behind the primary one
+    pub fn provider_backends(&self) -> Vec<ProviderBackend> {
+        match self {
+            Self::Server {
+                cardano_backends, ..
+            } => cardano_backends.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get how many provider backends must agree on critical queries
+    pub fn provider_quorum(&self) -> usize {
+        match self {
+            Self::Server { cardano_quorum, .. } => *cardano_quorum,
+            _ => 1,
+        }
+    }
+
+    /// Get how long provider answers are cached and how many provider
+    /// requests may be made per minute
+    pub fn provider_cache(&self) -> CachePolicy {
+        match self {
+            Self::Server {
+                cardano_cache_immutable_secs,
+                cardano_cache_volatile_secs,
+                cardano_cache_negative_secs,
+                cardano_request_budget,
+                ..
+            } => CachePolicy {
+                immutable: Duration::from_secs(*cardano_cache_immutable_secs),
+                volatile: Duration::from_secs(*cardano_cache_volatile_secs),
+                negative: Duration::from_secs(*cardano_cache_negative_secs),
+                settled_depth: self.deposit_confirm_depth(),
+                budget_per_minute: *cardano_request_budget,
+            },
+            _ => CachePolicy::default(),
+        }
+    }
+
+    /// Get the seconds between chain index polls, 0 when the index is off
+    pub fn index_poll_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                cardano_index_poll_secs,
+                ..
+            } => *cardano_index_poll_secs,
+            _ => 0,
+        }
+    }
+
+    /// Get the seconds between blocks on the emulated ledger
+    pub fn emulator_block_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                cardano_emulator_block_secs,
+                ..
+            } => *cardano_emulator_block_secs,
+            _ => 20,
+        }
+    }
+
+    /// Whether Cardano calls go to the in-process ledger emulator, which is
+    /// only honoured in dev mode
+    pub fn emulated_ledger(&self) -> bool {
+        self.dev_mode() && self.provider_type() == EMULATOR_PROVIDER
+    }
+
     /// Get the optional payment signing key
     pub fn payment_sk(&self) -> Option<String> {
         match self {
@@ -252,6 +1167,18 @@ impl Config {
         }
     }
 
+    /// Get the seconds between withdrawal reconciliation passes, 0 when
+    /// they are off
+    pub fn withdrawal_reconcile_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                withdrawal_reconcile_secs,
+                ..
+            } => *withdrawal_reconcile_secs,
+            _ => 0,
+        }
+    }
+
     /// Whether dev mode is enabled (skips chain dependencies)
     pub fn dev_mode(&self) -> bool {
         match self {
@@ -260,8 +1187,190 @@ impl Config {
         }
     }
 
+    /// Get the minimum age of spent notes before they are archived
+    pub fn spent_archive_after_secs(&self) -> Option<u64> {
+        match self {
+            Self::Server {
+                spent_archive_after_secs,
+                ..
+            } => *spent_archive_after_secs,
+            _ => None,
+        }
+    }
+
+    /// Get the seconds between spent-set archive passes
+    pub fn spent_archive_interval_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                spent_archive_interval_secs,
+                ..
+            } => *spent_archive_interval_secs,
+            _ => 3600,
+        }
+    }
+
+    /// Get how long events are kept in the event log (0 keeps them all)
+    pub fn event_retention_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                event_retention_secs,
+                ..
+            } => *event_retention_secs,
+            _ => 0,
+        }
+    }
+
+    /// Get the cap on concurrent event streams (0 means unlimited)
+    pub fn event_max_streams(&self) -> usize {
+        match self {
+            Self::Server {
+                event_max_streams, ..
+            } => *event_max_streams,
+            _ => 0,
+        }
+    }
+
+    /// Get the separate admin address for `/metrics`, if configured
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Server { metrics_addr, .. } => *metrics_addr,
+            _ => None,
+        }
+    }
+
+    /// Get the asset units reported individually in asset flow metrics
+    pub fn metrics_assets(&self) -> Vec<String> {
+        match self {
+            Self::Server { metrics_assets, .. } => metrics_assets.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the OTLP collector endpoint, if span export is enabled
+    pub fn otlp_endpoint(&self) -> Option<String> {
+        match self {
+            Self::Server { otlp_endpoint, .. } => otlp_endpoint.clone(),
+            _ => None,
+        }
+    }
+
+    /// Get the largest accepted `/rpc` body in bytes
+    pub fn rpc_max_body_bytes(&self) -> usize {
+        match self {
+            Self::Server {
+                rpc_max_body_bytes, ..
+            } => *rpc_max_body_bytes,
+            _ => 2 * 1024 * 1024,
+        }
+    }
+
+    /// Get the per-client `/rpc` request rate (0 means unlimited)
+    pub fn rpc_rate_limit(&self) -> u32 {
+        match self {
+            Self::Server { rpc_rate_limit, .. } => *rpc_rate_limit,
+            _ => 0,
+        }
+    }
+
+    /// Get the per-client rates for individual RPC methods
+    pub fn rpc_method_rate_limits(&self) -> Vec<MethodRateLimit> {
+        match self {
+            Self::Server {
+                rpc_method_rate_limits,
+                ..
+            } => rpc_method_rate_limits.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the cap on concurrent deposit and withdraw requests (0 means
+    /// unlimited)
+    pub fn rpc_max_concurrent_expensive(&self) -> usize {
+        match self {
+            Self::Server {
+                rpc_max_concurrent_expensive,
+                ..
+            } => *rpc_max_concurrent_expensive,
+            _ => 0,
+        }
+    }
+
+    /// Get the most requests a batch may carry (0 means batches are
+    /// disabled)
+    pub fn rpc_max_batch_size(&self) -> usize {
+        match self {
+            Self::Server {
+                rpc_max_batch_size, ..
+            } => *rpc_max_batch_size,
+            _ => 0,
+        }
+    }
+
+    /// Get the reverse proxies trusted to name the client in
+    /// `X-Forwarded-For`
+    pub fn rpc_trusted_proxies(&self) -> Vec<IpAddr> {
+        match self {
+            Self::Server {
+                rpc_trusted_proxies,
+                ..
+            } => rpc_trusted_proxies.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the HTTPS certificate chain path, if TLS is enabled
+    pub fn tls_cert_file(&self) -> Option<String> {
+        match self {
+            Self::Server { tls_cert_file, .. } => tls_cert_file.clone(),
+            _ => None,
+        }
+    }
+
+    /// Get the HTTPS private key path, if TLS is enabled
+    pub fn tls_key_file(&self) -> Option<String> {
+        match self {
+            Self::Server { tls_key_file, .. } => tls_key_file.clone(),
+            _ => None,
+        }
+    }
+
+    /// Get the CA bundle xnode client certificates must chain to, if mutual
+    /// TLS is enabled
+    pub fn xnode_client_ca_file(&self) -> Option<String> {
+        match self {
+            Self::Server {
+                xnode_client_ca_file,
+                ..
+            } => xnode_client_ca_file.clone(),
+            _ => None,
+        }
+    }
+
+    /// Get how long a signed node info document stays valid
+    pub fn node_info_ttl_secs(&self) -> u64 {
+        match self {
+            Self::Server {
+                node_info_ttl_secs, ..
+            } => *node_info_ttl_secs,
+            _ => 0,
+        }
+    }
+
+    /// Get the operator contact URIs published in the node info document
+    pub fn operator_contact(&self) -> Vec<String> {
+        match self {
+            Self::Server {
+                operator_contact, ..
+            } => operator_contact.clone(),
+            _ => Vec::new(),
+        }
+    }
+
     pub fn keypair(&self) -> Result<Keypair, Error> {
         match self {
+            Self::CheckConfig { .. } => Err(Error::InvalidInput {
+                reason: "check-config does not run a node".to_string(),
+            }),
             Self::GenerateKey => {
                 let mut rng = ChaCha20Rng::seed_from_u64(rng().random());
                 Ok(Keypair::random(&mut rng))
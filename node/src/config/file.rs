@@ -0,0 +1,673 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+};
+
+use mugraph_core::error::Error;
+use serde::{Deserialize, Serialize};
+
+use super::ProviderBackend;
+use crate::{
+    network::CardanoNetwork,
+    provider::{EMULATOR_PROVIDER, OGMIOS_PROVIDER},
+    routes::RPC_METHODS,
+};
+
+pub(super) const REDACTED: &str = "<redacted>";
+
+/// Provider backends accepted by `cardano.provider`.
+pub(super) const KNOWN_PROVIDERS: &[&str] = &[
+    "blockfrost",
+    "maestro",
+    "koios",
+    OGMIOS_PROVIDER,
+    EMULATOR_PROVIDER,
+];
+
+/// On-disk TOML configuration for `server`.
+///
+/// Every key is optional. Values here sit underneath environment variables
+/// and command-line flags, which always win when present.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(skip_serializing_if = "is_default")]
+    pub server: ServerSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub cardano: CardanoSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub deposit: DepositSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub withdraw: WithdrawSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub xnode: XNodeSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub archive: ArchiveSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub events: EventsSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub metrics: MetricsSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub tracing: TracingSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub rpc: RpcSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub tls: TlsSection,
+    #[serde(skip_serializing_if = "is_default")]
+    pub info: InfoSection,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<Seed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev_mode: Option<bool>,
+}
+
+/// `server.seed`. The delegate key is derived from it, so it is as secret
+/// as `secret_key` and is rendered as a placeholder once redacted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "u64")]
+pub enum Seed {
+    Value(u64),
+    Redacted,
+}
+
+impl Seed {
+    pub fn value(self) -> Option<u64> {
+        match self {
+            Self::Value(seed) => Some(seed),
+            Self::Redacted => None,
+        }
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(seed: u64) -> Self {
+        Self::Value(seed)
+    }
+}
+
+impl Serialize for Seed {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Value(seed) => s.serialize_u64(*seed),
+            Self::Redacted => s.serialize_str(REDACTED),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CardanoSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ogmios_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backends: Option<Vec<ProviderBackend>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_immutable_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_volatile_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_negative_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_budget: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_poll_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emulator_block_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_sk: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DepositSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_depth: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_blocks: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WithdrawSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tx_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_tolerance_pct: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct XNodeSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_registry_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spent_after_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_streams: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method_rate_limits: Option<BTreeMap<String, u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_expensive: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trusted_proxies: Option<Vec<IpAddr>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfoSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+}
+
+fn invalid_key(
+    path: &Path,
+    key: &str,
+    reason: impl std::fmt::Display,
+) -> Error {
+    Error::InvalidInput {
+        reason: format!(
+            "invalid config file {}: {key}: {reason}",
+            path.display()
+        ),
+    }
+}
+
+impl ConfigFile {
+    /// Read, parse and validate a config file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| Error::InvalidInput {
+                reason: format!(
+                    "failed to read config file {}: {e}",
+                    path.display()
+                ),
+            })?;
+
+        Self::parse(path, &contents)
+    }
+
+    /// Parse and validate config file contents. `path` is only used to label
+    /// error messages.
+    pub fn parse(
+        path: impl AsRef<Path>,
+        contents: &str,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file: Self =
+            toml::from_str(contents).map_err(|e| Error::InvalidInput {
+                reason: format!("invalid config file {}: {e}", path.display()),
+            })?;
+
+        file.validate(path)?;
+        Ok(file)
+    }
+
+    fn validate(&self, path: &Path) -> Result<(), Error> {
+        if let Some(network) = &self.cardano.network {
+            CardanoNetwork::parse(network)
+                .map_err(|e| invalid_key(path, "cardano.network", e))?;
+        }
+
+        if let Some(provider) = &self.cardano.provider
+            && !KNOWN_PROVIDERS.contains(&provider.as_str())
+        {
+            return Err(invalid_key(
+                path,
+                "cardano.provider",
+                format!(
+                    "unknown provider {provider:?}, expected one of {}",
+                    KNOWN_PROVIDERS.join(", ")
+                ),
+            ));
+        }
+
+        if let Some(url) = &self.cardano.provider_url
+            && reqwest::Url::parse(url).is_err()
+        {
+            return Err(invalid_key(
+                path,
+                "cardano.provider_url",
+                format!("{url:?} is not a valid URL"),
+            ));
+        }
+
+        if let Some(url) = &self.cardano.ogmios_url
+            && reqwest::Url::parse(url).is_err()
+        {
+            return Err(invalid_key(
+                path,
+                "cardano.ogmios_url",
+                format!("{url:?} is not a valid URL"),
+            ));
+        }
+
+        if let Some(reason) = self
+            .cardano
+            .backends
+            .iter()
+            .flatten()
+            .find_map(|backend| backend.validate().err())
+        {
+            return Err(invalid_key(path, "cardano.backends", reason));
+        }
+
+        if self.cardano.quorum == Some(0) {
+            return Err(invalid_key(
+                path,
+                "cardano.quorum",
+                "must be at least 1",
+            ));
+        }
+
+        if self.cardano.request_budget == Some(0) {
+            return Err(invalid_key(
+                path,
+                "cardano.request_budget",
+                "must be at least 1",
+            ));
+        }
+
+        if let Some(url) = &self.tracing.otlp_endpoint
+            && reqwest::Url::parse(url).is_err()
+        {
+            return Err(invalid_key(
+                path,
+                "tracing.otlp_endpoint",
+                format!("{url:?} is not a valid URL"),
+            ));
+        }
+
+        if let Some(secret_key) = &self.server.secret_key {
+            let valid = muhex::decode(secret_key)
+                .map(|bytes| bytes.len() == 32)
+                .unwrap_or(false);
+            if !valid {
+                return Err(invalid_key(
+                    path,
+                    "server.secret_key",
+                    "must be 32 bytes of hex",
+                ));
+            }
+        }
+
+        if let Some(pct) = self.withdraw.fee_tolerance_pct
+            && pct > 100
+        {
+            return Err(invalid_key(
+                path,
+                "withdraw.fee_tolerance_pct",
+                format!("{pct} is above 100"),
+            ));
+        }
+
+        if self.deposit.confirm_depth == Some(0) {
+            return Err(invalid_key(
+                path,
+                "deposit.confirm_depth",
+                "must be at least 1",
+            ));
+        }
+
+        if let Some(node_id) = &self.xnode.node_id
+            && node_id.trim().is_empty()
+        {
+            return Err(invalid_key(
+                path,
+                "xnode.node_id",
+                "must not be empty",
+            ));
+        }
+
+        if self.archive.interval_secs == Some(0) {
+            return Err(invalid_key(
+                path,
+                "archive.interval_secs",
+                "must be at least 1",
+            ));
+        }
+
+        if self.info.ttl_secs == Some(0) {
+            return Err(invalid_key(
+                path,
+                "info.ttl_secs",
+                "must be at least 1",
+            ));
+        }
+
+        if self.rpc.max_body_bytes == Some(0) {
+            return Err(invalid_key(
+                path,
+                "rpc.max_body_bytes",
+                "must be at least 1",
+            ));
+        }
+
+        if let Some(method) = self
+            .rpc
+            .method_rate_limits
+            .iter()
+            .flat_map(|limits| limits.keys())
+            .find(|method| !RPC_METHODS.contains(&method.as_str()))
+        {
+            return Err(invalid_key(
+                path,
+                "rpc.method_rate_limits",
+                format!(
+                    "unknown RPC method {method:?}, expected one of {}",
+                    RPC_METHODS.join(", ")
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check the settings that need one another. Flags, env vars and the
+    /// file may each supply half of a pair, so this runs on the merged
+    /// settings ([`super::Config::effective_file`]) rather than on the file
+    /// alone.
+    pub fn validate_requirements(&self) -> Result<(), Error> {
+        for (key, value, required, required_value) in [
+            (
+                "tls.cert_file",
+                &self.tls.cert_file,
+                "tls.key_file",
+                &self.tls.key_file,
+            ),
+            (
+                "tls.key_file",
+                &self.tls.key_file,
+                "tls.cert_file",
+                &self.tls.cert_file,
+            ),
+            (
+                "xnode.client_ca_file",
+                &self.xnode.client_ca_file,
+                "tls.cert_file",
+                &self.tls.cert_file,
+            ),
+        ] {
+            if value.is_some() && required_value.is_none() {
+                return Err(Error::InvalidInput {
+                    reason: format!("{required} is required when {key} is set"),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy of this file with every secret replaced by a placeholder.
+    pub fn redacted(mut self) -> Self {
+        for secret in [
+            &mut self.server.secret_key,
+            &mut self.cardano.api_key,
+            &mut self.cardano.payment_sk,
+        ]
+        .into_iter()
+        .chain(
+            self.cardano
+                .backends
+                .iter_mut()
+                .flatten()
+                .map(|backend| &mut backend.api_key),
+        ) {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        }
+        if self.server.seed.is_some() {
+            self.server.seed = Some(Seed::Redacted);
+        }
+
+        self
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string_pretty(self).map_err(|e| Error::Internal {
+            reason: format!("failed to render config: {e}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<ConfigFile, Error> {
+        ConfigFile::parse("node.toml", contents)
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let file = parse(
+            r#"
+            [server]
+            addr = "127.0.0.1:8080"
+            dev_mode = true
+
+            [cardano]
+            network = "preview"
+            provider = "maestro"
+
+            [deposit]
+            confirm_depth = 3
+
+            [withdraw]
+            fee_tolerance_pct = 10
+
+            [xnode]
+            node_id = "node://a"
+
+            [archive]
+            spent_after_secs = 86400
+            interval_secs = 600
+
+            [events]
+            retention_secs = 3600
+            max_streams = 32
+
+            [metrics]
+            addr = "127.0.0.1:9100"
+            assets = ["lovelace"]
+
+            [tracing]
+            otlp_endpoint = "http://localhost:4318"
+
+            [rpc]
+            rate_limit = 10
+            method_rate_limits = { withdraw = 1 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.server.addr, Some("127.0.0.1:8080".parse().unwrap()));
+        assert_eq!(file.server.dev_mode, Some(true));
+        assert_eq!(file.cardano.network.as_deref(), Some("preview"));
+        assert_eq!(file.cardano.provider.as_deref(), Some("maestro"));
+        assert_eq!(file.deposit.confirm_depth, Some(3));
+        assert_eq!(file.withdraw.fee_tolerance_pct, Some(10));
+        assert_eq!(file.xnode.node_id.as_deref(), Some("node://a"));
+        assert_eq!(file.archive.spent_after_secs, Some(86400));
+        assert_eq!(file.archive.interval_secs, Some(600));
+        assert_eq!(file.events.retention_secs, Some(3600));
+        assert_eq!(file.events.max_streams, Some(32));
+        assert_eq!(file.metrics.addr, Some("127.0.0.1:9100".parse().unwrap()));
+        assert_eq!(file.metrics.assets, Some(vec!["lovelace".to_string()]));
+        assert_eq!(
+            file.tracing.otlp_endpoint.as_deref(),
+            Some("http://localhost:4318")
+        );
+        assert_eq!(file.rpc.rate_limit, Some(10));
+        assert_eq!(
+            file.rpc.method_rate_limits,
+            Some(BTreeMap::from([("withdraw".to_string(), 1)]))
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected_with_their_name() {
+        let err = parse("[deposit]\nconfirm_dept = 3\n").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("node.toml"), "{message}");
+        assert!(message.contains("confirm_dept"), "{message}");
+    }
+
+    #[test]
+    fn type_errors_point_at_the_offending_line() {
+        let err = parse("[withdraw]\nmax_fee = \"lots\"\n").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("line 2"), "{message}");
+        assert!(message.contains("max_fee"), "{message}");
+    }
+
+    #[test]
+    fn semantic_errors_name_the_dotted_key() {
+        for (contents, key) in [
+            ("[cardano]\nnetwork = \"moon\"\n", "cardano.network"),
+            ("[cardano]\nprovider = \"acme\"\n", "cardano.provider"),
+            (
+                "[cardano]\nbackends = [{ provider = \"acme\" }]\n",
+                "cardano.backends",
+            ),
+            ("[cardano]\nquorum = 0\n", "cardano.quorum"),
+            ("[cardano]\nrequest_budget = 0\n", "cardano.request_budget"),
+            (
+                "[cardano]\nprovider_url = \"nope\"\n",
+                "cardano.provider_url",
+            ),
+            ("[server]\nsecret_key = \"abcd\"\n", "server.secret_key"),
+            (
+                "[tracing]\notlp_endpoint = \"x\"\n",
+                "tracing.otlp_endpoint",
+            ),
+            (
+                "[withdraw]\nfee_tolerance_pct = 101\n",
+                "withdraw.fee_tolerance_pct",
+            ),
+            ("[deposit]\nconfirm_depth = 0\n", "deposit.confirm_depth"),
+            ("[xnode]\nnode_id = \" \"\n", "xnode.node_id"),
+            ("[rpc]\nmax_body_bytes = 0\n", "rpc.max_body_bytes"),
+            (
+                "[rpc]\nmethod_rate_limits = { refesh = 1 }\n",
+                "rpc.method_rate_limits",
+            ),
+        ] {
+            let message = parse(contents).unwrap_err().to_string();
+            assert!(message.contains(key), "{key}: {message}");
+        }
+    }
+
+    #[test]
+    fn redaction_hides_every_secret() {
+        let file = parse(
+            r#"
+            [server]
+            seed = 424242
+            secret_key = "0707070707070707070707070707070707070707070707070707070707070707"
+
+            [cardano]
+            api_key = "project-key"
+            payment_sk = "deadbeef"
+            backends = [{ provider = "maestro", api_key = "backend-key" }]
+            "#,
+        )
+        .unwrap()
+        .redacted();
+
+        let rendered = file.to_toml().unwrap();
+        assert!(!rendered.contains("0707"));
+        assert!(!rendered.contains("project-key"));
+        assert!(!rendered.contains("deadbeef"));
+        assert!(!rendered.contains("backend-key"));
+        assert!(!rendered.contains("424242"));
+        assert_eq!(rendered.matches(REDACTED).count(), 5);
+    }
+}
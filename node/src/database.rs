@@ -1,23 +1,62 @@
-use std::{fs::OpenOptions, path::PathBuf};
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fs::OpenOptions,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use metrics::counter;
 use mugraph_core::{
     error::Error,
     types::{
         CardanoWallet, CrossNodeMessageRecord, CrossNodeTransferRecord,
-        DepositRecord, IdempotencyRecord, Signature, TransferAuditEvent,
-        UtxoRef, WithdrawalKey, WithdrawalRecord,
+        DepositRecord, EventKind, EventSubject, IdempotencyRecord, NodeEvent,
+        PublicKey, Signature, TransferAuditEvent, UtxoRef, WithdrawalKey,
+        WithdrawalRecord,
     },
 };
 use redb::{
     Builder, Database as Redb, Key, ReadOnlyTable, ReadTransaction,
-    ReadableDatabase, StorageBackend, Table, TableDefinition, Value,
-    WriteTransaction, backends::FileBackend,
+    ReadableDatabase, ReadableTable, StorageBackend, Table, TableDefinition,
+    Value, WriteTransaction, backends::FileBackend,
 };
+use tokio::sync::watch;
+
+use crate::spent_archive::SpentArchive;
 
 pub const NOTES: TableDefinition<Signature, bool> =
     TableDefinition::new("notes");
 
+/// Signatures in `NOTES` keyed by the unix timestamp they were spent at, so
+/// archival reads the oldest rows first, with the public key of the keyset
+/// that signed them. Rows spent before this table existed are given the time
+/// of the migration that created it, and rows spent before keysets were
+/// recorded are given [`UNKNOWN_KEYSET`].
+pub const NOTES_SPENT_AT: TableDefinition<(u64, Signature), [u8; 32]> =
+    TableDefinition::new("notes_spent_at_by_keyset");
+
+/// The rows of [`NOTES_SPENT_AT`] with a known keyset, keyed by that keyset
+/// so the notes of a retired one can be archived whatever their age
+pub const NOTES_BY_KEYSET: TableDefinition<([u8; 32], Signature), u64> =
+    TableDefinition::new("notes_by_keyset");
+
+/// [`NOTES_SPENT_AT`] before it recorded keysets, moved over by the migration
+const LEGACY_NOTES_SPENT_AT: TableDefinition<(u64, Signature), ()> =
+    TableDefinition::new("notes_by_spent_at");
+
+/// Keyset of notes spent before keysets were recorded
+pub const UNKNOWN_KEYSET: [u8; 32] = [0; 32];
+
+/// Schema version written by [`Database::migrate`]
+pub const CURRENT_SCHEMA_VERSION: u64 = 9;
+
+/// First schema version with [`NOTES_SPENT_AT`] populated on every spend
+const SPENT_AT_SCHEMA_VERSION: u64 = 8;
+
+/// First schema version recording the keyset of every spend
+const KEYSET_SCHEMA_VERSION: u64 = 9;
+
 /// Schema version key for database migrations
 pub const SCHEMA_VERSION: TableDefinition<&str, u64> =
     TableDefinition::new("schema_version");
@@ -50,47 +89,134 @@ pub const IDEMPOTENCY_KEYS: TableDefinition<&str, IdempotencyRecord> =
 pub const TRANSFER_AUDIT_LOG: TableDefinition<&str, TransferAuditEvent> =
     TableDefinition::new("transfer_audit_log");
 
+/// Node events indexed by cursor, stored as JSON since their tagged wire
+/// form does not round-trip through bincode
+pub const EVENT_LOG: TableDefinition<u64, &[u8]> =
+    TableDefinition::new("event_log");
+
+/// Highest confirmation count published for each unspent deposit
+pub const DEPOSIT_CONFIRMATIONS: TableDefinition<UtxoRef, u64> =
+    TableDefinition::new("deposit_confirmations");
+
+/// Outputs at the script address followed by the chain indexer, stored as
+/// JSON in the provider's UTxO shape
+pub const SCRIPT_UTXOS: TableDefinition<UtxoRef, &[u8]> =
+    TableDefinition::new("script_utxos");
+
+/// Block hash of each tip the chain indexer has seen, by height, for
+/// detecting rollbacks
+pub const CHAIN_INDEX_BLOCKS: TableDefinition<u64, &str> =
+    TableDefinition::new("chain_index_blocks");
+
+/// Where the chain indexer has caught up to, stored as JSON
+pub const CHAIN_INDEX: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("chain_index");
+
+/// What the node needs to settle each withdrawal after the request that
+/// burned its notes is gone, stored as JSON
+pub const WITHDRAWAL_SUBMISSIONS: TableDefinition<WithdrawalKey, &[u8]> =
+    TableDefinition::new("withdrawal_submissions");
+
 const METRIC_DB_READ: &str = "mugraph.node.database.read";
 const METRIC_DB_WRITE: &str = "mugraph.node.database.write";
 const METRIC_DB_WRITE_OPEN_TABLE: &str =
     "mugraph.node.database.write.open_table";
 const METRIC_DB_WRITE_COMMIT: &str = "mugraph.node.database.write.commit";
+const METRIC_DB_ARCHIVED: &str = "mugraph.node.database.notes_archived";
+const METRIC_DB_EVENTS: &str = "mugraph.node.database.events_appended";
+
+/// Upper bound on rows moved into a single archive segment per pass.
+const MAX_ARCHIVE_BATCH: usize = 1_000_000;
 
 #[derive(Debug)]
 pub struct Database {
     db: Redb,
+    archive: SpentArchive,
+    /// Cursor of the latest committed event, for waking subscribers
+    events: watch::Sender<u64>,
 }
 
-#[repr(transparent)]
-pub struct Read(ReadTransaction);
+/// Read transaction; its span stays open for as long as the transaction.
+pub struct Read {
+    tx: ReadTransaction,
+    span: tracing::Span,
+}
 
 impl Read {
-    #[tracing::instrument(skip_all)]
+    #[tracing::instrument(parent = &self.span, skip_all)]
     pub fn open_table<K: Key, V: Value>(
         &self,
         table: TableDefinition<K, V>,
     ) -> Result<ReadOnlyTable<K, V>, Error> {
-        Ok(self.0.open_table(table)?)
+        Ok(self.tx.open_table(table)?)
     }
 }
 
-#[repr(transparent)]
-pub struct Write(WriteTransaction);
+/// Write transaction; its span stays open until commit or abort.
+pub struct Write {
+    tx: WriteTransaction,
+    span: tracing::Span,
+    events: watch::Sender<u64>,
+    /// Cursor of the last event appended in this transaction
+    appended: Cell<Option<u64>>,
+}
 
 impl Write {
-    #[tracing::instrument(skip_all)]
+    #[tracing::instrument(parent = &self.span, skip_all)]
     pub fn open_table<K: Key, V: Value>(
         &self,
         table: TableDefinition<K, V>,
     ) -> Result<Table<'_, K, V>, Error> {
         counter!(METRIC_DB_WRITE_OPEN_TABLE).increment(1);
-        Ok(self.0.open_table(table)?)
+        Ok(self.tx.open_table(table)?)
+    }
+
+    /// Append an event to [`EVENT_LOG`]; subscribers see it once the
+    /// transaction commits.
+    #[tracing::instrument(parent = &self.span, skip_all)]
+    pub fn append_event(
+        &self,
+        subject: EventSubject,
+        event: EventKind,
+    ) -> Result<u64, Error> {
+        let mut table = self.tx.open_table(EVENT_LOG)?;
+        let cursor = match table.last()? {
+            Some((k, _)) => k.value() + 1,
+            None => 1,
+        };
+        let event = NodeEvent {
+            cursor,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            subject,
+            event,
+        };
+        let bytes =
+            serde_json::to_vec(&event).map_err(|e| Error::Internal {
+                reason: format!("failed to encode event: {e}"),
+            })?;
+        table.insert(cursor, bytes.as_slice())?;
+        self.appended.set(Some(cursor));
+
+        Ok(cursor)
     }
 
-    #[tracing::instrument(skip_all)]
+    #[tracing::instrument(parent = &self.span, skip_all)]
     pub fn commit(self) -> Result<(), Error> {
         counter!(METRIC_DB_WRITE_COMMIT).increment(1);
-        Ok(self.0.commit()?)
+        self.tx.commit()?;
+        if let Some(cursor) = self.appended.get() {
+            counter!(METRIC_DB_EVENTS).increment(1);
+            self.events.send_replace(cursor);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(parent = &self.span, skip_all)]
+    pub fn abort(self) -> Result<(), Error> {
+        Ok(self.tx.abort()?)
     }
 }
 
@@ -103,6 +229,7 @@ impl Database {
             std::fs::create_dir_all(parent)?;
         }
 
+        let archive = SpentArchive::open(path.with_extension("spent"))?;
         let is_new = !path.exists();
         let file = OpenOptions::new()
             .read(true)
@@ -114,6 +241,8 @@ impl Database {
 
         Ok(Self {
             db: Self::setup_with_backend(backend, is_new)?,
+            archive,
+            events: watch::Sender::new(0),
         })
     }
 
@@ -144,6 +273,7 @@ impl Database {
 
     /// Run database migrations to create new tables
     pub fn migrate(&self) -> Result<(), Error> {
+        let previous_version = self.schema_version()?;
         let w = self.db.begin_write()?;
 
         // Create CARDANO_WALLET table if it doesn't exist
@@ -181,10 +311,61 @@ impl Database {
             let _ = w.open_table(TRANSFER_AUDIT_LOG)?;
         }
 
+        // Create NOTES_SPENT_AT table if it doesn't exist, dating notes spent
+        // before it existed to now so they are not archived straight away,
+        // and carry over the rows dated before keysets were recorded
+        {
+            let mut spent_at = w.open_table(NOTES_SPENT_AT)?;
+            let _ = w.open_table(NOTES_BY_KEYSET)?;
+            if previous_version < SPENT_AT_SCHEMA_VERSION {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let notes = w.open_table(NOTES)?;
+                for row in notes.iter()? {
+                    let signature = row?.0.value();
+                    if signature != Signature::zero() {
+                        spent_at.insert((now, signature), UNKNOWN_KEYSET)?;
+                    }
+                }
+            } else if previous_version < KEYSET_SCHEMA_VERSION {
+                {
+                    let legacy = w.open_table(LEGACY_NOTES_SPENT_AT)?;
+                    for row in legacy.iter()? {
+                        spent_at.insert(row?.0.value(), UNKNOWN_KEYSET)?;
+                    }
+                }
+                w.delete_table(LEGACY_NOTES_SPENT_AT)?;
+            }
+        }
+
+        // Create EVENT_LOG table if it doesn't exist
+        {
+            let _ = w.open_table(EVENT_LOG)?;
+        }
+
+        // Create DEPOSIT_CONFIRMATIONS table if it doesn't exist
+        {
+            let _ = w.open_table(DEPOSIT_CONFIRMATIONS)?;
+        }
+
+        // Create the chain index tables if they don't exist
+        {
+            let _ = w.open_table(SCRIPT_UTXOS)?;
+            let _ = w.open_table(CHAIN_INDEX_BLOCKS)?;
+            let _ = w.open_table(CHAIN_INDEX)?;
+        }
+
+        // Create WITHDRAWAL_SUBMISSIONS table if it doesn't exist
+        {
+            let _ = w.open_table(WITHDRAWAL_SUBMISSIONS)?;
+        }
+
         // Update schema version
         {
             let mut t = w.open_table(SCHEMA_VERSION)?;
-            t.insert("version", 3)?;
+            t.insert("version", CURRENT_SCHEMA_VERSION)?;
         }
 
         w.commit()?;
@@ -201,23 +382,228 @@ impl Database {
         }
     }
 
-    #[tracing::instrument(skip_all)]
     #[inline]
     pub fn read(&self) -> Result<Read, Error> {
-        let result = self.db.begin_read().map(Read).map_err(Error::from)?;
+        let result = self
+            .db
+            .begin_read()
+            .map(|tx| Read {
+                tx,
+                span: tracing::info_span!("db_transaction", mode = "read"),
+            })
+            .map_err(Error::from)?;
         counter!(METRIC_DB_READ).increment(1);
 
         Ok(result)
     }
 
-    #[tracing::instrument(skip_all)]
     #[inline]
     pub fn write(&self) -> Result<Write, Error> {
-        let result = self.db.begin_write().map(Write).map_err(Error::from)?;
+        let result = self
+            .db
+            .begin_write()
+            .map(|tx| Write {
+                tx,
+                span: tracing::info_span!("db_transaction", mode = "write"),
+                events: self.events.clone(),
+                appended: Cell::new(None),
+            })
+            .map_err(Error::from)?;
         counter!(METRIC_DB_WRITE).increment(1);
 
         Ok(result)
     }
+
+    /// Wait for any in-flight write and commit an empty durable transaction,
+    /// so everything written so far is on disk before the process exits.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.write()?.commit()
+    }
+
+    /// Up to `limit` events with a cursor greater than `after`, oldest first.
+    ///
+    /// Fails with [`Error::CursorExpired`] when events after `after` have
+    /// already been pruned. Rows that fail to decode are logged and skipped.
+    pub fn events_after(
+        &self,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<NodeEvent>, Error> {
+        let r = self.read()?;
+        let table = r.open_table(EVENT_LOG)?;
+        check_event_cursor(&table, after)?;
+
+        let mut events = Vec::new();
+        for row in table.range(after.saturating_add(1)..)?.take(limit) {
+            let (k, v) = row?;
+            match serde_json::from_slice(v.value()) {
+                Ok(event) => events.push(event),
+                Err(e) => tracing::error!(
+                    cursor = k.value(),
+                    "skipping undecodable event: {e}"
+                ),
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Fail with [`Error::CursorExpired`] if events after `after` have been
+    /// pruned, so reading from it would skip them.
+    pub fn check_event_cursor(&self, after: u64) -> Result<(), Error> {
+        let r = self.read()?;
+        check_event_cursor(&r.open_table(EVENT_LOG)?, after)
+    }
+
+    /// Delete events created before `created_before` (unix seconds), oldest
+    /// first, and return how many were deleted.
+    ///
+    /// The newest event is always kept, since the next cursor follows it.
+    #[tracing::instrument(skip(self))]
+    pub fn prune_events(&self, created_before: u64) -> Result<usize, Error> {
+        let w = self.write()?;
+        let pruned = {
+            let mut table = w.open_table(EVENT_LOG)?;
+            let Some(newest) = table.last()?.map(|(k, _)| k.value()) else {
+                return Ok(0);
+            };
+
+            let mut expired = Vec::new();
+            for row in table.range(..newest)? {
+                let (k, v) = row?;
+                // Undecodable rows carry no timestamp; they go with the
+                // expired events around them
+                let created_at = serde_json::from_slice::<NodeEvent>(v.value())
+                    .map_or(0, |event| event.created_at);
+                if created_at >= created_before {
+                    break;
+                }
+                expired.push(k.value());
+            }
+
+            for cursor in &expired {
+                table.remove(cursor)?;
+            }
+            expired.len()
+        };
+        w.commit()?;
+
+        if pruned > 0 {
+            tracing::info!(pruned, "pruned expired events");
+        }
+
+        Ok(pruned)
+    }
+
+    /// Receiver that changes whenever a transaction with events commits.
+    pub fn subscribe_events(&self) -> watch::Receiver<u64> {
+        self.events.subscribe()
+    }
+
+    /// Archive of spent signatures that have been moved out of `NOTES`.
+    pub fn spent_archive(&self) -> &SpentArchive {
+        &self.archive
+    }
+
+    /// Whether `signature` was spent and has since been archived.
+    ///
+    /// Double-spend checks must consult this in addition to `NOTES`.
+    #[inline]
+    pub fn is_archived(&self, signature: &Signature) -> Result<bool, Error> {
+        self.archive.contains(signature)
+    }
+
+    /// Move every spent signature recorded at or before `spent_before` (unix
+    /// seconds), and every one signed by a keyset other than `active_keyset`,
+    /// out of the live tables and into a new archive segment. Notes of a
+    /// retired keyset no longer verify, so they are archived whatever their
+    /// age.
+    ///
+    /// The segment is made durable and visible to lookups before the rows are
+    /// deleted, so a signature is never absent from both places. Returns the
+    /// number of signatures archived.
+    #[tracing::instrument(skip(self))]
+    pub fn archive_spent_notes(
+        &self,
+        spent_before: u64,
+        active_keyset: &PublicKey,
+    ) -> Result<usize, Error> {
+        // Each signature with when it was spent and the keyset that signed it
+        let mut candidates = BTreeMap::new();
+        {
+            let r = self.read()?;
+            let spent_at = r.open_table(NOTES_SPENT_AT)?;
+            let last = (spent_before, Signature::from([0xff; 32]));
+
+            for row in spent_at.range(..=last)?.take(MAX_ARCHIVE_BATCH) {
+                let (key, keyset) = row?;
+                let (at, signature) = key.value();
+                candidates.insert(signature, (at, keyset.value()));
+            }
+
+            let by_keyset = r.open_table(NOTES_BY_KEYSET)?;
+            let active = active_keyset.0;
+            let retired =
+                by_keyset.range(..(active, Signature::zero()))?.chain(
+                    by_keyset.range((active, Signature::from([0xff; 32]))..)?,
+                );
+            for row in retired {
+                if candidates.len() >= MAX_ARCHIVE_BATCH {
+                    break;
+                }
+                let (key, at) = row?;
+                let (keyset, signature) = key.value();
+                if keyset != active {
+                    candidates.insert(signature, (at.value(), keyset));
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.archive
+            .add_segment(candidates.keys().copied().collect(), now)?;
+
+        let w = self.write()?;
+        {
+            let mut notes = w.open_table(NOTES)?;
+            let mut spent_at = w.open_table(NOTES_SPENT_AT)?;
+            let mut by_keyset = w.open_table(NOTES_BY_KEYSET)?;
+            for (signature, (at, keyset)) in &candidates {
+                notes.remove(signature)?;
+                spent_at.remove((*at, *signature))?;
+                if *keyset != UNKNOWN_KEYSET {
+                    by_keyset.remove((*keyset, *signature))?;
+                }
+            }
+        }
+        w.commit()?;
+
+        counter!(METRIC_DB_ARCHIVED).increment(candidates.len() as u64);
+        tracing::info!(archived = candidates.len(), "archived spent notes");
+
+        Ok(candidates.len())
+    }
+}
+
+fn check_event_cursor(
+    table: &impl ReadableTable<u64, &'static [u8]>,
+    after: u64,
+) -> Result<(), Error> {
+    match table.first()? {
+        Some((oldest, _)) if after.saturating_add(1) < oldest.value() => {
+            Err(Error::CursorExpired {
+                oldest_cursor: oldest.value(),
+            })
+        }
+        _ => Ok(()),
+    }
 }
 
 #[cfg(test)]
@@ -231,9 +617,209 @@ mod tests {
             METRIC_DB_WRITE,
             METRIC_DB_WRITE_OPEN_TABLE,
             METRIC_DB_WRITE_COMMIT,
+            METRIC_DB_ARCHIVED,
+            METRIC_DB_EVENTS,
         ] {
             assert!(metric.starts_with("mugraph.node.database"));
             assert!(!metric.contains("simulator"));
         }
     }
+
+    #[test]
+    fn archive_spent_notes_moves_only_old_rows_out_of_live_table() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::setup(dir.path().join("db.redb")).unwrap();
+        db.migrate().unwrap();
+
+        let old = Signature::from([1u8; 32]);
+        let fresh = Signature::from([3u8; 32]);
+
+        let w = db.write().unwrap();
+        {
+            let mut notes = w.open_table(NOTES).unwrap();
+            let mut spent_at = w.open_table(NOTES_SPENT_AT).unwrap();
+            for signature in [old, fresh] {
+                notes.insert(signature, true).unwrap();
+            }
+            spent_at.insert((100, old), UNKNOWN_KEYSET).unwrap();
+            spent_at.insert((500, fresh), UNKNOWN_KEYSET).unwrap();
+        }
+        w.commit().unwrap();
+
+        let active = PublicKey([7u8; 32]);
+        assert_eq!(db.archive_spent_notes(200, &active).unwrap(), 1);
+        assert_eq!(db.archive_spent_notes(200, &active).unwrap(), 0);
+
+        let r = db.read().unwrap();
+        let notes = r.open_table(NOTES).unwrap();
+        assert!(notes.get(old).unwrap().is_none());
+        assert!(notes.get(fresh).unwrap().is_some());
+        assert!(notes.get(Signature::zero()).unwrap().is_some());
+
+        assert!(db.is_archived(&old).unwrap());
+        assert!(!db.is_archived(&fresh).unwrap());
+
+        drop(notes);
+        drop(r);
+        drop(db);
+
+        let reopened = Database::setup(dir.path().join("db.redb")).unwrap();
+        assert!(reopened.is_archived(&old).unwrap());
+        assert_eq!(reopened.spent_archive().segment_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn notes_spent_before_upgrade_are_dated_to_the_migration() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::setup(dir.path().join("db.redb")).unwrap();
+        let legacy = Signature::from([2u8; 32]);
+
+        let w = db.write().unwrap();
+        w.open_table(NOTES).unwrap().insert(legacy, true).unwrap();
+        w.commit().unwrap();
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        db.migrate().unwrap();
+
+        let active = PublicKey([7u8; 32]);
+        assert_eq!(db.archive_spent_notes(before - 1, &active).unwrap(), 0);
+        assert!(!db.is_archived(&legacy).unwrap());
+
+        db.migrate().unwrap();
+        assert_eq!(db.archive_spent_notes(u64::MAX, &active).unwrap(), 1);
+        assert!(db.is_archived(&legacy).unwrap());
+    }
+
+    #[test]
+    fn notes_of_a_retired_keyset_are_archived_whatever_their_age() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::setup(dir.path().join("db.redb")).unwrap();
+        db.migrate().unwrap();
+
+        let retired = PublicKey([5u8; 32]);
+        let active = PublicKey([7u8; 32]);
+        let old_keyset = Signature::from([1u8; 32]);
+        let current = Signature::from([3u8; 32]);
+
+        let w = db.write().unwrap();
+        {
+            let mut notes = w.open_table(NOTES).unwrap();
+            let mut spent_at = w.open_table(NOTES_SPENT_AT).unwrap();
+            let mut by_keyset = w.open_table(NOTES_BY_KEYSET).unwrap();
+            for (signature, keyset) in
+                [(old_keyset, retired), (current, active)]
+            {
+                notes.insert(signature, true).unwrap();
+                spent_at.insert((500, signature), keyset.0).unwrap();
+                by_keyset.insert((keyset.0, signature), 500).unwrap();
+            }
+        }
+        w.commit().unwrap();
+
+        assert_eq!(db.archive_spent_notes(200, &active).unwrap(), 1);
+        assert!(db.is_archived(&old_keyset).unwrap());
+        assert!(!db.is_archived(&current).unwrap());
+
+        let r = db.read().unwrap();
+        assert!(r.open_table(NOTES).unwrap().get(current).unwrap().is_some());
+        assert!(
+            r.open_table(NOTES_BY_KEYSET)
+                .unwrap()
+                .get((retired.0, old_keyset))
+                .unwrap()
+                .is_none()
+        );
+        drop(r);
+
+        assert_eq!(db.archive_spent_notes(200, &active).unwrap(), 0);
+        assert_eq!(db.archive_spent_notes(u64::MAX, &active).unwrap(), 1);
+        assert!(db.is_archived(&current).unwrap());
+    }
+
+    #[test]
+    fn spend_dates_recorded_before_keysets_are_carried_over() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::setup(dir.path().join("db.redb")).unwrap();
+        let legacy = Signature::from([2u8; 32]);
+
+        let w = db.write().unwrap();
+        {
+            w.open_table(NOTES).unwrap().insert(legacy, true).unwrap();
+            w.open_table(LEGACY_NOTES_SPENT_AT)
+                .unwrap()
+                .insert((100, legacy), ())
+                .unwrap();
+            w.open_table(SCHEMA_VERSION)
+                .unwrap()
+                .insert("version", SPENT_AT_SCHEMA_VERSION)
+                .unwrap();
+        }
+        w.commit().unwrap();
+
+        db.migrate().unwrap();
+        assert_eq!(db.schema_version().unwrap(), CURRENT_SCHEMA_VERSION);
+
+        let r = db.read().unwrap();
+        assert_eq!(
+            r.open_table(NOTES_SPENT_AT)
+                .unwrap()
+                .get((100, legacy))
+                .unwrap()
+                .map(|keyset| keyset.value()),
+            Some(UNKNOWN_KEYSET)
+        );
+        drop(r);
+
+        let active = PublicKey([7u8; 32]);
+        assert_eq!(db.archive_spent_notes(200, &active).unwrap(), 1);
+        assert!(db.is_archived(&legacy).unwrap());
+    }
+
+    #[test]
+    fn appended_events_are_published_on_commit_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::setup(dir.path().join("db.redb")).unwrap();
+        db.migrate().unwrap();
+        let mut events = db.subscribe_events();
+
+        let w = db.write().unwrap();
+        w.append_event(
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalPending,
+        )
+        .unwrap();
+        w.abort().unwrap();
+        assert!(!events.has_changed().unwrap());
+        assert!(db.events_after(0, 10).unwrap().is_empty());
+
+        let w = db.write().unwrap();
+        let first = w
+            .append_event(
+                EventSubject::withdrawal("aa"),
+                EventKind::WithdrawalPending,
+            )
+            .unwrap();
+        let second = w
+            .append_event(
+                EventSubject::withdrawal("aa"),
+                EventKind::WithdrawalSubmitted,
+            )
+            .unwrap();
+        w.commit().unwrap();
+
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(*events.borrow_and_update(), 2);
+
+        let all = db.events_after(0, 10).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].event, EventKind::WithdrawalSubmitted);
+
+        let rest = db.events_after(first, 10).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].cursor, second);
+        assert_eq!(db.events_after(0, 1).unwrap()[0].cursor, first);
+    }
 }
@@ -1,12 +1,14 @@
 use mugraph_core::{
     error::Error,
-    types::{DepositRecord, UtxoRef},
+    types::{DepositRecord, EventKind, EventSubject, UtxoRef},
 };
 use redb::ReadableTable;
 
 use crate::{
-    database::{DEPOSITS, Database},
+    database::{DEPOSIT_CONFIRMATIONS, DEPOSITS, Database},
     provider::{Provider, UtxoInfo},
+    routes::Heartbeat,
+    supervisor::Shutdown,
 };
 
 /// Configuration for deposit monitoring
@@ -34,10 +36,12 @@ impl Default for DepositMonitorConfig {
 }
 
 /// Deposit monitor for handling reorgs and expirations
+#[derive(Clone)]
 pub struct DepositMonitor {
     config: DepositMonitorConfig,
     database: std::sync::Arc<Database>,
     provider: Provider,
+    heartbeat: std::sync::Arc<Heartbeat>,
 }
 
 impl DepositMonitor {
@@ -50,11 +54,23 @@ impl DepositMonitor {
             config,
             database,
             provider,
+            heartbeat: Default::default(),
         }
     }
 
-    /// Start the deposit monitoring background task
-    pub async fn start(self) {
+    /// Record every successful check on `heartbeat`, for readiness probes
+    pub fn with_heartbeat(
+        mut self,
+        heartbeat: std::sync::Arc<Heartbeat>,
+    ) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Run the deposit monitoring loop until `shutdown` is triggered
+    ///
+    /// A check that is already in progress finishes before the loop exits.
+    pub async fn run(self, shutdown: Shutdown) {
         tracing::info!(
             "Starting deposit monitor with {} block confirmation depth",
             self.config.confirm_depth
@@ -65,12 +81,19 @@ impl DepositMonitor {
         );
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
 
-            if let Err(e) = self.check_deposits().await {
-                tracing::error!("Error checking deposits: {}", e);
+            match self.check_deposits().await {
+                Ok(()) => self.heartbeat.beat(),
+                Err(e) => tracing::error!("Error checking deposits: {}", e),
             }
         }
+
+        tracing::info!("Deposit monitor stopped");
     }
 
     /// Check all pending deposits for:
@@ -128,7 +151,10 @@ impl DepositMonitor {
 
                 // Mark as expired by setting spent flag
                 // This prevents the deposit from being claimed
-                self.mark_deposit_spent(&utxo_ref)?;
+                self.retire_deposit(
+                    &utxo_ref,
+                    Some(EventKind::DepositExpired),
+                )?;
                 continue;
             }
 
@@ -137,6 +163,7 @@ impl DepositMonitor {
                 tip.block_height.saturating_sub(record.block_height);
             if blocks_elapsed >= self.config.confirm_depth {
                 // Deposit is confirmed, no need to re-check
+                self.publish_confirmations(&utxo_ref, blocks_elapsed)?;
                 continue;
             }
 
@@ -152,6 +179,7 @@ impl DepositMonitor {
                         hex::encode(&utxo_ref.tx_hash[..8]),
                         record.block_height
                     );
+                    self.publish_confirmations(&utxo_ref, blocks_elapsed)?;
                 }
                 Ok(false) => {
                     // UTxO no longer exists - was spent or reorged
@@ -161,7 +189,10 @@ impl DepositMonitor {
                     );
 
                     // Mark as spent/invalid
-                    self.mark_deposit_spent(&utxo_ref)?;
+                    self.retire_deposit(
+                        &utxo_ref,
+                        Some(EventKind::DepositInvalidated),
+                    )?;
                 }
                 Err(e) => {
                     tracing::error!(
@@ -263,6 +294,55 @@ impl DepositMonitor {
 
     /// Mark a deposit as spent (called when withdrawal is processed)
     pub fn mark_deposit_spent(&self, utxo_ref: &UtxoRef) -> Result<(), Error> {
+        self.retire_deposit(utxo_ref, None)
+    }
+
+    /// Publish confirmation progress for a deposit that is `confirmations`
+    /// blocks deep, unless that depth was already published.
+    fn publish_confirmations(
+        &self,
+        utxo_ref: &UtxoRef,
+        confirmations: u64,
+    ) -> Result<(), Error> {
+        let published = {
+            let read_tx = self.database.read()?;
+            let table = read_tx.open_table(DEPOSIT_CONFIRMATIONS)?;
+            table.get(utxo_ref)?.map(|v| v.value())
+        };
+
+        let required = self.config.confirm_depth;
+        let published = published.unwrap_or(0);
+        let event = if published >= required {
+            return Ok(());
+        } else if confirmations >= required {
+            EventKind::DepositConfirmed { confirmations }
+        } else if confirmations > published {
+            EventKind::DepositConfirming {
+                confirmations,
+                required,
+            }
+        } else {
+            return Ok(());
+        };
+
+        let write_tx = self.database.write()?;
+        {
+            let mut table = write_tx.open_table(DEPOSIT_CONFIRMATIONS)?;
+            table.insert(utxo_ref, confirmations)?;
+        }
+        write_tx.append_event(
+            EventSubject::deposit(&utxo_ref.tx_hash, utxo_ref.index),
+            event,
+        )?;
+        write_tx.commit()
+    }
+
+    /// Mark a deposit as spent, recording `event` for subscribers.
+    fn retire_deposit(
+        &self,
+        utxo_ref: &UtxoRef,
+        event: Option<EventKind>,
+    ) -> Result<(), Error> {
         // First, read the existing record
         let existing_record = {
             let read_tx = self.database.read()?;
@@ -277,6 +357,16 @@ impl DepositMonitor {
                 let mut table = write_tx.open_table(DEPOSITS)?;
                 record.spent = true;
                 table.insert(utxo_ref, &record)?;
+
+                let mut confirmations =
+                    write_tx.open_table(DEPOSIT_CONFIRMATIONS)?;
+                confirmations.remove(utxo_ref)?;
+            }
+            if let Some(event) = event {
+                write_tx.append_event(
+                    EventSubject::deposit(&utxo_ref.tx_hash, utxo_ref.index),
+                    event,
+                )?;
             }
             write_tx.commit()?;
         }
@@ -466,7 +556,7 @@ mod tests {
             wallet
                 .insert(
                     "wallet",
-                    &mugraph_core::types::CardanoWallet::new(
+                    mugraph_core::types::CardanoWallet::new(
                         vec![1u8; 32],
                         vec![2u8; 32],
                         vec![],
@@ -509,6 +599,7 @@ mod tests {
             }],
             datum_hash: None,
             datum: None,
+            inline_datum: false,
             script_ref: None,
             block_height: Some(10),
         };
@@ -560,6 +651,77 @@ mod tests {
         let deposits = r.open_table(DEPOSITS).unwrap();
         let stored = deposits.get(&utxo_ref).unwrap().unwrap().value();
         assert!(stored.spent);
+
+        let events = db.events_after(0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, EventKind::DepositInvalidated);
+    }
+
+    #[tokio::test]
+    async fn check_deposits_publishes_each_confirmation_depth_once() {
+        let base_url =
+            spawn_mock_server(StatusCode::OK, "addr_test1script").await;
+        let provider = Provider::new(
+            "blockfrost",
+            "key".to_string(),
+            "preprod".to_string(),
+            Some(base_url),
+        )
+        .unwrap();
+        let db = temp_db();
+
+        let utxo_ref = UtxoRef::new([0xcdu8; 32], 0);
+        let now = secs_since_unix_epoch(std::time::SystemTime::now());
+        seed_wallet_and_deposit(
+            &db,
+            utxo_ref.clone(),
+            DepositRecord::new(99, now, now + 3600),
+        );
+
+        let monitor = DepositMonitor::new(
+            DepositMonitorConfig {
+                confirm_depth: 3,
+                expiration_blocks: 10_000,
+                min_deposit_value: 1_000_000,
+                revalidation_interval: 60,
+            },
+            db.clone(),
+            provider,
+        );
+
+        // Tip is at 100, so the deposit is one block deep
+        monitor.check_deposits().await.unwrap();
+        monitor.check_deposits().await.unwrap();
+
+        // Three blocks deep
+        seed_wallet_and_deposit(
+            &db,
+            utxo_ref.clone(),
+            DepositRecord::new(97, now, now + 3600),
+        );
+        monitor.check_deposits().await.unwrap();
+        monitor.check_deposits().await.unwrap();
+
+        let events: Vec<_> = db
+            .events_after(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.subject, e.event))
+            .collect();
+        let subject = EventSubject::deposit(&[0xcdu8; 32], 0);
+        assert_eq!(
+            events,
+            vec![
+                (
+                    subject.clone(),
+                    EventKind::DepositConfirming {
+                        confirmations: 1,
+                        required: 3,
+                    },
+                ),
+                (subject, EventKind::DepositConfirmed { confirmations: 3 }),
+            ]
+        );
     }
 
     #[tokio::test]
@@ -1,9 +1,17 @@
-use std::net::SocketAddr;
+use std::{
+    future::{Future, IntoFuture},
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
 
 use color_eyre::eyre::Result;
 use mugraph_core::types::Keypair;
+use tokio::net::TcpListener;
 
 pub mod cardano;
+pub mod chain_index;
 pub mod config;
 pub mod database;
 pub(crate) mod deposit_datum;
@@ -15,19 +23,154 @@ pub mod peer_registry;
 pub mod provider;
 pub mod reconciler;
 pub mod routes;
+pub mod spent_archive;
+pub mod supervisor;
+pub mod telemetry;
+pub mod tls;
 pub(crate) mod tx_ids;
 pub mod tx_signer;
+pub mod withdrawal_monitor;
 
 use config::Config;
+use supervisor::Supervisor;
+use tls::{ClientInfo, ReloadableTls, TlsListener, TlsSettings};
+
+/// How long in-flight requests may take to complete after a shutdown signal.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long background workers may take to finish their current pass.
+const WORKER_GRACE: Duration = Duration::from_secs(30);
 
 pub async fn start(
     addr: SocketAddr,
     config: Config,
     keypair: Keypair,
 ) -> Result<()> {
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let listener = TcpListener::bind(addr).await?;
+    serve(listener, config, keypair, shutdown_signal()).await
+}
+
+/// Serve the node on `listener` until `shutdown` resolves, over TLS when a
+/// certificate is configured.
+///
+/// On shutdown the node stops accepting connections and drains in-flight
+/// requests (up to [`DRAIN_TIMEOUT`]), lets background workers finish their
+/// current pass, then flushes the database.
+pub async fn serve(
+    listener: TcpListener,
+    config: Config,
+    keypair: Keypair,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let metrics_addr = config.metrics_addr();
+    let tls = TlsSettings::from_config(&config)?
+        .map(ReloadableTls::new)
+        .transpose()?
+        .map(Arc::new);
+    let supervisor = Arc::new(Supervisor::new());
+    let router =
+        routes::router_with(config, keypair, supervisor.clone()).await?;
+    let stopping = supervisor.shutdown_signal();
+
+    if let Some(tls) = &tls {
+        let tls = tls.clone();
+        supervisor.spawn("tls_reloader", move |shutdown| {
+            tls.clone().watch(tls::RELOAD_INTERVAL, shutdown)
+        });
+    }
+
+    if let Some(metrics_addr) = metrics_addr {
+        let admin = TcpListener::bind(metrics_addr).await?;
+        let metrics = telemetry::metrics_router(telemetry::install_recorder()?);
+        let stopping = stopping.clone();
+        tracing::info!(addr = %metrics_addr, "Serving metrics on admin listener");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(admin, metrics)
+                .with_graceful_shutdown(async move { stopping.wait().await })
+                .await
+            {
+                tracing::error!("metrics listener stopped: {e}");
+            }
+        });
+    }
 
-    axum::serve(listener, routes::router(config, keypair).await?).await?;
+    let (signalled_tx, signalled_rx) = tokio::sync::oneshot::channel();
+    let graceful = {
+        let stopping = stopping.clone();
+        async move {
+            shutdown.await;
+            tracing::info!("Shutdown requested; draining in-flight requests");
+            // Fail readiness and stop workers while requests drain
+            stopping.trigger();
+            let _ = signalled_tx.send(());
+        }
+    };
+
+    let app = router.into_make_service_with_connect_info::<ClientInfo>();
+    let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> =
+        match tls {
+            Some(tls) => Box::pin(
+                axum::serve(TlsListener::new(listener, tls)?, app)
+                    .with_graceful_shutdown(graceful)
+                    .into_future(),
+            ),
+            None => Box::pin(
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(graceful)
+                    .into_future(),
+            ),
+        };
+
+    let drain_deadline = async {
+        match signalled_rx.await {
+            Ok(()) => tokio::time::sleep(DRAIN_TIMEOUT).await,
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => tracing::warn!(
+            "requests still in flight after {}s; closing them",
+            DRAIN_TIMEOUT.as_secs()
+        ),
+    }
+
+    supervisor.shutdown(WORKER_GRACE).await;
+    tracing::info!("Shutdown complete");
 
     Ok(())
 }
+
+/// Resolve on Ctrl-C, or on SIGTERM where supported.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(
+            tokio::signal::unix::SignalKind::terminate(),
+        ) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
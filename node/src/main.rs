@@ -1,13 +1,13 @@
 use color_eyre::eyre::Result;
-use mugraph_node::{config::Config, start};
+use mugraph_node::{config::Config, start, telemetry};
 use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    tracing_subscriber::fmt().init();
 
     let config = Config::new();
+    let _tracing = telemetry::init_tracing(config.otlp_endpoint().as_deref())?;
 
     match &config {
         Config::GenerateKey => {
@@ -32,6 +32,11 @@ async fn main() -> Result<()> {
 
             start(*addr, config, keypair).await?;
         }
+        Config::CheckConfig { .. } => {
+            let effective =
+                config.resolve_check_config().unwrap_or_else(|e| e.exit());
+            print!("{}", effective.effective_file().redacted().to_toml()?);
+        }
     }
 
     Ok(())
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InvalidCardanoNetwork {
     value: String,
@@ -76,10 +78,51 @@ impl CardanoNetwork {
             Self::Testnet => "https://cardano-testnet.blockfrost.io/api/v0",
         }
     }
+
+    pub fn koios_base_url(self) -> &'static str {
+        match self {
+            Self::Mainnet => "https://api.koios.rest/api/v1",
+            Self::Preprod => "https://preprod.koios.rest/api/v1",
+            Self::Preview => "https://preview.koios.rest/api/v1",
+            Self::Testnet => "https://testnet.koios.rest/api/v1",
+        }
+    }
+
+    /// Shelley-era slot to time mapping as `(zero_time_ms, zero_slot,
+    /// slot_length_ms)`, used to turn validity intervals into POSIX time
+    /// when evaluating scripts.
+    pub fn slot_config(self) -> (u64, u64, u32) {
+        match self {
+            Self::Mainnet => (1_596_059_091_000, 4_492_800, 1_000),
+            Self::Preprod => (1_655_769_600_000, 86_400, 1_000),
+            Self::Preview => (1_666_656_000_000, 0, 1_000),
+            Self::Testnet => (1_595_967_616_000, 1_598_400, 1_000),
+        }
+    }
+
+    /// Slots per Shelley-era epoch. Every network's Shelley zero slot opens
+    /// an epoch, so epochs start at whole multiples of this from it.
+    pub fn epoch_length(self) -> u64 {
+        match self {
+            Self::Mainnet | Self::Preprod | Self::Testnet => 432_000,
+            Self::Preview => 86_400,
+        }
+    }
+
+    /// Time from `now_ms` (POSIX milliseconds) until the next epoch begins,
+    /// when new protocol parameters take effect
+    pub fn time_to_next_epoch(self, now_ms: u64) -> Duration {
+        let (zero_time, _, slot_length) = self.slot_config();
+        let epoch_ms = self.epoch_length() * slot_length as u64;
+        let into_epoch = now_ms.saturating_sub(zero_time) % epoch_ms;
+        Duration::from_millis(epoch_ms - into_epoch)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::CardanoNetwork;
 
     #[test]
@@ -90,4 +133,23 @@ mod tests {
             assert_eq!(network.as_str(), raw);
         }
     }
+
+    #[test]
+    fn epochs_turn_over_on_their_boundaries() {
+        let network = CardanoNetwork::Preview;
+        let (zero_time, _, _) = network.slot_config();
+        let day = Duration::from_secs(86_400);
+
+        assert_eq!(network.time_to_next_epoch(zero_time), day);
+        assert_eq!(
+            network.time_to_next_epoch(zero_time + 3 * 86_400_000 - 1_000),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            CardanoNetwork::Mainnet.time_to_next_epoch(
+                CardanoNetwork::Mainnet.slot_config().0 + 86_400_000
+            ),
+            4 * day
+        );
+    }
 }
@@ -1,19 +1,44 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 
-use crate::network::CardanoNetwork;
+use crate::{
+    config::Config, database::Database, network::CardanoNetwork,
+    telemetry::observe_provider_call,
+};
 
 mod blockfrost;
+mod cache;
 mod common;
+mod emulator;
+mod indexed;
+mod koios;
 mod maestro;
+mod ogmios;
+mod quorum;
 
-pub use common::ProtocolParams;
+pub use cache::CACHE_PROVIDER;
+pub use common::{ProtocolParams, Ratio, declared_ex_units};
+pub use emulator::{DEFAULT_LEDGER, EMULATOR_PROVIDER};
+pub use indexed::INDEX_PROVIDER;
+pub use ogmios::{DEFAULT_KUPO_URL, DEFAULT_OGMIOS_URL, OGMIOS_PROVIDER};
+pub use quorum::QUORUM_PROVIDER;
 
 /// Cardano provider abstraction for UTxO queries and transaction submission
 #[derive(Debug, Clone)]
 pub enum Provider {
     Blockfrost(BlockfrostProvider),
     Maestro(MaestroProvider),
+    Koios(KoiosProvider),
+    Ogmios(OgmiosProvider),
+    Emulator(EmulatorProvider),
+    Quorum(QuorumProvider),
+    Cached(CachedProvider),
+    Indexed(IndexedProvider),
 }
 
 /// Blockfrost provider configuration
@@ -34,8 +59,166 @@ pub struct MaestroProvider {
     client: reqwest::Client,
 }
 
+/// Koios provider configuration
+#[derive(Debug, Clone)]
+pub struct KoiosProvider {
+    /// Optional bearer token; Koios serves anonymous requests on its free
+    /// tier
+    pub api_key: Option<String>,
+    pub base_url: String,
+    pub network: String,
+    client: reqwest::Client,
+}
+
+/// Self-hosted Kupo (UTxO index) and Ogmios (node bridge) configuration
+#[derive(Debug, Clone)]
+pub struct OgmiosProvider {
+    pub kupo_url: String,
+    pub ogmios_url: String,
+    pub network: String,
+    client: reqwest::Client,
+}
+
+/// In-process ledger emulator, shared by every provider naming the same
+/// instance
+#[derive(Debug, Clone)]
+pub struct EmulatorProvider {
+    pub network: String,
+    ledger: Arc<Mutex<emulator::Ledger>>,
+}
+
+/// Several backends behind one provider. Reads and submissions fail over
+/// in order; deposit UTxOs and transaction block heights must be confirmed
+/// by `quorum` backends giving the same answer.
+#[derive(Debug, Clone)]
+pub struct QuorumProvider {
+    pub backends: Vec<Provider>,
+    pub quorum: usize,
+}
+
+/// Caches answers from `inner` and limits how many requests reach it.
+/// Clones share the cache and the request budget.
+#[derive(Debug, Clone)]
+pub struct CachedProvider {
+    pub inner: Box<Provider>,
+    pub policy: CachePolicy,
+    pub priority: RequestPriority,
+    cache: Arc<Mutex<cache::Cache>>,
+}
+
+/// Answers reads about the script address from the local chain index while
+/// it is fresh, and everything else from `inner`
+#[derive(Debug, Clone)]
+pub struct IndexedProvider {
+    pub inner: Box<Provider>,
+    database: Arc<Database>,
+}
+
+/// How long provider answers are cached and how many requests may reach
+/// the backend per minute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// Transaction heights buried at least `settled_depth` blocks below
+    /// the tip
+    pub immutable: Duration,
+    /// The tip, unspent outputs and answers not yet settled. Protocol
+    /// parameters are kept until the epoch ends.
+    pub volatile: Duration,
+    /// Missing outputs and transactions
+    pub negative: Duration,
+    pub settled_depth: u64,
+    /// Unlimited when `None`
+    pub budget_per_minute: Option<u32>,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            immutable: Duration::from_secs(3600),
+            volatile: Duration::from_secs(5),
+            negative: Duration::from_secs(2),
+            settled_depth: 15,
+            budget_per_minute: None,
+        }
+    }
+}
+
+/// Background calls are shed before critical ones when the request budget
+/// runs low
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestPriority {
+    /// Deposit claims, withdrawals and anything a client is waiting on
+    #[default]
+    Critical,
+    /// Periodic work such as deposit revalidation, which can wait for the
+    /// next pass
+    Background,
+}
+
+impl RequestPriority {
+    /// Label used in metrics and errors
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::Background => "background",
+        }
+    }
+}
+
+/// The per-minute request budget has no room left for a call at this
+/// priority
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderBudgetExhausted {
+    pub operation: &'static str,
+    pub priority: RequestPriority,
+    pub budget: u32,
+}
+
+impl std::fmt::Display for ProviderBudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "provider request budget of {} per minute exhausted, shedding {} {}",
+            self.budget,
+            self.priority.as_str(),
+            self.operation
+        )
+    }
+}
+
+impl std::error::Error for ProviderBudgetExhausted {}
+
+/// Quorum backends answered a security-critical query differently and no
+/// single answer reached the quorum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderDisagreement {
+    pub operation: &'static str,
+    pub quorum: usize,
+    /// Backends grouped by the answer they gave
+    pub answers: Vec<Vec<String>>,
+}
+
+impl std::fmt::Display for ProviderDisagreement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let answers = self
+            .answers
+            .iter()
+            .map(|backends| backends.join(", "))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "provider backends disagree on {} (quorum {}): {}",
+            self.operation,
+            self.quorum,
+            answers.join(" vs ")
+        )
+    }
+}
+
+impl std::error::Error for ProviderDisagreement {}
+
 /// UTxO information from the blockchain
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UtxoInfo {
     pub tx_hash: String,
     pub output_index: u16,
@@ -44,13 +227,16 @@ pub struct UtxoInfo {
     pub datum_hash: Option<String>,
     /// Raw CBOR hex for inline or referenced datum (if available)
     pub datum: Option<String>,
+    /// Whether the output holds `datum` inline rather than by `datum_hash`
+    #[serde(default)]
+    pub inline_datum: bool,
     pub script_ref: Option<String>,
     /// Block height where this UTxO was created (for confirm depth checks)
     pub block_height: Option<u64>,
 }
 
 /// Asset amount (ADA or other tokens)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AssetAmount {
     pub unit: String,
     pub quantity: String,
@@ -70,6 +256,16 @@ pub struct ChainTip {
     pub block_height: u64,
 }
 
+/// Execution budget a script needs, as evaluated by the provider
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RedeemerBudget {
+    /// `spend`, `mint`, `publish`, `withdraw`, `vote` or `propose`
+    pub purpose: String,
+    pub index: u32,
+    pub memory: u64,
+    pub cpu: u64,
+}
+
 /// Chain observation status for a transaction
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TxSettlementState {
@@ -97,6 +293,43 @@ impl Provider {
         network: String,
         custom_url: Option<String>,
     ) -> Result<Self> {
+        match provider_type {
+            EMULATOR_PROVIDER => {
+                let name = custom_url.as_deref().unwrap_or(DEFAULT_LEDGER);
+                return Ok(Self::Emulator(EmulatorProvider::shared(
+                    name, network,
+                )));
+            }
+            OGMIOS_PROVIDER => {
+                return Ok(Self::Ogmios(OgmiosProvider {
+                    kupo_url: custom_url
+                        .unwrap_or_else(|| DEFAULT_KUPO_URL.to_string()),
+                    ogmios_url: DEFAULT_OGMIOS_URL.to_string(),
+                    network,
+                    client: reqwest::Client::new(),
+                }));
+            }
+            "koios" => {
+                let base_url = custom_url.unwrap_or_else(|| {
+                    CardanoNetwork::parse(&network)
+                        .map(|network| network.koios_base_url().to_string())
+                        .unwrap_or_else(|_| {
+                            format!("https://{network}.koios.rest/api/v1")
+                        })
+                });
+                let api_key = Some(api_key.trim().to_string())
+                    .filter(|api_key| !api_key.is_empty());
+
+                return Ok(Self::Koios(KoiosProvider {
+                    api_key,
+                    base_url,
+                    network,
+                    client: reqwest::Client::new(),
+                }));
+            }
+            _ => {}
+        }
+
         if api_key.trim().is_empty() {
             return Err(color_eyre::eyre::eyre!(
                 "Missing provider API key. Set CARDANO_API_KEY or pass --cardano-api-key"
@@ -144,60 +377,264 @@ impl Provider {
                 }))
             }
             _ => Err(color_eyre::eyre::eyre!(
-                "Unknown provider type: {}. Use 'blockfrost' or 'maestro'",
+                "Unknown provider type: {}. Use 'blockfrost', 'maestro', 'koios', 'ogmios' or 'emulator'",
                 provider_type
             )),
         }
     }
 
+    /// Create the provider `config` selects, wrapping it with any extra
+    /// backends in a quorum provider and caching its answers. The cache is
+    /// shared by every provider built for the same backends.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let network = config.network();
+        let ogmios_url = config.ogmios_url();
+        let primary = Self::backend(
+            &config.provider_type(),
+            config.provider_api_key(),
+            &network,
+            config.provider_url(),
+            ogmios_url.clone(),
+        )?;
+
+        let mut backends = vec![primary];
+        for backend in config.provider_backends() {
+            backends.push(Self::backend(
+                &backend.provider,
+                backend.api_key.unwrap_or_default(),
+                &network,
+                backend.provider_url,
+                ogmios_url.clone(),
+            )?);
+        }
+
+        let quorum = config.provider_quorum();
+        if quorum == 0 || quorum > backends.len() {
+            return Err(color_eyre::eyre::eyre!(
+                "provider quorum {quorum} must be between 1 and the {} configured backends",
+                backends.len()
+            ));
+        }
+
+        let provider = if backends.len() == 1 {
+            backends.remove(0)
+        } else {
+            Self::Quorum(QuorumProvider { backends, quorum })
+        };
+
+        // The emulator answers in-process, so there is nothing to save
+        if let Self::Emulator(_) = provider {
+            return Ok(provider);
+        }
+
+        Ok(Self::Cached(CachedProvider::shared(
+            provider,
+            config.provider_cache(),
+        )))
+    }
+
+    /// The same provider reading the script address from the chain index in
+    /// `database` while the index is fresh
+    pub fn indexed(self, database: Arc<Database>) -> Self {
+        Self::Indexed(IndexedProvider {
+            inner: Box::new(self),
+            database,
+        })
+    }
+
+    /// The same provider making its calls at `priority`. Only cached
+    /// providers have a budget to shed calls from.
+    pub fn with_priority(self, priority: RequestPriority) -> Self {
+        match self {
+            Self::Cached(provider) => Self::Cached(CachedProvider {
+                priority,
+                ..provider
+            }),
+            Self::Indexed(provider) => Self::Indexed(IndexedProvider {
+                inner: Box::new(provider.inner.with_priority(priority)),
+                ..provider
+            }),
+            provider => provider,
+        }
+    }
+
+    fn backend(
+        provider_type: &str,
+        api_key: String,
+        network: &str,
+        custom_url: Option<String>,
+        ogmios_url: Option<String>,
+    ) -> Result<Self> {
+        let provider =
+            Self::new(provider_type, api_key, network.to_string(), custom_url)?;
+
+        Ok(match (provider, ogmios_url) {
+            (Self::Ogmios(provider), Some(ogmios_url)) => {
+                Self::Ogmios(OgmiosProvider {
+                    ogmios_url,
+                    ..provider
+                })
+            }
+            (provider, _) => provider,
+        })
+    }
+
+    /// Backend name used as the `provider` metrics label
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blockfrost(_) => "blockfrost",
+            Self::Maestro(_) => "maestro",
+            Self::Koios(_) => "koios",
+            Self::Ogmios(_) => OGMIOS_PROVIDER,
+            Self::Emulator(_) => EMULATOR_PROVIDER,
+            Self::Quorum(_) => QUORUM_PROVIDER,
+            Self::Cached(_) => CACHE_PROVIDER,
+            Self::Indexed(_) => INDEX_PROVIDER,
+        }
+    }
+
     pub async fn get_utxo(
         &self,
         tx_hash: &str,
         output_index: u16,
     ) -> Result<Option<UtxoInfo>> {
-        match self {
-            Self::Blockfrost(provider) => {
-                provider.get_utxo(tx_hash, output_index).await
+        observe_provider_call(self.name(), "get_utxo", async {
+            match self {
+                Self::Blockfrost(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Maestro(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Koios(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Ogmios(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Emulator(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Quorum(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Cached(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
+                Self::Indexed(provider) => {
+                    provider.get_utxo(tx_hash, output_index).await
+                }
             }
-            Self::Maestro(provider) => {
-                provider.get_utxo(tx_hash, output_index).await
-            }
-        }
+        })
+        .await
     }
 
     pub async fn get_address_utxos(
         &self,
         address: &str,
     ) -> Result<Vec<UtxoInfo>> {
-        match self {
-            Self::Blockfrost(provider) => {
-                provider.get_address_utxos(address).await
-            }
-            Self::Maestro(provider) => {
-                provider.get_address_utxos(address).await
+        observe_provider_call(self.name(), "get_address_utxos", async {
+            match self {
+                Self::Blockfrost(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Maestro(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Koios(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Ogmios(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Emulator(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Quorum(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Cached(provider) => {
+                    provider.get_address_utxos(address).await
+                }
+                Self::Indexed(provider) => {
+                    provider.get_address_utxos(address).await
+                }
             }
-        }
+        })
+        .await
     }
 
     pub async fn submit_tx(&self, tx_cbor: &[u8]) -> Result<SubmitResponse> {
-        match self {
-            Self::Blockfrost(provider) => provider.submit_tx(tx_cbor).await,
-            Self::Maestro(provider) => provider.submit_tx(tx_cbor).await,
-        }
+        observe_provider_call(self.name(), "submit_tx", async {
+            match self {
+                Self::Blockfrost(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Maestro(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Koios(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Ogmios(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Emulator(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Quorum(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Cached(provider) => provider.submit_tx(tx_cbor).await,
+                Self::Indexed(provider) => provider.submit_tx(tx_cbor).await,
+            }
+        })
+        .await
     }
 
     pub async fn get_tip(&self) -> Result<ChainTip> {
-        match self {
-            Self::Blockfrost(provider) => provider.get_tip().await,
-            Self::Maestro(provider) => provider.get_tip().await,
-        }
+        observe_provider_call(self.name(), "get_tip", async {
+            match self {
+                Self::Blockfrost(provider) => provider.get_tip().await,
+                Self::Maestro(provider) => provider.get_tip().await,
+                Self::Koios(provider) => provider.get_tip().await,
+                Self::Ogmios(provider) => provider.get_tip().await,
+                Self::Emulator(provider) => provider.get_tip().await,
+                Self::Quorum(provider) => provider.get_tip().await,
+                Self::Cached(provider) => provider.get_tip().await,
+                Self::Indexed(provider) => provider.get_tip().await,
+            }
+        })
+        .await
     }
 
     pub async fn get_protocol_params(&self) -> Result<ProtocolParams> {
-        match self {
-            Self::Blockfrost(provider) => provider.get_protocol_params().await,
-            Self::Maestro(provider) => provider.get_protocol_params().await,
-        }
+        observe_provider_call(self.name(), "get_protocol_params", async {
+            match self {
+                Self::Blockfrost(provider) => {
+                    provider.get_protocol_params().await
+                }
+                Self::Maestro(provider) => provider.get_protocol_params().await,
+                Self::Koios(provider) => provider.get_protocol_params().await,
+                Self::Ogmios(provider) => provider.get_protocol_params().await,
+                Self::Emulator(provider) => {
+                    provider.get_protocol_params().await
+                }
+                Self::Quorum(provider) => provider.get_protocol_params().await,
+                Self::Cached(provider) => provider.get_protocol_params().await,
+                Self::Indexed(provider) => provider.get_protocol_params().await,
+            }
+        })
+        .await
+    }
+
+    /// Execution budgets for the scripts in `tx_cbor`. Only the ogmios
+    /// provider can evaluate transactions.
+    pub async fn evaluate_tx(
+        &self,
+        tx_cbor: &[u8],
+    ) -> Result<Vec<RedeemerBudget>> {
+        observe_provider_call(self.name(), "evaluate_tx", async {
+            match self {
+                Self::Ogmios(provider) => provider.evaluate_tx(tx_cbor).await,
+                Self::Quorum(provider) => provider.evaluate_tx(tx_cbor).await,
+                Self::Cached(provider) => provider.evaluate_tx(tx_cbor).await,
+                Self::Indexed(provider) => provider.evaluate_tx(tx_cbor).await,
+                _ => Err(color_eyre::eyre::eyre!(
+                    "{} cannot evaluate transactions",
+                    self.name()
+                )),
+            }
+        })
+        .await
     }
 
     pub async fn observe_tx_status(
@@ -207,14 +644,7 @@ impl Provider {
         previously_canonical: bool,
     ) -> Result<TxChainObservation> {
         let tip = self.get_tip().await?;
-        let tx_block_height = match self {
-            Self::Blockfrost(provider) => {
-                provider.get_tx_block_height(tx_hash).await?
-            }
-            Self::Maestro(provider) => {
-                provider.get_tx_block_height(tx_hash).await?
-            }
-        };
+        let tx_block_height = self.tx_block_height(tx_hash).await?;
 
         Ok(evaluate_tx_observation(
             tx_hash,
@@ -224,6 +654,38 @@ impl Provider {
             previously_canonical,
         ))
     }
+
+    async fn tx_block_height(&self, tx_hash: &str) -> Result<Option<u64>> {
+        observe_provider_call(self.name(), "get_tx_block_height", async {
+            match self {
+                Self::Blockfrost(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Maestro(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Koios(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Ogmios(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Emulator(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Quorum(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Cached(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+                Self::Indexed(provider) => {
+                    provider.get_tx_block_height(tx_hash).await
+                }
+            }
+        })
+        .await
+    }
 }
 
 pub fn evaluate_tx_observation(
@@ -299,6 +761,18 @@ mod tests {
         assert!(provider.is_ok());
     }
 
+    #[test]
+    fn test_emulator_provider_needs_no_api_key() {
+        let provider = Provider::new(
+            EMULATOR_PROVIDER,
+            "".to_string(),
+            "preprod".to_string(),
+            Some("emulator://provider-creation".to_string()),
+        )
+        .unwrap();
+        assert_eq!(provider.name(), "emulator");
+    }
+
     #[test]
     fn test_invalid_provider() {
         let provider = Provider::new(
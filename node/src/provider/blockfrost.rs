@@ -4,8 +4,8 @@ use serde::Deserialize;
 use super::{
     AssetAmount, BlockfrostProvider, ChainTip, SubmitResponse, UtxoInfo,
     common::{
-        ADDRESS_UTXO_PAGE_SIZE, ProtocolParams, parse_required,
-        send_with_retry, with_pagination,
+        ADDRESS_UTXO_PAGE_SIZE, ProtocolParams, Ratio, optional_ratio,
+        parse_required, send_with_retry, with_pagination,
     },
 };
 
@@ -61,10 +61,12 @@ impl BlockfrostProvider {
             .find(|output| output.output_index == output_index as i32);
 
         if let Some(output) = maybe_output {
-            let datum = if let Some(ref datum_hash) = output.data_hash {
-                self.fetch_datum_cbor(datum_hash).await?
-            } else {
-                None
+            let datum = match (&output.inline_datum, &output.data_hash) {
+                (Some(inline), _) => Some(inline.clone()),
+                (None, Some(datum_hash)) => {
+                    self.fetch_datum_cbor(datum_hash).await?
+                }
+                (None, None) => None,
             };
 
             return Ok(Some(UtxoInfo {
@@ -81,6 +83,7 @@ impl BlockfrostProvider {
                     .collect(),
                 datum_hash: output.data_hash,
                 datum,
+                inline_datum: output.inline_datum.is_some(),
                 script_ref: output.reference_script_hash,
                 block_height: tx_block_height,
             }));
@@ -156,7 +159,8 @@ impl BlockfrostProvider {
                     })
                     .collect(),
                 datum_hash: utxo.data_hash,
-                datum: None,
+                inline_datum: utxo.inline_datum.is_some(),
+                datum: utxo.inline_datum,
                 script_ref: utxo.reference_script_hash,
                 block_height,
             });
@@ -240,8 +244,8 @@ impl BlockfrostProvider {
                 "pool_deposit",
                 &response.pool_deposit,
             )?,
-            price_mem: parse_required("price_mem", &response.price_mem)?,
-            price_step: parse_required("price_step", &response.price_step)?,
+            price_mem: Ratio::parse("price_mem", &response.price_mem)?,
+            price_step: Ratio::parse("price_step", &response.price_step)?,
             max_tx_ex_mem: parse_required(
                 "max_tx_ex_mem",
                 &response.max_tx_ex_mem,
@@ -254,6 +258,10 @@ impl BlockfrostProvider {
                 "coins_per_utxo_size",
                 &response.coins_per_utxo_size,
             )?,
+            min_fee_ref_script_cost_per_byte: optional_ratio(
+                "min_fee_ref_script_cost_per_byte",
+                response.min_fee_ref_script_cost_per_byte.as_ref(),
+            )?,
         })
     }
 
@@ -307,6 +315,8 @@ struct BlockfrostUtxoOutput {
     amount: Vec<BlockfrostAssetAmount>,
     output_index: i32,
     data_hash: Option<String>,
+    #[serde(default)]
+    inline_datum: Option<String>,
     reference_script_hash: Option<String>,
 }
 
@@ -322,6 +332,8 @@ struct BlockfrostAddressUtxo {
     output_index: i32,
     amount: Vec<BlockfrostAssetAmount>,
     data_hash: Option<String>,
+    #[serde(default)]
+    inline_datum: Option<String>,
     reference_script_hash: Option<String>,
     block_height: Option<u64>,
 }
@@ -351,6 +363,8 @@ struct BlockfrostEpochParams {
     max_tx_ex_mem: String,
     max_tx_ex_steps: String,
     coins_per_utxo_size: String,
+    #[serde(default)]
+    min_fee_ref_script_cost_per_byte: Option<serde_json::Value>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,373 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::Result;
+use futures_util::future::{BoxFuture, FutureExt};
+
+use super::{
+    CachePolicy, CachedProvider, ChainTip, Provider, ProviderBudgetExhausted,
+    RedeemerBudget, RequestPriority, SubmitResponse, UtxoInfo,
+    common::ProtocolParams,
+};
+use crate::{
+    network::CardanoNetwork,
+    telemetry::{record_provider_cache, record_provider_shed},
+};
+
+pub const CACHE_PROVIDER: &str = "cache";
+
+/// The request budget is counted over fixed windows of this length
+const BUDGET_WINDOW: Duration = Duration::from_secs(60);
+
+/// Expired entries are dropped once the cache grows past this many
+const MAX_ENTRIES: usize = 10_000;
+
+/// Caches by backend identity, shared by every provider in the process
+/// built for the same backends.
+fn caches() -> &'static Mutex<HashMap<String, Arc<Mutex<Cache>>>> {
+    static CACHES: OnceLock<Mutex<HashMap<String, Arc<Mutex<Cache>>>>> =
+        OnceLock::new();
+    CACHES.get_or_init(Default::default)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Utxo(String, u16),
+    TxBlockHeight(String),
+    Tip,
+    ProtocolParams,
+}
+
+struct Entry {
+    expires: Instant,
+    value: Box<dyn Any + Send>,
+}
+
+pub(super) struct Cache {
+    entries: HashMap<CacheKey, Entry>,
+    /// Highest block the backend has reported, used to tell whether an
+    /// answer is settled
+    tip_height: Option<u64>,
+    window_start: Instant,
+    window_requests: u32,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            tip_height: None,
+            window_start: Instant::now(),
+            window_requests: 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for Cache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cache")
+            .field("entries", &self.entries.len())
+            .field("tip_height", &self.tip_height)
+            .field("window_requests", &self.window_requests)
+            .finish()
+    }
+}
+
+// Each call re-enters `Provider`, so the public methods return boxed futures
+// to keep the recursive future types finite.
+impl CachedProvider {
+    /// Cache `inner` with a cache and budget of its own
+    pub fn new(inner: Provider, policy: CachePolicy) -> Self {
+        Self {
+            inner: Box::new(inner),
+            policy,
+            priority: RequestPriority::Critical,
+            cache: Arc::default(),
+        }
+    }
+
+    /// Cache `inner` with the cache and budget of every other provider in
+    /// the process built for the same backends
+    pub fn shared(inner: Provider, policy: CachePolicy) -> Self {
+        let cache = caches()
+            .lock()
+            .expect("provider cache registry poisoned")
+            .entry(identity(&inner))
+            .or_default()
+            .clone();
+
+        Self {
+            cache,
+            ..Self::new(inner, policy)
+        }
+    }
+
+    pub(super) fn get_tx_block_height<'a>(
+        &'a self,
+        tx_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        self.cached(
+            "get_tx_block_height",
+            CacheKey::TxBlockHeight(tx_hash.to_string()),
+            self.inner.tx_block_height(tx_hash),
+            |height, tip| match height {
+                Some(height) => self.ttl_at(Some(*height), tip),
+                None => self.policy.negative,
+            },
+        )
+        .boxed()
+    }
+
+    /// An answer is also the claim that the output is still unspent, and
+    /// the depositor can reclaim a deposit with their own key at any time,
+    /// so outputs are only kept for the volatile TTL however deep they are.
+    /// The node's own submissions drop them at once.
+    pub(super) fn get_utxo<'a>(
+        &'a self,
+        tx_hash: &'a str,
+        output_index: u16,
+    ) -> BoxFuture<'a, Result<Option<UtxoInfo>>> {
+        self.cached(
+            "get_utxo",
+            CacheKey::Utxo(tx_hash.to_string(), output_index),
+            self.inner.get_utxo(tx_hash, output_index),
+            |utxo, _| match utxo {
+                Some(_) => self.policy.volatile,
+                None => self.policy.negative,
+            },
+        )
+        .boxed()
+    }
+
+    /// Address contents change with every transaction, so they are only
+    /// budgeted
+    pub(super) fn get_address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UtxoInfo>>> {
+        async move {
+            self.spend("get_address_utxos")?;
+            self.inner.get_address_utxos(address).await
+        }
+        .boxed()
+    }
+
+    pub(super) fn submit_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<SubmitResponse>> {
+        async move {
+            self.spend("submit_tx")?;
+            let submitted = self.inner.submit_tx(tx_cbor).await?;
+            self.cache()
+                .entries
+                .retain(|key, _| !matches!(key, CacheKey::Utxo(..)));
+
+            Ok(submitted)
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_tip(&self) -> BoxFuture<'_, Result<ChainTip>> {
+        async move {
+            let tip = self
+                .cached(
+                    "get_tip",
+                    CacheKey::Tip,
+                    self.inner.get_tip(),
+                    |_, _| self.policy.volatile,
+                )
+                .await?;
+
+            let mut cache = self.cache();
+            cache.tip_height = cache.tip_height.max(Some(tip.block_height));
+            Ok(tip)
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_protocol_params(
+        &self,
+    ) -> BoxFuture<'_, Result<ProtocolParams>> {
+        self.cached(
+            "get_protocol_params",
+            CacheKey::ProtocolParams,
+            self.inner.get_protocol_params(),
+            |_, _| self.until_next_epoch(),
+        )
+        .boxed()
+    }
+
+    pub(super) fn evaluate_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<RedeemerBudget>>> {
+        async move {
+            self.spend("evaluate_tx")?;
+            self.inner.evaluate_tx(tx_cbor).await
+        }
+        .boxed()
+    }
+
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().expect("provider cache poisoned")
+    }
+
+    /// How long to keep an answer about something in `block_height`: long
+    /// once it is buried `settled_depth` blocks below the tip, briefly
+    /// while a rollback could still change it
+    fn ttl_at(&self, block_height: Option<u64>, tip: Option<u64>) -> Duration {
+        match (block_height, tip) {
+            (Some(height), Some(tip))
+                if tip.saturating_sub(height) >= self.policy.settled_depth =>
+            {
+                self.policy.immutable
+            }
+            _ => self.policy.volatile,
+        }
+    }
+
+    /// How long to keep protocol parameters: they only change at an epoch
+    /// boundary, so until the next one on the backend's network
+    fn until_next_epoch(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        CardanoNetwork::parse(network(&self.inner))
+            .map(|network| network.time_to_next_epoch(now))
+            .unwrap_or(self.policy.volatile)
+    }
+
+    /// Answer from the cache, or spend a request on `fetch` and keep its
+    /// answer for the time `ttl` gives it
+    async fn cached<T: Clone + Send + 'static>(
+        &self,
+        operation: &'static str,
+        key: CacheKey,
+        fetch: impl Future<Output = Result<T>>,
+        ttl: impl FnOnce(&T, Option<u64>) -> Duration,
+    ) -> Result<T> {
+        let hit = {
+            let cache = self.cache();
+            cache
+                .entries
+                .get(&key)
+                .filter(|entry| entry.expires > Instant::now())
+                .and_then(|entry| entry.value.downcast_ref::<T>().cloned())
+        };
+        record_provider_cache(operation, hit.is_some());
+        if let Some(value) = hit {
+            return Ok(value);
+        }
+
+        self.spend(operation)?;
+        let value = fetch.await?;
+
+        let mut cache = self.cache();
+        let ttl = ttl(&value, cache.tip_height);
+        if !ttl.is_zero() {
+            let now = Instant::now();
+            if cache.entries.len() >= MAX_ENTRIES {
+                cache.entries.retain(|_, entry| entry.expires > now);
+            }
+            cache.entries.insert(
+                key,
+                Entry {
+                    expires: now + ttl,
+                    value: Box::new(value.clone()),
+                },
+            );
+        }
+
+        Ok(value)
+    }
+
+    /// Take one request from the budget. Background calls may only use
+    /// three quarters of it, leaving the rest for critical ones.
+    fn spend(&self, operation: &'static str) -> Result<()> {
+        let Some(budget) = self.policy.budget_per_minute else {
+            return Ok(());
+        };
+        let limit = match self.priority {
+            RequestPriority::Critical => budget,
+            RequestPriority::Background => budget - budget / 4,
+        };
+
+        let mut cache = self.cache();
+        let now = Instant::now();
+        if now.duration_since(cache.window_start) >= BUDGET_WINDOW {
+            cache.window_start = now;
+            cache.window_requests = 0;
+        }
+
+        if cache.window_requests >= limit {
+            record_provider_shed(operation, self.priority.as_str());
+            return Err(ProviderBudgetExhausted {
+                operation,
+                priority: self.priority,
+                budget,
+            }
+            .into());
+        }
+
+        cache.window_requests += 1;
+        Ok(())
+    }
+}
+
+/// What a provider talks to, so providers built separately for the same
+/// backends find the same cache
+fn identity(provider: &Provider) -> String {
+    match provider {
+        Provider::Blockfrost(provider) => {
+            format!("blockfrost {} {}", provider.network, provider.base_url)
+        }
+        Provider::Maestro(provider) => {
+            format!("maestro {} {}", provider.network, provider.base_url)
+        }
+        Provider::Koios(provider) => {
+            format!("koios {} {}", provider.network, provider.base_url)
+        }
+        Provider::Ogmios(provider) => format!(
+            "ogmios {} {} {}",
+            provider.network, provider.kupo_url, provider.ogmios_url
+        ),
+        Provider::Emulator(provider) => {
+            format!("emulator {} {:p}", provider.network, provider.ledger)
+        }
+        Provider::Quorum(provider) => format!(
+            "quorum {} [{}]",
+            provider.quorum,
+            provider
+                .backends
+                .iter()
+                .map(identity)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Provider::Cached(provider) => identity(&provider.inner),
+        Provider::Indexed(provider) => identity(&provider.inner),
+    }
+}
+
+/// Network of the backends behind `provider`
+fn network(provider: &Provider) -> &str {
+    match provider {
+        Provider::Blockfrost(provider) => &provider.network,
+        Provider::Maestro(provider) => &provider.network,
+        Provider::Koios(provider) => &provider.network,
+        Provider::Ogmios(provider) => &provider.network,
+        Provider::Emulator(provider) => &provider.network,
+        Provider::Quorum(provider) => {
+            provider.backends.first().map_or("", network)
+        }
+        Provider::Cached(provider) => network(&provider.inner),
+        Provider::Indexed(provider) => network(&provider.inner),
+    }
+}
@@ -1,11 +1,109 @@
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, eyre};
 use serde::{Deserialize, Serialize};
 use tokio::time::{Duration, sleep};
+use whisky_csl::csl;
+
+use super::UtxoInfo;
 
 pub(super) const PROVIDER_MAX_RETRIES: usize = 3;
 pub(super) const PROVIDER_BACKOFF_MS: u64 = 200;
 pub(super) const ADDRESS_UTXO_PAGE_SIZE: usize = 100;
 
+/// Reference script bytes charged at one price before it rises
+const REF_SCRIPT_TIER_BYTES: u64 = 25_600;
+/// Factor, as `numerator / denominator`, each further tier's price per byte
+/// is raised by
+const REF_SCRIPT_TIER_MULTIPLIER: (u128, u128) = (6, 5);
+
+/// An exact non-negative fraction. The ledger prices execution units and
+/// reference scripts as rationals, and rounds only the final fee.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct Ratio {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Ratio {
+    pub const fn new(numerator: u64, denominator: u64) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Parse a fraction such as `577/10000`, or a decimal such as `0.0577`
+    /// or `7.21e-5` as providers render them
+    pub fn parse(field: &str, value: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            eyre!("invalid protocol param {field}={value}: {reason}")
+        };
+
+        if let Some((numerator, denominator)) = value.split_once('/') {
+            let ratio = Self::new(
+                parse_required(field, numerator.trim())?,
+                parse_required(field, denominator.trim())?,
+            );
+            if ratio.denominator == 0 {
+                return Err(invalid("zero denominator"));
+            }
+            return Ok(ratio);
+        }
+
+        let (mantissa, exponent) = match value.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => {
+                (mantissa, parse_required::<i32>(field, exponent)?)
+            }
+            None => (value, 0),
+        };
+        let (whole, fraction) =
+            mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{whole}{fraction}");
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid("not a number"));
+        }
+
+        let scale = fraction.len() as i32 - exponent;
+        let mut numerator: u128 =
+            digits.parse().map_err(|_| invalid("too large"))?;
+        let mut denominator: u128 = 1;
+        for _ in 0..scale.unsigned_abs() {
+            let target = if scale > 0 {
+                &mut denominator
+            } else {
+                &mut numerator
+            };
+            *target = target
+                .checked_mul(10)
+                .ok_or_else(|| invalid("too precise"))?;
+        }
+        let gcd = gcd(numerator, denominator);
+        Ok(Self::new(
+            u64::try_from(numerator / gcd).map_err(|_| invalid("too large"))?,
+            u64::try_from(denominator / gcd)
+                .map_err(|_| invalid("too precise"))?,
+        ))
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+/// `numerator / denominator` rounded up, saturating at `u64::MAX`
+fn div_ceil(numerator: Option<u128>, denominator: Option<u128>) -> u64 {
+    match (numerator, denominator) {
+        (Some(n), Some(d)) if d > 0 => {
+            u64::try_from(n.div_ceil(d)).unwrap_or(u64::MAX)
+        }
+        _ => u64::MAX,
+    }
+}
+
 /// Protocol parameters for fee calculation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProtocolParams {
@@ -15,11 +113,218 @@ pub struct ProtocolParams {
     pub max_val_size: u64,
     pub key_deposit: u64,
     pub pool_deposit: u64,
-    pub price_mem: f64,
-    pub price_step: f64,
+    pub price_mem: Ratio,
+    pub price_step: Ratio,
     pub max_tx_ex_mem: u64,
     pub max_tx_ex_steps: u64,
     pub coins_per_utxo_byte: u64,
+    /// Lovelace per byte of reference script in the first price tier
+    pub min_fee_ref_script_cost_per_byte: Ratio,
+}
+
+impl ProtocolParams {
+    /// The smallest fee the ledger accepts for `tx` serialised to `size`
+    /// bytes: the linear size fee, the price of the execution budget its
+    /// redeemers declare, and the fee for the `ref_script_bytes` of
+    /// reference scripts its inputs and reference inputs carry
+    pub fn min_fee(
+        &self,
+        tx: &csl::Transaction,
+        size: u64,
+        ref_script_bytes: u64,
+    ) -> u64 {
+        let (mem, steps) = declared_ex_units(tx);
+        self.min_fee_a
+            .saturating_mul(size)
+            .saturating_add(self.min_fee_b)
+            .saturating_add(self.execution_fee(mem, steps))
+            .saturating_add(self.ref_script_fee(ref_script_bytes))
+    }
+
+    /// `mem * price_mem + steps * price_step`, rounded up once
+    fn execution_fee(&self, mem: u64, steps: u64) -> u64 {
+        let (pm, ps) = (self.price_mem, self.price_step);
+        let term = |price: Ratio, units: u64, other: Ratio| {
+            (price.numerator as u128)
+                .checked_mul(units as u128)?
+                .checked_mul(other.denominator as u128)
+        };
+        div_ceil(
+            term(pm, mem, ps)
+                .zip(term(ps, steps, pm))
+                .and_then(|(a, b)| a.checked_add(b)),
+            (pm.denominator as u128).checked_mul(ps.denominator as u128),
+        )
+    }
+
+    /// The tiered reference script fee: every [`REF_SCRIPT_TIER_BYTES`]
+    /// bytes cost [`REF_SCRIPT_TIER_MULTIPLIER`] times the tier before,
+    /// starting from `min_fee_ref_script_cost_per_byte`, rounded down once
+    fn ref_script_fee(&self, bytes: u64) -> u64 {
+        let (up, down) = REF_SCRIPT_TIER_MULTIPLIER;
+        let base = self.min_fee_ref_script_cost_per_byte;
+        if bytes == 0 || base.numerator == 0 {
+            return 0;
+        }
+
+        // Sum every tier over the common denominator `base.den * down^tiers`
+        let tiers =
+            u32::try_from(bytes / REF_SCRIPT_TIER_BYTES).unwrap_or(u32::MAX);
+        let rest = (bytes % REF_SCRIPT_TIER_BYTES) as u128;
+        let total = (0..=tiers).try_fold(0u128, |acc, tier| {
+            let chunk = if tier < tiers {
+                REF_SCRIPT_TIER_BYTES as u128
+            } else {
+                rest
+            };
+            let term = chunk
+                .checked_mul(base.numerator as u128)?
+                .checked_mul(up.checked_pow(tier)?)?
+                .checked_mul(down.checked_pow(tiers - tier)?)?;
+            acc.checked_add(term)
+        });
+        let denominator = down
+            .checked_pow(tiers)
+            .and_then(|d| d.checked_mul(base.denominator as u128));
+        match (total, denominator) {
+            (Some(n), Some(d)) if d > 0 => {
+                u64::try_from(n / d).unwrap_or(u64::MAX)
+            }
+            _ => u64::MAX,
+        }
+    }
+
+    /// The lovelace `output` must hold for its serialised size
+    pub fn min_ada(&self, output: &csl::TransactionOutput) -> Result<u64> {
+        let data_cost = csl::DataCost::new_coins_per_byte(&csl::BigNum::from(
+            self.coins_per_utxo_byte,
+        ));
+        csl::min_ada_for_output(output, &data_cost)
+            .map(u64::from)
+            .map_err(|e| eyre!("cannot size output: {e:?}"))
+    }
+}
+
+impl UtxoInfo {
+    /// Size of the reference script this output carries, as the ledger
+    /// charges for it: the script itself without its `script_ref` wrapping
+    pub fn reference_script_size(&self) -> Result<u64> {
+        let Some(script) = &self.script_ref else {
+            return Ok(0);
+        };
+        let script = csl::ScriptRef::from_hex(script).map_err(|e| {
+            eyre!(
+                "UTxO {}#{} has an invalid reference script: {e}",
+                self.tx_hash,
+                self.output_index
+            )
+        })?;
+        let size = match script.plutus_script() {
+            Some(plutus) => plutus.bytes().len(),
+            None => script
+                .native_script()
+                .map_or(0, |native| native.to_bytes().len()),
+        };
+        Ok(size as u64)
+    }
+
+    /// Hash of the reference script this output carries, if any
+    pub fn reference_script_hash(&self) -> Result<Option<csl::ScriptHash>> {
+        let Some(script) = &self.script_ref else {
+            return Ok(None);
+        };
+        let script = csl::ScriptRef::from_hex(script).map_err(|e| {
+            eyre!(
+                "UTxO {}#{} has an invalid reference script: {e}",
+                self.tx_hash,
+                self.output_index
+            )
+        })?;
+        Ok(script
+            .plutus_script()
+            .map(|script| script.hash())
+            .or_else(|| script.native_script().map(|script| script.hash())))
+    }
+
+    /// The ledger output this describes, with its datum in the form the
+    /// output holds it: inline, or by hash with the datum itself left to the
+    /// spending transaction's witness set.
+    pub fn to_output(&self) -> Result<csl::TransactionOutput> {
+        let invalid = |what: &str, e: String| {
+            eyre!(
+                "UTxO {}#{} has an invalid {what}: {e}",
+                self.tx_hash,
+                self.output_index
+            )
+        };
+
+        let address = csl::Address::from_bech32(&self.address)
+            .map_err(|e| invalid("address", e.to_string()))?;
+        let mut coin = csl::BigNum::zero();
+        let mut assets = csl::MultiAsset::new();
+        for amount in &self.amount {
+            let quantity = csl::BigNum::from_str(&amount.quantity)
+                .map_err(|e| invalid("quantity", e.to_string()))?;
+            if amount.unit == "lovelace" {
+                coin = quantity;
+                continue;
+            }
+
+            let (policy, name) = amount
+                .unit
+                .split_at_checked(56)
+                .ok_or_else(|| invalid("asset unit", amount.unit.clone()))?;
+            let policy = csl::ScriptHash::from_hex(policy)
+                .map_err(|e| invalid("policy id", e.to_string()))?;
+            let name = hex::decode(name)
+                .map_err(|e| e.to_string())
+                .and_then(|name| {
+                    csl::AssetName::new(name).map_err(|e| e.to_string())
+                })
+                .map_err(|e| invalid("asset name", e))?;
+            assets.set_asset(&policy, &name, &quantity);
+        }
+
+        let mut value = csl::Value::new(&coin);
+        if assets.len() > 0 {
+            value.set_multiasset(&assets);
+        }
+        let mut output = csl::TransactionOutput::new(&address, &value);
+        match (&self.datum, &self.datum_hash) {
+            (Some(datum), hash) if self.inline_datum || hash.is_none() => {
+                let datum = csl::PlutusData::from_hex(datum)
+                    .map_err(|e| invalid("datum", e.to_string()))?;
+                output.set_plutus_data(&datum);
+            }
+            (_, Some(hash)) => {
+                let hash = csl::DataHash::from_hex(hash)
+                    .map_err(|e| invalid("datum hash", e.to_string()))?;
+                output.set_data_hash(&hash);
+            }
+            _ => {}
+        }
+        if let Some(script) = &self.script_ref {
+            let script = csl::ScriptRef::from_hex(script)
+                .map_err(|e| invalid("reference script", e.to_string()))?;
+            output.set_script_ref(&script);
+        }
+
+        Ok(output)
+    }
+}
+
+/// Memory and steps declared across every redeemer of `tx`
+pub fn declared_ex_units(tx: &csl::Transaction) -> (u64, u64) {
+    let Some(redeemers) = tx.witness_set().redeemers() else {
+        return (0, 0);
+    };
+    (0..redeemers.len()).fold((0, 0), |(mem, steps), i| {
+        let units = redeemers.get(i).ex_units();
+        (
+            mem.saturating_add(u64::from(units.mem())),
+            steps.saturating_add(u64::from(units.steps())),
+        )
+    })
 }
 
 pub(super) async fn send_with_retry<F>(
@@ -80,6 +385,22 @@ where
     })
 }
 
+/// A protocol parameter providers send as a JSON number or string, and
+/// leave out before the era that introduced it
+pub(super) fn optional_ratio(
+    field: &str,
+    value: Option<&serde_json::Value>,
+) -> Result<Ratio> {
+    match value {
+        None | Some(serde_json::Value::Null) => Ok(Ratio::default()),
+        Some(serde_json::Value::String(value)) => Ratio::parse(field, value),
+        Some(serde_json::Value::Number(value)) => {
+            Ratio::parse(field, &value.to_string())
+        }
+        Some(value) => Err(eyre!("invalid protocol param {field}={value}")),
+    }
+}
+
 pub(super) fn with_pagination(
     base_url: &str,
     page: usize,
@@ -87,3 +408,94 @@ pub(super) fn with_pagination(
 ) -> String {
     format!("{base_url}?page={page}&count={count}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::AssetAmount;
+
+    fn params() -> ProtocolParams {
+        ProtocolParams {
+            min_fee_a: 0,
+            min_fee_b: 0,
+            max_tx_size: 16_384,
+            max_val_size: 5_000,
+            key_deposit: 2_000_000,
+            pool_deposit: 500_000_000,
+            price_mem: Ratio::new(577, 10_000),
+            price_step: Ratio::new(721, 10_000_000),
+            max_tx_ex_mem: 14_000_000,
+            max_tx_ex_steps: 10_000_000_000,
+            coins_per_utxo_byte: 4_310,
+            min_fee_ref_script_cost_per_byte: Ratio::new(15, 1),
+        }
+    }
+
+    #[test]
+    fn ratios_parse_fractions_and_decimals_exactly() {
+        let mem = Ratio::new(577, 10_000);
+        assert_eq!(Ratio::parse("price_mem", "577/10000").unwrap(), mem);
+        assert_eq!(Ratio::parse("price_mem", "0.0577").unwrap(), mem);
+        assert_eq!(Ratio::parse("price_mem", "5.77e-2").unwrap(), mem);
+        assert_eq!(
+            Ratio::parse("price_step", "7.21E-5").unwrap(),
+            Ratio::new(721, 10_000_000)
+        );
+        assert_eq!(Ratio::parse("cost", "15").unwrap(), Ratio::new(15, 1));
+        assert!(Ratio::parse("price_mem", "1/0").is_err());
+        assert!(Ratio::parse("price_mem", "-0.1").is_err());
+    }
+
+    #[test]
+    fn execution_fees_round_up_the_exact_sum() {
+        let params = params();
+        assert_eq!(params.execution_fee(10_000, 0), 577);
+        assert_eq!(params.execution_fee(1, 1), 1);
+        // 14M mem and 10G steps cost exactly 807_800 + 721_000
+        assert_eq!(params.execution_fee(14_000_000, 10_000_000_000), 1_528_800);
+    }
+
+    #[test]
+    fn reference_scripts_are_priced_in_growing_tiers() {
+        let params = params();
+        assert_eq!(params.ref_script_fee(0), 0);
+        assert_eq!(params.ref_script_fee(25_600), 384_000);
+        // The next 4_400 bytes cost 15 * 6/5 = 18 each
+        assert_eq!(params.ref_script_fee(30_000), 384_000 + 79_200);
+        // A byte in the third tier costs 21.6, and only the total is floored
+        assert_eq!(params.ref_script_fee(51_201), 384_000 + 460_800 + 21);
+        assert_eq!(params.ref_script_fee(51_205), 384_000 + 460_800 + 108);
+    }
+
+    #[test]
+    fn outputs_keep_the_datum_form_they_hold() {
+        let datum = csl::PlutusData::new_integer(&csl::BigInt::from(7));
+        let hash = csl::hash_plutus_data(&datum);
+        let utxo = |inline_datum| {
+            UtxoInfo {
+            tx_hash: "ab".repeat(32),
+            output_index: 0,
+            address: "addr_test1vru4e2un2tq50q4rv6qzk7t8w34gjdtw3y2uzuqxzj0ldrqqactxh"
+                .to_string(),
+            amount: vec![AssetAmount {
+                unit: "lovelace".to_string(),
+                quantity: "2000000".to_string(),
+            }],
+            datum_hash: Some(hash.to_hex()),
+            datum: Some(datum.to_hex()),
+            inline_datum,
+            script_ref: None,
+            block_height: None,
+        }
+        };
+
+        let inline = utxo(true).to_output().unwrap();
+        assert_eq!(inline.plutus_data(), Some(datum.clone()));
+        assert_eq!(inline.data_hash(), None);
+
+        // The datum itself is for the spending transaction to supply
+        let hashed = utxo(false).to_output().unwrap();
+        assert_eq!(hashed.plutus_data(), None);
+        assert_eq!(hashed.data_hash(), Some(hash));
+    }
+}
@@ -0,0 +1,948 @@
+//! In-process Cardano ledger for dev mode and tests.
+//!
+//! The ledger keeps a UTxO set with inline datums. Submitted transactions
+//! that pass the phase-1 checks wait in a mempool until the next block
+//! includes them. Blocks are produced on demand or on a timer, and the
+//! tip can be rolled back to exercise reorg handling. Plutus scripts are
+//! not evaluated: a script input only needs a spend redeemer and its
+//! script.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::Duration,
+};
+
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use color_eyre::eyre::{Result, eyre};
+use tokio::time::{MissedTickBehavior, interval};
+use whisky_csl::csl;
+
+use super::{
+    AssetAmount, ChainTip, EmulatorProvider, ProtocolParams, Ratio,
+    SubmitResponse, UtxoInfo, declared_ex_units,
+};
+use crate::{supervisor::Shutdown, tx_signer::compute_tx_hash};
+
+/// `--cardano-provider` value selecting the emulator.
+pub const EMULATOR_PROVIDER: &str = "emulator";
+
+/// Ledger used when `--cardano-provider-url` names no other instance.
+pub const DEFAULT_LEDGER: &str = "emulator://default";
+
+/// Slots between blocks, the mainnet average.
+const SLOTS_PER_BLOCK: u64 = 20;
+
+type Blake2b256 = Blake2b<U32>;
+
+/// `(tx_hash, output_index)` of an output.
+type OutRef = (String, u16);
+
+#[derive(Debug)]
+pub(super) struct Ledger {
+    utxos: BTreeMap<OutRef, UtxoInfo>,
+    /// Every block from genesis, which is never rolled back
+    blocks: Vec<Block>,
+    mempool: Vec<PendingTx>,
+    tx_heights: HashMap<String, u64>,
+    /// Funding transactions issued so far, for unique hashes
+    funded: u64,
+}
+
+#[derive(Debug)]
+struct Block {
+    height: u64,
+    slot: u64,
+    hash: String,
+    txs: Vec<AppliedTx>,
+}
+
+#[derive(Debug)]
+struct PendingTx {
+    hash: String,
+    inputs: Vec<OutRef>,
+    outputs: Vec<UtxoInfo>,
+}
+
+/// What a transaction changed, so a rollback can undo it.
+#[derive(Debug)]
+struct AppliedTx {
+    hash: String,
+    consumed: Vec<(OutRef, UtxoInfo)>,
+    produced: Vec<OutRef>,
+}
+
+/// Emulated ledgers by instance name, shared by every provider in the
+/// process that names the same one.
+fn ledgers() -> &'static Mutex<HashMap<String, Arc<Mutex<Ledger>>>> {
+    static LEDGERS: OnceLock<Mutex<HashMap<String, Arc<Mutex<Ledger>>>>> =
+        OnceLock::new();
+    LEDGERS.get_or_init(Default::default)
+}
+
+/// Protocol parameters the emulator charges and checks against, those of
+/// preprod at the time of writing.
+pub(super) fn protocol_params() -> ProtocolParams {
+    ProtocolParams {
+        min_fee_a: 44,
+        min_fee_b: 155_381,
+        max_tx_size: 16_384,
+        max_val_size: 5_000,
+        key_deposit: 2_000_000,
+        pool_deposit: 500_000_000,
+        price_mem: Ratio::new(577, 10_000),
+        price_step: Ratio::new(721, 10_000_000),
+        max_tx_ex_mem: 14_000_000,
+        max_tx_ex_steps: 10_000_000_000,
+        coins_per_utxo_byte: 4_310,
+        min_fee_ref_script_cost_per_byte: Ratio::new(15, 1),
+    }
+}
+
+impl EmulatorProvider {
+    /// The ledger registered under `name`, created empty on first use.
+    pub fn shared(name: &str, network: String) -> Self {
+        let ledger = ledgers()
+            .lock()
+            .expect("emulator registry poisoned")
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(Ledger::new())))
+            .clone();
+
+        Self { network, ledger }
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().expect("emulated ledger poisoned")
+    }
+
+    /// Queue a transaction paying `amount` to `address` out of thin air,
+    /// optionally with an inline datum (CBOR hex). The output exists once
+    /// the next block is produced; returns its `(tx_hash, output_index)`.
+    pub fn fund(
+        &self,
+        address: &str,
+        amount: Vec<AssetAmount>,
+        datum: Option<String>,
+    ) -> Result<(String, u16)> {
+        self.queue_output(address, amount, datum, None)
+    }
+
+    /// Queue a transaction paying `amount` to `address` out of thin air,
+    /// holding `script` as a reference script. The output exists once the
+    /// next block is produced; returns its `(tx_hash, output_index)`.
+    pub fn deploy_script(
+        &self,
+        address: &str,
+        amount: Vec<AssetAmount>,
+        script: &csl::PlutusScript,
+    ) -> Result<(String, u16)> {
+        let script_ref = csl::ScriptRef::new_plutus_script(script);
+        self.queue_output(
+            address,
+            amount,
+            None,
+            Some(hex::encode(script_ref.to_bytes())),
+        )
+    }
+
+    fn queue_output(
+        &self,
+        address: &str,
+        amount: Vec<AssetAmount>,
+        datum: Option<String>,
+        script_ref: Option<String>,
+    ) -> Result<(String, u16)> {
+        csl::Address::from_bech32(address)
+            .map_err(|e| eyre!("invalid address {address}: {e:?}"))?;
+        let datum_hash = datum
+            .as_deref()
+            .map(|datum| {
+                let bytes = hex::decode(datum)?;
+                let data = csl::PlutusData::from_bytes(bytes)
+                    .map_err(|e| eyre!("invalid datum: {e}"))?;
+                Ok::<_, color_eyre::Report>(hex::encode(
+                    csl::hash_plutus_data(&data).to_bytes(),
+                ))
+            })
+            .transpose()?;
+
+        let mut ledger = self.ledger();
+        ledger.funded += 1;
+        let mut hasher = Blake2b256::new();
+        hasher.update(b"mugraph-emulator-fund");
+        hasher.update(ledger.funded.to_be_bytes());
+        hasher.update(address.as_bytes());
+        let tx_hash = hex::encode(hasher.finalize());
+
+        ledger.mempool.push(PendingTx {
+            hash: tx_hash.clone(),
+            inputs: Vec::new(),
+            outputs: vec![UtxoInfo {
+                tx_hash: tx_hash.clone(),
+                output_index: 0,
+                address: address.to_string(),
+                amount,
+                datum_hash,
+                inline_datum: datum.is_some(),
+                datum,
+                script_ref,
+                block_height: None,
+            }],
+        });
+
+        Ok((tx_hash, 0))
+    }
+
+    /// Produce `blocks` blocks, the first including every queued
+    /// transaction whose inputs are still unspent.
+    pub fn advance(&self, blocks: u64) -> ChainTip {
+        let mut ledger = self.ledger();
+        for _ in 0..blocks {
+            ledger.produce_block();
+        }
+        ledger.tip()
+    }
+
+    /// Undo the last `depth` blocks. Their transactions are dropped rather
+    /// than queued again, as if the fork that replaced them never saw
+    /// them.
+    pub fn rollback(&self, depth: u64) -> Result<ChainTip> {
+        let mut ledger = self.ledger();
+        let tip = ledger.tip().block_height;
+        if depth > tip {
+            return Err(eyre!(
+                "cannot roll back {depth} blocks from height {tip}"
+            ));
+        }
+
+        for _ in 0..depth {
+            ledger.undo_block();
+        }
+        Ok(ledger.tip())
+    }
+
+    pub fn tip(&self) -> ChainTip {
+        self.ledger().tip()
+    }
+
+    /// Produce a block every `every` until `shutdown` is triggered.
+    pub async fn run(self, every: Duration, shutdown: Shutdown) {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => {}
+            }
+            let tip = self.advance(1);
+            tracing::debug!(height = tip.block_height, "emulator block");
+        }
+    }
+
+    pub(super) async fn get_utxo(
+        &self,
+        tx_hash: &str,
+        output_index: u16,
+    ) -> Result<Option<UtxoInfo>> {
+        Ok(self
+            .ledger()
+            .utxos
+            .get(&(tx_hash.to_string(), output_index))
+            .cloned())
+    }
+
+    pub(super) async fn get_address_utxos(
+        &self,
+        address: &str,
+    ) -> Result<Vec<UtxoInfo>> {
+        Ok(self
+            .ledger()
+            .utxos
+            .values()
+            .filter(|utxo| utxo.address == address)
+            .cloned()
+            .collect())
+    }
+
+    pub(super) async fn submit_tx(
+        &self,
+        tx_cbor: &[u8],
+    ) -> Result<SubmitResponse> {
+        let mut ledger = self.ledger();
+        let pending = ledger.check_phase_one(tx_cbor, &protocol_params())?;
+        let tx_hash = pending.hash.clone();
+        ledger.mempool.push(pending);
+
+        Ok(SubmitResponse { tx_hash })
+    }
+
+    pub(super) async fn get_tip(&self) -> Result<ChainTip> {
+        Ok(self.tip())
+    }
+
+    pub(super) async fn get_protocol_params(&self) -> Result<ProtocolParams> {
+        Ok(protocol_params())
+    }
+
+    pub(super) async fn get_tx_block_height(
+        &self,
+        tx_hash: &str,
+    ) -> Result<Option<u64>> {
+        Ok(self.ledger().tx_heights.get(tx_hash).copied())
+    }
+}
+
+impl Ledger {
+    fn new() -> Self {
+        Self {
+            utxos: BTreeMap::new(),
+            blocks: vec![Block {
+                height: 0,
+                slot: 0,
+                hash: hex::encode([0u8; 32]),
+                txs: Vec::new(),
+            }],
+            mempool: Vec::new(),
+            tx_heights: HashMap::new(),
+            funded: 0,
+        }
+    }
+
+    fn tip(&self) -> ChainTip {
+        let block = self.blocks.last().expect("genesis is never removed");
+        ChainTip {
+            slot: block.slot,
+            hash: block.hash.clone(),
+            block_height: block.height,
+        }
+    }
+
+    fn produce_block(&mut self) {
+        let parent = self.tip();
+        let height = parent.block_height + 1;
+        let mut hasher = Blake2b256::new();
+        hasher.update(parent.hash.as_bytes());
+        hasher.update(height.to_be_bytes());
+
+        let mut txs = Vec::new();
+        for pending in std::mem::take(&mut self.mempool) {
+            // A rollback may have removed an input since submission
+            if let Some(missing) =
+                pending.inputs.iter().find(|r| !self.utxos.contains_key(*r))
+            {
+                tracing::warn!(
+                    tx_hash = %pending.hash,
+                    "dropping emulated tx: input {}#{} is gone",
+                    missing.0,
+                    missing.1
+                );
+                continue;
+            }
+
+            let consumed = pending
+                .inputs
+                .into_iter()
+                .filter_map(|r| self.utxos.remove(&r).map(|utxo| (r, utxo)))
+                .collect();
+            let produced = pending
+                .outputs
+                .into_iter()
+                .map(|mut utxo| {
+                    let r = (utxo.tx_hash.clone(), utxo.output_index);
+                    utxo.block_height = Some(height);
+                    self.utxos.insert(r.clone(), utxo);
+                    r
+                })
+                .collect();
+
+            hasher.update(pending.hash.as_bytes());
+            self.tx_heights.insert(pending.hash.clone(), height);
+            txs.push(AppliedTx {
+                hash: pending.hash,
+                consumed,
+                produced,
+            });
+        }
+
+        self.blocks.push(Block {
+            height,
+            slot: parent.slot + SLOTS_PER_BLOCK,
+            hash: hex::encode(hasher.finalize()),
+            txs,
+        });
+    }
+
+    fn undo_block(&mut self) {
+        let Some(block) = self.blocks.pop_if(|block| block.height > 0) else {
+            return;
+        };
+
+        for tx in block.txs.into_iter().rev() {
+            for r in tx.produced {
+                self.utxos.remove(&r);
+            }
+            for (r, utxo) in tx.consumed {
+                self.utxos.insert(r, utxo);
+            }
+            self.tx_heights.remove(&tx.hash);
+        }
+    }
+
+    /// Check `tx_cbor` against the ledger as the node would before
+    /// admitting it to the mempool, short of running its scripts.
+    fn check_phase_one(
+        &self,
+        tx_cbor: &[u8],
+        params: &ProtocolParams,
+    ) -> Result<PendingTx> {
+        let tx = csl::Transaction::from_bytes(tx_cbor.to_vec())
+            .map_err(|e| eyre!("invalid transaction CBOR: {e}"))?;
+        let tx_hash = compute_tx_hash(tx_cbor)?;
+        let hash = hex::encode(tx_hash);
+        let reject = |reason: String| eyre!("tx {hash} rejected: {reason}");
+
+        let size = tx_cbor.len() as u64;
+        if size > params.max_tx_size {
+            return Err(reject(format!(
+                "size {size} exceeds maximum {}",
+                params.max_tx_size
+            )));
+        }
+        if !tx.is_valid() {
+            return Err(reject(
+                "transactions flagged invalid are not supported".to_string(),
+            ));
+        }
+
+        let body = tx.body();
+        if body.certs().is_some_and(|certs| certs.len() > 0)
+            || body.withdrawals().is_some_and(|w| w.len() > 0)
+        {
+            return Err(reject(
+                "certificates and reward withdrawals are not supported"
+                    .to_string(),
+            ));
+        }
+
+        let slot = self.tip().slot;
+        if let Some(ttl) = body.ttl_bignum()
+            && u64::from(ttl) <= slot
+        {
+            return Err(reject(format!("expired at slot {}", u64::from(ttl))));
+        }
+        if let Some(start) = body.validity_start_interval_bignum()
+            && u64::from(start) > slot
+        {
+            return Err(reject(format!(
+                "not valid before slot {}",
+                u64::from(start)
+            )));
+        }
+
+        let pending: HashSet<&OutRef> = self
+            .mempool
+            .iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .collect();
+        let resolve = |input: &csl::TransactionInput| {
+            let r = (
+                hex::encode(input.transaction_id().to_bytes()),
+                u16::try_from(input.index())
+                    .map_err(|_| reject("output index overflow".to_string()))?,
+            );
+            if pending.contains(&r) {
+                return Err(reject(format!(
+                    "input {}#{} is spent by a pending transaction",
+                    r.0, r.1
+                )));
+            }
+            match self.utxos.get(&r) {
+                Some(utxo) => Ok((r, utxo)),
+                None => Err(reject(format!(
+                    "input {}#{} does not exist or is already spent",
+                    r.0, r.1
+                ))),
+            }
+        };
+
+        let mut inputs = (&body.inputs())
+            .into_iter()
+            .map(resolve)
+            .collect::<Result<Vec<_>>>()?;
+        if inputs.is_empty() {
+            return Err(reject("no inputs".to_string()));
+        }
+        // Redeemer indexes refer to inputs in ledger order
+        inputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let references = body
+            .reference_inputs()
+            .map(|refs| (&refs).into_iter().map(resolve).collect())
+            .transpose()?
+            .unwrap_or_else(Vec::new);
+        let collateral = body
+            .collateral()
+            .map(|refs| (&refs).into_iter().map(resolve).collect())
+            .transpose()?
+            .unwrap_or_else(Vec::new);
+
+        // Value is preserved: inputs and mints cover outputs, fee and burns
+        let fee = u64::from(body.fee());
+        let mut produced_amounts = Vec::new();
+        let mut consumed = BTreeMap::new();
+        let mut spent = BTreeMap::new();
+        for (_, utxo) in &inputs {
+            add_amounts(&mut consumed, &utxo.amount)?;
+        }
+        if let Some(mint) = body.mint() {
+            add_amounts(
+                &mut consumed,
+                &asset_amounts(&mint.as_positive_multiasset()),
+            )?;
+            add_amounts(
+                &mut spent,
+                &asset_amounts(&mint.as_negative_multiasset()),
+            )?;
+        }
+        add_amounts(
+            &mut spent,
+            &[AssetAmount {
+                unit: "lovelace".to_string(),
+                quantity: fee.to_string(),
+            }],
+        )?;
+
+        for (index, output) in (&body.outputs()).into_iter().enumerate() {
+            let amount = value_amounts(&output.amount());
+            add_amounts(&mut spent, &amount)?;
+
+            let min_ada = params
+                .min_ada(output)
+                .map_err(|e| reject(format!("output {index}: {e}")))?;
+            if u64::from(output.amount().coin()) < min_ada {
+                return Err(reject(format!(
+                    "output {index} holds {} lovelace, below the minimum {}",
+                    output.amount().coin(),
+                    min_ada
+                )));
+            }
+
+            let (datum_hash, datum) = match output.plutus_data() {
+                Some(data) => (
+                    Some(hex::encode(csl::hash_plutus_data(&data).to_bytes())),
+                    Some(hex::encode(data.to_bytes())),
+                ),
+                None => (
+                    output.data_hash().map(|h| hex::encode(h.to_bytes())),
+                    None,
+                ),
+            };
+            produced_amounts.push(UtxoInfo {
+                tx_hash: hash.clone(),
+                output_index: u16::try_from(index)
+                    .map_err(|_| reject("too many outputs".to_string()))?,
+                address: output
+                    .address()
+                    .to_bech32(None)
+                    .map_err(|e| reject(format!("output {index}: {e:?}")))?,
+                amount,
+                datum_hash,
+                inline_datum: datum.is_some(),
+                datum,
+                script_ref: output
+                    .script_ref()
+                    .map(|script| hex::encode(script.to_bytes())),
+                block_height: None,
+            });
+        }
+        consumed.retain(|_, quantity| *quantity != 0);
+        spent.retain(|_, quantity| *quantity != 0);
+        if consumed != spent {
+            return Err(reject(format!(
+                "value not preserved: consumed {consumed:?}, produced {spent:?}"
+            )));
+        }
+
+        // Fee covers the size and the declared execution budget
+        let witnesses = tx.witness_set();
+        let redeemers: Vec<csl::Redeemer> = witnesses
+            .redeemers()
+            .map(|r| (0..r.len()).map(|i| r.get(i)).collect())
+            .unwrap_or_default();
+        let (mem, steps) = declared_ex_units(&tx);
+        if mem > params.max_tx_ex_mem || steps > params.max_tx_ex_steps {
+            return Err(reject(format!(
+                "execution budget {mem} mem / {steps} steps exceeds the limit"
+            )));
+        }
+        let mut ref_script_bytes = 0u64;
+        for (_, utxo) in inputs.iter().chain(&references) {
+            ref_script_bytes = ref_script_bytes.saturating_add(
+                utxo.reference_script_size()
+                    .map_err(|e| reject(e.to_string()))?,
+            );
+        }
+        let min_fee = params.min_fee(&tx, size, ref_script_bytes);
+        if fee < min_fee {
+            return Err(reject(format!("fee {fee} below minimum {min_fee}")));
+        }
+
+        // Every key witness is valid, and every key that must sign has
+        let mut signed = HashSet::new();
+        if let Some(vkeys) = witnesses.vkeys() {
+            for i in 0..vkeys.len() {
+                let witness = vkeys.get(i);
+                let key = witness.vkey().public_key();
+                if !key.verify(&tx_hash, &witness.signature()) {
+                    return Err(reject(format!(
+                        "invalid signature from {}",
+                        key.hash().to_hex()
+                    )));
+                }
+                signed.insert(key.hash().to_hex());
+            }
+        }
+
+        let mut required: Vec<String> = body
+            .required_signers()
+            .map(|keys| (&keys).into_iter().map(|k| k.to_hex()).collect())
+            .unwrap_or_default();
+        let mut scripts = HashSet::new();
+        for (position, (_, utxo)) in inputs.iter().enumerate() {
+            let credential = payment_credential(&utxo.address)?;
+            if let Some(key) = credential.to_keyhash() {
+                required.push(key.to_hex());
+            } else if let Some(script) = credential.to_scripthash() {
+                let redeemed = redeemers.iter().any(|r| {
+                    r.tag().kind() == csl::RedeemerTagKind::Spend
+                        && u64::from(r.index()) == position as u64
+                });
+                if !redeemed {
+                    return Err(reject(format!(
+                        "script input {position} has no spend redeemer"
+                    )));
+                }
+                scripts.insert(script.to_hex());
+            }
+        }
+        for (_, utxo) in &collateral {
+            match payment_credential(&utxo.address)?.to_keyhash() {
+                Some(key) => required.push(key.to_hex()),
+                None => {
+                    return Err(reject(
+                        "collateral must be locked by a key".to_string(),
+                    ));
+                }
+            }
+        }
+        if !redeemers.is_empty() && collateral.is_empty() {
+            return Err(reject("redeemers without collateral".to_string()));
+        }
+        if let Some(key) = required.iter().find(|key| !signed.contains(*key)) {
+            return Err(reject(format!("missing signature from {key}")));
+        }
+
+        // Scripts are supplied in the witness set or by reference
+        let mut available = HashSet::new();
+        if let Some(plutus) = witnesses.plutus_scripts() {
+            available.extend((0..plutus.len()).map(|i| plutus.get(i).hash()));
+        }
+        if let Some(native) = witnesses.native_scripts() {
+            available.extend((0..native.len()).map(|i| native.get(i).hash()));
+        }
+        for (_, utxo) in inputs.iter().chain(&references) {
+            if let Some(script) = utxo.script_ref.as_deref() {
+                available.extend(reference_script_hash(script));
+            }
+        }
+        let available: HashSet<String> =
+            available.iter().map(|hash| hash.to_hex()).collect();
+        if let Some(script) = scripts.iter().find(|s| !available.contains(*s)) {
+            return Err(reject(format!("script {script} is not supplied")));
+        }
+
+        Ok(PendingTx {
+            hash,
+            inputs: inputs.into_iter().map(|(r, _)| r).collect(),
+            outputs: produced_amounts,
+        })
+    }
+}
+
+fn payment_credential(address: &str) -> Result<csl::Credential> {
+    csl::Address::from_bech32(address)
+        .ok()
+        .and_then(|address| address.payment_cred())
+        .ok_or_else(|| eyre!("address {address} has no payment credential"))
+}
+
+fn reference_script_hash(script_ref: &str) -> Option<csl::ScriptHash> {
+    let script =
+        csl::ScriptRef::from_bytes(hex::decode(script_ref).ok()?).ok()?;
+    script
+        .plutus_script()
+        .map(|script| script.hash())
+        .or_else(|| script.native_script().map(|script| script.hash()))
+}
+
+fn value_amounts(value: &csl::Value) -> Vec<AssetAmount> {
+    let mut amounts = vec![AssetAmount {
+        unit: "lovelace".to_string(),
+        quantity: value.coin().to_str(),
+    }];
+    if let Some(assets) = value.multiasset() {
+        amounts.extend(asset_amounts(&assets));
+    }
+    amounts
+}
+
+fn asset_amounts(assets: &csl::MultiAsset) -> Vec<AssetAmount> {
+    let mut amounts = Vec::new();
+    let policies = assets.keys();
+    for i in 0..policies.len() {
+        let policy = policies.get(i);
+        let Some(named) = assets.get(&policy) else {
+            continue;
+        };
+        let names = named.keys();
+        for j in 0..names.len() {
+            let name = names.get(j);
+            if let Some(quantity) = named.get(&name) {
+                amounts.push(AssetAmount {
+                    unit: format!(
+                        "{}{}",
+                        policy.to_hex(),
+                        hex::encode(name.name())
+                    ),
+                    quantity: quantity.to_str(),
+                });
+            }
+        }
+    }
+    amounts
+}
+
+fn add_amounts(
+    totals: &mut BTreeMap<String, u128>,
+    amounts: &[AssetAmount],
+) -> Result<()> {
+    for amount in amounts {
+        let quantity: u128 = amount.quantity.parse().map_err(|e| {
+            eyre!(
+                "invalid quantity {} of {}: {e}",
+                amount.quantity,
+                amount.unit
+            )
+        })?;
+        let total = totals.entry(amount.unit.clone()).or_insert(0);
+        *total = total.saturating_add(quantity);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEE: u64 = 200_000;
+
+    fn key(seed: u8) -> csl::PrivateKey {
+        csl::PrivateKey::from_normal_bytes(&[seed; 32]).unwrap()
+    }
+
+    fn address(key: &csl::PrivateKey) -> String {
+        let credential = csl::Credential::from_keyhash(&key.to_public().hash());
+        csl::EnterpriseAddress::new(0, &credential)
+            .to_address()
+            .to_bech32(None)
+            .unwrap()
+    }
+
+    /// Spend `input` to `to`, paying `amount` and [`FEE`], signed by
+    /// `signer` if any.
+    fn spend(
+        input: &(String, u16),
+        to: &str,
+        amount: u64,
+        signer: Option<&csl::PrivateKey>,
+    ) -> Vec<u8> {
+        let mut inputs = csl::TransactionInputs::new();
+        inputs.add(&csl::TransactionInput::new(
+            &csl::TransactionHash::from_hex(&input.0).unwrap(),
+            input.1 as u32,
+        ));
+        let mut outputs = csl::TransactionOutputs::new();
+        outputs.add(&csl::TransactionOutput::new(
+            &csl::Address::from_bech32(to).unwrap(),
+            &csl::Value::new(&csl::BigNum::from(amount)),
+        ));
+        let body = csl::TransactionBody::new_tx_body(
+            &inputs,
+            &outputs,
+            &csl::BigNum::from(FEE),
+        );
+
+        let mut witnesses = csl::TransactionWitnessSet::new();
+        if let Some(signer) = signer {
+            let hash = csl::TransactionHash::from_bytes(
+                Blake2b256::digest(body.to_bytes()).to_vec(),
+            )
+            .unwrap();
+            let mut vkeys = csl::Vkeywitnesses::new();
+            vkeys.add(&csl::make_vkey_witness(&hash, signer));
+            witnesses.set_vkeys(&vkeys);
+        }
+        csl::Transaction::new(&body, &witnesses, None).to_bytes()
+    }
+
+    fn ledger(name: &str) -> EmulatorProvider {
+        EmulatorProvider::shared(name, "preprod".to_string())
+    }
+
+    fn lovelace(quantity: u64) -> Vec<AssetAmount> {
+        vec![AssetAmount {
+            unit: "lovelace".to_string(),
+            quantity: quantity.to_string(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn funded_outputs_appear_once_a_block_includes_them() {
+        let emulator = ledger("emulator://test-funding");
+        let owner = address(&key(1));
+        let (tx_hash, index) =
+            emulator.fund(&owner, lovelace(5_000_000), None).unwrap();
+        assert!(emulator.get_utxo(&tx_hash, index).await.unwrap().is_none());
+
+        let tip = emulator.advance(3);
+        assert_eq!(tip.block_height, 3);
+        assert_eq!(tip.slot, 3 * SLOTS_PER_BLOCK);
+
+        let utxo = emulator.get_utxo(&tx_hash, index).await.unwrap().unwrap();
+        assert_eq!(utxo.block_height, Some(1));
+        assert_eq!(
+            emulator.get_tx_block_height(&tx_hash).await.unwrap(),
+            Some(1)
+        );
+        assert_eq!(emulator.get_address_utxos(&owner).await.unwrap().len(), 1);
+
+        // Providers naming the same instance see the same ledger
+        assert_eq!(ledger("emulator://test-funding").tip().block_height, 3);
+        assert_eq!(ledger("emulator://test-other").tip().block_height, 0);
+    }
+
+    #[tokio::test]
+    async fn rollbacks_undo_blocks_and_their_transactions() {
+        let emulator = ledger("emulator://test-rollback");
+        emulator.advance(2);
+        let (tx_hash, index) = emulator
+            .fund(&address(&key(1)), lovelace(2_000_000), None)
+            .unwrap();
+        let included = emulator.advance(1);
+
+        let tip = emulator.rollback(1).unwrap();
+        assert_eq!(tip.block_height, 2);
+        assert!(emulator.get_utxo(&tx_hash, index).await.unwrap().is_none());
+        assert_eq!(emulator.get_tx_block_height(&tx_hash).await.unwrap(), None);
+
+        // The replacement block does not bring the transaction back
+        assert_ne!(emulator.advance(1).hash, included.hash);
+        assert!(emulator.get_utxo(&tx_hash, index).await.unwrap().is_none());
+        assert!(emulator.rollback(10).is_err());
+    }
+
+    #[test]
+    fn inline_datums_are_hashed_and_kept() {
+        let emulator = ledger("emulator://test-datum");
+        let owner = address(&key(1));
+        let datum = hex::encode(
+            csl::PlutusData::new_integer(&csl::BigInt::from(7)).to_bytes(),
+        );
+        assert!(
+            emulator
+                .fund(&owner, lovelace(1), Some("zz".into()))
+                .is_err()
+        );
+        let (tx_hash, index) = emulator
+            .fund(&owner, lovelace(2_000_000), Some(datum.clone()))
+            .unwrap();
+        emulator.advance(1);
+
+        let ledger = emulator.ledger();
+        let utxo = &ledger.utxos[&(tx_hash, index)];
+        assert_eq!(utxo.datum.as_deref(), Some(datum.as_str()));
+        assert_eq!(utxo.datum_hash.as_ref().map(String::len), Some(64));
+    }
+
+    #[tokio::test]
+    async fn submitted_transactions_pass_phase_one_before_inclusion() {
+        let emulator = ledger("emulator://test-submit");
+        let (alice, bob) = (key(1), key(2));
+        let funded = emulator
+            .fund(&address(&alice), lovelace(10_000_000), None)
+            .unwrap();
+        emulator.advance(1);
+
+        let pay =
+            |amount, signer| spend(&funded, &address(&bob), amount, signer);
+        let rejected = |tx: Vec<u8>| {
+            let emulator = emulator.clone();
+            async move { emulator.submit_tx(&tx).await.unwrap_err().to_string() }
+        };
+
+        assert!(
+            rejected(pay(9_800_000, None))
+                .await
+                .contains("missing signature")
+        );
+        assert!(
+            rejected(pay(9_800_000, Some(&bob)))
+                .await
+                .contains("missing signature")
+        );
+        assert!(
+            rejected(pay(9_900_000, Some(&alice)))
+                .await
+                .contains("value not preserved")
+        );
+
+        let submitted = emulator
+            .submit_tx(&pay(9_800_000, Some(&alice)))
+            .await
+            .unwrap();
+        assert!(
+            rejected(pay(9_800_000, Some(&alice)))
+                .await
+                .contains("pending transaction")
+        );
+
+        let tip = emulator.advance(1);
+        assert_eq!(
+            emulator
+                .get_tx_block_height(&submitted.tx_hash)
+                .await
+                .unwrap(),
+            Some(tip.block_height)
+        );
+        let received =
+            emulator.get_address_utxos(&address(&bob)).await.unwrap();
+        assert_eq!(received[0].amount[0].quantity, "9800000");
+        assert!(
+            rejected(pay(9_800_000, Some(&alice)))
+                .await
+                .contains("already spent")
+        );
+
+        // Rolling the spend back makes the funded output spendable again
+        emulator.rollback(1).unwrap();
+        emulator
+            .submit_tx(&pay(9_800_000, Some(&alice)))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use color_eyre::eyre::Result;
+use futures_util::future::{BoxFuture, FutureExt};
+use mugraph_core::types::UtxoRef;
+use whisky_csl::csl;
+
+use super::{
+    ChainTip, IndexedProvider, RedeemerBudget, SubmitResponse, UtxoInfo,
+    common::ProtocolParams,
+};
+use crate::{
+    chain_index::{self, IndexState},
+    tx_ids::parse_utxo_ref,
+};
+
+pub const INDEX_PROVIDER: &str = "index";
+
+// Each call re-enters `Provider`, so the public methods return boxed futures
+// to keep the recursive future types finite.
+impl IndexedProvider {
+    pub(super) fn get_tx_block_height<'a>(
+        &'a self,
+        tx_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        self.inner.tx_block_height(tx_hash).boxed()
+    }
+
+    /// Outputs the index has never seen, such as ones away from the script
+    /// address, are looked up with the provider. So are indexed outputs
+    /// missing their datum or height, which some backends leave out of
+    /// address listings; the provider's answer then completes the index.
+    pub(super) fn get_utxo<'a>(
+        &'a self,
+        tx_hash: &'a str,
+        output_index: u16,
+    ) -> BoxFuture<'a, Result<Option<UtxoInfo>>> {
+        async move {
+            if self.fresh()?.is_some()
+                && let Ok(utxo_ref) = parse_utxo_ref(tx_hash, output_index)
+                && let Some(indexed) =
+                    chain_index::indexed_utxo(&self.database, &utxo_ref)?
+            {
+                if !indexed.is_unspent() {
+                    return Ok(None);
+                }
+                if indexed.utxo.datum.is_some()
+                    && indexed.utxo.block_height.is_some()
+                {
+                    return Ok(Some(indexed.utxo));
+                }
+
+                let utxo = self.inner.get_utxo(tx_hash, output_index).await?;
+                if let Some(utxo) = &utxo
+                    && let Err(e) = chain_index::complete_utxo(
+                        &self.database,
+                        &utxo_ref,
+                        utxo,
+                    )
+                {
+                    tracing::warn!(
+                        "failed to complete {tx_hash}#{output_index} in the chain index: {e}"
+                    );
+                }
+                return Ok(utxo);
+            }
+
+            self.inner.get_utxo(tx_hash, output_index).await
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UtxoInfo>>> {
+        async move {
+            if self
+                .fresh()?
+                .is_some_and(|state| state.script_address == address)
+            {
+                return Ok(chain_index::unspent_utxos(&self.database)?);
+            }
+
+            self.inner.get_address_utxos(address).await
+        }
+        .boxed()
+    }
+
+    /// Submits through `inner`, then records the transaction as the spender
+    /// of its indexed inputs
+    pub(super) fn submit_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<SubmitResponse>> {
+        async move {
+            let submitted = self.inner.submit_tx(tx_cbor).await?;
+
+            let inputs = spent_inputs(tx_cbor);
+            if let Err(e) = chain_index::record_spends(
+                &self.database,
+                &inputs,
+                &submitted.tx_hash,
+            ) {
+                tracing::warn!(
+                    tx_hash = %submitted.tx_hash,
+                    "failed to record spends in the chain index: {e}"
+                );
+            }
+
+            Ok(submitted)
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_tip(&self) -> BoxFuture<'_, Result<ChainTip>> {
+        async move {
+            if let Some(state) = self.fresh()? {
+                return Ok(state.tip);
+            }
+
+            self.inner.get_tip().await
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_protocol_params(
+        &self,
+    ) -> BoxFuture<'_, Result<ProtocolParams>> {
+        self.inner.get_protocol_params().boxed()
+    }
+
+    pub(super) fn evaluate_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<RedeemerBudget>>> {
+        self.inner.evaluate_tx(tx_cbor).boxed()
+    }
+
+    fn fresh(&self) -> Result<Option<IndexState>> {
+        Ok(chain_index::fresh_state(&self.database)?)
+    }
+}
+
+/// Inputs of `tx_cbor`, or none if it does not decode
+fn spent_inputs(tx_cbor: &[u8]) -> Vec<UtxoRef> {
+    let Ok(tx) = csl::Transaction::from_bytes(tx_cbor.to_vec()) else {
+        return Vec::new();
+    };
+
+    let inputs = tx.body().inputs();
+    (0..inputs.len())
+        .filter_map(|i| {
+            let input = inputs.get(i);
+            let tx_hash = input.transaction_id().to_bytes().try_into().ok()?;
+            let index = u16::try_from(input.index()).ok()?;
+            Some(UtxoRef::new(tx_hash, index))
+        })
+        .collect()
+}
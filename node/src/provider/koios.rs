@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    AssetAmount, ChainTip, KoiosProvider, SubmitResponse, UtxoInfo,
+    common::{
+        ADDRESS_UTXO_PAGE_SIZE, ProtocolParams, Ratio, optional_ratio,
+        send_with_retry,
+    },
+};
+
+impl KoiosProvider {
+    /// Koios bearer tokens are optional; anonymous calls use the free tier
+    fn authorized(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    pub(super) async fn get_tx_block_height(
+        &self,
+        tx_hash: &str,
+    ) -> Result<Option<u64>> {
+        let url = format!("{}/tx_info", self.base_url);
+        let body = json!({ "_tx_hashes": [tx_hash] });
+        let response: Vec<KoiosTxInfo> = send_with_retry(
+            || self.authorized(self.client.post(&url).json(&body)),
+            "Failed to fetch transaction info from Koios",
+        )
+        .await?
+        .json()
+        .await
+        .context("Failed to parse Koios transaction response")?;
+
+        Ok(response.into_iter().find_map(|tx| tx.block_height))
+    }
+
+    pub(super) async fn get_utxo(
+        &self,
+        tx_hash: &str,
+        output_index: u16,
+    ) -> Result<Option<UtxoInfo>> {
+        let url = format!("{}/utxo_info", self.base_url);
+        let body = json!({
+            "_utxo_refs": [format!("{tx_hash}#{output_index}")],
+            "_extended": true,
+        });
+        let response: Vec<KoiosUtxo> = send_with_retry(
+            || self.authorized(self.client.post(&url).json(&body)),
+            "Failed to fetch UTxO from Koios",
+        )
+        .await?
+        .json()
+        .await
+        .context("Failed to parse Koios response")?;
+
+        let Some(utxo) = response.into_iter().find(|utxo| {
+            utxo.tx_index == output_index && !utxo.is_spent.unwrap_or(false)
+        }) else {
+            return Ok(None);
+        };
+
+        let datum = match (&utxo.inline_datum, &utxo.datum_hash) {
+            (Some(inline), _) => Some(inline.bytes.clone()),
+            (None, Some(datum_hash)) => self.get_datum(datum_hash).await?,
+            (None, None) => None,
+        };
+
+        Ok(Some(utxo.into_utxo(datum)))
+    }
+
+    pub(super) async fn get_address_utxos(
+        &self,
+        address: &str,
+    ) -> Result<Vec<UtxoInfo>> {
+        let body = json!({ "_addresses": [address], "_extended": true });
+
+        let mut all = Vec::new();
+        for page in 0.. {
+            // PostgREST pages are only stable under an explicit order
+            let url = format!(
+                "{}/address_utxos?order=tx_hash.asc,tx_index.asc&offset={}&limit={}",
+                self.base_url,
+                page * ADDRESS_UTXO_PAGE_SIZE,
+                ADDRESS_UTXO_PAGE_SIZE
+            );
+            let response: Vec<KoiosUtxo> = send_with_retry(
+                || self.authorized(self.client.post(&url).json(&body)),
+                "Failed to fetch address UTxOs from Koios",
+            )
+            .await?
+            .json()
+            .await
+            .context("Failed to parse Koios response")?;
+
+            if response.is_empty() {
+                break;
+            }
+
+            let page_len = response.len();
+            all.extend(response);
+            if page_len < ADDRESS_UTXO_PAGE_SIZE {
+                break;
+            }
+        }
+
+        // Outputs that only carry a datum hash are resolved as in get_utxo
+        let hashes: Vec<&str> = all
+            .iter()
+            .filter(|utxo| utxo.inline_datum.is_none())
+            .filter_map(|utxo| utxo.datum_hash.as_deref())
+            .collect();
+        let datums = self.get_datums(&hashes).await?;
+
+        Ok(all
+            .into_iter()
+            .map(|utxo| {
+                let datum = match (&utxo.inline_datum, &utxo.datum_hash) {
+                    (Some(inline), _) => Some(inline.bytes.clone()),
+                    (None, Some(datum_hash)) => datums.get(datum_hash).cloned(),
+                    (None, None) => None,
+                };
+                utxo.into_utxo(datum)
+            })
+            .collect())
+    }
+
+    pub(super) async fn submit_tx(
+        &self,
+        tx_cbor: &[u8],
+    ) -> Result<SubmitResponse> {
+        let url = format!("{}/submittx", self.base_url);
+        let tx_hash: String = send_with_retry(
+            || {
+                self.authorized(
+                    self.client
+                        .post(&url)
+                        .header("Content-Type", "application/cbor")
+                        .body(tx_cbor.to_vec()),
+                )
+            },
+            "Failed to submit transaction to Koios",
+        )
+        .await?
+        .json()
+        .await
+        .context("Failed to parse Koios submission response")?;
+
+        Ok(SubmitResponse { tx_hash })
+    }
+
+    pub(super) async fn get_tip(&self) -> Result<ChainTip> {
+        let url = format!("{}/tip", self.base_url);
+        let response: Vec<KoiosTip> = send_with_retry(
+            || self.authorized(self.client.get(&url)),
+            "Failed to fetch tip from Koios",
+        )
+        .await?
+        .json()
+        .await
+        .context("Failed to parse Koios response")?;
+
+        let tip = response
+            .into_iter()
+            .next()
+            .ok_or_else(|| color_eyre::eyre::eyre!("Koios returned no tip"))?;
+
+        Ok(ChainTip {
+            slot: tip.abs_slot,
+            hash: tip.hash,
+            block_height: tip.block_no,
+        })
+    }
+
+    pub(super) async fn get_protocol_params(&self) -> Result<ProtocolParams> {
+        let url = format!("{}/cli_protocol_params", self.base_url);
+        let response: KoiosProtocolParams = send_with_retry(
+            || self.authorized(self.client.get(&url)),
+            "Failed to fetch protocol params from Koios",
+        )
+        .await?
+        .json()
+        .await
+        .context("Failed to parse Koios response")?;
+
+        Ok(ProtocolParams {
+            min_fee_a: response.tx_fee_per_byte,
+            min_fee_b: response.tx_fee_fixed,
+            max_tx_size: response.max_tx_size,
+            max_val_size: response.max_value_size,
+            key_deposit: response.stake_address_deposit,
+            pool_deposit: response.stake_pool_deposit,
+            price_mem: Ratio::parse(
+                "price_mem",
+                &response.execution_unit_prices.price_memory.to_string(),
+            )?,
+            price_step: Ratio::parse(
+                "price_step",
+                &response.execution_unit_prices.price_steps.to_string(),
+            )?,
+            max_tx_ex_mem: response.max_tx_execution_units.memory,
+            max_tx_ex_steps: response.max_tx_execution_units.steps,
+            coins_per_utxo_byte: response.utxo_cost_per_byte,
+            min_fee_ref_script_cost_per_byte: optional_ratio(
+                "min_fee_ref_script_cost_per_byte",
+                response.min_fee_ref_script_cost_per_byte.as_ref(),
+            )?,
+        })
+    }
+
+    async fn get_datum(&self, datum_hash: &str) -> Result<Option<String>> {
+        Ok(self.get_datums(&[datum_hash]).await?.remove(datum_hash))
+    }
+
+    /// Datum bytes by hash, fetched a page of hashes at a time
+    async fn get_datums(
+        &self,
+        datum_hashes: &[&str],
+    ) -> Result<HashMap<String, String>> {
+        let url = format!("{}/datum_info", self.base_url);
+        let mut unique = datum_hashes.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+
+        let mut datums = HashMap::new();
+        for chunk in unique.chunks(ADDRESS_UTXO_PAGE_SIZE) {
+            let body = json!({ "_datum_hashes": chunk });
+            let response: Vec<KoiosDatum> = send_with_retry(
+                || self.authorized(self.client.post(&url).json(&body)),
+                "Failed to fetch datum from Koios",
+            )
+            .await?
+            .json()
+            .await
+            .context("Failed to parse Koios datum response")?;
+
+            datums.extend(
+                response
+                    .into_iter()
+                    .map(|datum| (datum.datum_hash, datum.bytes)),
+            );
+        }
+
+        Ok(datums)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosUtxo {
+    tx_hash: String,
+    tx_index: u16,
+    address: String,
+    value: String,
+    datum_hash: Option<String>,
+    inline_datum: Option<KoiosInlineDatum>,
+    reference_script: Option<KoiosReferenceScript>,
+    #[serde(default)]
+    asset_list: Vec<KoiosAsset>,
+    block_height: Option<u64>,
+    is_spent: Option<bool>,
+}
+
+impl KoiosUtxo {
+    fn into_utxo(self, datum: Option<String>) -> UtxoInfo {
+        let mut amount = vec![AssetAmount {
+            unit: "lovelace".to_string(),
+            quantity: self.value,
+        }];
+        amount.extend(self.asset_list.into_iter().map(|asset| AssetAmount {
+            unit: format!("{}{}", asset.policy_id, asset.asset_name),
+            quantity: asset.quantity,
+        }));
+
+        UtxoInfo {
+            tx_hash: self.tx_hash,
+            output_index: self.tx_index,
+            address: self.address,
+            amount,
+            datum_hash: self.datum_hash,
+            datum,
+            inline_datum: self.inline_datum.is_some(),
+            script_ref: self.reference_script.map(|script| script.hash),
+            block_height: self.block_height,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosInlineDatum {
+    bytes: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosReferenceScript {
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosAsset {
+    policy_id: String,
+    #[serde(default)]
+    asset_name: String,
+    quantity: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosDatum {
+    datum_hash: String,
+    bytes: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosTxInfo {
+    block_height: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosTip {
+    hash: String,
+    abs_slot: u64,
+    block_no: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KoiosProtocolParams {
+    tx_fee_per_byte: u64,
+    tx_fee_fixed: u64,
+    max_tx_size: u64,
+    max_value_size: u64,
+    stake_address_deposit: u64,
+    stake_pool_deposit: u64,
+    execution_unit_prices: KoiosExecutionPrices,
+    max_tx_execution_units: KoiosExecutionUnits,
+    utxo_cost_per_byte: u64,
+    #[serde(default)]
+    min_fee_ref_script_cost_per_byte: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KoiosExecutionPrices {
+    price_memory: serde_json::Number,
+    price_steps: serde_json::Number,
+}
+
+#[derive(Debug, Deserialize)]
+struct KoiosExecutionUnits {
+    memory: u64,
+    steps: u64,
+}
@@ -4,8 +4,8 @@ use serde::Deserialize;
 use super::{
     AssetAmount, ChainTip, MaestroProvider, SubmitResponse, UtxoInfo,
     common::{
-        ADDRESS_UTXO_PAGE_SIZE, ProtocolParams, parse_required,
-        send_with_retry, with_pagination,
+        ADDRESS_UTXO_PAGE_SIZE, ProtocolParams, Ratio, optional_ratio,
+        parse_required, send_with_retry, with_pagination,
     },
 };
 
@@ -71,6 +71,8 @@ impl MaestroProvider {
                 })
                 .collect(),
             datum_hash: response.datum_hash,
+            // The datum Maestro returns with an output is the one it holds
+            inline_datum: response.datum.is_some(),
             datum: response.datum,
             script_ref: response.reference_script_hash,
             block_height: response.block_height,
@@ -122,6 +124,7 @@ impl MaestroProvider {
                     .collect(),
                 datum_hash: utxo.datum_hash,
                 datum: None,
+                inline_datum: false,
                 script_ref: utxo.reference_script_hash,
                 block_height: None,
             })
@@ -195,8 +198,8 @@ impl MaestroProvider {
                 "pool_deposit",
                 &response.pool_deposit,
             )?,
-            price_mem: parse_required("price_mem", &response.price_mem)?,
-            price_step: parse_required("price_step", &response.price_step)?,
+            price_mem: Ratio::parse("price_mem", &response.price_mem)?,
+            price_step: Ratio::parse("price_step", &response.price_step)?,
             max_tx_ex_mem: parse_required(
                 "max_tx_ex_mem",
                 &response.max_tx_ex_mem,
@@ -209,6 +212,10 @@ impl MaestroProvider {
                 "coins_per_utxo_byte",
                 &response.coins_per_utxo_byte,
             )?,
+            min_fee_ref_script_cost_per_byte: optional_ratio(
+                "min_fee_ref_script_cost_per_byte",
+                response.min_fee_ref_script_cost_per_byte.as_ref(),
+            )?,
         })
     }
 }
@@ -268,4 +275,6 @@ struct MaestroProtocolParams {
     max_tx_ex_mem: String,
     max_tx_ex_steps: String,
     coins_per_utxo_byte: String,
+    #[serde(default)]
+    min_fee_ref_script_cost_per_byte: Option<serde_json::Value>,
 }
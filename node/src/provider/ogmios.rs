@@ -0,0 +1,608 @@
+use std::collections::BTreeMap;
+
+use color_eyre::eyre::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::{
+    net::TcpStream,
+    time::{Duration, sleep, timeout},
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message,
+};
+
+use super::{
+    AssetAmount, ChainTip, OgmiosProvider, RedeemerBudget, SubmitResponse,
+    UtxoInfo,
+    common::{
+        PROVIDER_BACKOFF_MS, PROVIDER_MAX_RETRIES, ProtocolParams, Ratio,
+        optional_ratio, send_with_retry,
+    },
+};
+
+pub const OGMIOS_PROVIDER: &str = "ogmios";
+pub const DEFAULT_KUPO_URL: &str = "http://127.0.0.1:1442";
+pub const DEFAULT_OGMIOS_URL: &str = "ws://127.0.0.1:1337";
+
+const OGMIOS_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Ogmios error code for a chain-sync intersection that is not on chain
+const INTERSECTION_NOT_FOUND: i64 = 1000;
+
+impl OgmiosProvider {
+    pub(super) async fn get_tx_block_height(
+        &self,
+        tx_hash: &str,
+    ) -> Result<Option<u64>> {
+        let matches = self
+            .kupo_matches(
+                &format!("*@{tx_hash}"),
+                false,
+                "Failed to fetch transaction outputs from Kupo",
+            )
+            .await?;
+
+        match matches.first() {
+            Some(output) => self.block_height_of(&output.created_at).await,
+            None => Ok(None),
+        }
+    }
+
+    pub(super) async fn get_utxo(
+        &self,
+        tx_hash: &str,
+        output_index: u16,
+    ) -> Result<Option<UtxoInfo>> {
+        let matches = self
+            .kupo_matches(
+                &format!("{output_index}@{tx_hash}"),
+                true,
+                "Failed to fetch UTxO from Kupo",
+            )
+            .await?;
+
+        let Some(output) = matches.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let datum = match &output.datum_hash {
+            Some(hash) => self.get_datum(hash).await?,
+            None => None,
+        };
+        let block_height = self.block_height_of(&output.created_at).await?;
+
+        Ok(Some(KupoMatch::into_utxo(output, datum, block_height)))
+    }
+
+    pub(super) async fn get_address_utxos(
+        &self,
+        address: &str,
+    ) -> Result<Vec<UtxoInfo>> {
+        let matches = self
+            .kupo_matches(
+                address,
+                true,
+                "Failed to fetch address UTxOs from Kupo",
+            )
+            .await?;
+
+        Ok(matches
+            .into_iter()
+            .map(|output| KupoMatch::into_utxo(output, None, None))
+            .collect())
+    }
+
+    pub(super) async fn submit_tx(
+        &self,
+        tx_cbor: &[u8],
+    ) -> Result<SubmitResponse> {
+        let response: OgmiosSubmitResponse = self
+            .ogmios_call(
+                "submitTransaction",
+                json!({ "transaction": { "cbor": hex::encode(tx_cbor) } }),
+            )
+            .await
+            .context("Failed to submit transaction to Ogmios")?;
+
+        Ok(SubmitResponse {
+            tx_hash: response.transaction.id,
+        })
+    }
+
+    pub(super) async fn get_tip(&self) -> Result<ChainTip> {
+        let mut session = self.connect().await?;
+        let tip: Value = session
+            .request("queryNetwork/tip", Value::Null)
+            .await
+            .context("Failed to fetch tip from Ogmios")?;
+        let height: Value = session
+            .request("queryNetwork/blockHeight", Value::Null)
+            .await
+            .context("Failed to fetch block height from Ogmios")?;
+        session.close().await;
+
+        // Both queries answer "origin" before the first block
+        let tip = serde_json::from_value::<OgmiosPoint>(tip).ok();
+        Ok(match (tip, height.as_u64()) {
+            (Some(tip), Some(block_height)) => ChainTip {
+                slot: tip.slot,
+                hash: tip.id,
+                block_height,
+            },
+            _ => ChainTip {
+                slot: 0,
+                hash: String::new(),
+                block_height: 0,
+            },
+        })
+    }
+
+    pub(super) async fn get_protocol_params(&self) -> Result<ProtocolParams> {
+        let response: OgmiosProtocolParams = self
+            .ogmios_call("queryLedgerState/protocolParameters", Value::Null)
+            .await
+            .context("Failed to fetch protocol params from Ogmios")?;
+
+        Ok(ProtocolParams {
+            min_fee_a: response.min_fee_coefficient,
+            min_fee_b: response.min_fee_constant.ada.lovelace,
+            max_tx_size: response.max_transaction_size.bytes,
+            max_val_size: response.max_value_size.bytes,
+            key_deposit: response.stake_credential_deposit.ada.lovelace,
+            pool_deposit: response.stake_pool_deposit.ada.lovelace,
+            price_mem: Ratio::parse(
+                "price_mem",
+                &response.script_execution_prices.memory,
+            )?,
+            price_step: Ratio::parse(
+                "price_step",
+                &response.script_execution_prices.cpu,
+            )?,
+            max_tx_ex_mem: response.max_execution_units_per_transaction.memory,
+            max_tx_ex_steps: response.max_execution_units_per_transaction.cpu,
+            coins_per_utxo_byte: response.min_utxo_deposit_coefficient,
+            min_fee_ref_script_cost_per_byte: optional_ratio(
+                "min_fee_ref_script_cost_per_byte",
+                response
+                    .min_fee_reference_scripts
+                    .as_ref()
+                    .map(|fee| &fee.base),
+            )?,
+        })
+    }
+
+    pub(super) async fn evaluate_tx(
+        &self,
+        tx_cbor: &[u8],
+    ) -> Result<Vec<RedeemerBudget>> {
+        let response: Vec<OgmiosEvaluation> = self
+            .ogmios_call(
+                "evaluateTransaction",
+                json!({ "transaction": { "cbor": hex::encode(tx_cbor) } }),
+            )
+            .await
+            .context("Failed to evaluate transaction with Ogmios")?;
+
+        Ok(response
+            .into_iter()
+            .map(|evaluation| RedeemerBudget {
+                purpose: evaluation.validator.purpose,
+                index: evaluation.validator.index,
+                memory: evaluation.budget.memory,
+                cpu: evaluation.budget.cpu,
+            })
+            .collect())
+    }
+
+    async fn kupo_matches(
+        &self,
+        pattern: &str,
+        unspent: bool,
+        context: &str,
+    ) -> Result<Vec<KupoMatch>> {
+        let mut url = format!("{}/matches/{}", self.kupo_url, pattern);
+        if unspent {
+            url.push_str("?unspent");
+        }
+
+        let resp = send_with_retry(|| self.client.get(&url), context).await?;
+        if resp.status().as_u16() == 404 {
+            return Ok(Vec::new());
+        }
+
+        resp.json().await.context("Failed to parse Kupo response")
+    }
+
+    async fn get_datum(&self, datum_hash: &str) -> Result<Option<String>> {
+        let url = format!("{}/datums/{}", self.kupo_url, datum_hash);
+        let resp = send_with_retry(
+            || self.client.get(&url),
+            "Failed to fetch datum from Kupo",
+        )
+        .await?;
+        if resp.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        let response: Option<KupoDatum> = resp
+            .json()
+            .await
+            .context("Failed to parse Kupo datum response")?;
+
+        Ok(response.and_then(|datum| datum.datum))
+    }
+
+    /// Kupo only records the slot and header hash of the block an output was
+    /// created in. Ogmios resolves that point to a height by intersecting the
+    /// chain there and reading the block that follows it.
+    async fn block_height_of(&self, point: &KupoPoint) -> Result<Option<u64>> {
+        let mut session = self.connect().await?;
+        let intersection = session
+            .request::<OgmiosIntersection>(
+                "findIntersection",
+                json!({ "points": [{ "slot": point.slot_no, "id": point.header_hash }] }),
+            )
+            .await;
+
+        let intersection = match intersection {
+            Ok(intersection) => intersection,
+            Err(OgmiosError::Rpc { code, .. })
+                if code == INTERSECTION_NOT_FOUND =>
+            {
+                session.close().await;
+                return Ok(None);
+            }
+            Err(e) => return Err(e).context("Failed to find block in Ogmios"),
+        };
+
+        if intersection.tip.id == point.header_hash {
+            session.close().await;
+            return Ok(Some(intersection.tip.height));
+        }
+
+        // The first response after an intersection is always a roll back
+        // to the intersection itself.
+        let _: Value = session.request("nextBlock", Value::Null).await?;
+        let next: OgmiosNextBlock = session
+            .request("nextBlock", Value::Null)
+            .await
+            .context("Failed to read block from Ogmios")?;
+        session.close().await;
+
+        Ok(next.block.map(|block| block.height.saturating_sub(1)))
+    }
+
+    async fn ogmios_call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T> {
+        let mut session = self.connect().await?;
+        let result = session.request(method, params).await;
+        session.close().await;
+
+        Ok(result?)
+    }
+
+    async fn connect(&self) -> Result<OgmiosSession> {
+        let mut delay = PROVIDER_BACKOFF_MS;
+        for attempt in 1..=PROVIDER_MAX_RETRIES {
+            match timeout(
+                OGMIOS_REQUEST_TIMEOUT,
+                connect_async(self.ogmios_url.as_str()),
+            )
+            .await
+            {
+                Ok(Ok((socket, _))) => {
+                    return Ok(OgmiosSession { socket, next_id: 0 });
+                }
+                Ok(Err(e)) if attempt == PROVIDER_MAX_RETRIES => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Failed to connect to Ogmios (network error after {} attempts): {}",
+                        attempt,
+                        e
+                    ));
+                }
+                Err(_) if attempt == PROVIDER_MAX_RETRIES => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "Failed to connect to Ogmios: timed out after {} attempts",
+                        attempt
+                    ));
+                }
+                _ => {}
+            }
+
+            sleep(Duration::from_millis(delay)).await;
+            delay *= 2;
+        }
+
+        Err(color_eyre::eyre::eyre!(
+            "Failed to connect to Ogmios: exceeded max retries"
+        ))
+    }
+}
+
+/// One Ogmios JSON-RPC connection. Requests are answered in order, so each
+/// call waits for the response carrying its own id.
+struct OgmiosSession {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl OgmiosSession {
+    async fn request<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, OgmiosError> {
+        timeout(OGMIOS_REQUEST_TIMEOUT, self.exchange(method, params))
+            .await
+            .map_err(|_| {
+                OgmiosError::Transport(format!("{method} timed out"))
+            })?
+    }
+
+    async fn exchange<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, OgmiosError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut request =
+            json!({ "jsonrpc": "2.0", "method": method, "id": id });
+        if !params.is_null() {
+            request["params"] = params;
+        }
+
+        self.socket
+            .send(Message::text(request.to_string()))
+            .await
+            .map_err(|e| OgmiosError::Transport(e.to_string()))?;
+
+        while let Some(message) = self.socket.next().await {
+            let text = match message
+                .map_err(|e| OgmiosError::Transport(e.to_string()))?
+            {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            let response: OgmiosResponse = serde_json::from_str(&text)
+                .map_err(|e| OgmiosError::Transport(e.to_string()))?;
+            if response.id.as_ref().and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+
+            if let Some(error) = response.error {
+                return Err(OgmiosError::Rpc {
+                    code: error.code,
+                    message: error.message,
+                    data: error.data,
+                });
+            }
+
+            return serde_json::from_value(
+                response.result.unwrap_or(Value::Null),
+            )
+            .map_err(|e| {
+                OgmiosError::Transport(format!(
+                    "invalid {method} response: {e}"
+                ))
+            });
+        }
+
+        Err(OgmiosError::Transport(format!(
+            "connection closed before {method} response"
+        )))
+    }
+
+    async fn close(mut self) {
+        let _ = self.socket.close(None).await;
+    }
+}
+
+#[derive(Debug)]
+enum OgmiosError {
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    Transport(String),
+}
+
+impl std::fmt::Display for OgmiosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rpc {
+                code,
+                message,
+                data: Some(data),
+            } => write!(f, "Ogmios error {code}: {message} ({data})"),
+            Self::Rpc { code, message, .. } => {
+                write!(f, "Ogmios error {code}: {message}")
+            }
+            Self::Transport(reason) => {
+                write!(f, "Ogmios transport error: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OgmiosError {}
+
+#[derive(Debug, Deserialize)]
+struct KupoMatch {
+    transaction_id: String,
+    output_index: u16,
+    address: String,
+    value: KupoValue,
+    datum_hash: Option<String>,
+    datum_type: Option<String>,
+    script_hash: Option<String>,
+    created_at: KupoPoint,
+}
+
+impl KupoMatch {
+    fn into_utxo(
+        self,
+        datum: Option<String>,
+        block_height: Option<u64>,
+    ) -> UtxoInfo {
+        let mut amount = vec![AssetAmount {
+            unit: "lovelace".to_string(),
+            quantity: self.value.coins.to_string(),
+        }];
+        amount.extend(self.value.assets.into_iter().map(|(unit, quantity)| {
+            AssetAmount {
+                unit: unit.replace('.', ""),
+                quantity: quantity.to_string(),
+            }
+        }));
+
+        UtxoInfo {
+            tx_hash: self.transaction_id,
+            output_index: self.output_index,
+            address: self.address,
+            amount,
+            datum_hash: self.datum_hash,
+            datum,
+            inline_datum: self.datum_type.as_deref() == Some("inline"),
+            script_ref: self.script_hash,
+            block_height,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KupoValue {
+    coins: u64,
+    #[serde(default)]
+    assets: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KupoPoint {
+    slot_no: u64,
+    header_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KupoDatum {
+    datum: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosResponse {
+    id: Option<Value>,
+    result: Option<Value>,
+    error: Option<OgmiosRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosRpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosPoint {
+    slot: u64,
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosIntersection {
+    tip: OgmiosChainTip,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosChainTip {
+    id: String,
+    height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosNextBlock {
+    block: Option<OgmiosBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosBlock {
+    height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosSubmitResponse {
+    transaction: OgmiosTransactionId,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosTransactionId {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosEvaluation {
+    validator: OgmiosValidator,
+    budget: OgmiosBudget,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosValidator {
+    purpose: String,
+    index: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosBudget {
+    memory: u64,
+    cpu: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OgmiosProtocolParams {
+    min_fee_coefficient: u64,
+    min_fee_constant: OgmiosAda,
+    max_transaction_size: OgmiosBytes,
+    max_value_size: OgmiosBytes,
+    stake_credential_deposit: OgmiosAda,
+    stake_pool_deposit: OgmiosAda,
+    script_execution_prices: OgmiosPrices,
+    max_execution_units_per_transaction: OgmiosBudget,
+    min_utxo_deposit_coefficient: u64,
+    #[serde(default)]
+    min_fee_reference_scripts: Option<OgmiosReferenceScriptFee>,
+}
+
+/// Only the base price is read; the tier size and multiplier are fixed by
+/// the ledger
+#[derive(Debug, Deserialize)]
+struct OgmiosReferenceScriptFee {
+    base: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosAda {
+    ada: OgmiosLovelace,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosLovelace {
+    lovelace: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosBytes {
+    bytes: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OgmiosPrices {
+    memory: String,
+    cpu: String,
+}
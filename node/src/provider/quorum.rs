@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+
+use color_eyre::eyre::Result;
+use futures_util::future::{BoxFuture, FutureExt, join_all};
+
+use super::{
+    ChainTip, Provider, ProviderDisagreement, QuorumProvider, RedeemerBudget,
+    SubmitResponse, UtxoInfo, common::ProtocolParams,
+};
+use crate::telemetry::record_provider_disagreement;
+
+pub const QUORUM_PROVIDER: &str = "quorum";
+
+// Each backend call re-enters `Provider`, so the public methods return boxed
+// futures to keep the recursive future types finite.
+impl QuorumProvider {
+    /// The block height `quorum` backends agree a tx was included at
+    pub(super) fn get_tx_block_height<'a>(
+        &'a self,
+        tx_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>>> {
+        self.agree(
+            "get_tx_block_height",
+            move |backend| backend.tx_block_height(tx_hash),
+            |height| *height,
+        )
+        .boxed()
+    }
+
+    /// The output `quorum` backends agree exists, with the same address,
+    /// value, datum and block height
+    pub(super) fn get_utxo<'a>(
+        &'a self,
+        tx_hash: &'a str,
+        output_index: u16,
+    ) -> BoxFuture<'a, Result<Option<UtxoInfo>>> {
+        self.agree(
+            "get_utxo",
+            move |backend| backend.get_utxo(tx_hash, output_index),
+            |utxo| utxo.as_ref().map(UtxoAgreement::of),
+        )
+        .boxed()
+    }
+
+    /// The outputs at `address` that `quorum` backends list with the same
+    /// address and value. Their datum and block height are kept only where
+    /// `quorum` backends agree on those as well, and are otherwise left out
+    /// for [`get_utxo`](Self::get_utxo) to settle. The chain index is filled
+    /// from this listing, so one backend cannot slip an output into it.
+    pub(super) fn get_address_utxos<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<UtxoInfo>>> {
+        async move {
+            if self.quorum <= 1 {
+                return self
+                    .failover("get_address_utxos", move |backend| {
+                        backend.get_address_utxos(address)
+                    })
+                    .await;
+            }
+
+            let answers = join_all(
+                self.backends
+                    .iter()
+                    .map(|backend| backend.get_address_utxos(address)),
+            )
+            .await;
+
+            let mut failures = Vec::new();
+            let mut listings = Vec::new();
+            for (label, answer) in self.labels().into_iter().zip(answers) {
+                match answer {
+                    Ok(utxos) => listings.push(utxos),
+                    Err(e) => failures.push(format!("{label}: {e}")),
+                }
+            }
+            if listings.len() < self.quorum {
+                return Err(self.unavailable("get_address_utxos", &failures));
+            }
+
+            let mut listed: BTreeMap<(String, u16), Vec<UtxoInfo>> =
+                BTreeMap::new();
+            for utxos in listings {
+                for utxo in utxos {
+                    listed
+                        .entry((utxo.tx_hash.to_lowercase(), utxo.output_index))
+                        .or_default()
+                        .push(utxo);
+                }
+            }
+
+            let mut disagreed = false;
+            let mut agreed = Vec::new();
+            for answers in listed.into_values() {
+                match self.agreed_output(answers) {
+                    Some(utxo) => agreed.push(utxo),
+                    None => disagreed = true,
+                }
+            }
+            if disagreed {
+                record_provider_disagreement("get_address_utxos");
+                tracing::warn!(
+                    address,
+                    "provider backends disagree on address outputs"
+                );
+            }
+
+            Ok(agreed)
+        }
+        .boxed()
+    }
+
+    pub(super) fn submit_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<SubmitResponse>> {
+        self.failover("submit_tx", move |backend| backend.submit_tx(tx_cbor))
+            .boxed()
+    }
+
+    /// Tips move independently on each backend, so instead of exact
+    /// agreement this takes the highest tip that `quorum` backends have
+    /// reached. One backend cannot inflate confirmation counts alone.
+    pub(super) fn get_tip(&self) -> BoxFuture<'_, Result<ChainTip>> {
+        async move {
+            let answers =
+                join_all(self.backends.iter().map(|backend| backend.get_tip()))
+                    .await;
+
+            let mut failures = Vec::new();
+            let mut tips = Vec::new();
+            for (label, answer) in self.labels().into_iter().zip(answers) {
+                match answer {
+                    Ok(tip) => tips.push(tip),
+                    Err(e) => failures.push(format!("{label}: {e}")),
+                }
+            }
+
+            tips.sort_by_key(|tip| std::cmp::Reverse(tip.block_height));
+            tips.into_iter()
+                .nth(self.quorum - 1)
+                .ok_or_else(|| self.unavailable("get_tip", &failures))
+        }
+        .boxed()
+    }
+
+    pub(super) fn get_protocol_params(
+        &self,
+    ) -> BoxFuture<'_, Result<ProtocolParams>> {
+        self.failover("get_protocol_params", |backend| {
+            backend.get_protocol_params()
+        })
+        .boxed()
+    }
+
+    pub(super) fn evaluate_tx<'a>(
+        &'a self,
+        tx_cbor: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<RedeemerBudget>>> {
+        self.failover("evaluate_tx", move |backend| {
+            backend.evaluate_tx(tx_cbor)
+        })
+        .boxed()
+    }
+
+    /// The output `quorum` of `answers` list with the same address and
+    /// value, with its datum and height cleared unless `quorum` agree on
+    /// them too
+    fn agreed_output(&self, answers: Vec<UtxoInfo>) -> Option<UtxoInfo> {
+        let mut groups: Vec<(UtxoAgreement, Vec<UtxoInfo>)> = Vec::new();
+        for utxo in answers {
+            let key = UtxoAgreement::of_value(&utxo);
+            match groups.iter_mut().find(|group| group.0 == key) {
+                Some(group) => group.1.push(utxo),
+                None => groups.push((key, vec![utxo])),
+            }
+        }
+
+        let mut agreed = groups
+            .into_iter()
+            .filter(|group| group.1.len() >= self.quorum);
+        let (Some((_, answers)), None) = (agreed.next(), agreed.next()) else {
+            return None;
+        };
+
+        let mut details: Vec<(UtxoAgreement, usize)> = Vec::new();
+        for utxo in &answers {
+            let key = UtxoAgreement::of(utxo);
+            match details.iter_mut().find(|detail| detail.0 == key) {
+                Some(detail) => detail.1 += 1,
+                None => details.push((key, 1)),
+            }
+        }
+        let settled = details
+            .iter()
+            .find(|detail| detail.1 >= self.quorum)
+            .map(|detail| &detail.0);
+
+        let mut answers = answers.into_iter();
+        match settled {
+            Some(settled) => {
+                answers.find(|utxo| UtxoAgreement::of(utxo) == *settled)
+            }
+            None => answers.next().map(|utxo| UtxoInfo {
+                datum: None,
+                inline_datum: false,
+                block_height: None,
+                ..utxo
+            }),
+        }
+    }
+
+    /// Backend names with their position, so two backends of the same type
+    /// can be told apart
+    fn labels(&self) -> Vec<String> {
+        self.backends
+            .iter()
+            .enumerate()
+            .map(|(i, backend)| format!("{}[{i}]", backend.name()))
+            .collect()
+    }
+
+    fn unavailable(
+        &self,
+        operation: &str,
+        failures: &[String],
+    ) -> color_eyre::eyre::Report {
+        color_eyre::eyre::eyre!(
+            "fewer than {} of {} provider backends answered {operation}: {}",
+            self.quorum,
+            self.backends.len(),
+            failures.join("; ")
+        )
+    }
+
+    /// Ask the backends in order and return the first answer
+    async fn failover<'a, T, F>(
+        &'a self,
+        operation: &'static str,
+        call: impl Fn(&'a Provider) -> F,
+    ) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut failures = Vec::new();
+        for (label, backend) in self.labels().into_iter().zip(&self.backends) {
+            match call(backend).await {
+                Ok(answer) => return Ok(answer),
+                Err(e) => {
+                    tracing::warn!(
+                        backend = %label,
+                        operation,
+                        "provider backend failed, trying the next: {e}"
+                    );
+                    failures.push(format!("{label}: {e}"));
+                }
+            }
+        }
+
+        Err(color_eyre::eyre::eyre!(
+            "all {} provider backends failed {operation}: {}",
+            self.backends.len(),
+            failures.join("; ")
+        ))
+    }
+
+    /// Ask every backend and return the answer exactly one group of at
+    /// least `quorum` backends agrees on, compared by `key`
+    async fn agree<'a, T, K, F>(
+        &'a self,
+        operation: &'static str,
+        call: impl Fn(&'a Provider) -> F,
+        key: impl Fn(&T) -> K,
+    ) -> Result<T>
+    where
+        K: PartialEq,
+        F: Future<Output = Result<T>>,
+    {
+        let answers = join_all(self.backends.iter().map(&call)).await;
+
+        let mut groups: Vec<(K, T, Vec<String>)> = Vec::new();
+        let mut failures = Vec::new();
+        for (label, answer) in self.labels().into_iter().zip(answers) {
+            match answer {
+                Ok(answer) => {
+                    let answer_key = key(&answer);
+                    match groups.iter_mut().find(|group| group.0 == answer_key)
+                    {
+                        Some(group) => group.2.push(label),
+                        None => groups.push((answer_key, answer, vec![label])),
+                    }
+                }
+                Err(e) => failures.push(format!("{label}: {e}")),
+            }
+        }
+
+        if groups.len() > 1 {
+            record_provider_disagreement(operation);
+            tracing::warn!(
+                operation,
+                answers = ?groups.iter().map(|group| &group.2).collect::<Vec<_>>(),
+                "provider backends disagree"
+            );
+        }
+
+        let mut agreed = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.2.len() >= self.quorum)
+            .map(|(i, _)| i);
+        match (agreed.next(), agreed.next()) {
+            (Some(i), None) => Ok(groups.swap_remove(i).1),
+            _ if groups.len() > 1 => Err(ProviderDisagreement {
+                operation,
+                quorum: self.quorum,
+                answers: groups.into_iter().map(|group| group.2).collect(),
+            }
+            .into()),
+            _ => Err(self.unavailable(operation, &failures)),
+        }
+    }
+}
+
+/// The parts of an output that must match across backends. Asset lists are
+/// compared as totals per unit, whatever order the backend lists them in.
+#[derive(Debug, PartialEq, Eq)]
+struct UtxoAgreement {
+    address: String,
+    amount: BTreeMap<String, String>,
+    datum: Option<String>,
+    inline_datum: bool,
+    block_height: Option<u64>,
+}
+
+impl UtxoAgreement {
+    fn of(utxo: &UtxoInfo) -> Self {
+        let mut totals: BTreeMap<String, u128> = BTreeMap::new();
+        let mut unparsed = BTreeMap::new();
+        for asset in &utxo.amount {
+            match asset.quantity.parse::<u128>() {
+                Ok(quantity) => {
+                    *totals.entry(asset.unit.clone()).or_default() += quantity
+                }
+                Err(_) => {
+                    unparsed.insert(asset.unit.clone(), asset.quantity.clone());
+                }
+            }
+        }
+        let mut amount: BTreeMap<String, String> = totals
+            .into_iter()
+            .map(|(unit, quantity)| (unit, quantity.to_string()))
+            .collect();
+        amount.extend(unparsed);
+
+        Self {
+            address: utxo.address.clone(),
+            amount,
+            datum: utxo.datum.as_ref().map(|datum| datum.to_lowercase()),
+            inline_datum: utxo.inline_datum,
+            block_height: utxo.block_height,
+        }
+    }
+
+    /// The address and value alone, which decide whether an output exists
+    fn of_value(utxo: &UtxoInfo) -> Self {
+        Self {
+            datum: None,
+            inline_datum: false,
+            block_height: None,
+            ..Self::of(utxo)
+        }
+    }
+}
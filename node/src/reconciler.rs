@@ -3,16 +3,21 @@ use std::{sync::Arc, time::Duration};
 use blake3::Hasher;
 use mugraph_core::{
     error::Error,
-    types::{CrossNodeMessageRecord, TransferAuditEvent},
+    types::{
+        CrossNodeMessageRecord, EventKind, EventSubject, TransferAuditEvent,
+    },
 };
 use redb::ReadableTable;
 use tokio::time::{MissedTickBehavior, interval};
 
 use crate::{
     database::{
-        CROSS_NODE_MESSAGES, CROSS_NODE_TRANSFERS, Database, TRANSFER_AUDIT_LOG,
+        CROSS_NODE_MESSAGES, CROSS_NODE_TRANSFERS, Database,
+        TRANSFER_AUDIT_LOG, Write,
     },
-    lifecycle::apply_retry_exhaustion_to_record,
+    lifecycle::{apply_retry_exhaustion_to_record, status_payload_from_record},
+    routes::Heartbeat,
+    supervisor::Shutdown,
 };
 
 const DEFAULT_MAX_ATTEMPTS: u32 = 12;
@@ -40,18 +45,26 @@ pub async fn reconciler_loop(
     database: Arc<Database>,
     tick: Duration,
     policy: RetryPolicy,
+    heartbeat: Arc<Heartbeat>,
+    shutdown: Shutdown,
 ) {
     let mut ticker = interval(tick);
     ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     loop {
-        ticker.tick().await;
-        if let Err(e) = reconcile_once(&database, policy, now_secs()) {
-            tracing::error!("reconciler tick failed: {}", e);
+        tokio::select! {
+            biased;
+            _ = shutdown.wait() => break,
+            _ = ticker.tick() => {}
+        }
+        match reconcile_once(&database, policy, now_secs()) {
+            Ok(()) => heartbeat.beat(),
+            Err(e) => tracing::error!("reconciler tick failed: {}", e),
         }
     }
 }
 
+#[tracing::instrument(name = "reconciler_tick", skip(database, policy))]
 pub fn reconcile_once(
     database: &Database,
     policy: RetryPolicy,
@@ -133,6 +146,7 @@ pub fn reconcile_once(
 
                     if exhausted_after_increment {
                         handle_exhaustion(
+                            &write_tx,
                             &message,
                             now,
                             &mut transfers,
@@ -159,6 +173,7 @@ pub fn reconcile_once(
                     )
                     .increment(1);
                     handle_exhaustion(
+                        &write_tx,
                         &message,
                         now,
                         &mut transfers,
@@ -178,6 +193,7 @@ pub fn reconcile_once(
 }
 
 fn handle_exhaustion(
+    write_tx: &Write,
     message: &CrossNodeMessageRecord,
     now: u64,
     transfers: &mut redb::Table<
@@ -217,6 +233,10 @@ fn handle_exhaustion(
         apply_retry_exhaustion_to_record(&mut transfer);
         transfer.updated_at = now;
         transfers.insert(message.transfer_id.as_str(), &transfer)?;
+        write_tx.append_event(
+            EventSubject::transfer(&message.transfer_id),
+            EventKind::TransferStatus(status_payload_from_record(&transfer)),
+        )?;
     }
 
     metrics::counter!(
@@ -352,7 +372,7 @@ fn now_nanos() -> u128 {
 
 #[cfg(test)]
 mod tests {
-    use mugraph_core::types::CrossNodeTransferRecord;
+    use mugraph_core::types::{CrossNodeTransferRecord, TransferChainState};
     use proptest::prelude::*;
 
     use super::*;
@@ -427,6 +447,15 @@ mod tests {
         let transfer = t.get("tr-1").unwrap().unwrap().value();
         assert_eq!(transfer.credit_state, "held");
         assert_eq!(transfer.chain_state, "invalidated");
+
+        let events = db.events_after(0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].subject, EventSubject::transfer("tr-1"));
+        assert!(matches!(
+            &events[0].event,
+            EventKind::TransferStatus(status)
+                if status.chain_state == TransferChainState::Invalidated
+        ));
     }
 
     #[test]
@@ -0,0 +1,425 @@
+//! `batch` requests: several calls in one round trip, answered in order.
+//! Atomic batches commit all of their refreshes in one write transaction.
+
+use std::net::IpAddr;
+
+use axum::{Json, extract::State};
+use mugraph_core::{
+    error::{Error, ErrorCode},
+    types::{BatchRequest, ProtocolVersion, Refresh, Request, Response},
+};
+
+use super::{
+    Context, apply_refresh, batch_item_rejection, batch_too_large,
+    record_refresh, request_method, require_peer_certificate, rpc,
+};
+use crate::tls::ClientInfo;
+
+/// Run every request in `batch`, each under its own rate limits and checked
+/// against the `version` its client declared. Errors in one request do not
+/// stop the others unless the batch is atomic.
+pub async fn run_batch(
+    ctx: &Context,
+    ip: IpAddr,
+    client: Option<&ClientInfo>,
+    version: Option<ProtocolVersion>,
+    batch: BatchRequest,
+) -> Response {
+    if let Err(response) = validate(ctx, &batch) {
+        return response;
+    }
+
+    if batch.atomic {
+        return run_atomic(ctx, ip, client, version, batch.requests);
+    }
+
+    let mut responses = Vec::with_capacity(batch.requests.len());
+    for request in batch.requests {
+        let method = request_method(&request);
+        let _permit = match admit(ctx, ip, client, version, &request) {
+            Ok(permit) => permit,
+            Err(e) => {
+                responses.push(batch_item_rejection(method, e));
+                continue;
+            }
+        };
+
+        let Json(response) =
+            Box::pin(rpc(State(ctx.clone()), Json(request))).await;
+        responses.push(response);
+    }
+
+    Response::Batch { responses }
+}
+
+/// Check the batch as a whole before any of it runs.
+fn validate(ctx: &Context, batch: &BatchRequest) -> Result<(), Response> {
+    let max_batch_size = ctx.config.rpc_max_batch_size();
+    if max_batch_size == 0 {
+        return Err(Response::error(
+            ErrorCode::MethodDisabled,
+            "batch requests are disabled on this node",
+        ));
+    }
+    if batch.requests.len() > max_batch_size {
+        return Err(batch_too_large(batch.requests.len(), max_batch_size));
+    }
+
+    for request in &batch.requests {
+        match request {
+            Request::Batch(_) => {
+                return Err(Response::error(
+                    ErrorCode::InvalidInput,
+                    "batches cannot be nested",
+                ));
+            }
+            Request::Refresh(_) => {}
+            _ if batch.atomic => {
+                return Err(Response::error(
+                    ErrorCode::InvalidInput,
+                    format!(
+                        "atomic batches may only carry refresh requests, got {}",
+                        request_method(request)
+                    ),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Rate limits, the xnode client certificate check and the features the
+/// declared version allows, for one request.
+fn admit(
+    ctx: &Context,
+    ip: IpAddr,
+    client: Option<&ClientInfo>,
+    version: Option<ProtocolVersion>,
+    request: &Request,
+) -> Result<Option<tokio::sync::OwnedSemaphorePermit>, Error> {
+    let permit = ctx.limits.check(ip, request_method(request))?;
+
+    if ctx.config.xnode_client_ca_file().is_some() {
+        require_peer_certificate(request, client)?;
+    }
+
+    ProtocolVersion::negotiate(version, request)?;
+
+    Ok(permit)
+}
+
+/// Apply every refresh in one write transaction. The first failure aborts
+/// it: that request gets its error and every other one `BATCH_ABORTED`.
+fn run_atomic(
+    ctx: &Context,
+    ip: IpAddr,
+    client: Option<&ClientInfo>,
+    version: Option<ProtocolVersion>,
+    requests: Vec<Request>,
+) -> Response {
+    // Concurrency permits are held until the transaction commits, so every
+    // request in the batch counts against the cap while it runs
+    let mut permits = Vec::with_capacity(requests.len());
+    for (i, request) in requests.iter().enumerate() {
+        match admit(ctx, ip, client, version, request) {
+            Ok(permit) => permits.push(permit),
+            Err(e) => {
+                let method = request_method(request);
+                return aborted(
+                    requests.len(),
+                    i,
+                    batch_item_rejection(method, e),
+                );
+            }
+        }
+    }
+
+    let refreshes: Vec<Refresh> = requests
+        .into_iter()
+        .filter_map(|request| match request {
+            Request::Refresh(refresh) => Some(refresh),
+            _ => None,
+        })
+        .collect();
+
+    let w = match ctx.database.write() {
+        Ok(w) => w,
+        Err(e) => return e.into(),
+    };
+
+    let mut responses = Vec::with_capacity(refreshes.len());
+    for (i, refresh) in refreshes.iter().enumerate() {
+        match apply_refresh(refresh, ctx.keypair, &ctx.database, &w) {
+            Ok(response) => responses.push(response),
+            Err(e) => {
+                if let Err(abort) = w.abort() {
+                    tracing::warn!(error = %abort, "failed to abort batch");
+                }
+                return aborted(refreshes.len(), i, e.into());
+            }
+        }
+    }
+
+    if let Err(e) = w.commit() {
+        return e.into();
+    }
+    drop(permits);
+    for refresh in &refreshes {
+        record_refresh(refresh);
+    }
+
+    Response::Batch { responses }
+}
+
+/// Responses for an atomic batch whose request `failed` answered `error`.
+fn aborted(len: usize, failed: usize, error: Response) -> Response {
+    let responses = (0..len)
+        .map(|i| {
+            if i == failed {
+                error.clone()
+            } else {
+                Response::error(
+                    ErrorCode::BatchAborted,
+                    format!("rolled back because request {failed} failed"),
+                )
+            }
+        })
+        .collect();
+
+    Response::Batch { responses }
+}
+
+#[cfg(test)]
+mod tests {
+    use mugraph_core::{
+        builder::RefreshBuilder,
+        crypto,
+        types::{Hash, Keypair, Note, Signature},
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{config::Config, routes::tests::test_context};
+
+    /// Address in-process callers are rate limited under
+    const LOCAL: IpAddr = IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED);
+
+    fn signed_note(keypair: &Keypair, seed: u64, amount: u64) -> Note {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut note = Note {
+            delegate: keypair.public_key,
+            policy_id: Default::default(),
+            asset_name: Default::default(),
+            nonce: Hash::random(&mut rng),
+            amount,
+            signature: Signature::default(),
+            dleq: None,
+        };
+
+        let blind = crypto::blind_note(&mut rng, &note);
+        let signed =
+            crypto::sign_blinded(&mut rng, &keypair.secret_key, &blind.point);
+        note.signature = crypto::unblind_signature(
+            &signed.signature,
+            &blind.factor,
+            &keypair.public_key,
+        )
+        .unwrap();
+        note
+    }
+
+    fn spend(note: &Note) -> Request {
+        Request::Refresh(
+            RefreshBuilder::new()
+                .input(note.clone())
+                .output(note.policy_id, note.asset_name, note.amount)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn batch(requests: Vec<Request>, atomic: bool) -> BatchRequest {
+        BatchRequest { requests, atomic }
+    }
+
+    fn responses(response: Response) -> Vec<Response> {
+        match response {
+            Response::Batch { responses } => responses,
+            other => panic!("expected a batch response, got {other:?}"),
+        }
+    }
+
+    async fn responses_of(
+        ctx: &Context,
+        requests: Vec<Request>,
+        atomic: bool,
+    ) -> Vec<Response> {
+        responses(
+            run_batch(ctx, LOCAL, None, None, batch(requests, atomic)).await,
+        )
+    }
+
+    fn error_code(response: &Response) -> Option<ErrorCode> {
+        match response {
+            Response::Error { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_answers_each_request_in_order() {
+        let ctx = test_context();
+        let note = signed_note(&ctx.keypair, 1, 10);
+
+        // The second spend fails without undoing the first
+        let responses = responses_of(
+            &ctx,
+            vec![spend(&note), spend(&note), Request::Info],
+            false,
+        )
+        .await;
+
+        assert_eq!(responses.len(), 3);
+        assert!(matches!(responses[0], Response::Transaction { .. }));
+        assert_eq!(error_code(&responses[1]), Some(ErrorCode::AlreadySpent));
+        assert!(matches!(responses[2], Response::Info { .. }));
+    }
+
+    #[tokio::test]
+    async fn atomic_batch_rolls_back_every_refresh_on_failure() {
+        let ctx = test_context();
+        let first = signed_note(&ctx.keypair, 1, 10);
+        let second = signed_note(&ctx.keypair, 2, 20);
+
+        // The second request double-spends the first note
+        let responses = responses_of(
+            &ctx,
+            vec![spend(&first), spend(&first), spend(&second)],
+            true,
+        )
+        .await;
+        assert_eq!(
+            responses.iter().map(error_code).collect::<Vec<_>>(),
+            [
+                Some(ErrorCode::BatchAborted),
+                Some(ErrorCode::AlreadySpent),
+                Some(ErrorCode::BatchAborted),
+            ]
+        );
+
+        // Nothing was committed, so both notes are still spendable at once
+        let responses =
+            responses_of(&ctx, vec![spend(&first), spend(&second)], true).await;
+        assert!(
+            responses
+                .iter()
+                .all(|r| matches!(r, Response::Transaction { .. })),
+            "{responses:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn items_are_held_to_the_declared_version() {
+        let ctx = test_context();
+        let note = signed_note(&ctx.keypair, 1, 10);
+
+        // `node_info` arrived in 1.2, after batches
+        let answered = responses(
+            run_batch(
+                &ctx,
+                LOCAL,
+                None,
+                Some(ProtocolVersion::V1_1),
+                batch(vec![Request::Info, Request::NodeInfo], false),
+            )
+            .await,
+        );
+        assert!(matches!(answered[0], Response::Info { .. }));
+        assert_eq!(
+            error_code(&answered[1]),
+            Some(ErrorCode::UnsupportedVersion)
+        );
+
+        let mut blinded = spend(&note);
+        if let Request::Refresh(refresh) = &mut blinded {
+            refresh.blinded_points = vec![Signature::default()];
+        }
+        let aborted = responses(
+            run_batch(
+                &ctx,
+                LOCAL,
+                None,
+                Some(ProtocolVersion::V1_0),
+                batch(vec![blinded, spend(&note)], true),
+            )
+            .await,
+        );
+        assert_eq!(
+            aborted.iter().map(error_code).collect::<Vec<_>>(),
+            [
+                Some(ErrorCode::UnsupportedVersion),
+                Some(ErrorCode::BatchAborted),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_is_refused_as_a_whole_when_invalid() {
+        let mut ctx = test_context();
+        let note = signed_note(&ctx.keypair, 1, 10);
+
+        let atomic_info = run_batch(
+            &ctx,
+            LOCAL,
+            None,
+            None,
+            batch(vec![Request::Info], true),
+        )
+        .await;
+        assert_eq!(error_code(&atomic_info), Some(ErrorCode::InvalidInput));
+
+        let nested = run_batch(
+            &ctx,
+            LOCAL,
+            None,
+            None,
+            batch(vec![Request::Batch(batch(Vec::new(), false))], false),
+        )
+        .await;
+        assert_eq!(error_code(&nested), Some(ErrorCode::InvalidInput));
+
+        if let Config::Server {
+            rpc_max_batch_size, ..
+        } = &mut ctx.config
+        {
+            *rpc_max_batch_size = 1;
+        }
+        let too_large = run_batch(
+            &ctx,
+            LOCAL,
+            None,
+            None,
+            batch(vec![Request::Info, Request::Info], false),
+        )
+        .await;
+        assert_eq!(error_code(&too_large), Some(ErrorCode::PayloadTooLarge));
+
+        if let Config::Server {
+            rpc_max_batch_size, ..
+        } = &mut ctx.config
+        {
+            *rpc_max_batch_size = 0;
+        }
+        let disabled = run_batch(
+            &ctx,
+            LOCAL,
+            None,
+            None,
+            batch(vec![spend(&note)], false),
+        )
+        .await;
+        assert_eq!(error_code(&disabled), Some(ErrorCode::MethodDisabled));
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
 use ed25519_dalek::{Signature, Verifier, VerifyingKey};
-use mugraph_core::{error::Error, types::XNodeEnvelope};
+use mugraph_core::{
+    error::{Error, ErrorCode},
+    types::{Request, XNodeEnvelope},
+};
 use serde::Serialize;
 
 use super::protocol_reject;
-use crate::routes::Context;
+use crate::{routes::Context, tls::ClientInfo};
 
 pub(super) const MAX_CLOCK_SKEW_SECS: i64 = 300;
 pub(super) const MAX_COMMAND_EXPIRY_HORIZON_SECS: i64 = 900;
@@ -18,7 +21,7 @@ pub(super) fn validate_freshness(
     let sent_at = DateTime::parse_from_rfc3339(sent_at)
         .map_err(|e| {
             protocol_reject(
-                "SCHEMA_VALIDATION_FAILED",
+                ErrorCode::SchemaValidationFailed,
                 format!("invalid sent_at timestamp format: {e}"),
             )
         })?
@@ -27,14 +30,14 @@ pub(super) fn validate_freshness(
 
     let expires_at = expires_at.ok_or_else(|| {
         protocol_reject(
-            "SCHEMA_VALIDATION_FAILED",
+            ErrorCode::SchemaValidationFailed,
             "expires_at is required for command envelopes",
         )
     })?;
     let expires_at = DateTime::parse_from_rfc3339(expires_at)
         .map_err(|e| {
             protocol_reject(
-                "SCHEMA_VALIDATION_FAILED",
+                ErrorCode::SchemaValidationFailed,
                 format!("invalid expires_at timestamp format: {e}"),
             )
         })?
@@ -43,28 +46,28 @@ pub(super) fn validate_freshness(
 
     if expires_at <= sent_at {
         return Err(protocol_reject(
-            "REPLAY_DETECTED",
+            ErrorCode::ReplayDetected,
             "expired command envelope",
         ));
     }
 
     if (now - sent_at).abs() > MAX_CLOCK_SKEW_SECS {
         return Err(protocol_reject(
-            "REPLAY_DETECTED",
+            ErrorCode::ReplayDetected,
             "sent_at outside allowed clock skew",
         ));
     }
 
     if expires_at < now {
         return Err(protocol_reject(
-            "REPLAY_DETECTED",
+            ErrorCode::ReplayDetected,
             "command envelope already expired",
         ));
     }
 
     if expires_at - sent_at > MAX_COMMAND_EXPIRY_HORIZON_SECS {
         return Err(protocol_reject(
-            "SCHEMA_VALIDATION_FAILED",
+            ErrorCode::SchemaValidationFailed,
             "command expiry horizon exceeds policy",
         ));
     }
@@ -79,7 +82,7 @@ pub(super) fn validate_query_freshness(
     let sent_at = DateTime::parse_from_rfc3339(sent_at)
         .map_err(|e| {
             protocol_reject(
-                "SCHEMA_VALIDATION_FAILED",
+                ErrorCode::SchemaValidationFailed,
                 format!("invalid sent_at timestamp format: {e}"),
             )
         })?
@@ -88,7 +91,7 @@ pub(super) fn validate_query_freshness(
 
     if (now - sent_at).abs() > MAX_CLOCK_SKEW_SECS {
         return Err(protocol_reject(
-            "REPLAY_DETECTED",
+            ErrorCode::ReplayDetected,
             "sent_at outside allowed clock skew",
         ));
     }
@@ -102,7 +105,7 @@ pub(super) fn validate_destination_binding<T>(
 ) -> Result<(), Error> {
     if request.origin_node_id == request.destination_node_id {
         return Err(protocol_reject(
-            "AUTHZ_DENIED",
+            ErrorCode::AuthzDenied,
             "origin and destination nodes must differ",
         ));
     }
@@ -111,14 +114,14 @@ pub(super) fn validate_destination_binding<T>(
         || !request.destination_node_id.starts_with("node://")
     {
         return Err(protocol_reject(
-            "SCHEMA_VALIDATION_FAILED",
+            ErrorCode::SchemaValidationFailed,
             "origin/destination node ids must use node:// scheme",
         ));
     }
 
     if request.destination_node_id != local_node_id {
         return Err(protocol_reject(
-            "AUTHZ_DENIED",
+            ErrorCode::AuthzDenied,
             "destination_node_id does not match local node id",
         ));
     }
@@ -126,6 +129,46 @@ pub(super) fn validate_destination_binding<T>(
     Ok(())
 }
 
+/// Mutual-TLS gate for xnode requests.
+///
+/// The handshake already chained the client certificate to the configured
+/// CA; here it must also name the envelope's origin node as a URI SAN. The
+/// signature check then ties that node to the signing `TrustedPeer`, so the
+/// transport and message identities agree. Other requests pass untouched.
+pub fn require_peer_certificate(
+    request: &Request,
+    client: Option<&ClientInfo>,
+) -> Result<(), Error> {
+    let origin_node_id = match request {
+        Request::CrossNodeTransferCreate(r) => &r.origin_node_id,
+        Request::CrossNodeTransferNotify(r) => &r.origin_node_id,
+        Request::CrossNodeTransferStatus(r) => &r.origin_node_id,
+        Request::CrossNodeTransferAck(r) => &r.origin_node_id,
+        _ => return Ok(()),
+    };
+
+    let Some(client) = client.filter(|c| c.certificates.is_some()) else {
+        return Err(protocol_reject(
+            ErrorCode::AuthzDenied,
+            "xnode requests require a client certificate",
+        ));
+    };
+
+    if !client.has_uri_identity(origin_node_id) {
+        tracing::warn!(
+            origin_node_id = %origin_node_id,
+            peer = %client.addr,
+            "client certificate does not match xnode origin"
+        );
+        return Err(protocol_reject(
+            ErrorCode::AuthzDenied,
+            format!("client certificate does not identify {origin_node_id}"),
+        ));
+    }
+
+    Ok(())
+}
+
 fn load_peer_registry_for_auth(
     ctx: &Context,
 ) -> Result<std::borrow::Cow<'_, crate::peer_registry::PeerRegistry>, Error> {
@@ -137,7 +180,7 @@ fn load_peer_registry_for_auth(
 
     let Some(registry) = ctx.peer_registry.as_ref() else {
         return Err(protocol_reject(
-            "AUTHZ_DENIED",
+            ErrorCode::AuthzDenied,
             "xnode peer registry is required for cross-node command auth",
         ));
     };
@@ -150,7 +193,10 @@ pub(super) fn validate_auth_signature<T: Serialize + Clone>(
     ctx: &Context,
 ) -> Result<(), Error> {
     if request.auth.alg != "Ed25519" {
-        return Err(protocol_reject("AUTHZ_DENIED", "unsupported auth.alg"));
+        return Err(protocol_reject(
+            ErrorCode::AuthzDenied,
+            "unsupported auth.alg",
+        ));
     }
 
     let registry = load_peer_registry_for_auth(ctx)?;
@@ -165,39 +211,42 @@ pub(super) fn validate_auth_signature<T: Serialize + Clone>(
                 && p.auth_alg == request.auth.alg
         })
         .ok_or_else(|| {
-            protocol_reject("UNKNOWN_KEY_ID", "untrusted origin node or key id")
+            protocol_reject(
+                ErrorCode::UnknownKeyId,
+                "untrusted origin node or key id",
+            )
         })?;
 
     let pubkey = muhex::decode(&peer.public_key_hex).map_err(|e| {
         protocol_reject(
-            "SCHEMA_VALIDATION_FAILED",
+            ErrorCode::SchemaValidationFailed,
             format!("invalid trusted peer public key hex: {e}"),
         )
     })?;
     let verifying_key = VerifyingKey::from_bytes(
         &pubkey.as_slice().try_into().map_err(|_| {
             protocol_reject(
-                "SCHEMA_VALIDATION_FAILED",
+                ErrorCode::SchemaValidationFailed,
                 "trusted peer public key must be 32 bytes",
             )
         })?,
     )
     .map_err(|e| {
         protocol_reject(
-            "SCHEMA_VALIDATION_FAILED",
+            ErrorCode::SchemaValidationFailed,
             format!("invalid trusted peer public key: {e}"),
         )
     })?;
 
     let sig_bytes = muhex::decode(&request.auth.sig).map_err(|e| {
         protocol_reject(
-            "INVALID_SIGNATURE",
+            ErrorCode::InvalidSignature,
             format!("invalid auth signature hex: {e}"),
         )
     })?;
     let sig = Signature::try_from(sig_bytes.as_slice()).map_err(|e| {
         protocol_reject(
-            "INVALID_SIGNATURE",
+            ErrorCode::InvalidSignature,
             format!("invalid auth signature bytes: {e}"),
         )
     })?;
@@ -205,7 +254,7 @@ pub(super) fn validate_auth_signature<T: Serialize + Clone>(
     let payload = canonical_auth_payload(request)?;
     verifying_key.verify(&payload, &sig).map_err(|e| {
         protocol_reject(
-            "INVALID_SIGNATURE",
+            ErrorCode::InvalidSignature,
             format!("invalid auth signature: {e}"),
         )
     })?;
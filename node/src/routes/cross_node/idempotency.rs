@@ -1,6 +1,6 @@
 use blake3::Hasher;
 use mugraph_core::{
-    error::Error,
+    error::{Error, ErrorCode},
     types::{CrossNodeMessageRecord, IdempotencyRecord, XNodeEnvelope},
 };
 use redb::ReadableTable;
@@ -45,7 +45,7 @@ pub(super) fn check_replay_and_idempotency<T: Serialize + Clone>(
             )
             .increment(1);
             return Err(protocol_reject(
-                "REPLAY_DETECTED",
+                ErrorCode::ReplayDetected,
                 "duplicate message_id",
             ));
         }
@@ -74,7 +74,7 @@ pub(super) fn check_replay_and_idempotency<T: Serialize + Clone>(
                 )
                 .increment(1);
                 return Err(protocol_reject(
-                    "IDEMPOTENCY_CONFLICT",
+                    ErrorCode::IdempotencyConflict,
                     "idempotency conflict",
                 ));
             }
@@ -1,18 +1,20 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use mugraph_core::{
-    error::Error,
+    error::{Error, ErrorCode},
     types::{
-        CrossNodeTransferRecord, Response, XNodeEnvelope, XNodeMessageType,
-        validate_envelope_basics,
+        CrossNodeTransferRecord, EventKind, EventSubject, Response,
+        XNodeEnvelope, XNodeMessageType, validate_envelope_basics,
     },
 };
 use redb::ReadableTable;
 use serde::Serialize;
 
 use crate::{
-    database::CROSS_NODE_TRANSFERS, lifecycle::status_payload_from_record,
+    database::CROSS_NODE_TRANSFERS,
+    lifecycle::status_payload_from_record,
     routes::Context,
+    telemetry::{current_trace_context, set_remote_parent},
 };
 
 mod audit;
@@ -23,6 +25,7 @@ mod status;
 #[cfg(test)]
 mod tests;
 
+pub use self::auth::require_peer_certificate;
 use self::{
     audit::{
         audit_event, audit_reject, audit_status_events, emit_chain_metrics,
@@ -35,16 +38,13 @@ use self::{
     status::sign_status_response,
 };
 
-fn protocol_reject(code: &str, detail: impl Into<String>) -> Error {
-    Error::InvalidInput {
-        reason: format!("{code}: {}", detail.into()),
+fn protocol_reject(code: ErrorCode, detail: impl Into<String>) -> Error {
+    Error::Rejected {
+        code,
+        reason: detail.into(),
     }
 }
 
-fn error_code(reason: &str) -> &str {
-    reason.split(':').next().unwrap_or("INTERNAL_ERROR")
-}
-
 const M3_MESSAGE_RECEIVE_COUNTER: &str = "mugraph_message_receive_total";
 
 fn emit_receive_metrics(message_type: &str, result: &str) {
@@ -56,18 +56,35 @@ fn emit_receive_metrics(message_type: &str, result: &str) {
     .increment(1);
 }
 
-fn reject_audit_event(code: &str) -> &'static str {
+fn reject_audit_event(code: ErrorCode) -> &'static str {
     match code {
-        "IDEMPOTENCY_CONFLICT" => "transfer.idempotency_conflict",
-        "REPLAY_DETECTED" => "transfer.replay_rejected",
+        ErrorCode::IdempotencyConflict => "transfer.idempotency_conflict",
         _ => "transfer.replay_rejected",
     }
 }
 
+/// Span for an inbound xnode message, continuing the sender's trace.
+fn xnode_span<T>(
+    request: &XNodeEnvelope<T>,
+    message_type: &'static str,
+) -> tracing::Span {
+    let span = tracing::info_span!(
+        "xnode_message",
+        message_type,
+        transfer_id = %request.transfer_id,
+        correlation_id = %request.correlation_id,
+        origin_node_id = %request.origin_node_id,
+    );
+    set_remote_parent(&span, request.trace_context.as_deref());
+    span
+}
+
 pub fn handle_create(
     request: &XNodeEnvelope<mugraph_core::types::TransferInitPayload>,
     ctx: &Context,
 ) -> Result<Response, Error> {
+    let _span = xnode_span(request, "transfer_init").entered();
+
     let decision = match enforce_command_security(
         request,
         XNodeMessageType::TransferInit,
@@ -75,29 +92,26 @@ pub fn handle_create(
     ) {
         Ok(d) => d,
         Err(e) => {
-            let mut event = "transfer.replay_rejected";
-            if let Error::InvalidInput { reason } = &e {
-                let code = error_code(reason);
-                event = reject_audit_event(code);
-                if code == "REPLAY_DETECTED" {
-                    metrics::counter!("mugraph_replay_rejections_total", "message_type" => "transfer_init".to_string()).increment(1);
-                }
-                if code == "IDEMPOTENCY_CONFLICT" {
-                    metrics::counter!("mugraph_idempotency_conflicts_total", "operation" => "transfer_init".to_string()).increment(1);
-                }
-                tracing::warn!(
-                    transfer_id = %request.transfer_id,
-                    origin_node_id = %request.origin_node_id,
-                    destination_node_id = %request.destination_node_id,
-                    protocol_version = %request.version,
-                    state = "rejected",
-                    message_id = %request.message_id,
-                    message_type = "transfer_init",
-                    idempotency_key = %request.idempotency_key,
-                    error_code = code,
-                    "transfer.message.receive"
-                );
+            let code = e.code();
+            let event = reject_audit_event(code);
+            if code == ErrorCode::ReplayDetected {
+                metrics::counter!("mugraph_replay_rejections_total", "message_type" => "transfer_init".to_string()).increment(1);
+            }
+            if code == ErrorCode::IdempotencyConflict {
+                metrics::counter!("mugraph_idempotency_conflicts_total", "operation" => "transfer_init".to_string()).increment(1);
             }
+            tracing::warn!(
+                transfer_id = %request.transfer_id,
+                origin_node_id = %request.origin_node_id,
+                destination_node_id = %request.destination_node_id,
+                protocol_version = %request.version,
+                state = "rejected",
+                message_id = %request.message_id,
+                message_type = "transfer_init",
+                idempotency_key = %request.idempotency_key,
+                error_code = code.as_str(),
+                "transfer.message.receive"
+            );
             emit_receive_metrics("transfer_init", "rejected");
             if let Err(audit_err) =
                 audit_reject(ctx, &request.transfer_id, event, e.to_string())
@@ -118,7 +132,7 @@ pub fn handle_create(
             let mut transfers = write_tx.open_table(CROSS_NODE_TRANSFERS)?;
             if transfers.get(request.transfer_id.as_str())?.is_some() {
                 return Err(protocol_reject(
-                    "TRANSFER_ALREADY_EXISTS",
+                    ErrorCode::TransferAlreadyExists,
                     "transfer_id already exists",
                 ));
             }
@@ -141,6 +155,12 @@ pub fn handle_create(
                 mugraph_core::types::TransferCreditState::None,
             );
             transfers.insert(request.transfer_id.as_str(), &transfer)?;
+            write_tx.append_event(
+                EventSubject::transfer(&request.transfer_id),
+                EventKind::TransferStatus(status_payload_from_record(
+                    &transfer,
+                )),
+            )?;
         }
         write_tx.commit()?;
 
@@ -178,6 +198,8 @@ pub fn handle_notify(
     request: &XNodeEnvelope<mugraph_core::types::TransferNoticePayload>,
     ctx: &Context,
 ) -> Result<Response, Error> {
+    let _span = xnode_span(request, "transfer_notice").entered();
+
     let decision = match enforce_command_security(
         request,
         XNodeMessageType::TransferNotice,
@@ -185,23 +207,20 @@ pub fn handle_notify(
     ) {
         Ok(d) => d,
         Err(e) => {
-            let mut event = "transfer.replay_rejected";
-            if let Error::InvalidInput { reason } = &e {
-                let code = error_code(reason);
-                event = reject_audit_event(code);
-                tracing::warn!(
-                    transfer_id = %request.transfer_id,
-                    origin_node_id = %request.origin_node_id,
-                    destination_node_id = %request.destination_node_id,
-                    protocol_version = %request.version,
-                    state = "rejected",
-                    message_id = %request.message_id,
-                    message_type = "transfer_notice",
-                    idempotency_key = %request.idempotency_key,
-                    error_code = code,
-                    "transfer.message.receive"
-                );
-            }
+            let code = e.code();
+            let event = reject_audit_event(code);
+            tracing::warn!(
+                transfer_id = %request.transfer_id,
+                origin_node_id = %request.origin_node_id,
+                destination_node_id = %request.destination_node_id,
+                protocol_version = %request.version,
+                state = "rejected",
+                message_id = %request.message_id,
+                message_type = "transfer_notice",
+                idempotency_key = %request.idempotency_key,
+                error_code = code.as_str(),
+                "transfer.message.receive"
+            );
             emit_receive_metrics("transfer_notice", "rejected");
             if let Err(audit_err) =
                 audit_reject(ctx, &request.transfer_id, event, e.to_string())
@@ -220,7 +239,7 @@ pub fn handle_notify(
         let transfers = read_tx.open_table(CROSS_NODE_TRANSFERS)?;
         if transfers.get(request.transfer_id.as_str())?.is_none() {
             return Err(protocol_reject(
-                "TRANSFER_NOT_FOUND",
+                ErrorCode::TransferNotFound,
                 "cannot accept notice for unknown transfer",
             ));
         }
@@ -259,6 +278,12 @@ pub fn handle_notify(
                 });
                 updated.updated_at = now;
                 transfers.insert(request.transfer_id.as_str(), &updated)?;
+                write_tx.append_event(
+                    EventSubject::transfer(&request.transfer_id),
+                    EventKind::TransferStatus(status_payload_from_record(
+                        &updated,
+                    )),
+                )?;
             }
         }
         write_tx.commit()?;
@@ -291,6 +316,8 @@ pub fn handle_status(
     request: &XNodeEnvelope<mugraph_core::types::TransferStatusQueryPayload>,
     ctx: &Context,
 ) -> Result<Response, Error> {
+    let _span = xnode_span(request, "transfer_status_query").entered();
+
     enforce_query_security(
         request,
         XNodeMessageType::TransferStatusQuery,
@@ -305,7 +332,10 @@ pub fn handle_status(
             .get(request.transfer_id.as_str())?
             .map(|v| v.value())
             .ok_or_else(|| {
-                protocol_reject("TRANSFER_NOT_FOUND", "transfer not found")
+                protocol_reject(
+                    ErrorCode::TransferNotFound,
+                    "transfer not found",
+                )
             })?
     };
 
@@ -338,6 +368,7 @@ pub fn handle_status(
         destination_node_id: request.origin_node_id.clone(),
         sent_at: chrono::Utc::now().to_rfc3339(),
         expires_at: None,
+        trace_context: current_trace_context(),
         payload,
         auth: mugraph_core::types::XNodeAuth {
             alg: "Ed25519".to_string(),
@@ -357,6 +388,8 @@ pub fn handle_ack(
     request: &XNodeEnvelope<mugraph_core::types::TransferAckPayload>,
     ctx: &Context,
 ) -> Result<Response, Error> {
+    let _span = xnode_span(request, "transfer_ack").entered();
+
     let decision = match enforce_command_security(
         request,
         XNodeMessageType::TransferAck,
@@ -364,23 +397,20 @@ pub fn handle_ack(
     ) {
         Ok(d) => d,
         Err(e) => {
-            let mut event = "transfer.replay_rejected";
-            if let Error::InvalidInput { reason } = &e {
-                let code = error_code(reason);
-                event = reject_audit_event(code);
-                tracing::warn!(
-                    transfer_id = %request.transfer_id,
-                    origin_node_id = %request.origin_node_id,
-                    destination_node_id = %request.destination_node_id,
-                    protocol_version = %request.version,
-                    state = "rejected",
-                    message_id = %request.message_id,
-                    message_type = "transfer_ack",
-                    idempotency_key = %request.idempotency_key,
-                    error_code = code,
-                    "transfer.message.receive"
-                );
-            }
+            let code = e.code();
+            let event = reject_audit_event(code);
+            tracing::warn!(
+                transfer_id = %request.transfer_id,
+                origin_node_id = %request.origin_node_id,
+                destination_node_id = %request.destination_node_id,
+                protocol_version = %request.version,
+                state = "rejected",
+                message_id = %request.message_id,
+                message_type = "transfer_ack",
+                idempotency_key = %request.idempotency_key,
+                error_code = code.as_str(),
+                "transfer.message.receive"
+            );
             emit_receive_metrics("transfer_ack", "rejected");
             if let Err(audit_err) =
                 audit_reject(ctx, &request.transfer_id, event, e.to_string())
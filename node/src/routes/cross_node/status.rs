@@ -1,5 +1,8 @@
 use ed25519_dalek::Signer;
-use mugraph_core::{error::Error, types::XNodeEnvelope};
+use mugraph_core::{
+    error::{Error, ErrorCode},
+    types::XNodeEnvelope,
+};
 use serde::Serialize;
 
 use super::{auth::canonical_auth_payload, protocol_reject};
@@ -13,7 +16,7 @@ pub(super) fn sign_status_response<T: Serialize + Clone>(
     let table = read_tx.open_table(CARDANO_WALLET)?;
     let wallet = table.get("wallet")?.map(|v| v.value()).ok_or_else(|| {
         protocol_reject(
-            "AUTHZ_DENIED",
+            ErrorCode::AuthzDenied,
             "wallet not initialized; cannot sign status response",
         )
     })?;
@@ -21,7 +24,7 @@ pub(super) fn sign_status_response<T: Serialize + Clone>(
     let sk_bytes: [u8; 32] =
         wallet.payment_sk.as_slice().try_into().map_err(|_| {
             protocol_reject(
-                "SCHEMA_VALIDATION_FAILED",
+                ErrorCode::SchemaValidationFailed,
                 "wallet payment signing key must be 32 bytes",
             )
         })?;
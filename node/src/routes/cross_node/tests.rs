@@ -1,11 +1,14 @@
 use chrono::Utc;
 use ed25519_dalek::{Signer, SigningKey};
-use mugraph_core::types::{
-    CrossNodeTransferRecord, TransferAckPayload, TransferAckStatus,
-    TransferChainState, TransferCreditState, TransferInitPayload,
-    TransferNoticePayload, TransferNoticeStage, TransferQueryType,
-    TransferSettlementState, TransferStatusPayload, TransferStatusQueryPayload,
-    XNodeAuth, XNodeEnvelope, XNodeMessageType,
+use mugraph_core::{
+    error::ErrorCode,
+    types::{
+        CrossNodeTransferRecord, TransferAckPayload, TransferAckStatus,
+        TransferChainState, TransferCreditState, TransferInitPayload,
+        TransferNoticePayload, TransferNoticeStage, TransferQueryType,
+        TransferSettlementState, TransferStatusPayload,
+        TransferStatusQueryPayload, XNodeAuth, XNodeEnvelope, XNodeMessageType,
+    },
 };
 use proptest::prelude::*;
 use tempfile::TempDir;
@@ -23,25 +26,21 @@ use crate::{
 };
 
 fn test_config_with_registry(path: &str) -> Config {
-    Config::Server {
-        addr: "127.0.0.1:9999".parse().unwrap(),
-        seed: Some(42),
-        secret_key: None,
-        cardano_network: "preprod".to_string(),
-        cardano_provider: "blockfrost".to_string(),
-        cardano_api_key: None,
-        cardano_provider_url: None,
-        cardano_payment_sk: None,
-        xnode_peer_registry_file: Some(path.to_string()),
-        xnode_node_id: "node://b".to_string(),
-        deposit_confirm_depth: 15,
-        deposit_expiration_blocks: 1440,
-        min_deposit_value: None,
-        max_tx_size: 16384,
-        max_withdrawal_fee: 2_000_000,
-        fee_tolerance_pct: 5,
-        dev_mode: false,
+    let mut config = Config::test_server();
+    if let Config::Server {
+        xnode_peer_registry_file,
+        xnode_node_id,
+        min_deposit_value,
+        dev_mode,
+        ..
+    } = &mut config
+    {
+        *xnode_peer_registry_file = Some(path.to_string());
+        *xnode_node_id = "node://b".to_string();
+        *min_deposit_value = None;
+        *dev_mode = false;
     }
+    config
 }
 
 fn write_registry(dir: &TempDir, pk: &SigningKey) -> String {
@@ -66,7 +65,7 @@ fn test_ctx(registry_path: &str) -> Context {
         let mut t = w.open_table(crate::database::CARDANO_WALLET).unwrap();
         t.insert(
             "wallet",
-            &mugraph_core::types::CardanoWallet::new(
+            mugraph_core::types::CardanoWallet::new(
                 vec![7u8; 32],
                 vec![8u8; 32],
                 vec![],
@@ -92,6 +91,8 @@ fn test_ctx(registry_path: &str) -> Context {
         database,
         config,
         peer_registry: Some(std::sync::Arc::new(registry)),
+        health: Default::default(),
+        limits: Default::default(),
     }
 }
 
@@ -134,6 +135,7 @@ fn create_request() -> XNodeEnvelope<TransferInitPayload> {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: Some(now_rfc3339_offset(120)),
+        trace_context: None,
         payload: TransferInitPayload {
             asset: "lovelace".to_string(),
             amount: "1".to_string(),
@@ -189,7 +191,7 @@ fn create_rejects_invalid_signature_and_records_audit_event() {
     request.auth.sig = "deadbeef".to_string();
 
     let err = handle_create(&request, &ctx).unwrap_err();
-    assert!(matches!(err, Error::InvalidInput { .. }));
+    assert!(matches!(err, Error::Rejected { .. }));
 
     let read = ctx.database.read().unwrap();
     let table = read.open_table(TRANSFER_AUDIT_LOG).unwrap();
@@ -218,13 +220,14 @@ fn requests_are_rejected_if_peer_registry_file_disappears() {
 
     let err = handle_create(&request, &ctx).unwrap_err();
     match err {
+        Error::Rejected {
+            code: ErrorCode::AuthzDenied,
+            ..
+        } => {}
         Error::InvalidInput { reason } => {
-            assert!(
-                reason.contains("AUTHZ_DENIED")
-                    || reason.contains("failed to read peer registry")
-            );
+            assert!(reason.contains("failed to read peer registry"))
         }
-        _ => panic!("expected InvalidInput authz error"),
+        _ => panic!("expected authz rejection"),
     }
 }
 
@@ -247,10 +250,11 @@ fn runtime_registry_reload_honors_revocation() {
 
     let err = validate_auth_signature(&request, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("UNKNOWN_KEY_ID"));
-        }
-        _ => panic!("expected invalid input with unknown key id"),
+        Error::Rejected {
+            code: ErrorCode::UnknownKeyId,
+            ..
+        } => {}
+        _ => panic!("expected unknown key id rejection"),
     }
 }
 
@@ -272,10 +276,11 @@ fn duplicate_message_id_is_rejected_deterministically() {
 
     let second = handle_create(&request, &ctx).unwrap_err();
     match second {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("REPLAY_DETECTED"));
-        }
-        _ => panic!("expected InvalidInput replay error"),
+        Error::Rejected {
+            code: ErrorCode::ReplayDetected,
+            ..
+        } => {}
+        _ => panic!("expected replay rejection"),
     }
 }
 
@@ -297,10 +302,11 @@ fn idempotency_conflict_is_rejected() {
 
     let err = handle_create(&request_b, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("IDEMPOTENCY_CONFLICT"))
-        }
-        _ => panic!("expected InvalidInput conflict"),
+        Error::Rejected {
+            code: ErrorCode::IdempotencyConflict,
+            ..
+        } => {}
+        _ => panic!("expected idempotency conflict"),
     }
 }
 
@@ -323,9 +329,10 @@ fn create_rejects_duplicate_transfer_id_across_idempotency_keys() {
 
     let err = handle_create(&second, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("TRANSFER_ALREADY_EXISTS"))
-        }
+        Error::Rejected {
+            code: ErrorCode::TransferAlreadyExists,
+            ..
+        } => {}
         _ => panic!("expected duplicate transfer_id rejection"),
     }
 }
@@ -343,10 +350,11 @@ fn create_rejects_destination_binding_mismatch() {
 
     let err = handle_create(&request, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("AUTHZ_DENIED"))
-        }
-        _ => panic!("expected InvalidInput authz error"),
+        Error::Rejected {
+            code: ErrorCode::AuthzDenied,
+            ..
+        } => {}
+        _ => panic!("expected authz rejection"),
     }
 }
 
@@ -364,10 +372,11 @@ fn create_rejects_sent_at_outside_clock_skew_window() {
 
     let err = handle_create(&request, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("REPLAY_DETECTED"))
-        }
-        _ => panic!("expected InvalidInput replay error"),
+        Error::Rejected {
+            code: ErrorCode::ReplayDetected,
+            ..
+        } => {}
+        _ => panic!("expected replay rejection"),
     }
 }
 
@@ -386,10 +395,11 @@ fn create_rejects_expiry_horizon_above_policy() {
 
     let err = handle_create(&request, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("SCHEMA_VALIDATION_FAILED"))
-        }
-        _ => panic!("expected InvalidInput schema error"),
+        Error::Rejected {
+            code: ErrorCode::SchemaValidationFailed,
+            ..
+        } => {}
+        _ => panic!("expected schema validation rejection"),
     }
 }
 
@@ -413,6 +423,7 @@ fn status_response_contains_chain_and_credit_state() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: None,
+        trace_context: None,
         payload: TransferStatusQueryPayload {
             query_type: TransferQueryType::Current,
         },
@@ -432,6 +443,60 @@ fn status_response_contains_chain_and_credit_state() {
     }
 }
 
+#[test]
+fn status_response_continues_the_senders_trace() {
+    use opentelemetry::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let provider =
+        opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("test")),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    let registry_dir = TempDir::new().unwrap();
+    let registry_path = write_registry(&registry_dir, &signer);
+    let ctx = test_ctx(&registry_path);
+    seed_transfer(&ctx, "tr", "submitted", "none");
+
+    let sender_trace_id = "0af7651916cd43dd8448eb211c80319c";
+    let sender_traceparent =
+        format!("00-{sender_trace_id}-b7ad6b7169203331-01");
+    let mut request = XNodeEnvelope {
+        m: "xnode".to_string(),
+        version: "3.0".to_string(),
+        message_type: XNodeMessageType::TransferStatusQuery,
+        message_id: "mid".to_string(),
+        transfer_id: "tr".to_string(),
+        idempotency_key: "ik".to_string(),
+        correlation_id: "corr".to_string(),
+        origin_node_id: "node://a".to_string(),
+        destination_node_id: "node://b".to_string(),
+        sent_at: now_rfc3339_offset(0),
+        expires_at: None,
+        trace_context: Some(sender_traceparent.clone()),
+        payload: TransferStatusQueryPayload {
+            query_type: TransferQueryType::Current,
+        },
+        auth: auth(),
+    };
+    sign_envelope(&mut request, &signer);
+
+    let response = handle_status(&request, &ctx).unwrap();
+    let Response::CrossNodeTransferStatus(env) = response else {
+        panic!("unexpected response variant");
+    };
+    let traceparent =
+        env.trace_context.expect("response carries trace context");
+    assert!(
+        traceparent.starts_with(&format!("00-{sender_trace_id}-")),
+        "{traceparent}"
+    );
+    assert_ne!(traceparent, sender_traceparent);
+}
+
 #[test]
 fn status_mapping_invalidated_held_maps_to_manual_review() {
     let record = CrossNodeTransferRecord {
@@ -478,6 +543,7 @@ fn status_query_emits_required_audit_events() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: None,
+        trace_context: None,
         payload: TransferStatusQueryPayload {
             query_type: TransferQueryType::Current,
         },
@@ -529,6 +595,7 @@ fn audit_status_events_propagates_audit_failures() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: None,
+        trace_context: None,
         payload: TransferStatusQueryPayload {
             query_type: TransferQueryType::Current,
         },
@@ -579,6 +646,7 @@ fn ack_accepts_valid_request() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: Some(now_rfc3339_offset(120)),
+        trace_context: None,
         payload: TransferAckPayload {
             ack_for_message_id: "mid2".to_string(),
             ack_status: TransferAckStatus::Processed,
@@ -614,6 +682,7 @@ fn notify_rejects_unknown_transfer_id() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: Some(now_rfc3339_offset(120)),
+        trace_context: None,
         payload: TransferNoticePayload {
             notice_stage: TransferNoticeStage::Confirmed,
             tx_hash: "abcd".to_string(),
@@ -625,9 +694,10 @@ fn notify_rejects_unknown_transfer_id() {
 
     let err = handle_notify(&request, &ctx).unwrap_err();
     match err {
-        Error::InvalidInput { reason } => {
-            assert!(reason.contains("TRANSFER_NOT_FOUND"));
-        }
+        Error::Rejected {
+            code: ErrorCode::TransferNotFound,
+            ..
+        } => {}
         other => panic!("unexpected error: {other:?}"),
     }
 }
@@ -655,6 +725,7 @@ fn notify_persists_notice_derived_chain_state() {
         destination_node_id: "node://b".to_string(),
         sent_at: now_rfc3339_offset(0),
         expires_at: Some(now_rfc3339_offset(120)),
+        trace_context: None,
         payload: TransferNoticePayload {
             notice_stage: TransferNoticeStage::Confirmed,
             tx_hash: "abcd".to_string(),
@@ -742,7 +813,7 @@ proptest! {
         sign_envelope(&mut request, &signer);
 
         let err = handle_create(&request, &ctx).unwrap_err();
-        let is_invalid = matches!(err, Error::InvalidInput { .. });
+        let is_invalid = matches!(err, Error::Rejected { .. });
         prop_assert!(is_invalid);
     }
 
@@ -821,6 +892,7 @@ proptest! {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Confirmed,
                 tx_hash: "abcd".to_string(),
@@ -31,7 +31,10 @@ use self::{
         CanonicalPayload, CanonicalUtxo, build_canonical_payload,
         compute_intent_hash, verify_cip8_cose_signature,
     },
-    source_validation::{validate_deposit_amounts, validate_deposit_datum},
+    source_validation::{
+        validate_deposit_amounts, validate_deposit_datum,
+        validate_deposit_min_ada,
+    },
 };
 
 /// Handle deposit request
@@ -68,7 +71,7 @@ pub async fn handle_deposit(
 
     // 3. Fetch UTxO from Cardano provider and validate
     let provider = create_provider(ctx)?;
-    validate_deposit_source(
+    let utxo_info = validate_deposit_source(
         request,
         &claims,
         &wallet,
@@ -84,6 +87,14 @@ pub async fn handle_deposit(
     // 6. Record deposit in database
     let deposit_ref = persist_deposit(request, ctx, &provider, &wallet).await?;
 
+    crate::telemetry::record_asset_flow(
+        "deposit",
+        &ctx.config.metrics_assets(),
+        utxo_info.amount.iter().filter_map(|asset| {
+            Some((asset.unit.as_str(), asset.quantity.parse().ok()?))
+        }),
+    );
+
     tracing::info!(
         "Deposit processed successfully: {}",
         &deposit_ref[..std::cmp::min(32, deposit_ref.len())]
@@ -576,25 +587,16 @@ mod wallet_tests {
         database.migrate().unwrap();
         std::mem::forget(dir);
 
-        let config = Config::Server {
-            addr: "127.0.0.1:9999".parse().unwrap(),
-            seed: Some(7),
-            secret_key: None,
-            cardano_network: "preprod".to_string(),
-            cardano_provider: "blockfrost".to_string(),
-            cardano_api_key: Some("test".to_string()),
-            cardano_provider_url: None,
-            cardano_payment_sk: None,
-            xnode_peer_registry_file: None,
-            xnode_node_id: "node://local".to_string(),
-            deposit_confirm_depth: 15,
-            deposit_expiration_blocks: 1440,
-            min_deposit_value: Some(1_000_000),
-            max_tx_size: 16384,
-            max_withdrawal_fee: 2_000_000,
-            fee_tolerance_pct: 5,
-            dev_mode: true,
-        };
+        let mut config = Config::test_server();
+        if let Config::Server {
+            seed,
+            cardano_api_key,
+            ..
+        } = &mut config
+        {
+            *seed = Some(7);
+            *cardano_api_key = Some("test".to_string());
+        }
         let keypair = config.keypair().unwrap();
 
         Context {
@@ -602,6 +604,8 @@ mod wallet_tests {
             database,
             config,
             peer_registry: None,
+            health: Default::default(),
+            limits: Default::default(),
         }
     }
 
@@ -777,6 +781,7 @@ mod datum_tests {
                 quantity: "1000000".to_string(),
             }],
             datum_hash: None,
+            inline_datum: datum_hex.is_some(),
             datum: datum_hex,
             script_ref: None,
             block_height: Some(100),
@@ -1043,7 +1048,7 @@ mod datum_tests {
 #[cfg(test)]
 mod amount_validation_tests {
     use super::*;
-    use crate::provider::{AssetAmount, UtxoInfo};
+    use crate::provider::{AssetAmount, EmulatorProvider, Provider, UtxoInfo};
 
     fn request_with_output_count(count: usize) -> DepositRequest {
         DepositRequest {
@@ -1067,6 +1072,7 @@ mod amount_validation_tests {
             amount,
             datum_hash: None,
             datum: None,
+            inline_datum: false,
             script_ref: None,
             block_height: Some(100),
         }
@@ -1160,6 +1166,44 @@ mod amount_validation_tests {
         validate_deposit_amounts(&request, &utxo, 1_000_000)
             .expect("exact minimum and asset-count boundary should pass");
     }
+
+    #[tokio::test]
+    async fn validate_deposit_min_ada_sizes_the_utxo_with_its_datum() {
+        let provider = Provider::Emulator(EmulatorProvider::shared(
+            "emulator://deposit-min-ada",
+            "preprod".to_string(),
+        ));
+        let utxo = |lovelace: u64| {
+            UtxoInfo {
+            address:
+                "addr_test1wpd95kj6tfd95kj6tfd95kj6tfd95kj6tfd95kj6tfd95ksdf2n77"
+                    .to_string(),
+            datum: Some(hex::encode(
+                csl::PlutusData::new_bytes(vec![7u8; 64]).to_bytes(),
+            )),
+            inline_datum: true,
+            ..utxo_with_amounts(vec![AssetAmount {
+                unit: "lovelace".to_string(),
+                quantity: lovelace.to_string(),
+            }])
+        }
+        };
+
+        // Above the configured 1 ADA default, but not enough for the datum
+        let err = validate_deposit_min_ada(&utxo(1_100_000), &provider)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:?}").contains(
+                "Deposit UTxO holds 1100000 lovelace, below the protocol minimum"
+            ),
+            "{err:?}"
+        );
+
+        validate_deposit_min_ada(&utxo(2_000_000), &provider)
+            .await
+            .expect("enough for its size");
+    }
 }
 
 #[cfg(test)]
@@ -1182,7 +1226,17 @@ mod handle_deposit_flow_tests {
     use serde_json::json;
 
     use super::*;
-    use crate::{config::Config, database::Database};
+    use crate::{
+        chain_index::{self, ChainIndex},
+        config::Config,
+        database::Database,
+        provider::Provider,
+    };
+
+    /// Enterprise script address on preprod, so the deposit UTxO can be
+    /// sized against the protocol parameters
+    const SCRIPT_ADDRESS: &str =
+        "addr_test1wpd95kj6tfd95kj6tfd95kj6tfd95kj6tfd95kj6tfd95ksdf2n77";
 
     fn build_cip8_signature(sk: &SigningKey, payload: &[u8]) -> Vec<u8> {
         let header = Header {
@@ -1224,25 +1278,18 @@ mod handle_deposit_flow_tests {
         let database = Arc::new(Database::setup(db_path).unwrap());
         database.migrate().unwrap();
 
-        let config = Config::Server {
-            addr: "127.0.0.1:9999".parse().unwrap(),
-            seed: Some(42),
-            secret_key: None,
-            cardano_network: "preprod".to_string(),
-            cardano_provider: "blockfrost".to_string(),
-            cardano_api_key: Some("test".to_string()),
-            cardano_provider_url: Some(provider_url),
-            cardano_payment_sk: None,
-            xnode_peer_registry_file: None,
-            xnode_node_id: "node://local".to_string(),
-            deposit_confirm_depth: 5,
-            deposit_expiration_blocks: 1440,
-            min_deposit_value: Some(1_000_000),
-            max_tx_size: 16384,
-            max_withdrawal_fee: 2_000_000,
-            fee_tolerance_pct: 5,
-            dev_mode: true,
-        };
+        let mut config = Config::test_server();
+        if let Config::Server {
+            cardano_api_key,
+            cardano_provider_url,
+            deposit_confirm_depth,
+            ..
+        } = &mut config
+        {
+            *cardano_api_key = Some("test".to_string());
+            *cardano_provider_url = Some(provider_url);
+            *deposit_confirm_depth = 5;
+        }
 
         let keypair = config.keypair().unwrap();
 
@@ -1251,6 +1298,8 @@ mod handle_deposit_flow_tests {
             database,
             config,
             peer_registry: None,
+            health: Default::default(),
+            limits: Default::default(),
         }
     }
 
@@ -1260,7 +1309,7 @@ mod handle_deposit_flow_tests {
             let mut t = w.open_table(CARDANO_WALLET).unwrap();
             t.insert(
                 "wallet",
-                &mugraph_core::types::CardanoWallet::new(
+                mugraph_core::types::CardanoWallet::new(
                     vec![1u8; 32],
                     payment_vk,
                     vec![],
@@ -1310,14 +1359,15 @@ mod handle_deposit_flow_tests {
                 bool,
             )>,
         ) -> impl IntoResponse {
-            let (script_address, _datum_hex, include_output) = state;
+            let (script_address, datum_hex, include_output) = state;
 
             let outputs = if include_output {
                 vec![json!({
                     "output_index": 0,
                     "address": script_address,
-                    "amount": [{"unit":"lovelace","quantity":"1000000"}],
+                    "amount": [{"unit":"lovelace","quantity":"2000000"}],
                     "data_hash": "datumhash",
+                    "inline_datum": datum_hex,
                     "reference_script_hash": null
                 })]
             } else {
@@ -1344,13 +1394,39 @@ mod handle_deposit_flow_tests {
             (StatusCode::OK, axum::Json(json!({"cbor": datum_hex})))
         }
 
+        /// Address listings name the datum by hash only and leave out the
+        /// block height, as Blockfrost's do
+        async fn address_utxos(
+            axum::extract::State(state): axum::extract::State<(
+                String,
+                String,
+                bool,
+            )>,
+        ) -> impl IntoResponse {
+            let (_script_address, _datum_hex, include_output) = state;
+            let outputs = if include_output {
+                vec![json!({
+                    "tx_hash": "ab".repeat(32),
+                    "output_index": 0,
+                    "amount": [{"unit":"lovelace","quantity":"2000000"}],
+                    "data_hash": "datumhash",
+                    "reference_script_hash": null
+                })]
+            } else {
+                Vec::new()
+            };
+            (StatusCode::OK, axum::Json(json!(outputs)))
+        }
+
         let app = Router::new()
             .route("/blocks/latest", get(move || async move {
                 (StatusCode::OK, axum::Json(json!({"slot": 1000, "hash": "tip", "height": tip_height})))
             }))
+            .route("/addresses/{address}/utxos", get(address_utxos))
             .route("/txs/{tx_hash}", get(tx_info))
             .route("/txs/{tx_hash}/utxos", get(tx_utxos))
             .route("/scripts/datum/{datum_hash}/cbor", get(datum_cbor))
+            .route("/epochs/latest/parameters", get(protocol_params))
             .with_state((script_address, datum_cbor_hex, include_output));
 
         let listener =
@@ -1363,6 +1439,22 @@ mod handle_deposit_flow_tests {
         format!("http://{addr}")
     }
 
+    async fn protocol_params() -> impl IntoResponse {
+        axum::Json(json!({
+            "min_fee_a": "44",
+            "min_fee_b": "155381",
+            "max_tx_size": "16384",
+            "max_val_size": "5000",
+            "key_deposit": "2000000",
+            "pool_deposit": "500000000",
+            "price_mem": "0.0577",
+            "price_step": "0.0000721",
+            "max_tx_ex_mem": "14000000",
+            "max_tx_ex_steps": "10000000000",
+            "coins_per_utxo_size": "4310"
+        }))
+    }
+
     async fn spawn_provider_mock(
         script_address: String,
         datum_cbor_hex: String,
@@ -1390,7 +1482,7 @@ mod handle_deposit_flow_tests {
             Path(tx_hash): Path<String>,
             axum::extract::State(state): axum::extract::State<(String, String)>,
         ) -> impl IntoResponse {
-            let (script_address, _datum_hex) = state;
+            let (script_address, datum_hex) = state;
             (
                 StatusCode::OK,
                 axum::Json(json!({
@@ -1400,6 +1492,7 @@ mod handle_deposit_flow_tests {
                         "address": script_address,
                         "amount": [{"unit":"lovelace","quantity":"1000000"}],
                         "data_hash": "datumhash",
+                        "inline_datum": datum_hex,
                         "reference_script_hash": null
                     }]
                 })),
@@ -1448,7 +1541,7 @@ mod handle_deposit_flow_tests {
             Path(tx_hash): Path<String>,
             axum::extract::State(state): axum::extract::State<(String, String)>,
         ) -> impl IntoResponse {
-            let (script_address, _datum_hex) = state;
+            let (script_address, datum_hex) = state;
             (
                 StatusCode::OK,
                 axum::Json(json!({
@@ -1458,6 +1551,7 @@ mod handle_deposit_flow_tests {
                         "address": script_address,
                         "amount": [{"unit":"lovelace","quantity":"1000000"}],
                         "data_hash": "datumhash",
+                        "inline_datum": datum_hex,
                         "reference_script_hash": null
                     }]
                 })),
@@ -1507,7 +1601,7 @@ mod handle_deposit_flow_tests {
             network: "preprod".to_string(),
         };
 
-        insert_wallet(ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
         let wallet = {
             let r = ctx.database.read().unwrap();
             let t = r.open_table(CARDANO_WALLET).unwrap();
@@ -1533,7 +1627,7 @@ mod handle_deposit_flow_tests {
         let payload = build_canonical_payload(
             &request,
             &ctx.keypair.public_key,
-            "addr_test1script",
+            SCRIPT_ADDRESS,
         );
         request.signature = build_cip8_signature(user_sk, &payload);
 
@@ -1551,13 +1645,13 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let response = handle_deposit(&request, &ctx)
             .await
@@ -1570,6 +1664,54 @@ mod handle_deposit_flow_tests {
         assert!(deposits.get(&utxo_ref).unwrap().is_some());
     }
 
+    #[tokio::test]
+    async fn handle_deposit_reads_the_datum_the_index_lacks_from_the_provider()
+    {
+        let user_sk = SigningKey::from_bytes(&[12u8; 32]);
+        let node_sk = SigningKey::from_bytes(&[23u8; 32]);
+        let node_pk = node_sk.verifying_key().to_bytes();
+
+        let seed_ctx = mk_context("http://127.0.0.1:1".to_string());
+        let (request, datum_cbor_hex) =
+            prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
+
+        let url = spawn_provider_mock(
+            SCRIPT_ADDRESS.to_string(),
+            datum_cbor_hex.clone(),
+            100,
+        )
+        .await;
+        let ctx = mk_context(url);
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
+
+        ChainIndex::new(
+            ctx.database.clone(),
+            Provider::from_config(&ctx.config).unwrap(),
+            std::time::Duration::from_secs(20),
+        )
+        .sync()
+        .await
+        .unwrap()
+        .expect("wallet exists");
+        let utxo_ref = UtxoRef::new([0xabu8; 32], 0);
+        let indexed = chain_index::indexed_utxo(&ctx.database, &utxo_ref)
+            .unwrap()
+            .expect("indexed");
+        assert_eq!(indexed.utxo.datum, None);
+
+        let response = handle_deposit(&request, &ctx)
+            .await
+            .expect("deposit accepted");
+        assert!(matches!(response, Response::Deposit { .. }));
+
+        // The provider's answer completes the index
+        let indexed = chain_index::indexed_utxo(&ctx.database, &utxo_ref)
+            .unwrap()
+            .expect("indexed");
+        assert_eq!(indexed.utxo.datum, Some(datum_cbor_hex));
+        assert_eq!(indexed.utxo.block_height, Some(90));
+    }
+
     #[tokio::test]
     async fn handle_deposit_happy_path_persists_expected_intent_hash() {
         let user_sk = SigningKey::from_bytes(&[14u8; 32]);
@@ -1581,13 +1723,13 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         handle_deposit(&request, &ctx)
             .await
@@ -1621,13 +1763,13 @@ mod handle_deposit_flow_tests {
         request.message = "{}".to_string();
 
         let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(format!("{err:?}").contains("Missing user_pubkey"));
@@ -1649,14 +1791,11 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         // tx block is 90 in mock; tip 92 => 2 confirmations < configured depth 5.
-        let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
-            datum_cbor_hex,
-            92,
-        )
-        .await;
+        let url =
+            spawn_provider_mock(SCRIPT_ADDRESS.to_string(), datum_cbor_hex, 92)
+                .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(format!("{err:?}").contains("not sufficiently confirmed"));
@@ -1678,14 +1817,11 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         // tx block is 90 in mock; tip 95 => exactly 5 confirmations, which should pass.
-        let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
-            datum_cbor_hex,
-            95,
-        )
-        .await;
+        let url =
+            spawn_provider_mock(SCRIPT_ADDRESS.to_string(), datum_cbor_hex, 95)
+                .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let response = handle_deposit(&request, &ctx)
             .await
@@ -1710,7 +1846,7 @@ mod handle_deposit_flow_tests {
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(format!("{err:?}").contains("not at script address"));
@@ -1727,14 +1863,14 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock_with_outputs(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
             false,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(format!("{err:?}").contains("UTxO not found on chain"));
@@ -1752,13 +1888,13 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock_without_block_height(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(
@@ -1784,12 +1920,12 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock_with_tip_failure(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(
@@ -1815,13 +1951,13 @@ mod handle_deposit_flow_tests {
 
         // Provider serves malformed/incorrect datum payload.
         let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             "00".to_string(),
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         let err = handle_deposit(&request, &ctx).await.unwrap_err();
         assert!(matches!(err, Error::InvalidInput { .. }));
@@ -1838,13 +1974,13 @@ mod handle_deposit_flow_tests {
             prepare_request_and_datum(&seed_ctx, &user_sk, &node_pk);
 
         let url = spawn_provider_mock(
-            "addr_test1script".to_string(),
+            SCRIPT_ADDRESS.to_string(),
             datum_cbor_hex,
             100,
         )
         .await;
         let ctx = mk_context(url);
-        insert_wallet(&ctx, node_pk.to_vec(), "addr_test1script");
+        insert_wallet(&ctx, node_pk.to_vec(), SCRIPT_ADDRESS);
 
         handle_deposit(&request, &ctx)
             .await
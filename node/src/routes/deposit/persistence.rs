@@ -1,7 +1,7 @@
 use color_eyre::eyre::Result;
 use mugraph_core::{
     error::Error,
-    types::{DepositRequest, UtxoRef},
+    types::{DepositRequest, EventKind, EventSubject, UtxoRef},
 };
 use redb::ReadableTable;
 
@@ -61,17 +61,14 @@ pub(super) fn store_wallet_if_absent(
     Ok(selected)
 }
 
-/// Create Cardano provider from configuration
+/// Create Cardano provider from configuration, reading the script address
+/// from the chain index while it is fresh
 pub(super) fn create_provider(ctx: &Context) -> Result<Provider, Error> {
-    Provider::new(
-        &ctx.config.provider_type(),
-        ctx.config.provider_api_key(),
-        ctx.config.network(),
-        ctx.config.provider_url(),
-    )
-    .map_err(|e| Error::Internal {
-        reason: e.to_string(),
-    })
+    Provider::from_config(&ctx.config)
+        .map(|provider| provider.indexed(ctx.database.clone()))
+        .map_err(|e| Error::Internal {
+            reason: e.to_string(),
+        })
 }
 
 pub(super) async fn persist_deposit(
@@ -141,7 +138,14 @@ pub(super) async fn record_deposit(
             expires_at,
             intent_hash,
         );
-        insert_deposit_if_absent(&mut table, utxo_ref, record)?;
+        insert_deposit_if_absent(&mut table, utxo_ref.clone(), record)?;
+
+        write_tx.append_event(
+            EventSubject::deposit(&utxo_ref.tx_hash, utxo_ref.index),
+            EventKind::DepositRecorded {
+                block_height: tip.block_height,
+            },
+        )?;
     }
     write_tx.commit()?;
 
@@ -19,7 +19,7 @@ pub(super) async fn validate_deposit_source(
     provider: &Provider,
     ctx: &Context,
     delegate_pk: &PublicKey,
-) -> Result<(), Error> {
+) -> Result<UtxoInfo, Error> {
     let utxo_info =
         fetch_and_validate_utxo(request, wallet, provider, ctx).await?;
     validate_parsed_deposit_datum(
@@ -34,6 +34,39 @@ pub(super) async fn validate_deposit_source(
         &utxo_info,
         ctx.config.min_deposit_value(),
     )?;
+    validate_deposit_min_ada(&utxo_info, provider).await?;
+    Ok(utxo_info)
+}
+
+/// Validate that the deposit UTxO holds the minimum ADA the current
+/// protocol parameters require for its size, so it can be spent back out
+/// in the same shape
+pub(super) async fn validate_deposit_min_ada(
+    utxo_info: &UtxoInfo,
+    provider: &Provider,
+) -> Result<(), Error> {
+    let params = provider.get_protocol_params().await.map_err(|e| {
+        Error::NetworkError {
+            reason: format!("Failed to fetch protocol parameters: {}", e),
+        }
+    })?;
+    let output = utxo_info.to_output().map_err(|e| Error::InvalidInput {
+        reason: e.to_string(),
+    })?;
+    let min_ada = params.min_ada(&output).map_err(|e| Error::InvalidInput {
+        reason: format!("Cannot size deposit UTxO: {}", e),
+    })?;
+
+    let lovelace = u64::from(output.amount().coin());
+    if lovelace < min_ada {
+        return Err(Error::InvalidInput {
+            reason: format!(
+                "Deposit UTxO holds {} lovelace, below the protocol minimum {} lovelace for its size",
+                lovelace, min_ada
+            ),
+        });
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,669 @@
+//! Event log subscriptions: `/v1/events` returns a page for catch-up and
+//! polling, `/v1/events/stream` follows the log over Server-Sent Events.
+//! Both serve the events of the subjects named in their query, never the
+//! whole log. Events older than the retention window are pruned in the
+//! background.
+//!
+//! The per-client rate limit is checked when a request arrives, so an open
+//! stream is not charged for the events it carries; the number of streams
+//! open at once is capped instead (`--event-max-streams`).
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, Query, State, rejection::QueryRejection},
+    http::HeaderMap,
+    response::{
+        IntoResponse, Response as HttpResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, Stream};
+use mugraph_core::{
+    error::Error,
+    types::{EventPage, EventSubject, NodeEvent, Response},
+};
+use serde::Deserialize;
+use tokio::{
+    sync::{OwnedSemaphorePermit, watch},
+    time::{MissedTickBehavior, interval},
+};
+
+use super::{Context, rejection_response, rpc_response};
+use crate::{database::Database, supervisor::Shutdown, tls::ClientInfo};
+
+/// Most events returned in one page, or read from the log per refill of a
+/// stream.
+pub const MAX_EVENT_PAGE: usize = 500;
+
+const DEFAULT_EVENT_PAGE: usize = 100;
+
+/// Rate-limit bucket shared by both event routes.
+const EVENTS_METHOD: &str = "events";
+
+/// Query string of the event routes, which require at least one subject
+/// filter. With several, events matching any of them are returned.
+#[derive(Debug, Default, Deserialize)]
+pub struct EventQuery {
+    /// Only events after this cursor
+    #[serde(default)]
+    pub after: u64,
+    /// Deposit UTxO as `tx_hash:index`
+    pub deposit: Option<String>,
+    /// Withdrawal transaction hash
+    pub withdrawal: Option<String>,
+    /// Cross-node transfer id
+    pub transfer: Option<String>,
+    /// Page size for `/v1/events`, at most [`MAX_EVENT_PAGE`]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct EventFilter {
+    subjects: Vec<EventSubject>,
+}
+
+impl EventFilter {
+    fn from_query(query: &EventQuery) -> Result<Self, Error> {
+        let mut subjects = Vec::new();
+
+        if let Some(deposit) = &query.deposit {
+            let (tx_hash, index) = deposit
+                .rsplit_once(':')
+                .and_then(|(tx_hash, index)| {
+                    Some((tx_hash, index.parse::<u16>().ok()?))
+                })
+                .ok_or_else(|| Error::InvalidInput {
+                    reason: format!(
+                        "deposit filter must be `tx_hash:index`, got {deposit}"
+                    ),
+                })?;
+            subjects.push(EventSubject::Deposit {
+                tx_hash: tx_hash.to_ascii_lowercase(),
+                index,
+            });
+        }
+        if let Some(tx_hash) = &query.withdrawal {
+            subjects.push(EventSubject::withdrawal(tx_hash));
+        }
+        if let Some(transfer_id) = &query.transfer {
+            subjects.push(EventSubject::transfer(transfer_id));
+        }
+        if subjects.is_empty() {
+            return Err(Error::InvalidInput {
+                reason: "events need a deposit, withdrawal or transfer filter"
+                    .to_string(),
+            });
+        }
+
+        Ok(Self { subjects })
+    }
+
+    fn matches(&self, event: &NodeEvent) -> bool {
+        self.subjects.contains(&event.subject)
+    }
+}
+
+/// Admit the request against the per-client rate limit, then parse its
+/// query.
+fn admit(
+    ctx: &Context,
+    client: Option<Extension<ConnectInfo<ClientInfo>>>,
+    query: Result<Query<EventQuery>, QueryRejection>,
+) -> Result<(EventQuery, EventFilter), Error> {
+    let ip = client
+        .map(|Extension(ConnectInfo(client))| client.addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    ctx.limits.check(ip, EVENTS_METHOD)?;
+
+    let Query(query) = query.map_err(|rejection| Error::InvalidInput {
+        reason: rejection.body_text(),
+    })?;
+    let filter = EventFilter::from_query(&query)?;
+
+    Ok((query, filter))
+}
+
+fn refuse(error: Error) -> HttpResponse {
+    match error {
+        Error::RateLimited { .. } => rejection_response(EVENTS_METHOD, error),
+        error => rpc_response(error.into()),
+    }
+}
+
+/// `GET /v1/events`
+pub async fn events_page(
+    State(ctx): State<Context>,
+    client: Option<Extension<ConnectInfo<ClientInfo>>>,
+    query: Result<Query<EventQuery>, QueryRejection>,
+) -> HttpResponse {
+    let (query, filter) = match admit(&ctx, client, query) {
+        Ok(admitted) => admitted,
+        Err(e) => return refuse(e),
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENT_PAGE)
+        .clamp(1, MAX_EVENT_PAGE);
+
+    match ctx.database.events_after(query.after, limit) {
+        Ok(events) => {
+            let next = events.last().map_or(query.after, |e| e.cursor);
+            let events =
+                events.into_iter().filter(|e| filter.matches(e)).collect();
+            Json(EventPage { events, next }).into_response()
+        }
+        Err(e) => rpc_response(e.into()),
+    }
+}
+
+/// `GET /v1/events/stream`
+///
+/// A `Last-Event-ID` header, as sent by reconnecting `EventSource` clients,
+/// takes precedence over `after`.
+pub async fn events_stream(
+    State(ctx): State<Context>,
+    client: Option<Extension<ConnectInfo<ClientInfo>>>,
+    headers: HeaderMap,
+    query: Result<Query<EventQuery>, QueryRejection>,
+) -> HttpResponse {
+    let (query, filter) = match admit(&ctx, client, query) {
+        Ok(admitted) => admitted,
+        Err(e) => return refuse(e),
+    };
+    let cursor = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+        .unwrap_or(query.after);
+    if let Err(e) = ctx.database.check_event_cursor(cursor) {
+        return rpc_response(e.into());
+    }
+    let permit = match ctx.limits.open_stream() {
+        Ok(permit) => permit,
+        Err(e) => return refuse(e),
+    };
+
+    let follow = Follow {
+        changes: ctx.database.subscribe_events(),
+        database: ctx.database.clone(),
+        shutdown: ctx.health.supervisor.shutdown_signal(),
+        filter,
+        cursor,
+        caught_up: false,
+        pending: VecDeque::new(),
+        done: false,
+        _permit: permit,
+    };
+
+    Sse::new(follow.into_stream())
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// State of one live subscription.
+struct Follow {
+    database: Arc<Database>,
+    changes: watch::Receiver<u64>,
+    shutdown: Shutdown,
+    filter: EventFilter,
+    /// Last cursor read from the log, matching or not
+    cursor: u64,
+    /// Whether the replay has reached the end of the log, after which only
+    /// the events of new commits are read
+    caught_up: bool,
+    pending: VecDeque<NodeEvent>,
+    done: bool,
+    /// Slot under the cap on open streams, freed when the stream is dropped
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Follow {
+    /// Replay the log after `cursor` a page at a time, then read the events
+    /// of each new commit. Ends on shutdown, or after reporting a storage
+    /// error as an `error` event.
+    fn into_stream(self) -> impl Stream<Item = Result<Event, Infallible>> {
+        stream::unfold(self, |mut state| async move {
+            loop {
+                if let Some(event) = state.pending.pop_front() {
+                    return Some((Ok(sse_event(&event)), state));
+                }
+                if state.done {
+                    return None;
+                }
+
+                // Mark the latest commit seen before reading, so one that
+                // races with the read still wakes us
+                let latest = *state.changes.borrow_and_update();
+                if state.caught_up && latest <= state.cursor {
+                    tokio::select! {
+                        _ = state.shutdown.wait() => return None,
+                        changed = state.changes.changed() => {
+                            if changed.is_err() {
+                                return None;
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                // Once caught up, cursors up to `latest` are committed and
+                // consecutive, so exactly the new ones are read
+                let limit = if state.caught_up {
+                    usize::try_from(latest - state.cursor)
+                        .map_or(MAX_EVENT_PAGE, |new| new.min(MAX_EVENT_PAGE))
+                } else {
+                    MAX_EVENT_PAGE
+                };
+                match state.database.events_after(state.cursor, limit) {
+                    Ok(events) => {
+                        let read_to =
+                            events.last().map_or(state.cursor, |e| e.cursor);
+                        if state.caught_up {
+                            state.cursor =
+                                read_to.max(state.cursor + limit as u64);
+                        } else {
+                            state.caught_up = events.len() < limit;
+                            state.cursor = read_to;
+                        }
+                        let filter = &state.filter;
+                        state.pending.extend(
+                            events.into_iter().filter(|e| filter.matches(e)),
+                        );
+                    }
+                    Err(e) => {
+                        tracing::error!("event stream read failed: {e}");
+                        state.done = true;
+                        let event = Event::default()
+                            .event("error")
+                            .json_data(Response::from(e))
+                            .unwrap_or_default();
+                        return Some((Ok(event), state));
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Periodically delete events older than `retention` from the event log.
+pub async fn prune_loop(
+    database: Arc<Database>,
+    tick: Duration,
+    retention: Duration,
+    shutdown: Shutdown,
+) {
+    let mut ticker = interval(tick);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown.wait() => break,
+            _ = ticker.tick() => {}
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let cutoff = now.saturating_sub(retention.as_secs());
+
+        let db = database.clone();
+        match tokio::task::spawn_blocking(move || db.prune_events(cutoff)).await
+        {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::error!("event log pruning failed: {}", e),
+            Err(e) => {
+                tracing::error!("event log pruning task panicked: {}", e)
+            }
+        }
+    }
+}
+
+fn sse_event(event: &NodeEvent) -> Event {
+    Event::default()
+        .id(event.cursor.to_string())
+        .json_data(event)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request as HttpRequest, StatusCode},
+        routing::get,
+    };
+    use futures_util::StreamExt;
+    use mugraph_core::{error::ErrorCode, types::EventKind};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::routes::tests::test_context;
+
+    fn app(ctx: &Context) -> Router {
+        Router::new()
+            .route("/v1/events", get(events_page))
+            .route("/v1/events/stream", get(events_stream))
+            .with_state(ctx.clone())
+    }
+
+    fn append(ctx: &Context, subject: EventSubject, kind: EventKind) {
+        let w = ctx.database.write().unwrap();
+        w.append_event(subject, kind).unwrap();
+        w.commit().unwrap();
+    }
+
+    async fn page(ctx: &Context, query: &str) -> EventPage {
+        let response = app(ctx)
+            .oneshot(
+                HttpRequest::get(format!("/v1/events?{query}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn next_frame(
+        frames: &mut (
+                 impl futures_util::Stream<
+            Item = Result<axum::body::Bytes, axum::Error>,
+        > + Unpin
+             ),
+    ) -> Option<String> {
+        tokio::time::timeout(Duration::from_secs(5), frames.next())
+            .await
+            .expect("no event within 5s")
+            .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+    }
+
+    fn query(deposit: Option<&str>, withdrawal: Option<&str>) -> EventQuery {
+        EventQuery {
+            deposit: deposit.map(str::to_string),
+            withdrawal: withdrawal.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn event(subject: EventSubject) -> NodeEvent {
+        NodeEvent {
+            cursor: 1,
+            created_at: 0,
+            subject,
+            event: mugraph_core::types::EventKind::WithdrawalPending,
+        }
+    }
+
+    #[test]
+    fn filter_needs_a_subject() {
+        let err = EventFilter::from_query(&query(None, None)).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn filter_matches_any_subject_case_insensitively() {
+        let filter =
+            EventFilter::from_query(&query(Some("ABCD:2"), Some("EF")))
+                .unwrap();
+
+        assert!(filter.matches(&event(EventSubject::Deposit {
+            tx_hash: "abcd".to_string(),
+            index: 2,
+        })));
+        assert!(filter.matches(&event(EventSubject::withdrawal("ef"))));
+        assert!(!filter.matches(&event(EventSubject::Deposit {
+            tx_hash: "abcd".to_string(),
+            index: 3,
+        })));
+        assert!(!filter.matches(&event(EventSubject::transfer("ef"))));
+    }
+
+    #[test]
+    fn malformed_deposit_filter_is_invalid_input() {
+        for deposit in ["abcd", "abcd:x", "abcd:70000"] {
+            let err = EventFilter::from_query(&query(Some(deposit), None))
+                .unwrap_err();
+            assert_eq!(err.code(), ErrorCode::InvalidInput);
+        }
+    }
+
+    #[tokio::test]
+    async fn page_filters_and_advances_past_non_matching_events() {
+        let ctx = test_context();
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalPending,
+        );
+        append(
+            &ctx,
+            EventSubject::transfer("tr-1"),
+            EventKind::WithdrawalPending,
+        );
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalSubmitted,
+        );
+
+        let first = page(&ctx, "withdrawal=AA&limit=2").await;
+        assert_eq!(first.events.len(), 1);
+        assert_eq!(first.events[0].cursor, 1);
+        assert_eq!(first.next, 2);
+
+        let second =
+            page(&ctx, &format!("withdrawal=aa&after={}", first.next)).await;
+        assert_eq!(second.events.len(), 1);
+        assert_eq!(second.events[0].event, EventKind::WithdrawalSubmitted);
+        assert_eq!(second.next, 3);
+
+        let drained = page(&ctx, "withdrawal=aa&after=3").await;
+        assert!(drained.events.is_empty());
+        assert_eq!(drained.next, 3);
+    }
+
+    #[tokio::test]
+    async fn stream_resumes_from_last_event_id_and_follows_new_commits() {
+        let ctx = test_context();
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalPending,
+        );
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalFailed,
+        );
+
+        let response = app(&ctx)
+            .oneshot(
+                HttpRequest::get("/v1/events/stream?withdrawal=aa&after=0")
+                    .header("last-event-id", "1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut frames = response.into_body().into_data_stream();
+
+        let replayed = next_frame(&mut frames).await.unwrap();
+        assert!(replayed.contains("id: 2"), "{replayed}");
+        assert!(replayed.contains("withdrawal_failed"), "{replayed}");
+
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalPending,
+        );
+        let live = next_frame(&mut frames).await.unwrap();
+        assert!(live.contains("id: 3"), "{live}");
+
+        ctx.health.supervisor.shutdown_signal().trigger();
+        assert_eq!(next_frame(&mut frames).await, None);
+    }
+
+    async fn error_of(ctx: &Context, uri: &str) -> (StatusCode, Response) {
+        let response = app(ctx)
+            .oneshot(HttpRequest::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn events_require_a_subject_filter() {
+        let ctx = test_context();
+
+        for uri in ["/v1/events?after=0", "/v1/events/stream"] {
+            let (status, body) = error_of(&ctx, uri).await;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            let Response::Error { code, .. } = body else {
+                panic!("expected an error, got {body:?}");
+            };
+            assert_eq!(code, ErrorCode::InvalidInput);
+        }
+    }
+
+    #[tokio::test]
+    async fn open_streams_are_capped() {
+        let mut ctx = test_context();
+        let mut config = ctx.config.clone();
+        if let crate::config::Config::Server {
+            event_max_streams, ..
+        } = &mut config
+        {
+            *event_max_streams = 1;
+        }
+        ctx.limits = Arc::new(crate::routes::RpcLimiter::from_config(&config));
+
+        let open = app(&ctx)
+            .oneshot(
+                HttpRequest::get("/v1/events/stream?withdrawal=aa")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(open.status(), StatusCode::OK);
+
+        let (status, body) =
+            error_of(&ctx, "/v1/events/stream?withdrawal=bb").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        let Response::Error { code, .. } = body else {
+            panic!("expected an error, got {body:?}");
+        };
+        assert_eq!(code, ErrorCode::RateLimited);
+
+        drop(open);
+        let reopened = app(&ctx)
+            .oneshot(
+                HttpRequest::get("/v1/events/stream?withdrawal=bb")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reopened.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn caught_up_streams_read_only_the_commits_announced() {
+        let ctx = test_context();
+        for kind in [
+            EventKind::WithdrawalPending,
+            EventKind::WithdrawalSubmitted,
+            EventKind::WithdrawalFailed,
+        ] {
+            append(&ctx, EventSubject::withdrawal("aa"), kind);
+        }
+
+        let (commits, changes) = watch::channel(1);
+        let follow = Follow {
+            database: ctx.database.clone(),
+            changes,
+            shutdown: ctx.health.supervisor.shutdown_signal(),
+            filter: EventFilter::from_query(&query(None, Some("aa"))).unwrap(),
+            cursor: 1,
+            caught_up: true,
+            pending: VecDeque::new(),
+            done: false,
+            _permit: None,
+        };
+        let mut events = Box::pin(follow.into_stream());
+        let quiet = Duration::from_millis(100);
+
+        // Nothing announced past the cursor, so nothing is read
+        assert!(tokio::time::timeout(quiet, events.next()).await.is_err());
+
+        commits.send_replace(2);
+        let next = events.next().await.unwrap().unwrap();
+        assert!(format!("{next:?}").contains("withdrawal_submitted"));
+        assert!(tokio::time::timeout(quiet, events.next()).await.is_err());
+
+        commits.send_replace(3);
+        let next = events.next().await.unwrap().unwrap();
+        assert!(format!("{next:?}").contains("withdrawal_failed"));
+    }
+
+    #[tokio::test]
+    async fn pruned_cursors_are_gone_with_the_oldest_kept_cursor() {
+        let ctx = test_context();
+        for kind in [
+            EventKind::WithdrawalPending,
+            EventKind::WithdrawalSubmitted,
+            EventKind::WithdrawalFailed,
+        ] {
+            append(&ctx, EventSubject::withdrawal("aa"), kind);
+        }
+
+        assert_eq!(ctx.database.prune_events(u64::MAX).unwrap(), 2);
+        assert_eq!(ctx.database.prune_events(u64::MAX).unwrap(), 0);
+
+        for uri in [
+            "/v1/events?withdrawal=aa&after=1",
+            "/v1/events/stream?withdrawal=aa&after=1",
+        ] {
+            let (status, body) = error_of(&ctx, uri).await;
+            assert_eq!(status, StatusCode::GONE, "{uri}");
+            let Response::Error { code, details, .. } = body else {
+                panic!("expected an error, got {body:?}");
+            };
+            assert_eq!(code, ErrorCode::CursorExpired);
+            assert_eq!(details["oldest_cursor"], "3");
+        }
+
+        let resumed = page(&ctx, "withdrawal=aa&after=2").await;
+        assert_eq!(resumed.events.len(), 1);
+        assert_eq!(resumed.events[0].event, EventKind::WithdrawalFailed);
+
+        append(
+            &ctx,
+            EventSubject::withdrawal("aa"),
+            EventKind::WithdrawalPending,
+        );
+        assert_eq!(
+            page(&ctx, "withdrawal=aa&after=3").await.events[0].cursor,
+            4
+        );
+    }
+}
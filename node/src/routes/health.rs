@@ -0,0 +1,540 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{Json, extract::State, http::StatusCode};
+use mugraph_core::error::Error;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::Context;
+use crate::{
+    database::{
+        CARDANO_WALLET, CURRENT_SCHEMA_VERSION, Database, SCHEMA_VERSION,
+    },
+    provider::Provider,
+    supervisor::{Supervisor, TaskState, TaskStatus},
+};
+
+/// Upper bound for any single probe, so a wedged dependency cannot hang the
+/// endpoint itself.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A deposit-monitor tick older than this marks the node unready.
+pub const DEPOSIT_MONITOR_STALE_AFTER: Duration = Duration::from_secs(180);
+
+/// A reconciler tick older than this marks the node unready.
+pub const RECONCILER_STALE_AFTER: Duration = Duration::from_secs(60);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Unix time of a background task's last successful tick.
+#[derive(Debug, Default)]
+pub struct Heartbeat(AtomicU64);
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.beat_at(now_secs());
+    }
+
+    pub fn beat_at(&self, now: u64) {
+        self.0.store(now, Ordering::Relaxed);
+    }
+
+    /// Seconds since the last beat, or `None` if it never beat.
+    pub fn age_secs(&self, now: u64) -> Option<u64> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(now.saturating_sub(last)),
+        }
+    }
+}
+
+/// Liveness signals shared between the router and background tasks.
+#[derive(Debug, Default)]
+pub struct HealthState {
+    pub deposit_monitor: Arc<Heartbeat>,
+    pub reconciler: Arc<Heartbeat>,
+    pub supervisor: Arc<Supervisor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Fail,
+    /// Not applicable in this configuration (e.g. chain checks in dev mode)
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<BTreeMap<&'static str, TaskState>>,
+}
+
+impl CheckResult {
+    fn with_status(status: CheckStatus) -> Self {
+        Self {
+            status,
+            error: None,
+            schema_version: None,
+            age_secs: None,
+            peers: None,
+            tasks: None,
+        }
+    }
+
+    fn ok() -> Self {
+        Self::with_status(CheckStatus::Ok)
+    }
+
+    fn skipped() -> Self {
+        Self::with_status(CheckStatus::Skipped)
+    }
+
+    fn fail(error: impl ToString) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::with_status(CheckStatus::Fail)
+        }
+    }
+
+    fn from_result(result: Result<(), Error>) -> Self {
+        match result {
+            Ok(()) => Self::ok(),
+            Err(e) => Self::fail(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+impl HealthReport {
+    fn new(checks: BTreeMap<&'static str, CheckResult>) -> Self {
+        let status = if checks.values().any(|c| c.status == CheckStatus::Fail) {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Ok
+        };
+
+        Self { status, checks }
+    }
+
+    fn into_response(self) -> (StatusCode, Json<Self>) {
+        let code = match self.status {
+            CheckStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::OK,
+        };
+        (code, Json(self))
+    }
+}
+
+/// Run a blocking database probe off the async runtime, bounded by
+/// [`CHECK_TIMEOUT`].
+async fn blocking_check<T: Send + 'static>(
+    database: &Arc<Database>,
+    probe: fn(&Database) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let database = database.clone();
+    match tokio::time::timeout(
+        CHECK_TIMEOUT,
+        tokio::task::spawn_blocking(move || probe(&database)),
+    )
+    .await
+    {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(Error::Internal {
+            reason: format!("health probe panicked: {e}"),
+        }),
+        Err(_) => Err(Error::Internal {
+            reason: format!("timed out after {}s", CHECK_TIMEOUT.as_secs()),
+        }),
+    }
+}
+
+/// Begin and abort a write transaction, proving the database file is
+/// writable and the writer lock is obtainable.
+fn probe_writable(database: &Database) -> Result<(), Error> {
+    let w = database.write()?;
+    let _ = w.open_table(SCHEMA_VERSION)?;
+    w.abort()
+}
+
+fn probe_wallet(database: &Database) -> Result<bool, Error> {
+    let r = database.read()?;
+    let table = r.open_table(CARDANO_WALLET)?;
+    Ok(table.get("wallet")?.is_some())
+}
+
+async fn check_database(database: &Arc<Database>) -> CheckResult {
+    CheckResult::from_result(blocking_check(database, probe_writable).await)
+}
+
+async fn check_schema(database: &Arc<Database>) -> CheckResult {
+    match blocking_check(database, Database::schema_version).await {
+        Ok(version) => CheckResult {
+            schema_version: Some(version),
+            ..if version == CURRENT_SCHEMA_VERSION {
+                CheckResult::ok()
+            } else {
+                CheckResult::fail(format!(
+                    "expected schema version {CURRENT_SCHEMA_VERSION}"
+                ))
+            }
+        },
+        Err(e) => CheckResult::fail(e),
+    }
+}
+
+async fn check_wallet(database: &Arc<Database>) -> CheckResult {
+    match blocking_check(database, probe_wallet).await {
+        Ok(true) => CheckResult::ok(),
+        Ok(false) => CheckResult::fail("Cardano wallet not initialized"),
+        Err(e) => CheckResult::fail(e),
+    }
+}
+
+async fn check_provider(ctx: &Context) -> CheckResult {
+    let provider = match Provider::from_config(&ctx.config) {
+        Ok(provider) => provider,
+        Err(e) => return CheckResult::fail(e),
+    };
+
+    match tokio::time::timeout(CHECK_TIMEOUT, provider.get_tip()).await {
+        Ok(Ok(_)) => CheckResult::ok(),
+        Ok(Err(e)) => CheckResult::fail(e),
+        Err(_) => CheckResult::fail(format!(
+            "timed out after {}s",
+            CHECK_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+fn check_heartbeat(
+    heartbeat: &Heartbeat,
+    stale_after: Duration,
+    now: u64,
+) -> CheckResult {
+    match heartbeat.age_secs(now) {
+        None => CheckResult::fail("no successful tick yet"),
+        Some(age) if age > stale_after.as_secs() => CheckResult {
+            age_secs: Some(age),
+            ..CheckResult::fail(format!(
+                "last successful tick is older than {}s",
+                stale_after.as_secs()
+            ))
+        },
+        Some(age) => CheckResult {
+            age_secs: Some(age),
+            ..CheckResult::ok()
+        },
+    }
+}
+
+fn check_peer_registry(ctx: &Context) -> CheckResult {
+    match (&ctx.peer_registry, ctx.config.xnode_peer_registry_file()) {
+        (Some(registry), _) => CheckResult {
+            peers: Some(registry.peers.len()),
+            ..CheckResult::ok()
+        },
+        (None, Some(path)) => {
+            CheckResult::fail(format!("peer registry {path} is not loaded"))
+        }
+        (None, None) => CheckResult::skipped(),
+    }
+}
+
+/// Supervised background tasks: fails while the node is draining for
+/// shutdown or while any task is backing off after a crash.
+fn check_tasks(supervisor: &Supervisor) -> CheckResult {
+    let tasks = supervisor.states();
+    let restarting: Vec<_> = tasks
+        .iter()
+        .filter(|(_, state)| state.status == TaskStatus::Restarting)
+        .map(|(name, _)| *name)
+        .collect();
+
+    let result = if supervisor.is_shutting_down() {
+        CheckResult::fail("shutting down")
+    } else if !restarting.is_empty() {
+        CheckResult::fail(format!("restarting: {}", restarting.join(", ")))
+    } else {
+        CheckResult::ok()
+    };
+
+    CheckResult {
+        tasks: Some(tasks),
+        ..result
+    }
+}
+
+/// Liveness: the process answers and its database is usable. Deliberately
+/// ignores external dependencies, which a restart cannot fix.
+pub async fn livez(
+    State(ctx): State<Context>,
+) -> (StatusCode, Json<HealthReport>) {
+    let checks =
+        BTreeMap::from([("database", check_database(&ctx.database).await)]);
+    HealthReport::new(checks).into_response()
+}
+
+/// Readiness: every dependency needed to serve deposits, withdrawals and
+/// cross-node traffic.
+pub async fn readyz(
+    State(ctx): State<Context>,
+) -> (StatusCode, Json<HealthReport>) {
+    let now = now_secs();
+    let mut checks = BTreeMap::from([
+        ("database", check_database(&ctx.database).await),
+        ("schema", check_schema(&ctx.database).await),
+        ("peer_registry", check_peer_registry(&ctx)),
+        ("tasks", check_tasks(&ctx.health.supervisor)),
+    ]);
+
+    if ctx.config.dev_mode() && !ctx.config.emulated_ledger() {
+        for name in ["wallet", "provider", "deposit_monitor", "reconciler"] {
+            checks.insert(name, CheckResult::skipped());
+        }
+    } else {
+        checks.insert("wallet", check_wallet(&ctx.database).await);
+        checks.insert("provider", check_provider(&ctx).await);
+        checks.insert(
+            "deposit_monitor",
+            check_heartbeat(
+                &ctx.health.deposit_monitor,
+                DEPOSIT_MONITOR_STALE_AFTER,
+                now,
+            ),
+        );
+        checks.insert(
+            "reconciler",
+            check_heartbeat(
+                &ctx.health.reconciler,
+                RECONCILER_STALE_AFTER,
+                now,
+            ),
+        );
+    }
+
+    HealthReport::new(checks).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, routing::get};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn chain_config(provider_url: String) -> Config {
+        let mut config = Config::test_server();
+        if let Config::Server {
+            cardano_api_key,
+            cardano_provider_url,
+            min_deposit_value,
+            dev_mode,
+            ..
+        } = &mut config
+        {
+            *cardano_api_key = Some("test".to_string());
+            *cardano_provider_url = Some(provider_url);
+            *min_deposit_value = None;
+            *dev_mode = false;
+        }
+        config
+    }
+
+    fn chain_ctx(dir: &TempDir, provider_url: String) -> Context {
+        let database =
+            Arc::new(Database::setup(dir.path().join("db.redb")).unwrap());
+        database.migrate().unwrap();
+        let config = chain_config(provider_url);
+
+        Context {
+            keypair: config.keypair().unwrap(),
+            database,
+            config,
+            peer_registry: None,
+            health: Default::default(),
+            limits: Default::default(),
+        }
+    }
+
+    fn seed_wallet(database: &Database) {
+        let w = database.write().unwrap();
+        {
+            let mut t = w.open_table(CARDANO_WALLET).unwrap();
+            t.insert(
+                "wallet",
+                mugraph_core::types::CardanoWallet::new(
+                    vec![7u8; 32],
+                    vec![8u8; 32],
+                    vec![],
+                    vec![],
+                    "addr_test...".to_string(),
+                    "preprod".to_string(),
+                ),
+            )
+            .unwrap();
+        }
+        w.commit().unwrap();
+    }
+
+    async fn spawn_blockfrost_tip() -> String {
+        let app = Router::new().route(
+            "/blocks/latest",
+            get(|| async {
+                Json(serde_json::json!({
+                    "slot": 10,
+                    "hash": "abc",
+                    "height": 5,
+                }))
+            }),
+        );
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn readyz_passes_when_every_dependency_is_healthy() {
+        let dir = TempDir::new().unwrap();
+        let ctx = chain_ctx(&dir, spawn_blockfrost_tip().await);
+        seed_wallet(&ctx.database);
+        ctx.health.deposit_monitor.beat();
+        ctx.health.reconciler.beat();
+
+        let (status, Json(report)) = readyz(State(ctx)).await;
+
+        assert_eq!(status, StatusCode::OK, "{report:?}");
+        assert_eq!(
+            report.checks["schema"].schema_version,
+            Some(CURRENT_SCHEMA_VERSION)
+        );
+        assert_eq!(report.checks["provider"].status, CheckStatus::Ok);
+        assert_eq!(report.checks["peer_registry"].status, CheckStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn readyz_reports_each_failing_dependency() {
+        let dir = TempDir::new().unwrap();
+        let ctx = chain_ctx(&dir, "http://127.0.0.1:1".to_string());
+
+        let (status, Json(report)) = readyz(State(ctx.clone())).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report.status, CheckStatus::Fail);
+        assert_eq!(report.checks["database"].status, CheckStatus::Ok);
+        for name in ["wallet", "provider", "deposit_monitor", "reconciler"] {
+            let check = &report.checks[name];
+            assert_eq!(check.status, CheckStatus::Fail, "{name}");
+            assert!(check.error.is_some(), "{name}");
+        }
+
+        // Liveness ignores external dependencies
+        let (status, Json(report)) = livez(State(ctx)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report.checks.len(), 1);
+    }
+
+    #[test]
+    fn heartbeat_reports_age_since_last_beat() {
+        let heartbeat = Heartbeat::default();
+        assert_eq!(heartbeat.age_secs(100), None);
+
+        heartbeat.beat_at(90);
+        assert_eq!(heartbeat.age_secs(100), Some(10));
+        // Clock going backwards must not underflow
+        assert_eq!(heartbeat.age_secs(80), Some(0));
+    }
+
+    #[test]
+    fn stale_heartbeat_fails_with_its_age() {
+        let heartbeat = Heartbeat::default();
+        let stale_after = Duration::from_secs(60);
+
+        assert_eq!(
+            check_heartbeat(&heartbeat, stale_after, 1_000).status,
+            CheckStatus::Fail
+        );
+
+        heartbeat.beat_at(950);
+        let fresh = check_heartbeat(&heartbeat, stale_after, 1_000);
+        assert_eq!(fresh.status, CheckStatus::Ok);
+        assert_eq!(fresh.age_secs, Some(50));
+
+        let stale = check_heartbeat(&heartbeat, stale_after, 1_100);
+        assert_eq!(stale.status, CheckStatus::Fail);
+        assert_eq!(stale.age_secs, Some(150));
+    }
+
+    #[test]
+    fn report_fails_when_any_check_fails() {
+        let report = HealthReport::new(BTreeMap::from([
+            ("a", CheckResult::ok()),
+            ("b", CheckResult::skipped()),
+        ]));
+        assert_eq!(report.status, CheckStatus::Ok);
+        assert_eq!(report.into_response().0, StatusCode::OK);
+
+        let report = HealthReport::new(BTreeMap::from([
+            ("a", CheckResult::ok()),
+            ("b", CheckResult::fail("boom")),
+        ]));
+        assert_eq!(report.status, CheckStatus::Fail);
+        assert_eq!(report.into_response().0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn tasks_check_fails_while_restarting_or_shutting_down() {
+        let supervisor = Supervisor::with_backoff(
+            Duration::from_secs(3600),
+            Duration::from_secs(3600),
+        );
+        assert_eq!(check_tasks(&supervisor).status, CheckStatus::Ok);
+
+        supervisor.spawn("crashy", |_| async { panic!("boom") });
+        for _ in 0..100 {
+            if supervisor.states()["crashy"].status == TaskStatus::Restarting {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let check = check_tasks(&supervisor);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.error.as_deref(), Some("restarting: crashy"));
+        assert_eq!(check.tasks.unwrap()["crashy"].restarts, 1);
+
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        let check = check_tasks(&supervisor);
+        assert_eq!(check.error.as_deref(), Some("shutting down"));
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response as HttpResponse},
+};
+use mugraph_core::{
+    error::{Error, ErrorCode},
+    types::Response,
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{MissedTickBehavior, interval},
+};
+
+use super::RPC_METHODS;
+use crate::{config::Config, supervisor::Shutdown, tls::ClientInfo};
+
+/// Methods that call out to the chain provider or build transactions, and
+/// share the concurrency cap.
+pub const EXPENSIVE_METHODS: &[&str] = &["deposit", "withdraw"];
+
+/// How often buckets that have refilled are dropped. A bucket refills
+/// within two seconds, so one client is tracked at most this long past its
+/// last request.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rate {
+    per_sec: f64,
+    burst: f64,
+}
+
+impl Rate {
+    /// A sustained rate with bursts of twice that; `None` when `per_sec` is 0.
+    fn new(per_sec: u32) -> Option<Self> {
+        (per_sec > 0).then(|| Self {
+            per_sec: f64::from(per_sec),
+            burst: f64::from(per_sec) * 2.0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_sec).min(rate.burst);
+        self.updated = now;
+    }
+
+    /// Whole seconds until the next token, never less than one.
+    fn retry_after_secs(&self, rate: Rate) -> u64 {
+        ((1.0 - self.tokens) / rate.per_sec).ceil().max(1.0) as u64
+    }
+}
+
+/// A client-wide bucket (`None`) or one for a single method.
+type BucketKey = (IpAddr, Option<&'static str>);
+
+/// Token buckets per client IP and per (client IP, method), plus shared
+/// caps on in-flight expensive requests and open event streams. The default
+/// limits nothing.
+#[derive(Debug, Default)]
+pub struct RpcLimiter {
+    per_ip: Option<Rate>,
+    per_method: HashMap<&'static str, Rate>,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    expensive: Option<Arc<Semaphore>>,
+    streams: Option<Arc<Semaphore>>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+fn rate_limited(scope: &str, retry_after_secs: u64) -> Error {
+    Error::RateLimited {
+        scope: scope.to_string(),
+        retry_after_secs,
+    }
+}
+
+impl RpcLimiter {
+    pub fn from_config(config: &Config) -> Self {
+        let per_method = config
+            .rpc_method_rate_limits()
+            .into_iter()
+            .filter_map(|limit| {
+                let method =
+                    RPC_METHODS.iter().find(|m| **m == limit.method)?;
+                Some((*method, Rate::new(limit.per_sec)?))
+            })
+            .collect();
+        let max_concurrent = config.rpc_max_concurrent_expensive();
+        let max_streams = config.event_max_streams();
+
+        Self {
+            per_ip: Rate::new(config.rpc_rate_limit()),
+            per_method,
+            buckets: Default::default(),
+            expensive: (max_concurrent > 0)
+                .then(|| Arc::new(Semaphore::new(max_concurrent))),
+            streams: (max_streams > 0)
+                .then(|| Arc::new(Semaphore::new(max_streams))),
+            trusted_proxies: config.rpc_trusted_proxies(),
+        }
+    }
+
+    /// Whether any request rate is limited, so buckets need sweeping
+    pub fn limits_rates(&self) -> bool {
+        self.per_ip.is_some() || !self.per_method.is_empty()
+    }
+
+    /// Address `client` is rate limited under. A trusted proxy's
+    /// `forwarded_for` header is read from the right, skipping the other
+    /// trusted proxies, up to the address that connected to them.
+    /// Requests without a peer address (in-process callers) share one
+    /// bucket.
+    pub fn client_ip(
+        &self,
+        client: Option<&ClientInfo>,
+        forwarded_for: Option<&str>,
+    ) -> IpAddr {
+        let Some(client) = client else {
+            return IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        };
+
+        let mut ip = client.addr.ip();
+        let hops = forwarded_for.into_iter().flat_map(|h| h.rsplit(','));
+        for hop in hops {
+            if !self.trusted_proxies.contains(&ip) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(hop) => ip = hop,
+                Err(_) => break,
+            }
+        }
+        ip
+    }
+
+    /// Publish the configured limits as gauges.
+    pub fn record_limits(&self, max_body_bytes: usize) {
+        metrics::gauge!("mugraph.node.rpc.limit", "limit" => "max_body_bytes")
+            .set(max_body_bytes as f64);
+        metrics::gauge!("mugraph.node.rpc.limit", "limit" => "rate_per_ip")
+            .set(self.per_ip.map_or(0.0, |rate| rate.per_sec));
+        metrics::gauge!(
+            "mugraph.node.rpc.limit",
+            "limit" => "max_concurrent_expensive",
+        )
+        .set(
+            self.expensive
+                .as_ref()
+                .map_or(0.0, |s| s.available_permits() as f64),
+        );
+        metrics::gauge!("mugraph.node.rpc.limit", "limit" => "max_event_streams")
+            .set(
+                self.streams
+                    .as_ref()
+                    .map_or(0.0, |s| s.available_permits() as f64),
+            );
+        for (method, rate) in &self.per_method {
+            metrics::gauge!(
+                "mugraph.node.rpc.method_rate_limit",
+                "method" => *method,
+            )
+            .set(rate.per_sec);
+        }
+    }
+
+    fn rate_for(&self, method: Option<&'static str>) -> Option<Rate> {
+        match method {
+            None => self.per_ip,
+            Some(method) => self.per_method.get(method).copied(),
+        }
+    }
+
+    /// Admit one `method` request from `ip`, or fail with
+    /// [`Error::RateLimited`].
+    ///
+    /// Expensive methods hold the returned permit until they finish. Tokens
+    /// are only taken once every applicable bucket has one to spare.
+    pub fn check(
+        &self,
+        ip: IpAddr,
+        method: &'static str,
+    ) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        self.take_tokens(ip, method, Instant::now())?;
+
+        match &self.expensive {
+            Some(semaphore) if EXPENSIVE_METHODS.contains(&method) => semaphore
+                .clone()
+                .try_acquire_owned()
+                .map(Some)
+                .map_err(|_| rate_limited("concurrency", 1)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Admit one more open event stream, or fail with
+    /// [`Error::RateLimited`] while the cap is reached. The stream holds the
+    /// returned permit until it closes.
+    pub fn open_stream(&self) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        match &self.streams {
+            Some(semaphore) => semaphore
+                .clone()
+                .try_acquire_owned()
+                .map(Some)
+                .map_err(|_| rate_limited("streams", 1)),
+            None => Ok(None),
+        }
+    }
+
+    fn take_tokens(
+        &self,
+        ip: IpAddr,
+        method: &'static str,
+        now: Instant,
+    ) -> Result<(), Error> {
+        let scopes = [(None, "ip"), (Some(method), "method")];
+        let mut buckets = self.buckets.lock().expect("rate buckets poisoned");
+
+        for (key, scope) in scopes {
+            let Some(rate) = self.rate_for(key) else {
+                continue;
+            };
+            let bucket = buckets
+                .entry((ip, key))
+                .or_insert_with(|| Bucket::full(rate, now));
+            bucket.refill(rate, now);
+            if bucket.tokens < 1.0 {
+                return Err(rate_limited(scope, bucket.retry_after_secs(rate)));
+            }
+        }
+
+        for (key, _) in scopes {
+            if let Some(bucket) = buckets.get_mut(&(ip, key)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop the buckets that have refilled: a fresh one would be the same.
+    fn sweep(&self, now: Instant) {
+        let mut buckets = self.buckets.lock().expect("rate buckets poisoned");
+        buckets.retain(|(_, method), bucket| {
+            let Some(rate) = self.rate_for(*method) else {
+                return false;
+            };
+            bucket.refill(rate, now);
+            bucket.tokens < rate.burst
+        });
+    }
+}
+
+/// Periodically drop idle rate limit buckets, off the request path.
+pub async fn sweep_loop(limiter: Arc<RpcLimiter>, shutdown: Shutdown) {
+    let mut ticker = interval(SWEEP_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown.wait() => break,
+            _ = ticker.tick() => limiter.sweep(Instant::now()),
+        }
+    }
+}
+
+fn count_rejection(method: &'static str, reason: &str) {
+    metrics::counter!(
+        "mugraph.node.rpc.rejections_total",
+        "method" => method,
+        "reason" => reason.to_string(),
+    )
+    .increment(1);
+}
+
+/// Scope and retry delay of a limiter error.
+fn rejection_scope(error: &Error) -> (&str, u64) {
+    match error {
+        Error::RateLimited {
+            scope,
+            retry_after_secs,
+        } => (scope.as_str(), *retry_after_secs),
+        _ => ("other", 1),
+    }
+}
+
+/// `429 Too Many Requests` with a `Retry-After` header for a limiter error.
+pub fn rejection_response(method: &'static str, error: Error) -> HttpResponse {
+    let (scope, retry_after) = rejection_scope(&error);
+    count_rejection(method, scope);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        Json(Response::from(error)),
+    )
+        .into_response()
+}
+
+/// Limiter error for one request in a batch; the other requests still run.
+pub fn batch_item_rejection(method: &'static str, error: Error) -> Response {
+    count_rejection(method, rejection_scope(&error).0);
+    error.into()
+}
+
+/// Error for a batch carrying more than `max_batch_size` requests.
+pub fn batch_too_large(len: usize, max_batch_size: usize) -> Response {
+    count_rejection("batch", "batch_too_large");
+
+    Response::error(
+        ErrorCode::PayloadTooLarge,
+        format!(
+            "batch of {len} requests exceeds the limit of {max_batch_size}"
+        ),
+    )
+}
+
+/// Error for a body over `max_body_bytes`, answered with `413 Payload Too
+/// Large`.
+pub fn payload_too_large(max_body_bytes: usize) -> Response {
+    count_rejection("unknown", "body_too_large");
+
+    Response::error(
+        ErrorCode::PayloadTooLarge,
+        format!("request body exceeds {max_body_bytes} bytes"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, time::Duration};
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn limiter(per_ip: u32, per_method: &[(&'static str, u32)]) -> RpcLimiter {
+        RpcLimiter {
+            per_ip: Rate::new(per_ip),
+            per_method: per_method
+                .iter()
+                .filter_map(|(m, r)| Some((*m, Rate::new(*r)?)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn scope_of(error: Error) -> (String, u64) {
+        match error {
+            Error::RateLimited {
+                scope,
+                retry_after_secs,
+            } => (scope, retry_after_secs),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn client_bucket_allows_burst_then_refills() {
+        let limiter = limiter(2, &[]);
+        let start = Instant::now();
+
+        for _ in 0..4 {
+            limiter.take_tokens(CLIENT, "refresh", start).unwrap();
+        }
+        let err = limiter.take_tokens(CLIENT, "refresh", start).unwrap_err();
+        assert_eq!(scope_of(err), ("ip".to_string(), 1));
+
+        // Other clients have their own bucket
+        limiter.take_tokens(OTHER, "refresh", start).unwrap();
+
+        let later = start + Duration::from_millis(500);
+        limiter.take_tokens(CLIENT, "refresh", later).unwrap();
+    }
+
+    #[test]
+    fn method_bucket_is_checked_without_spending_client_tokens() {
+        let limiter = limiter(1, &[("withdraw", 1)]);
+        let start = Instant::now();
+
+        limiter.take_tokens(CLIENT, "withdraw", start).unwrap();
+        limiter.take_tokens(CLIENT, "withdraw", start).unwrap();
+        let err = limiter.take_tokens(CLIENT, "withdraw", start).unwrap_err();
+        assert_eq!(scope_of(err).0, "ip");
+
+        let limiter = self::limiter(10, &[("withdraw", 1)]);
+        limiter.take_tokens(CLIENT, "withdraw", start).unwrap();
+        limiter.take_tokens(CLIENT, "withdraw", start).unwrap();
+        let err = limiter.take_tokens(CLIENT, "withdraw", start).unwrap_err();
+        assert_eq!(scope_of(err).0, "method");
+
+        // A denied method request leaves the client bucket untouched
+        for _ in 0..18 {
+            limiter.take_tokens(CLIENT, "refresh", start).unwrap();
+        }
+    }
+
+    #[test]
+    fn expensive_methods_share_a_concurrency_cap() {
+        let limiter = RpcLimiter {
+            expensive: Some(Arc::new(Semaphore::new(1))),
+            ..Default::default()
+        };
+
+        let permit = limiter.check(CLIENT, "deposit").unwrap();
+        assert!(permit.is_some());
+        let err = limiter.check(OTHER, "withdraw").unwrap_err();
+        assert_eq!(scope_of(err), ("concurrency".to_string(), 1));
+        assert!(limiter.check(OTHER, "refresh").unwrap().is_none());
+
+        drop(permit);
+        assert!(limiter.check(OTHER, "withdraw").unwrap().is_some());
+    }
+
+    #[test]
+    fn event_streams_share_a_cap() {
+        let limiter = RpcLimiter {
+            streams: Some(Arc::new(Semaphore::new(1))),
+            ..Default::default()
+        };
+
+        let stream = limiter.open_stream().unwrap();
+        assert!(stream.is_some());
+        let err = limiter.open_stream().unwrap_err();
+        assert_eq!(scope_of(err), ("streams".to_string(), 1));
+
+        drop(stream);
+        assert!(limiter.open_stream().unwrap().is_some());
+        assert!(RpcLimiter::default().open_stream().unwrap().is_none());
+    }
+
+    #[test]
+    fn sweeps_drop_refilled_buckets() {
+        let limiter = limiter(1, &[]);
+        let start = Instant::now();
+        for i in 0..1_000u32 {
+            let ip = IpAddr::V4(Ipv4Addr::from(i));
+            limiter.take_tokens(ip, "refresh", start).unwrap();
+        }
+
+        let later = start + Duration::from_secs(10);
+        limiter.take_tokens(CLIENT, "refresh", later).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1_001);
+
+        limiter.sweep(later);
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn trusted_proxies_name_the_client() {
+        let proxy = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 9));
+        let limiter = RpcLimiter {
+            trusted_proxies: vec![proxy, OTHER],
+            ..Default::default()
+        };
+        let peer = |ip: IpAddr| ClientInfo {
+            addr: (ip, 443).into(),
+            certificates: None,
+        };
+
+        // Untrusted peers are limited by their own address, whatever they claim
+        let claimed = Some("192.0.2.7");
+        assert_eq!(limiter.client_ip(Some(&peer(CLIENT)), claimed), CLIENT);
+
+        // The rightmost address a trusted proxy did not add is the client
+        let chain = Some("198.51.100.1, 192.0.2.7, 10.0.0.2");
+        assert_eq!(
+            limiter.client_ip(Some(&peer(proxy)), chain),
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7))
+        );
+
+        // A proxy without the header, or with garbage in it, is its own client
+        assert_eq!(limiter.client_ip(Some(&peer(proxy)), None), proxy);
+        assert_eq!(
+            limiter.client_ip(Some(&peer(proxy)), Some("unknown")),
+            proxy
+        );
+        assert_eq!(
+            limiter.client_ip(None, claimed),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+    }
+}
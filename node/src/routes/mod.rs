@@ -1,34 +1,64 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use axum::{
     Json, Router,
-    extract::State,
+    extract::{
+        ConnectInfo, DefaultBodyLimit, FromRequestParts, State,
+        rejection::JsonRejection,
+    },
+    http::{HeaderValue, StatusCode, request::Parts},
+    response::{IntoResponse, Response as HttpResponse},
     routing::{get, post},
 };
 use color_eyre::eyre::Result;
 use mugraph_core::{
-    error::Error,
-    types::{Keypair, Request, Response},
+    error::{Error, ErrorCode},
+    types::{
+        Feature, Keypair, PROTOCOL_VERSION_HEADER, ProtocolInfo,
+        ProtocolVersion, PublicKey, Request, Response,
+    },
 };
 
+mod batch;
 mod cross_node;
 mod deposit;
+mod events;
+mod health;
+mod limits;
+mod node_info;
+mod openapi;
 mod refresh;
+mod rest;
 mod withdraw;
 
+pub use batch::*;
 pub use cross_node::*;
 pub use deposit::*;
+pub use events::*;
+pub use health::*;
+pub use limits::*;
+pub use node_info::*;
+pub use openapi::*;
 pub use refresh::*;
 pub use withdraw::*;
 
 use crate::{
     cardano::setup_cardano_wallet,
+    chain_index::ChainIndex,
     config::Config,
     database::{CARDANO_WALLET, Database},
     deposit_monitor::{DepositMonitor, DepositMonitorConfig},
     peer_registry::PeerRegistry,
-    provider::Provider,
+    provider::{
+        DEFAULT_LEDGER, EMULATOR_PROVIDER, EmulatorProvider, Provider,
+        RequestPriority,
+    },
     reconciler::{RetryPolicy, reconciler_loop},
+    spent_archive::archiver_loop,
+    supervisor::Supervisor,
+    telemetry::{install_recorder, metrics_router},
+    tls::ClientInfo,
+    withdrawal_monitor::{WithdrawalMonitor, WithdrawalMonitorConfig},
 };
 
 #[derive(Clone)]
@@ -37,6 +67,8 @@ pub struct Context {
     database: Arc<Database>,
     config: Config,
     peer_registry: Option<Arc<PeerRegistry>>,
+    health: Arc<HealthState>,
+    limits: Arc<RpcLimiter>,
 }
 
 fn default_database_path() -> std::path::PathBuf {
@@ -60,6 +92,47 @@ fn default_database_path() -> std::path::PathBuf {
 }
 
 pub async fn router(config: Config, keypair: Keypair) -> Result<Router, Error> {
+    router_with(config, keypair, Arc::new(Supervisor::new())).await
+}
+
+/// Build the router, running its background workers under `supervisor`.
+///
+/// The caller owns shutdown: [`Supervisor::shutdown`] stops the workers and
+/// flushes the database.
+pub async fn router_with(
+    config: Config,
+    keypair: Keypair,
+    supervisor: Arc<Supervisor>,
+) -> Result<Router, Error> {
+    let emulated = config.provider_type() == EMULATOR_PROVIDER
+        || config
+            .provider_backends()
+            .iter()
+            .any(|backend| backend.provider == EMULATOR_PROVIDER);
+    if emulated && !config.dev_mode() {
+        return Err(Error::InvalidInput {
+            reason: "the emulator provider is only available in dev mode"
+                .to_string(),
+        });
+    }
+
+    let backends = config.provider_backends().len() + 1;
+    let quorum = config.provider_quorum();
+    if quorum == 0 || quorum > backends {
+        return Err(Error::InvalidInput {
+            reason: format!(
+                "cardano quorum {quorum} must be between 1 and the {backends} configured provider backends"
+            ),
+        });
+    }
+
+    if config.provider_cache().budget_per_minute == Some(0) {
+        return Err(Error::InvalidInput {
+            reason: "cardano request budget must be at least 1 per minute"
+                .to_string(),
+        });
+    }
+
     let database = Arc::new(Database::setup(default_database_path())?);
 
     // Run database migrations
@@ -79,32 +152,117 @@ pub async fn router(config: Config, keypair: Keypair) -> Result<Router, Error> {
         None
     };
 
-    if config.dev_mode() {
+    {
+        let database = database.clone();
+        supervisor.on_shutdown("database_flush", move || database.flush());
+    }
+
+    if let Some(max_age) = config.spent_archive_after_secs() {
+        start_spent_archiver(
+            &supervisor,
+            database.clone(),
+            config.spent_archive_interval_secs(),
+            max_age,
+            keypair.public_key,
+        );
+    }
+
+    if config.event_retention_secs() > 0 {
+        start_event_pruner(
+            &supervisor,
+            database.clone(),
+            config.event_retention_secs(),
+        );
+    }
+
+    let health_state = Arc::new(HealthState {
+        supervisor: supervisor.clone(),
+        ..Default::default()
+    });
+
+    if config.dev_mode() && !config.emulated_ledger() {
         tracing::warn!(
             "dev mode enabled — skipping Cardano wallet, deposit monitor, and reconciler"
         );
     } else {
+        if config.emulated_ledger() {
+            tracing::warn!(
+                "dev mode enabled — Cardano calls go to the in-process ledger emulator"
+            );
+            start_emulator_blocks(&supervisor, &config);
+        }
+
         // Initialize Cardano wallet on startup
         initialize_cardano_wallet(&config, &database).await?;
 
+        // Follow the script address so routes can read it locally
+        start_chain_index(&supervisor, &config, database.clone())?;
+
         // Start deposit monitor background task
-        start_deposit_monitor(&config, database.clone())?;
+        start_deposit_monitor(
+            &supervisor,
+            &config,
+            database.clone(),
+            health_state.deposit_monitor.clone(),
+        )?;
+
+        // Settle withdrawals whose requests never finished
+        start_withdrawal_monitor(
+            &supervisor,
+            &config,
+            database.clone(),
+            keypair,
+        )?;
 
         // Start cross-node reconciler worker for retry/recovery convergence
-        start_cross_node_reconciler(database.clone())?;
+        start_cross_node_reconciler(
+            &supervisor,
+            database.clone(),
+            health_state.reconciler.clone(),
+        )?;
     }
 
-    let router = Router::new()
+    let metrics = install_recorder()?;
+    let serve_metrics_here = config.metrics_addr().is_none();
+
+    let max_body_bytes = config.rpc_max_body_bytes();
+    let limits = Arc::new(RpcLimiter::from_config(&config));
+    limits.record_limits(max_body_bytes);
+    if limits.limits_rates() {
+        let limits = limits.clone();
+        supervisor.spawn("rate_limit_sweeper", move |shutdown| {
+            limits::sweep_loop(limits.clone(), shutdown)
+        });
+    }
+
+    let mut router = Router::new()
         .layer(tower_http::trace::TraceLayer::new_for_http())
         .route("/health", get(health))
-        .route("/rpc", post(rpc))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route(
+            "/rpc",
+            post(rpc_endpoint).layer(DefaultBodyLimit::max(max_body_bytes)),
+        )
+        .merge(rest::router().layer(DefaultBodyLimit::max(max_body_bytes)))
+        .route("/v1/events", get(events_page))
+        .route("/v1/events/stream", get(events_stream))
+        .route("/v1/openapi.json", get(openapi_json))
+        .route(WELL_KNOWN_NODE_INFO, get(well_known_node_info))
         .with_state(Context {
             database,
             keypair,
             config,
             peer_registry,
+            health: health_state,
+            limits,
         });
 
+    // Without a dedicated admin listener, /metrics rides on the main one
+    if serve_metrics_here {
+        router = router.merge(metrics_router(metrics));
+    }
+
     Ok(router)
 }
 
@@ -153,21 +311,69 @@ async fn initialize_cardano_wallet(
     Ok(())
 }
 
+/// Produce blocks on the emulated ledger at the configured interval
+fn start_emulator_blocks(supervisor: &Supervisor, config: &Config) {
+    let secs = config.emulator_block_secs();
+    if secs == 0 {
+        return;
+    }
+
+    let emulator = EmulatorProvider::shared(
+        config.provider_url().as_deref().unwrap_or(DEFAULT_LEDGER),
+        config.network(),
+    );
+    supervisor.spawn("emulator_blocks", move |shutdown| {
+        emulator
+            .clone()
+            .run(std::time::Duration::from_secs(secs), shutdown)
+    });
+}
+
+/// Start the chain index background task, unless it is disabled
+fn start_chain_index(
+    supervisor: &Supervisor,
+    config: &Config,
+    database: Arc<Database>,
+) -> Result<(), Error> {
+    let secs = config.index_poll_secs();
+    if secs == 0 {
+        return Ok(());
+    }
+
+    let provider =
+        Provider::from_config(config).map_err(|e| Error::Internal {
+            reason: format!("Failed to create provider for chain index: {}", e),
+        })?;
+    let index = ChainIndex::new(
+        database,
+        provider.with_priority(RequestPriority::Background),
+        std::time::Duration::from_secs(secs),
+    );
+
+    supervisor
+        .spawn("chain_index", move |shutdown| index.clone().run(shutdown));
+
+    Ok(())
+}
+
 /// Start the deposit monitor background task
 fn start_deposit_monitor(
+    supervisor: &Supervisor,
     config: &Config,
     database: Arc<Database>,
+    heartbeat: Arc<Heartbeat>,
 ) -> Result<(), Error> {
-    // Create provider for the monitor using config
-    let provider = Provider::new(
-        &config.provider_type(),
-        config.provider_api_key(),
-        config.network(),
-        config.provider_url(),
-    )
-    .map_err(|e| Error::Internal {
-        reason: format!("Failed to create provider for deposit monitor: {}", e),
-    })?;
+    // Revalidation can wait for the next pass, so it is shed before
+    // deposit claims when the request budget runs low
+    let provider = Provider::from_config(config)
+        .map_err(|e| Error::Internal {
+            reason: format!(
+                "Failed to create provider for deposit monitor: {}",
+                e
+            ),
+        })?
+        .with_priority(RequestPriority::Background)
+        .indexed(database.clone());
 
     // Create monitor configuration from config
     let monitor_config = DepositMonitorConfig {
@@ -178,11 +384,11 @@ fn start_deposit_monitor(
     };
 
     // Create and start monitor
-    let monitor = DepositMonitor::new(monitor_config, database, provider);
+    let monitor = DepositMonitor::new(monitor_config, database, provider)
+        .with_heartbeat(heartbeat);
 
-    // Spawn the monitor as a background task
-    tokio::spawn(async move {
-        monitor.start().await;
+    supervisor.spawn("deposit_monitor", move |shutdown| {
+        monitor.clone().run(shutdown)
     });
 
     tracing::info!("Deposit monitor started in background");
@@ -190,35 +396,343 @@ fn start_deposit_monitor(
     Ok(())
 }
 
-fn start_cross_node_reconciler(database: Arc<Database>) -> Result<(), Error> {
-    tokio::spawn(async move {
+/// Start the withdrawal monitor background task, unless it is disabled
+fn start_withdrawal_monitor(
+    supervisor: &Supervisor,
+    config: &Config,
+    database: Arc<Database>,
+    keypair: Keypair,
+) -> Result<(), Error> {
+    let secs = config.withdrawal_reconcile_secs();
+    if secs == 0 {
+        return Ok(());
+    }
+
+    let provider = Provider::from_config(config)
+        .map_err(|e| Error::Internal {
+            reason: format!(
+                "Failed to create provider for withdrawal monitor: {}",
+                e
+            ),
+        })?
+        .with_priority(RequestPriority::Background);
+    let monitor = WithdrawalMonitor::new(
+        WithdrawalMonitorConfig {
+            network_byte: config.network_byte(),
+            interval: secs,
+            confirm_depth: config.deposit_confirm_depth(),
+            ..Default::default()
+        },
+        database,
+        provider,
+        keypair,
+    );
+
+    supervisor.spawn("withdrawal_monitor", move |shutdown| {
+        monitor.clone().run(shutdown)
+    });
+
+    tracing::info!("Withdrawal monitor started in background");
+
+    Ok(())
+}
+
+fn start_cross_node_reconciler(
+    supervisor: &Supervisor,
+    database: Arc<Database>,
+    heartbeat: Arc<Heartbeat>,
+) -> Result<(), Error> {
+    supervisor.spawn("reconciler", move |shutdown| {
         reconciler_loop(
-            database,
+            database.clone(),
             std::time::Duration::from_secs(5),
             RetryPolicy::default(),
+            heartbeat.clone(),
+            shutdown,
         )
-        .await;
     });
 
     tracing::info!("Cross-node reconciler started in background");
     Ok(())
 }
 
+fn start_spent_archiver(
+    supervisor: &Supervisor,
+    database: Arc<Database>,
+    interval_secs: u64,
+    max_age_secs: u64,
+    active_keyset: PublicKey,
+) {
+    supervisor.spawn("spent_archiver", move |shutdown| {
+        archiver_loop(
+            database.clone(),
+            std::time::Duration::from_secs(interval_secs),
+            std::time::Duration::from_secs(max_age_secs),
+            active_keyset,
+            shutdown,
+        )
+    });
+
+    tracing::info!(
+        interval_secs,
+        max_age_secs,
+        "Spent-set archiver started in background"
+    );
+}
+
+fn start_event_pruner(
+    supervisor: &Supervisor,
+    database: Arc<Database>,
+    retention_secs: u64,
+) {
+    supervisor.spawn("event_pruner", move |shutdown| {
+        events::prune_loop(
+            database.clone(),
+            std::time::Duration::from_secs(3600),
+            std::time::Duration::from_secs(retention_secs),
+            shutdown,
+        )
+    });
+
+    tracing::info!(retention_secs, "Event log pruner started in background");
+}
+
 pub async fn health() -> &'static str {
     "OK"
 }
 
-#[tracing::instrument(skip_all)]
+/// Wire name of every request variant, as returned by `request_method`
+pub const RPC_METHODS: &[&str] = &[
+    "refresh",
+    "emit",
+    "public_key",
+    "deposit",
+    "withdraw",
+    "cross_node_transfer_create",
+    "cross_node_transfer_notify",
+    "cross_node_transfer_status",
+    "cross_node_transfer_ack",
+    "batch",
+    "node_info",
+    "withdrawal_status",
+];
+
+/// Wire name of a request, used as the `method` metrics label
+fn request_method(request: &Request) -> &'static str {
+    match request {
+        Request::Refresh(_) => "refresh",
+        Request::Emit { .. } => "emit",
+        Request::Info => "public_key",
+        Request::Deposit(_) => "deposit",
+        Request::Withdraw(_) => "withdraw",
+        Request::CrossNodeTransferCreate(_) => "cross_node_transfer_create",
+        Request::CrossNodeTransferNotify(_) => "cross_node_transfer_notify",
+        Request::CrossNodeTransferStatus(_) => "cross_node_transfer_status",
+        Request::CrossNodeTransferAck(_) => "cross_node_transfer_ack",
+        Request::Batch(_) => "batch",
+        Request::NodeInfo => "node_info",
+        Request::WithdrawalStatus(_) => "withdrawal_status",
+    }
+}
+
+/// `/rpc` entry point: applies the body size, rate and concurrency limits
+/// before handing the request to [`rpc`].
+pub async fn rpc_endpoint(
+    State(ctx): State<Context>,
+    caller: Caller,
+    payload: Result<Json<Request>, JsonRejection>,
+) -> HttpResponse {
+    let request = match payload {
+        Ok(Json(request)) => request,
+        Err(rejection)
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+        {
+            return rpc_response(payload_too_large(
+                ctx.config.rpc_max_body_bytes(),
+            ));
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    handle_request(ctx, caller, request).await
+}
+
+/// Header in which reverse proxies list the addresses they forward for
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Peer of a request, the clients it forwarded for if it is a proxy, and
+/// the protocol version it declared in [`PROTOCOL_VERSION_HEADER`].
+pub struct Caller {
+    client: Option<ClientInfo>,
+    forwarded_for: Option<String>,
+    version: Option<ProtocolVersion>,
+}
+
+impl Caller {
+    /// Address the caller is rate limited under
+    fn ip(&self, ctx: &Context) -> IpAddr {
+        ctx.limits
+            .client_ip(self.client.as_ref(), self.forwarded_for.as_deref())
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = HttpResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let client = parts
+            .extensions
+            .get::<ConnectInfo<ClientInfo>>()
+            .map(|ConnectInfo(client)| client.clone());
+        let forwarded_for = parts
+            .headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .reduce(|all, value| format!("{all},{value}").into())
+            .map(|all| all.into_owned());
+
+        let version = match parts.headers.get(PROTOCOL_VERSION_HEADER) {
+            Some(value) => {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                match value.parse() {
+                    Ok(version) => Some(version),
+                    Err(e) => return Err(rpc_response(Response::from(e))),
+                }
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            client,
+            forwarded_for,
+            version,
+        })
+    }
+}
+
+/// Shared by `/rpc` and the `/v1` routes: rate and concurrency limits, the
+/// xnode client certificate check and version negotiation, then [`rpc`].
+/// Answers carry the negotiated version in [`PROTOCOL_VERSION_HEADER`].
+async fn handle_request(
+    ctx: Context,
+    caller: Caller,
+    request: Request,
+) -> HttpResponse {
+    let method = request_method(&request);
+    let ip = caller.ip(&ctx);
+    let Caller {
+        client, version, ..
+    } = caller;
+    let _permit = match ctx.limits.check(ip, method) {
+        Ok(permit) => permit,
+        Err(e) => return rejection_response(method, e),
+    };
+
+    if ctx.config.xnode_client_ca_file().is_some()
+        && let Err(e) = require_peer_certificate(&request, client.as_ref())
+    {
+        return rpc_response(e.into());
+    }
+
+    let declared = version;
+    let version = match ProtocolVersion::negotiate(declared, &request) {
+        Ok(version) => version,
+        Err(e) => return rpc_response(e.into()),
+    };
+
+    // Batch items are limited and checked one by one against the caller and
+    // the version it declared
+    let Json(response) = match request {
+        Request::Batch(batch) => {
+            observe(method, async {
+                Json(
+                    run_batch(&ctx, ip, client.as_ref(), declared, batch).await,
+                )
+            })
+            .await
+        }
+        request => rpc(State(ctx), Json(request)).await,
+    };
+
+    let mut response = rpc_response(response);
+    response.headers_mut().insert(
+        PROTOCOL_VERSION_HEADER,
+        HeaderValue::from_str(&version.to_string())
+            .expect("versions are ASCII"),
+    );
+    response
+}
+
+/// Versions and features this node serves; batches are left out when
+/// disabled.
+fn protocol_info(config: &Config) -> ProtocolInfo {
+    let mut protocol = ProtocolInfo::current();
+    if config.rpc_max_batch_size() == 0 {
+        protocol
+            .features
+            .retain(|feature| *feature != Feature::Batch);
+    }
+    protocol
+}
+
+/// Serialise `response`, answering errors with the HTTP status their code
+/// maps to.
+pub fn rpc_response(response: Response) -> HttpResponse {
+    let status = match &response {
+        Response::Error { code, .. } => {
+            StatusCode::from_u16(code.http_status())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        _ => StatusCode::OK,
+    };
+    (status, Json(response)).into_response()
+}
+
 pub async fn rpc(
     State(ctx): State<Context>,
     Json(request): Json<Request>,
 ) -> Json<Response> {
+    let method = request_method(&request);
+    observe(method, dispatch(ctx, request)).await
+}
+
+/// Run a handler inside the request span, recording its latency and
+/// outcome.
+#[tracing::instrument(skip_all, fields(method))]
+async fn observe(
+    method: &'static str,
+    handler: impl Future<Output = Json<Response>>,
+) -> Json<Response> {
+    tracing::Span::current().record("method", method);
+    let started = std::time::Instant::now();
+
+    let response = handler.await;
+
+    let outcome = match &response.0 {
+        Response::Error { .. } => "error",
+        _ => "ok",
+    };
+    metrics::histogram!("mugraph.node.rpc.duration_seconds", "method" => method)
+        .record(started.elapsed().as_secs_f64());
+    metrics::counter!(
+        "mugraph.node.rpc.requests_total",
+        "method" => method,
+        "outcome" => outcome,
+    )
+    .increment(1);
+
+    response
+}
+
+async fn dispatch(ctx: Context, request: Request) -> Json<Response> {
     match request {
         Request::Refresh(t) => match refresh(&t, ctx.keypair, &ctx.database) {
             Ok(response) => Json(response),
-            Err(e) => Json(Response::Error {
-                reason: e.to_string(),
-            }),
+            Err(e) => Json(e.into()),
         },
         Request::Info => {
             // Load cardano script address if available
@@ -227,17 +741,23 @@ pub async fn rpc(
             Json(Response::Info {
                 delegate_pk: ctx.keypair.public_key,
                 cardano_script_address: script_address,
+                protocol: protocol_info(&ctx.config),
             })
         }
+        Request::NodeInfo => match signed_node_info(&ctx) {
+            Ok(info) => Json(Response::NodeInfo(Box::new(info))),
+            Err(e) => Json(e.into()),
+        },
         Request::Emit {
             policy_id,
             asset_name,
             amount,
         } => {
             if !ctx.config.dev_mode() {
-                return Json(Response::Error {
-                    reason: "Emit is only available in dev mode".to_string(),
-                });
+                return Json(Response::error(
+                    ErrorCode::MethodDisabled,
+                    "Emit is only available in dev mode",
+                ));
             }
             let mut rng = rand::rng();
             match emit_note(
@@ -248,59 +768,55 @@ pub async fn rpc(
                 &mut rng,
             ) {
                 Ok(note) => Json(Response::Emit(Box::new(note))),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
         Request::Deposit(deposit_request) => {
             match deposit::handle_deposit(&deposit_request, &ctx).await {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
         Request::Withdraw(withdraw_request) => {
             match withdraw::handle_withdraw(&withdraw_request, &ctx).await {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
+            }
+        }
+        Request::WithdrawalStatus(request) => {
+            match withdraw::handle_withdrawal_status(&request, &ctx) {
+                Ok(response) => Json(response),
+                Err(e) => Json(e.into()),
             }
         }
         Request::CrossNodeTransferCreate(request) => {
             match cross_node::handle_create(&request, &ctx) {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
         Request::CrossNodeTransferNotify(request) => {
             match cross_node::handle_notify(&request, &ctx) {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
         Request::CrossNodeTransferStatus(request) => {
             match cross_node::handle_status(&request, &ctx) {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
         Request::CrossNodeTransferAck(request) => {
             match cross_node::handle_ack(&request, &ctx) {
                 Ok(response) => Json(response),
-                Err(e) => Json(Response::Error {
-                    reason: e.to_string(),
-                }),
+                Err(e) => Json(e.into()),
             }
         }
+        Request::Batch(batch) => {
+            let ip = ctx.limits.client_ip(None, None);
+            Json(run_batch(&ctx, ip, None, None, batch).await)
+        }
     }
 }
 
@@ -336,48 +852,30 @@ mod tests {
 
     use super::*;
 
-    fn test_config(xnode_peer_registry_file: Option<String>) -> Config {
-        Config::Server {
-            addr: "127.0.0.1:9999".parse().unwrap(),
-            seed: Some(42),
-            secret_key: None,
-            cardano_network: "preprod".to_string(),
-            cardano_provider: "blockfrost".to_string(),
-            cardano_api_key: Some("test".to_string()),
-            cardano_provider_url: None,
-            cardano_payment_sk: None,
+    fn test_config(peer_registry_file: Option<String>) -> Config {
+        let mut config = Config::test_server();
+        if let Config::Server {
+            cardano_api_key,
             xnode_peer_registry_file,
-            xnode_node_id: "node://b".to_string(),
-            deposit_confirm_depth: 15,
-            deposit_expiration_blocks: 1440,
-            min_deposit_value: Some(1_000_000),
-            max_tx_size: 16384,
-            max_withdrawal_fee: 2_000_000,
-            fee_tolerance_pct: 5,
-            dev_mode: false,
+            xnode_node_id,
+            dev_mode,
+            ..
+        } = &mut config
+        {
+            *cardano_api_key = Some("test".to_string());
+            *xnode_peer_registry_file = peer_registry_file;
+            *xnode_node_id = "node://b".to_string();
+            *dev_mode = false;
         }
+        config
     }
 
     fn unseeded_dev_config() -> Config {
-        Config::Server {
-            addr: "127.0.0.1:9999".parse().unwrap(),
-            seed: None,
-            secret_key: None,
-            cardano_network: "preprod".to_string(),
-            cardano_provider: "blockfrost".to_string(),
-            cardano_api_key: None,
-            cardano_provider_url: None,
-            cardano_payment_sk: None,
-            xnode_peer_registry_file: None,
-            xnode_node_id: "node://local".to_string(),
-            deposit_confirm_depth: 15,
-            deposit_expiration_blocks: 1440,
-            min_deposit_value: Some(1_000_000),
-            max_tx_size: 16384,
-            max_withdrawal_fee: 2_000_000,
-            fee_tolerance_pct: 5,
-            dev_mode: true,
+        let mut config = Config::test_server();
+        if let Config::Server { seed, .. } = &mut config {
+            *seed = None;
         }
+        config
     }
 
     fn write_registry(pk: &SigningKey) -> String {
@@ -415,7 +913,7 @@ mod tests {
         env
     }
 
-    fn test_context() -> Context {
+    pub(super) fn test_context() -> Context {
         let db_path = std::env::temp_dir().join(format!(
             "mugraph-rpc-test-{}.db",
             std::time::SystemTime::now()
@@ -439,6 +937,8 @@ mod tests {
             database,
             config,
             peer_registry,
+            health: Default::default(),
+            limits: Default::default(),
         }
     }
 
@@ -477,6 +977,7 @@ mod tests {
             Response::Info {
                 delegate_pk,
                 cardano_script_address,
+                ..
             } => {
                 assert_eq!(delegate_pk, expected_delegate_pk);
                 assert_eq!(cardano_script_address, None);
@@ -526,6 +1027,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: now_rfc3339_offset(0),
             expires_at: Some(now_rfc3339_offset(120)),
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Confirmed,
                 tx_hash: "abcd".to_string(),
@@ -564,6 +1066,7 @@ mod tests {
             destination_node_id: "node://b".to_string(),
             sent_at: "2026-02-26T18:00:00Z".to_string(),
             expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Confirmed,
                 tx_hash: "abcd".to_string(),
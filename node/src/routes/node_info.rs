@@ -0,0 +1,174 @@
+//! Signed node information document, answered by the `node_info` method
+//! and served bare at [`WELL_KNOWN_NODE_INFO`].
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response as HttpResponse},
+};
+use mugraph_core::{
+    error::Error,
+    types::{
+        CardanoInfo, DepositTerms, Keypair, Keyset, NodeInfoDocument, PeerInfo,
+        RpcLimits, SignedNodeInfo, WithdrawalTerms,
+    },
+};
+
+use super::{Caller, Context, protocol_info, rejection_response, rpc_response};
+use crate::{
+    config::Config,
+    database::{CARDANO_WALLET, Database},
+    peer_registry::PeerRegistry,
+};
+
+pub const WELL_KNOWN_NODE_INFO: &str = "/.well-known/mugraph-node.json";
+
+/// Rate-limit bucket shared with the `node_info` method.
+const NODE_INFO_METHOD: &str = "node_info";
+
+/// Assemble the document from `config`, the Cardano wallet row and the
+/// peer registry, valid from `now` for the configured TTL.
+pub fn node_info_document(
+    keypair: &Keypair,
+    database: &Database,
+    config: &Config,
+    peer_registry: Option<&PeerRegistry>,
+    now: u64,
+) -> Result<NodeInfoDocument, Error> {
+    let wallet = {
+        let read_tx = database.read()?;
+        let table = read_tx.open_table(CARDANO_WALLET)?;
+        table.get("wallet")?.map(|wallet| wallet.value())
+    };
+
+    let peers = peer_registry
+        .map(|registry| {
+            registry
+                .peers
+                .iter()
+                .filter(|peer| !peer.revoked)
+                .map(|peer| PeerInfo {
+                    node_id: peer.node_id.clone(),
+                    endpoint: peer.endpoint.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(NodeInfoDocument {
+        node_id: config.xnode_node_id(),
+        delegate_pk: keypair.public_key,
+        keysets: vec![Keyset {
+            public_key: keypair.public_key,
+            active: true,
+        }],
+        protocol: protocol_info(config),
+        cardano: CardanoInfo {
+            network: config.network(),
+            script_address: wallet
+                .as_ref()
+                .map(|wallet| wallet.script_address.clone()),
+            script_hash: wallet
+                .as_ref()
+                .map(|wallet| hex::encode(&wallet.script_hash)),
+        },
+        deposits: DepositTerms {
+            min_value: config.min_deposit_value(),
+            confirm_depth: config.deposit_confirm_depth(),
+            expiration_blocks: config.deposit_expiration_blocks(),
+            native_assets: true,
+        },
+        withdrawals: WithdrawalTerms {
+            max_fee: config.max_withdrawal_fee(),
+            fee_tolerance_pct: config.fee_tolerance_pct(),
+            max_tx_size: config.max_tx_size() as u64,
+        },
+        limits: RpcLimits {
+            max_body_bytes: config.rpc_max_body_bytes() as u64,
+            rate_limit: config.rpc_rate_limit(),
+            method_rate_limits: config
+                .rpc_method_rate_limits()
+                .into_iter()
+                .map(|limit| (limit.method, limit.per_sec))
+                .collect(),
+            max_batch_size: config.rpc_max_batch_size() as u64,
+        },
+        peers,
+        contact: config.operator_contact(),
+        issued_at: now,
+        expires_at: now.saturating_add(config.node_info_ttl_secs()),
+    })
+}
+
+/// The current document, freshly signed with the delegate key.
+pub fn signed_node_info(ctx: &Context) -> Result<SignedNodeInfo, Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Internal {
+            reason: e.to_string(),
+        })?
+        .as_secs();
+    let document = node_info_document(
+        &ctx.keypair,
+        &ctx.database,
+        &ctx.config,
+        ctx.peer_registry.as_deref(),
+        now,
+    )?;
+
+    SignedNodeInfo::sign(&mut rand::rng(), &ctx.keypair, document)
+}
+
+/// `GET /.well-known/mugraph-node.json`: the signed document without the
+/// RPC envelope, cacheable until it expires.
+pub async fn well_known_node_info(
+    State(ctx): State<Context>,
+    caller: Caller,
+) -> HttpResponse {
+    if let Err(e) = ctx.limits.check(caller.ip(&ctx), NODE_INFO_METHOD) {
+        return rejection_response(NODE_INFO_METHOD, e);
+    }
+
+    match signed_node_info(&ctx) {
+        Ok(info) => (
+            [(
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", ctx.config.node_info_ttl_secs()),
+            )],
+            Json(info),
+        )
+            .into_response(),
+        Err(e) => rpc_response(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::tests::test_context;
+
+    #[test]
+    fn document_is_signed_and_reflects_the_config() {
+        let ctx = test_context();
+        let info = signed_node_info(&ctx).unwrap();
+
+        let document = info.document().unwrap();
+        assert_eq!(document.node_id, ctx.config.xnode_node_id());
+        assert_eq!(document.cardano.network, ctx.config.network());
+        assert_eq!(
+            document.expires_at - document.issued_at,
+            ctx.config.node_info_ttl_secs()
+        );
+        assert_eq!(
+            document.limits.max_batch_size,
+            ctx.config.rpc_max_batch_size() as u64
+        );
+        assert!(document.keysets.iter().all(|keyset| keyset.active));
+        assert!(!document.peers.is_empty());
+        info.verify(&ctx.keypair.public_key, document.issued_at)
+            .unwrap();
+    }
+}
@@ -0,0 +1,449 @@
+//! OpenAPI description of the HTTP surface, generated from the request and
+//! response types so it cannot fall behind them.
+
+use std::sync::OnceLock;
+
+use axum::{Json, http::Method};
+use mugraph_core::types::{
+    BatchRequest, DepositRequest, EventPage, NodeEvent, NodeInfoDocument,
+    PROTOCOL_VERSION_HEADER, ProtocolVersion, Refresh, Request, Response,
+    SignedNodeInfo, TransferAckPayload, TransferInitPayload,
+    TransferNoticePayload, TransferStatusQueryPayload, WithdrawRequest,
+    XNodeEnvelope,
+};
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use super::HealthReport;
+
+const DESCRIPTION: &str = "\
+HTTP+JSON surface for a Mugraph node. Mugraph is a Layer 2 network for \
+untraceable payments on Cardano.
+
+Every operation is available through `POST /rpc`, a tagged union where `m` \
+selects the method and `p` carries its payload, and through the \
+resource-oriented `/v1` routes. Both share the same handlers, limits and \
+response envelope: `{\"m\": \"<method>\", \"r\": {...}}` on success and \
+`{\"m\": \"error\", \"r\": {\"reason\", \"code\", \"retryable\", \"details\"}}` \
+on failure, answered with the HTTP status mapped from `code`.
+
+A `batch` request carries several requests and is answered with one \
+response per request, in order, each rate limited and version checked on \
+its own. With \
+`atomic` set, a batch of refreshes commits all of them or none: when one \
+fails, the others answer `BATCH_ABORTED`.
+
+Clients declare the protocol version they speak in the \
+`mugraph-protocol-version` header. The node refuses other majors, and \
+requests using features newer than the declared version, with \
+`UNSUPPORTED_VERSION`; it answers in the negotiated version, echoed in the \
+same header. `public_key` (`/v1/info`) reports the supported versions and \
+enabled features under `protocol`.
+
+`node_info`, also served without the envelope at \
+`/.well-known/mugraph-node.json`, describes the node in full: network, \
+deposit and withdrawal terms, limits, protocol versions, keysets, peers \
+and operator contact. It is signed with the delegate key, so wallets can \
+pin and check it offline, and expires at `expires_at`.
+
+`withdrawal_status` (`GET /v1/withdrawals/{tx_hash}`) reports whether a \
+withdrawal is `pending`, `completed`, `failed` or `refunded`. The node \
+keeps settling pending and failed withdrawals in the background: those \
+that reach the chain are completed, and those it has not seen are \
+submitted again until their TTL passes. A withdrawal that carried \
+`refund_outputs` is refunded once its TTL has passed or one of its inputs \
+was spent elsewhere, and its status then carries `refund_notes`, one per \
+burned note.
+
+`/v1/events` pages through the events of the deposits, withdrawals or \
+transfers named in its query (deposit confirmation progress, withdrawal \
+submission and cross-node transfer status) and `/v1/events/stream` \
+follows them over Server-Sent Events. Both need at least one `deposit`, \
+`withdrawal` or `transfer` filter, and the node caps how many streams are \
+open at once, answering `RATE_LIMITED` (429) past it. Each event carries \
+a `cursor`; pass the last one seen as `after`, or as `Last-Event-ID` when \
+reconnecting, to resume without gaps. Events are kept for a retention \
+window; a cursor older than it answers `CURSOR_EXPIRED` (410) with the \
+oldest kept cursor in `details.oldest_cursor`.
+
+Cross-node (`/v1/xnode`) bodies are signed envelopes and, when the node \
+requires client certificates, must be sent over mutual TLS by the origin \
+node.";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+/// Body of a request or successful response.
+#[derive(Clone, Copy)]
+pub enum Body {
+    Text,
+    Json(SchemaFn),
+    /// Server-Sent Events whose `data` fields hold this schema
+    EventStream(SchemaFn),
+}
+
+/// Optional query string parameter.
+pub struct Param {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub schema: SchemaFn,
+}
+
+/// One documented route.
+pub struct Operation {
+    pub method: Method,
+    /// Path template; `{name}` segments are string path parameters
+    pub path: &'static str,
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    pub query: &'static [Param],
+    pub request: Option<Body>,
+    pub response: Body,
+    /// Fails with an error [`Response`]
+    pub errors: bool,
+    /// Negotiates the protocol version in [`PROTOCOL_VERSION_HEADER`]
+    pub versioned: bool,
+}
+
+const fn rpc_operation(
+    method: Method,
+    path: &'static str,
+    operation_id: &'static str,
+    summary: &'static str,
+    request: Option<SchemaFn>,
+) -> Operation {
+    Operation {
+        method,
+        path,
+        operation_id,
+        summary,
+        query: &[],
+        request: match request {
+            Some(request) => Some(Body::Json(request)),
+            None => None,
+        },
+        response: Body::Json(schema::<Response>),
+        errors: true,
+        versioned: true,
+    }
+}
+
+const EVENT_QUERY: &[Param] = &[
+    Param {
+        name: "after",
+        description: "Only events after this cursor",
+        schema: schema::<u64>,
+    },
+    Param {
+        name: "deposit",
+        description: "Only events for this deposit UTxO, as `tx_hash:index`",
+        schema: schema::<String>,
+    },
+    Param {
+        name: "withdrawal",
+        description: "Only events for this withdrawal transaction hash",
+        schema: schema::<String>,
+    },
+    Param {
+        name: "transfer",
+        description: "Only events for this cross-node transfer id",
+        schema: schema::<String>,
+    },
+    Param {
+        name: "limit",
+        description: "Page size for `/v1/events`, at most 500",
+        schema: schema::<u32>,
+    },
+];
+
+/// Every route served by [`super::router_with`].
+pub const OPERATIONS: &[Operation] = &[
+    Operation {
+        method: Method::GET,
+        path: "/health",
+        operation_id: "health",
+        summary: "Plain-text liveness probe",
+        query: &[],
+        request: None,
+        response: Body::Text,
+        errors: false,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/livez",
+        operation_id: "livez",
+        summary: "Liveness with structured checks",
+        query: &[],
+        request: None,
+        response: Body::Json(schema::<HealthReport>),
+        errors: false,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/readyz",
+        operation_id: "readyz",
+        summary: "Readiness of every dependency",
+        query: &[],
+        request: None,
+        response: Body::Json(schema::<HealthReport>),
+        errors: false,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/metrics",
+        operation_id: "metrics",
+        summary: "Prometheus metrics, unless served on a separate admin listener",
+        query: &[],
+        request: None,
+        response: Body::Text,
+        errors: false,
+        versioned: false,
+    },
+    rpc_operation(
+        Method::POST,
+        "/rpc",
+        "rpc",
+        "Any operation, selected by the `m` tag",
+        Some(schema::<Request>),
+    ),
+    Operation {
+        method: Method::GET,
+        path: "/v1/openapi.json",
+        operation_id: "openapi",
+        summary: "This document",
+        query: &[],
+        request: None,
+        response: Body::Json(schema::<Value>),
+        errors: false,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/.well-known/mugraph-node.json",
+        operation_id: "well_known_node_info",
+        summary: "Node info document signed with the delegate key; cacheable \
+                  until `expires_at`",
+        query: &[],
+        request: None,
+        response: Body::Json(schema::<SignedNodeInfo>),
+        errors: true,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/v1/events",
+        operation_id: "events",
+        summary: "Page through the events of the named subjects",
+        query: EVENT_QUERY,
+        request: None,
+        response: Body::Json(schema::<EventPage>),
+        errors: true,
+        versioned: false,
+    },
+    Operation {
+        method: Method::GET,
+        path: "/v1/events/stream",
+        operation_id: "events_stream",
+        summary: "Follow the events of the named subjects; resumes from `Last-Event-ID`",
+        query: EVENT_QUERY,
+        request: None,
+        response: Body::EventStream(schema::<NodeEvent>),
+        errors: true,
+        versioned: false,
+    },
+    rpc_operation(
+        Method::GET,
+        "/v1/info",
+        "info",
+        "Delegate public key and deposit script address",
+        None,
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/refresh",
+        "refresh",
+        "Spend input notes and sign blinded outputs",
+        Some(schema::<Refresh>),
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/deposits",
+        "deposit",
+        "Claim notes for a confirmed deposit UTxO",
+        Some(schema::<DepositRequest>),
+    ),
+    rpc_operation(
+        Method::PUT,
+        "/v1/withdrawals/{tx_hash}",
+        "withdraw",
+        "Burn notes and submit the withdrawal transaction `tx_hash`",
+        Some(schema::<WithdrawRequest>),
+    ),
+    rpc_operation(
+        Method::GET,
+        "/v1/withdrawals/{tx_hash}",
+        "withdrawal_status",
+        "Status of withdrawal `tx_hash`, as the node last settled it",
+        None,
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/batch",
+        "batch",
+        "Run several requests in order, optionally committing every refresh \
+         at once",
+        Some(schema::<BatchRequest>),
+    ),
+    rpc_operation(
+        Method::PUT,
+        "/v1/xnode/transfers/{id}",
+        "xnode_transfer_create",
+        "Open cross-node transfer `id`",
+        Some(schema::<XNodeEnvelope<TransferInitPayload>>),
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/xnode/transfers/{id}/notices",
+        "xnode_transfer_notify",
+        "Report chain progress for transfer `id`",
+        Some(schema::<XNodeEnvelope<TransferNoticePayload>>),
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/xnode/transfers/{id}/status",
+        "xnode_transfer_status",
+        "Query the signed status of transfer `id`",
+        Some(schema::<XNodeEnvelope<TransferStatusQueryPayload>>),
+    ),
+    rpc_operation(
+        Method::POST,
+        "/v1/xnode/transfers/{id}/acks",
+        "xnode_transfer_ack",
+        "Acknowledge a status message for transfer `id`",
+        Some(schema::<XNodeEnvelope<TransferAckPayload>>),
+    ),
+];
+
+fn content(generator: &mut SchemaGenerator, body: Body) -> Value {
+    match body {
+        Body::Text => {
+            json!({ "text/plain": { "schema": { "type": "string" } } })
+        }
+        Body::Json(schema) => {
+            json!({ "application/json": { "schema": schema(generator) } })
+        }
+        Body::EventStream(schema) => {
+            json!({ "text/event-stream": { "schema": schema(generator) } })
+        }
+    }
+}
+
+fn operation(generator: &mut SchemaGenerator, op: &Operation) -> Value {
+    let version = op.versioned.then(|| schema::<ProtocolVersion>(generator));
+    let parameters: Vec<Value> = op
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })
+        })
+        .chain(op.query.iter().map(|param| {
+            json!({
+                "name": param.name,
+                "in": "query",
+                "required": false,
+                "description": param.description,
+                "schema": (param.schema)(generator),
+            })
+        }))
+        .chain(version.map(|schema| {
+            json!({
+                "name": PROTOCOL_VERSION_HEADER,
+                "in": "header",
+                "required": false,
+                "description": "Protocol version the client speaks, as \
+                                `major.minor`; requests without it are \
+                                served at the node's version",
+                "schema": schema,
+            })
+        }))
+        .collect();
+
+    let mut responses = Map::new();
+    responses.insert(
+        "200".to_string(),
+        json!({
+            "description": "Success",
+            "content": content(generator, op.response),
+        }),
+    );
+    if op.errors {
+        responses.insert(
+            "default".to_string(),
+            json!({
+                "description": "An `error` response; the status follows its `code`",
+                "content": content(generator, Body::Json(schema::<Response>)),
+            }),
+        );
+    }
+
+    let mut operation = json!({
+        "operationId": op.operation_id,
+        "summary": op.summary,
+        "responses": responses,
+    });
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+    if let Some(body) = op.request {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": content(generator, body),
+        });
+    }
+    operation
+}
+
+/// The OpenAPI 3.0 document for [`OPERATIONS`].
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+
+    let mut paths = Map::new();
+    for op in OPERATIONS {
+        let item = paths
+            .entry(op.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[op.method.as_str().to_ascii_lowercase()] =
+            operation(&mut generator, op);
+    }
+    // Signed node info carries its document as JSON text in this shape
+    generator.subschema_for::<NodeInfoDocument>();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Mugraph Node API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": DESCRIPTION,
+        },
+        "paths": paths,
+        "components": { "schemas": generator.take_definitions(true) },
+    })
+}
+
+/// `GET /v1/openapi.json`
+pub async fn openapi_json() -> Json<Value> {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    Json(DOCUMENT.get_or_init(document).clone())
+}
@@ -10,7 +10,9 @@ use mugraph_core::{
 use rand::{CryptoRng, RngCore};
 use redb::ReadableTable;
 
-use crate::database::{Database, NOTES};
+use crate::database::{
+    Database, NOTES, NOTES_BY_KEYSET, NOTES_SPENT_AT, Write,
+};
 
 #[inline]
 pub fn emit_note<R: RngCore + CryptoRng>(
@@ -49,6 +51,22 @@ pub fn refresh(
     transaction: &Refresh,
     keypair: Keypair,
     database: &Database,
+) -> Result<Response, Error> {
+    let w = database.write()?;
+    let response = apply_refresh(transaction, keypair, database, &w)?;
+    w.commit()?;
+    record_refresh(transaction);
+
+    Ok(response)
+}
+
+/// Spend the inputs and sign the outputs of `transaction` inside `w`,
+/// leaving the commit to the caller.
+pub(crate) fn apply_refresh(
+    transaction: &Refresh,
+    keypair: Keypair,
+    database: &Database,
+    w: &Write,
 ) -> Result<Response, Error> {
     transaction.verify()?;
 
@@ -75,10 +93,16 @@ pub fn refresh(
     let mut rng = rand::rng();
     let mut outputs = Vec::with_capacity(output_count);
     let mut output_idx = 0usize;
-    let w = database.write()?;
+    let spent_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
 
     {
         let mut table = w.open_table(NOTES)?;
+        let mut spent_at_table = w.open_table(NOTES_SPENT_AT)?;
+        let mut by_keyset_table = w.open_table(NOTES_BY_KEYSET)?;
+        let keyset = keypair.public_key.0;
 
         for (i, atom) in transaction.atoms.iter().enumerate() {
             if transaction.is_output(i) {
@@ -116,8 +140,10 @@ pub fn refresh(
                 });
             }
 
-            // Check if already spent
-            if table.get(signature)?.is_some() {
+            // Check if already spent, either live or archived
+            if table.get(signature)?.is_some()
+                || database.is_archived(&signature)?
+            {
                 return Err(Error::AlreadySpent { signature });
             }
 
@@ -131,13 +157,29 @@ pub fn refresh(
 
             // Mark as spent
             table.insert(signature, true)?;
+            spent_at_table.insert((spent_at, signature), keyset)?;
+            by_keyset_table.insert((keyset, signature), spent_at)?;
         }
     }
 
-    w.commit()?;
     Ok(Response::Transaction { outputs })
 }
 
+/// Count the atoms of a committed refresh.
+pub(crate) fn record_refresh(transaction: &Refresh) {
+    let output_count = transaction
+        .atoms
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| transaction.is_output(*i))
+        .count();
+
+    metrics::counter!("mugraph.node.refresh.inputs_total")
+        .increment((transaction.atoms.len() - output_count) as u64);
+    metrics::counter!("mugraph.node.refresh.outputs_total")
+        .increment(output_count as u64);
+}
+
 #[cfg(test)]
 mod tests {
     use mugraph_core::{
@@ -259,6 +301,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn refresh_rejects_notes_spent_before_archival() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let keypair = Keypair::random(&mut rng);
+        let note = signed_note(&keypair, 10);
+        let db = temp_db();
+
+        let refresh_tx = RefreshBuilder::new()
+            .input(note.clone())
+            .output(note.policy_id, note.asset_name, 10)
+            .build()
+            .unwrap();
+
+        refresh(&refresh_tx, keypair, &db).expect("first spend succeeds");
+        assert_eq!(
+            db.archive_spent_notes(u64::MAX, &keypair.public_key)
+                .unwrap(),
+            1
+        );
+
+        let result = refresh(&refresh_tx, keypair, &db);
+        assert!(
+            matches!(result, Err(Error::AlreadySpent { signature }) if signature == note.signature),
+            "archived note must still be rejected as spent: {result:?}"
+        );
+    }
+
     #[test]
     fn refresh_rejects_unbalanced_transaction() {
         let mut rng = StdRng::seed_from_u64(42);
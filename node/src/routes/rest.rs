@@ -0,0 +1,218 @@
+//! Resource-oriented `/v1` routes. Each one maps onto a [`Request`] and goes
+//! through the same limits and handlers as `/rpc`.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State, rejection::JsonRejection},
+    http::StatusCode,
+    response::Response as HttpResponse,
+    routing::{get, post, put},
+};
+use mugraph_core::{
+    error::ErrorCode,
+    types::{
+        BatchRequest, DepositRequest, Refresh, Request, Response,
+        TransferAckPayload, TransferInitPayload, TransferNoticePayload,
+        TransferStatusQueryPayload, WithdrawRequest, WithdrawalStatusRequest,
+        XNodeEnvelope,
+    },
+};
+
+use super::{Caller, Context, handle_request, payload_too_large, rpc_response};
+
+pub(super) fn router() -> Router<Context> {
+    Router::new()
+        .route("/v1/info", get(info))
+        .route("/v1/refresh", post(refresh))
+        .route("/v1/deposits", post(deposit))
+        .route(
+            "/v1/withdrawals/{tx_hash}",
+            put(withdraw).get(withdrawal_status),
+        )
+        .route("/v1/batch", post(batch))
+        .route("/v1/xnode/transfers/{id}", put(transfer_create))
+        .route("/v1/xnode/transfers/{id}/notices", post(transfer_notify))
+        .route("/v1/xnode/transfers/{id}/status", post(transfer_status))
+        .route("/v1/xnode/transfers/{id}/acks", post(transfer_ack))
+}
+
+/// Unwrap a JSON body, turning rejections into error responses.
+fn body<T>(
+    ctx: &Context,
+    payload: Result<Json<T>, JsonRejection>,
+) -> Result<T, Response> {
+    match payload {
+        Ok(Json(body)) => Ok(body),
+        Err(rejection)
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+        {
+            Err(payload_too_large(ctx.config.rpc_max_body_bytes()))
+        }
+        Err(rejection) => Err(Response::error(
+            ErrorCode::MalformedJson,
+            rejection.body_text(),
+        )),
+    }
+}
+
+/// Reject bodies that name a different resource than the path.
+fn require_path_match(
+    field: &str,
+    path: &str,
+    body: &str,
+) -> Result<(), Response> {
+    if path == body {
+        return Ok(());
+    }
+
+    Err(Response::error(
+        ErrorCode::InvalidInput,
+        format!(
+            "{field} in the body ({body}) does not match the path ({path})"
+        ),
+    ))
+}
+
+async fn info(State(ctx): State<Context>, caller: Caller) -> HttpResponse {
+    handle_request(ctx, caller, Request::Info).await
+}
+
+async fn refresh(
+    State(ctx): State<Context>,
+    caller: Caller,
+    payload: Result<Json<Refresh>, JsonRejection>,
+) -> HttpResponse {
+    match body(&ctx, payload) {
+        Ok(refresh) => {
+            handle_request(ctx, caller, Request::Refresh(refresh)).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
+
+async fn deposit(
+    State(ctx): State<Context>,
+    caller: Caller,
+    payload: Result<Json<DepositRequest>, JsonRejection>,
+) -> HttpResponse {
+    match body(&ctx, payload) {
+        Ok(deposit) => {
+            handle_request(ctx, caller, Request::Deposit(deposit)).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
+
+async fn withdraw(
+    State(ctx): State<Context>,
+    Path(tx_hash): Path<String>,
+    caller: Caller,
+    payload: Result<Json<WithdrawRequest>, JsonRejection>,
+) -> HttpResponse {
+    let withdraw = match body(&ctx, payload) {
+        Ok(withdraw) => withdraw,
+        Err(error) => return rpc_response(error),
+    };
+    if let Err(error) =
+        require_path_match("tx_hash", &tx_hash, &withdraw.tx_hash)
+    {
+        return rpc_response(error);
+    }
+
+    handle_request(ctx, caller, Request::Withdraw(withdraw)).await
+}
+
+async fn withdrawal_status(
+    State(ctx): State<Context>,
+    Path(tx_hash): Path<String>,
+    caller: Caller,
+) -> HttpResponse {
+    let request =
+        Request::WithdrawalStatus(WithdrawalStatusRequest { tx_hash });
+    handle_request(ctx, caller, request).await
+}
+
+async fn batch(
+    State(ctx): State<Context>,
+    caller: Caller,
+    payload: Result<Json<BatchRequest>, JsonRejection>,
+) -> HttpResponse {
+    match body(&ctx, payload) {
+        Ok(batch) => handle_request(ctx, caller, Request::Batch(batch)).await,
+        Err(error) => rpc_response(error),
+    }
+}
+
+/// Unwrap an xnode envelope whose `transfer_id` must match the path.
+fn envelope<T>(
+    ctx: &Context,
+    transfer_id: &str,
+    payload: Result<Json<XNodeEnvelope<T>>, JsonRejection>,
+) -> Result<XNodeEnvelope<T>, Response> {
+    let envelope = body(ctx, payload)?;
+    require_path_match("transfer_id", transfer_id, &envelope.transfer_id)?;
+    Ok(envelope)
+}
+
+async fn transfer_create(
+    State(ctx): State<Context>,
+    Path(id): Path<String>,
+    caller: Caller,
+    payload: Result<Json<XNodeEnvelope<TransferInitPayload>>, JsonRejection>,
+) -> HttpResponse {
+    match envelope(&ctx, &id, payload) {
+        Ok(envelope) => {
+            let request = Request::CrossNodeTransferCreate(envelope);
+            handle_request(ctx, caller, request).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
+
+async fn transfer_notify(
+    State(ctx): State<Context>,
+    Path(id): Path<String>,
+    caller: Caller,
+    payload: Result<Json<XNodeEnvelope<TransferNoticePayload>>, JsonRejection>,
+) -> HttpResponse {
+    match envelope(&ctx, &id, payload) {
+        Ok(envelope) => {
+            let request = Request::CrossNodeTransferNotify(envelope);
+            handle_request(ctx, caller, request).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
+
+async fn transfer_status(
+    State(ctx): State<Context>,
+    Path(id): Path<String>,
+    caller: Caller,
+    payload: Result<
+        Json<XNodeEnvelope<TransferStatusQueryPayload>>,
+        JsonRejection,
+    >,
+) -> HttpResponse {
+    match envelope(&ctx, &id, payload) {
+        Ok(envelope) => {
+            let request = Request::CrossNodeTransferStatus(envelope);
+            handle_request(ctx, caller, request).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
+
+async fn transfer_ack(
+    State(ctx): State<Context>,
+    Path(id): Path<String>,
+    caller: Caller,
+    payload: Result<Json<XNodeEnvelope<TransferAckPayload>>, JsonRejection>,
+) -> HttpResponse {
+    match envelope(&ctx, &id, payload) {
+        Ok(envelope) => {
+            let request = Request::CrossNodeTransferAck(envelope);
+            handle_request(ctx, caller, request).await
+        }
+        Err(error) => rpc_response(error),
+    }
+}
@@ -3,17 +3,14 @@ use mugraph_core::error::Error;
 
 use crate::{database::CARDANO_WALLET, provider::Provider, routes::Context};
 
-/// Create Cardano provider from configuration
+/// Create Cardano provider from configuration, reading the script address
+/// from the chain index while it is fresh
 pub(super) fn create_provider(ctx: &Context) -> Result<Provider, Error> {
-    Provider::new(
-        &ctx.config.provider_type(),
-        ctx.config.provider_api_key(),
-        ctx.config.network(),
-        ctx.config.provider_url(),
-    )
-    .map_err(|e| Error::Internal {
-        reason: e.to_string(),
-    })
+    Provider::from_config(&ctx.config)
+        .map(|provider| provider.indexed(ctx.database.clone()))
+        .map_err(|e| Error::Internal {
+            reason: e.to_string(),
+        })
 }
 
 /// Load Cardano wallet for signing
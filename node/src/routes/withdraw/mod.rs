@@ -6,9 +6,10 @@ use blake2::Digest;
 use color_eyre::eyre::Result;
 use mugraph_core::{
     crypto,
-    error::Error,
+    error::{Error, ErrorCode},
     types::{
         BlindSignature, Keypair, Response, WithdrawRequest, WithdrawalStatus,
+        WithdrawalStatusRequest,
     },
 };
 #[cfg(test)]
@@ -17,13 +18,16 @@ use whisky_csl::csl;
 #[cfg(test)]
 use crate::tx_signer::compute_tx_hash;
 use crate::{
-    database::WITHDRAWALS, routes::Context,
+    database::WITHDRAWALS,
+    routes::Context,
     tx_signer::attach_witness_to_transaction,
+    withdrawal_monitor::{WithdrawalSubmission, load_withdrawal},
 };
 
 mod input_validation;
 mod io;
 mod parsed_tx;
+mod script_eval;
 mod state;
 mod tx_checks;
 
@@ -43,11 +47,13 @@ use self::{
         validate_user_witnesses_with_parsed_tx,
     },
     io::{create_provider, load_wallet, submit_transaction},
+    script_eval::evaluate_script_inputs,
     state::{
         atomic_burn_and_record_pending, mark_withdrawal_completed,
         mark_withdrawal_failed,
     },
     tx_checks::{
+        validate_fee_and_min_ada,
         validate_network_and_change_outputs_with_parsed_tx,
         validate_parsed_fee, validate_transaction_balance_with_parsed_tx,
         validate_withdraw_intent_metadata_with_parsed_tx,
@@ -61,10 +67,12 @@ use self::{
 /// 3. Ensure all inputs reference script UTxOs
 /// 4. Validate user signatures (transaction witnesses via whisky-csl)
 /// 5. Check outputs match burned notes minus fees
-/// 6. Burn notes
+/// 6. Evaluate script inputs locally with the wallet's validator
 /// 7. Attach node witness and re-serialize
-/// 8. Submit transaction to provider
-/// 9. Return signed CBOR + hash + change notes
+/// 8. Check the fee and output minimums against protocol parameters
+/// 9. Burn notes
+/// 10. Submit transaction to provider
+/// 11. Return signed CBOR + hash + change notes
 pub async fn handle_withdraw(
     request: &WithdrawRequest,
     ctx: &Context,
@@ -150,6 +158,14 @@ pub async fn handle_withdraw(
         &request.change_outputs,
     )?;
 
+    // 9a. Refunds, if requested, match the burned notes in number
+    validate_refund_outputs(request)?;
+
+    // 9b. Run the scripts locally, so a transaction that would fail phase
+    // two is refused before any notes are burned
+    let ref_script_bytes =
+        evaluate_script_inputs(&parsed_tx, &wallet, &provider).await?;
+
     // 9. Create signed transaction (without burning notes yet)
     // This prepares the transaction for submission but doesn't modify state
 
@@ -167,6 +183,20 @@ pub async fn handle_withdraw(
     })?;
     let signed_cbor_hex = hex::encode(&signed_cbor);
 
+    // 9c. Hold the transaction as submitted, node witness included, to the
+    // fee and minimum ADA of this epoch's protocol parameters
+    let params = provider.get_protocol_params().await.map_err(|e| {
+        Error::NetworkError {
+            reason: format!("Failed to fetch protocol parameters: {}", e),
+        }
+    })?;
+    validate_fee_and_min_ada(
+        &signed_cbor,
+        &params,
+        ref_script_bytes,
+        ctx.config.fee_tolerance_pct(),
+    )?;
+
     // Calculate change notes before any state changes
     let change_notes = calculate_change_notes(
         request,
@@ -178,7 +208,14 @@ pub async fn handle_withdraw(
     // 9. Update state atomically BEFORE submitting to provider
     // This ensures we only submit if we can properly track the withdrawal
     let pending_tx_hash = request.tx_hash.clone();
-    match atomic_burn_and_record_pending(request, ctx, &pending_tx_hash) {
+    let submission = WithdrawalSubmission::new(&signed_cbor, consumed_deposits)
+        .with_refund_outputs(request.refund_outputs.clone());
+    match atomic_burn_and_record_pending(
+        request,
+        ctx,
+        &pending_tx_hash,
+        &submission,
+    ) {
         Ok(()) => {
             tracing::info!("Notes burned and withdrawal recorded as pending");
         }
@@ -231,8 +268,20 @@ pub async fn handle_withdraw(
     }
 
     // 11. Mark withdrawal as completed
-    let mark_result =
-        mark_withdrawal_completed(ctx, &pending_tx_hash, &consumed_deposits);
+    let mark_result = mark_withdrawal_completed(
+        ctx,
+        &pending_tx_hash,
+        &submission.consumed_deposits,
+    );
+    if mark_result.is_ok() {
+        crate::telemetry::record_asset_flow(
+            "withdrawal",
+            &ctx.config.metrics_assets(),
+            input_totals
+                .iter()
+                .map(|(unit, amount)| (unit.as_str(), *amount)),
+        );
+    }
 
     finalize_withdraw_response(
         mark_result,
@@ -278,6 +327,35 @@ fn finalize_withdraw_response(
     }
 }
 
+/// Report where a withdrawal the node burned notes for stands
+pub fn handle_withdrawal_status(
+    request: &WithdrawalStatusRequest,
+    ctx: &Context,
+) -> Result<Response, Error> {
+    let key = crate::tx_ids::parse_withdrawal_key(
+        &request.tx_hash,
+        ctx.config.network_byte(),
+    )?;
+    let Some((record, submission)) = load_withdrawal(&ctx.database, &key)?
+    else {
+        return Err(Error::Rejected {
+            code: ErrorCode::WithdrawalNotFound,
+            reason: format!("Unknown withdrawal {}", request.tx_hash),
+        });
+    };
+
+    Ok(Response::WithdrawalStatus {
+        tx_hash: hex::encode(key.tx_hash),
+        status: record.status,
+        updated_at: record.timestamp,
+        resubmissions: submission.as_ref().map_or(0, |s| s.resubmissions),
+        invalid_hereafter: submission
+            .as_ref()
+            .and_then(|s| s.invalid_hereafter),
+        refund_notes: submission.map(|s| s.refund_notes).unwrap_or_default(),
+    })
+}
+
 /// Check if withdrawal has already been processed (idempotency)
 fn check_idempotency(
     request: &WithdrawRequest,
@@ -299,6 +377,12 @@ fn check_idempotency(
                 reason: "Withdrawal already completed".to_string(),
             });
         }
+        // Refunded notes were reissued; the transaction cannot land
+        if record.status == WithdrawalStatus::Refunded {
+            return Err(Error::InvalidInput {
+                reason: "Withdrawal already refunded".to_string(),
+            });
+        }
         // Log warning if retrying a failed withdrawal
         if record.status == WithdrawalStatus::Failed {
             tracing::warn!(
@@ -311,6 +395,34 @@ fn check_idempotency(
     Ok(())
 }
 
+/// Check that refund outputs, when given, are one valid blinded point per
+/// burned note, so a refund can be signed without the request.
+///
+/// Their value is not checked. The asset and amount each point commits to
+/// are hidden by its blinding, so it is trusted exactly like the value of
+/// `change_outputs`.
+fn validate_refund_outputs(request: &WithdrawRequest) -> Result<(), Error> {
+    if request.refund_outputs.is_empty() {
+        return Ok(());
+    }
+
+    if request.refund_outputs.len() != request.notes.len() {
+        return Err(Error::InvalidInput {
+            reason: format!(
+                "Refund outputs must match burned notes: request provided {} refund_outputs for {} notes",
+                request.refund_outputs.len(),
+                request.notes.len()
+            ),
+        });
+    }
+
+    for refund_output in &request.refund_outputs {
+        refund_output.signature.0.to_point()?;
+    }
+
+    Ok(())
+}
+
 /// Calculate change notes by signing the request-provided blinded change
 /// outputs.
 fn calculate_change_notes(
@@ -371,25 +483,18 @@ mod tests {
         database.migrate().unwrap();
         std::mem::forget(dir);
 
-        let config = Config::Server {
-            addr: "127.0.0.1:9999".parse().unwrap(),
-            seed: Some(7),
-            secret_key: None,
-            cardano_network: "preprod".to_string(),
-            cardano_provider: "blockfrost".to_string(),
-            cardano_api_key: Some("test".to_string()),
-            cardano_provider_url: provider_url,
-            cardano_payment_sk: None,
-            xnode_peer_registry_file: None,
-            xnode_node_id: "node://local".to_string(),
-            deposit_confirm_depth: 15,
-            deposit_expiration_blocks: 1440,
-            min_deposit_value: Some(1_000_000),
-            max_tx_size: 16384,
-            max_withdrawal_fee: 2_000_000,
-            fee_tolerance_pct: 5,
-            dev_mode: true,
-        };
+        let mut config = Config::test_server();
+        if let Config::Server {
+            seed,
+            cardano_api_key,
+            cardano_provider_url,
+            ..
+        } = &mut config
+        {
+            *seed = Some(7);
+            *cardano_api_key = Some("test".to_string());
+            *cardano_provider_url = provider_url;
+        }
         let keypair = config.keypair().unwrap();
 
         Context {
@@ -397,6 +502,8 @@ mod tests {
             database,
             config,
             peer_registry: None,
+            health: Default::default(),
+            limits: Default::default(),
         }
     }
 
@@ -412,7 +519,7 @@ mod tests {
             table
                 .insert(
                     "wallet",
-                    &mugraph_core::types::CardanoWallet::new(
+                    mugraph_core::types::CardanoWallet::new(
                         payment_sk,
                         payment_vk,
                         vec![],
@@ -466,7 +573,7 @@ mod tests {
         {
             let mut table = write_tx.open_table(WITHDRAWALS).unwrap();
             table
-                .insert(&withdrawal_key_from_hex(tx_hash), &record)
+                .insert(withdrawal_key_from_hex(tx_hash), &record)
                 .unwrap();
         }
         write_tx.commit().unwrap();
@@ -509,6 +616,7 @@ mod tests {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_withdraw_request_with_outputs(
         user_sk: &SigningKey,
         input_tx_hash: [u8; 32],
@@ -599,6 +707,7 @@ mod tests {
                 proof: Default::default(),
             }],
             change_outputs,
+            refund_outputs: vec![],
             tx_cbor: hex::encode(tx_cbor),
             tx_hash,
         }
@@ -702,6 +811,7 @@ mod tests {
                 proof: Default::default(),
             }],
             change_outputs: vec![],
+            refund_outputs: vec![],
             tx_hash: hex::encode(compute_tx_hash(&tx_cbor).unwrap()),
             tx_cbor: hex::encode(tx_cbor),
         }
@@ -715,6 +825,24 @@ mod tests {
         mugraph_core::types::WithdrawalKey::new(0, array)
     }
 
+    /// Protocol parameters under which the 170_000 lovelace the test
+    /// requests pay is exactly the minimum fee
+    async fn protocol_params() -> impl IntoResponse {
+        axum::Json(json!({
+            "min_fee_a": "0",
+            "min_fee_b": "170000",
+            "max_tx_size": "16384",
+            "max_val_size": "5000",
+            "key_deposit": "2000000",
+            "pool_deposit": "500000000",
+            "price_mem": "0",
+            "price_step": "0",
+            "max_tx_ex_mem": "14000000",
+            "max_tx_ex_steps": "10000000000",
+            "coins_per_utxo_size": "4310"
+        }))
+    }
+
     async fn spawn_withdraw_provider_mock(
         script_address: String,
         datum_cbor_hex: String,
@@ -804,6 +932,7 @@ mod tests {
             .route("/txs/{tx_hash}", get(tx_info))
             .route("/txs/{tx_hash}/utxos", get(tx_utxos))
             .route("/scripts/datum/{datum_hash}/cbor", get(datum_cbor))
+            .route("/epochs/latest/parameters", get(protocol_params))
             .route("/tx/submit", post(submit))
             .with_state((
                 script_address,
@@ -921,6 +1050,96 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    fn preprod_params() -> crate::provider::ProtocolParams {
+        crate::provider::ProtocolParams {
+            min_fee_a: 44,
+            min_fee_b: 155_381,
+            max_tx_size: 16_384,
+            max_val_size: 5_000,
+            key_deposit: 2_000_000,
+            pool_deposit: 500_000_000,
+            price_mem: crate::provider::Ratio::new(577, 10_000),
+            price_step: crate::provider::Ratio::new(721, 10_000_000),
+            max_tx_ex_mem: 14_000_000,
+            max_tx_ex_steps: 10_000_000_000,
+            coins_per_utxo_byte: 4_310,
+            min_fee_ref_script_cost_per_byte: crate::provider::Ratio::new(
+                15, 1,
+            ),
+        }
+    }
+
+    #[test]
+    fn fee_check_rejects_under_and_overpaying_fees() {
+        let params = preprod_params();
+        let probe = minimal_tx_with_values(1_000_000, 200_000);
+        let min_fee = params.min_fee(&probe, probe.to_bytes().len() as u64, 0);
+
+        let exact = minimal_tx_with_values(1_000_000, min_fee).to_bytes();
+        assert_eq!(
+            validate_fee_and_min_ada(&exact, &params, 0, 5).unwrap(),
+            min_fee
+        );
+
+        let under = minimal_tx_with_values(1_000_000, min_fee - 1).to_bytes();
+        let err = validate_fee_and_min_ada(&under, &params, 0, 5).unwrap_err();
+        assert!(
+            format!("{err:?}")
+                .contains(&format!("below the minimum {min_fee}")),
+            "{err:?}"
+        );
+
+        let within = min_fee * 105 / 100;
+        let tolerated = minimal_tx_with_values(1_000_000, within).to_bytes();
+        assert!(validate_fee_and_min_ada(&tolerated, &params, 0, 5).is_ok());
+
+        let over = minimal_tx_with_values(1_000_000, within + 1).to_bytes();
+        let err = validate_fee_and_min_ada(&over, &params, 0, 5).unwrap_err();
+        assert!(format!("{err:?}").contains("by more than 5%"), "{err:?}");
+    }
+
+    #[test]
+    fn fee_check_charges_for_reference_scripts() {
+        let params = preprod_params();
+        let probe = minimal_tx_with_values(1_000_000, 200_000);
+        let size = probe.to_bytes().len() as u64;
+        let base = params.min_fee(&probe, size, 0);
+        let with_script = params.min_fee(&probe, size, 1_000);
+        assert_eq!(with_script, base + 15_000);
+
+        let tx = minimal_tx_with_values(1_000_000, base).to_bytes();
+        let err = validate_fee_and_min_ada(&tx, &params, 1_000, 5).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("1000 bytes of reference scripts"),
+            "{err:?}"
+        );
+        let tx = minimal_tx_with_values(1_000_000, with_script).to_bytes();
+        assert!(validate_fee_and_min_ada(&tx, &params, 1_000, 5).is_ok());
+    }
+
+    #[test]
+    fn fee_check_rejects_outputs_below_min_ada() {
+        let params = preprod_params();
+        let probe = minimal_tx_with_values(1_000_000, 200_000);
+        let min_fee = params.min_fee(&probe, probe.to_bytes().len() as u64, 0);
+        let min_ada = params
+            .min_ada(&probe.body().outputs().get(0))
+            .expect("sized");
+
+        let tx = minimal_tx_with_values(min_ada, min_fee).to_bytes();
+        assert!(validate_fee_and_min_ada(&tx, &params, 0, 5).is_ok());
+
+        let tx = minimal_tx_with_values(min_ada - 1, min_fee).to_bytes();
+        let err = validate_fee_and_min_ada(&tx, &params, 0, 5).unwrap_err();
+        assert!(
+            format!("{err:?}").contains(&format!(
+                "Output 0 holds {} lovelace, below the minimum {min_ada}",
+                min_ada - 1
+            )),
+            "{err:?}"
+        );
+    }
+
     /// required_signers present but missing matching witness => reject
     #[tokio::test]
     async fn test_required_signer_missing_witness() {
@@ -1373,6 +1592,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_refund_outputs_must_match_burned_notes() {
+        let request = |notes: usize, refund_outputs| WithdrawRequest {
+            notes: vec![BlindSignature::default(); notes],
+            change_outputs: vec![],
+            refund_outputs,
+            tx_cbor: String::new(),
+            tx_hash: String::new(),
+        };
+
+        validate_refund_outputs(&request(2, vec![])).unwrap();
+        validate_refund_outputs(&request(
+            2,
+            vec![
+                sample_change_output(1, b"refund-a"),
+                sample_change_output(2, b"refund-b"),
+            ],
+        ))
+        .unwrap();
+
+        let err = validate_refund_outputs(&request(
+            2,
+            vec![sample_change_output(1, b"refund-a")],
+        ))
+        .unwrap_err();
+        assert!(
+            format!("{err:?}").contains("1 refund_outputs for 2 notes"),
+            "{err:?}"
+        );
+
+        let mut invalid = BlindSignature::default();
+        invalid.signature.0 = mugraph_core::types::Signature::from([0xff; 32]);
+        assert!(validate_refund_outputs(&request(1, vec![invalid])).is_err());
+    }
+
     #[test]
     fn test_calculate_change_notes_returns_empty_for_no_change_outputs() {
         let tx = minimal_tx_with_values(1_000_000, 170_000);
@@ -1394,6 +1648,7 @@ mod tests {
             &WithdrawRequest {
                 notes: vec![],
                 change_outputs: vec![],
+                refund_outputs: vec![],
                 tx_cbor: String::new(),
                 tx_hash: String::new(),
             },
@@ -1431,6 +1686,7 @@ mod tests {
             &WithdrawRequest {
                 notes: vec![],
                 change_outputs: change_outputs.clone(),
+                refund_outputs: vec![],
                 tx_cbor: String::new(),
                 tx_hash: String::new(),
             },
@@ -1479,6 +1735,7 @@ mod tests {
             &WithdrawRequest {
                 notes: vec![],
                 change_outputs: change_outputs.clone(),
+                refund_outputs: vec![],
                 tx_cbor: String::new(),
                 tx_hash: String::new(),
             },
@@ -1636,6 +1893,20 @@ mod tests {
                 .value()
                 .spent
         );
+
+        // Kept so the withdrawal monitor could settle it had we crashed
+        let (_, submission) = load_withdrawal(&ctx.database, &key)
+            .unwrap()
+            .expect("withdrawal recorded");
+        let submission = submission.expect("submission recorded");
+        let Response::Withdraw { signed_tx_cbor, .. } = response else {
+            unreachable!()
+        };
+        assert_eq!(submission.signed_tx_cbor, signed_tx_cbor);
+        assert_eq!(
+            submission.consumed_deposits,
+            vec![mugraph_core::types::UtxoRef::new(input_tx_hash, 0)]
+        );
     }
 
     #[tokio::test]
@@ -1643,7 +1914,7 @@ mod tests {
         let user_sk = SigningKey::from_bytes(&[4u8; 32]);
         let (payment_sk, payment_vk) = generate_payment_keypair().unwrap();
         let input_tx_hash = [0xacu8; 32];
-        let input_value = 1_170_000u64;
+        let input_value = 2_070_000u64;
 
         let script_addr = {
             let key_hash =
@@ -1665,7 +1936,7 @@ mod tests {
                         .to_string(),
                     900_000,
                 ),
-                (script_addr.clone(), 100_000),
+                (script_addr.clone(), 1_000_000),
             ],
             170_000,
             "preprod",
@@ -1727,6 +1998,86 @@ mod tests {
         assert!(withdrawals.get(&key).unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn handle_withdraw_rejects_failing_scripts_before_burning_notes() {
+        let user_sk = SigningKey::from_bytes(&[14u8; 32]);
+        let (payment_sk, payment_vk) = generate_payment_keypair().unwrap();
+        let input_tx_hash = [0xaeu8; 32];
+        let input_value = 1_170_000u64;
+        // Spends the script input without a redeemer, so phase two fails
+        let request = build_withdraw_request(
+            &user_sk,
+            input_tx_hash,
+            input_value,
+            1_000_000,
+            170_000,
+            "preprod",
+        );
+
+        let script_cbor = crate::cardano::compile_validator().unwrap();
+        let script_hash = crate::cardano::compute_script_hash(&script_cbor);
+        let script_addr =
+            crate::cardano::build_script_address(&script_hash, "preprod")
+                .unwrap();
+        let node_hash = csl::PublicKey::from_bytes(&payment_vk)
+            .unwrap()
+            .hash()
+            .to_bytes();
+        let user_hash =
+            csl::PublicKey::from_bytes(user_sk.verifying_key().as_bytes())
+                .unwrap()
+                .hash()
+                .to_bytes();
+        let provider_url = spawn_withdraw_provider_mock(
+            script_addr.clone(),
+            build_datum_cbor_hex(user_hash, node_hash, vec![0u8; 32]),
+            input_value,
+            StatusCode::OK,
+            request.tx_hash.clone(),
+        )
+        .await;
+        let ctx = test_context_with_provider_url(Some(provider_url));
+        let write_tx = ctx.database.write().unwrap();
+        {
+            let mut table = write_tx.open_table(CARDANO_WALLET).unwrap();
+            table
+                .insert(
+                    "wallet",
+                    mugraph_core::types::CardanoWallet::new(
+                        payment_sk,
+                        payment_vk,
+                        script_cbor,
+                        script_hash,
+                        script_addr,
+                        "preprod".to_string(),
+                    ),
+                )
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+        seed_deposit(
+            &ctx,
+            mugraph_core::types::UtxoRef::new(input_tx_hash, 0),
+            [0u8; 32],
+        );
+
+        let err = handle_withdraw(&request, &ctx).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::ScriptEvaluationFailed {
+                    input_index: Some(0),
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert_preflight_rejection_leaves_state_untouched(
+            &ctx,
+            &request.tx_hash,
+        );
+    }
+
     #[tokio::test]
     async fn handle_withdraw_hash_mismatch_does_not_mutate_notes_or_withdrawals()
      {
@@ -2495,24 +2846,87 @@ mod tests {
                 proof: Default::default(),
             }],
             change_outputs: vec![],
+            refund_outputs: vec![],
         };
 
-        atomic_burn_and_record_pending(&request, &ctx, &request.tx_hash)
-            .expect("first burn succeeds");
+        atomic_burn_and_record_pending(
+            &request,
+            &ctx,
+            &request.tx_hash,
+            &WithdrawalSubmission::default(),
+        )
+        .expect("first burn succeeds");
 
         mark_withdrawal_failed(&ctx, &request.tx_hash).expect("mark as failed");
 
         check_idempotency(&request, &ctx)
             .expect("failed withdrawals are retryable");
 
-        let second =
-            atomic_burn_and_record_pending(&request, &ctx, &request.tx_hash);
+        let second = atomic_burn_and_record_pending(
+            &request,
+            &ctx,
+            &request.tx_hash,
+            &WithdrawalSubmission::default(),
+        );
         assert!(
             second.is_ok(),
             "retry should reuse failed pending state without burning notes again"
         );
     }
 
+    #[test]
+    fn retry_refuses_a_withdrawal_refunded_since_its_check() {
+        let ctx = test_context();
+        let request = WithdrawRequest {
+            tx_hash: "ab".repeat(32),
+            tx_cbor: "00".to_string(),
+            notes: vec![BlindSignature {
+                signature: mugraph_core::types::Blinded(
+                    mugraph_core::types::Signature::from([4u8; 32]),
+                ),
+                proof: Default::default(),
+            }],
+            change_outputs: vec![],
+            refund_outputs: vec![],
+        };
+
+        atomic_burn_and_record_pending(
+            &request,
+            &ctx,
+            &request.tx_hash,
+            &WithdrawalSubmission::default(),
+        )
+        .expect("first burn succeeds");
+        mark_withdrawal_failed(&ctx, &request.tx_hash).expect("mark as failed");
+        check_idempotency(&request, &ctx)
+            .expect("failed withdrawals are retryable");
+
+        // The monitor refunds it after the retry's check
+        seed_withdrawal_record(
+            &ctx,
+            &request.tx_hash,
+            mugraph_core::types::WithdrawalRecord::refunded(),
+        );
+
+        let err = atomic_burn_and_record_pending(
+            &request,
+            &ctx,
+            &request.tx_hash,
+            &WithdrawalSubmission::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{err:?}").contains("Withdrawal already refunded"));
+
+        let read_tx = ctx.database.read().unwrap();
+        let table = read_tx.open_table(WITHDRAWALS).unwrap();
+        let record = table
+            .get(withdrawal_key_from_hex(&request.tx_hash))
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!(record.status, WithdrawalStatus::Refunded);
+    }
+
     #[test]
     fn completion_rejects_unknown_withdrawal_hash() {
         let ctx = test_context();
@@ -2526,10 +2940,16 @@ mod tests {
                 proof: Default::default(),
             }],
             change_outputs: vec![],
+            refund_outputs: vec![],
         };
 
-        atomic_burn_and_record_pending(&request, &ctx, &request.tx_hash)
-            .expect("first burn succeeds");
+        atomic_burn_and_record_pending(
+            &request,
+            &ctx,
+            &request.tx_hash,
+            &WithdrawalSubmission::default(),
+        )
+        .expect("first burn succeeds");
 
         let mismatched = "cd".repeat(32);
         let err =
@@ -2545,6 +2965,7 @@ mod tests {
             tx_cbor: "00".to_string(),
             notes: vec![],
             change_outputs: vec![],
+            refund_outputs: vec![],
         };
         seed_withdrawal_record(
             &ctx,
@@ -2556,6 +2977,111 @@ mod tests {
         assert!(format!("{err:?}").contains("Withdrawal already completed"));
     }
 
+    #[test]
+    fn withdrawal_status_reports_the_recorded_state() {
+        let ctx = test_context();
+        let tx_hash = "AB".repeat(32);
+        seed_withdrawal_record(
+            &ctx,
+            &tx_hash,
+            mugraph_core::types::WithdrawalRecord::failed(),
+        );
+
+        let response = handle_withdrawal_status(
+            &WithdrawalStatusRequest {
+                tx_hash: tx_hash.clone(),
+            },
+            &ctx,
+        )
+        .unwrap();
+        let Response::WithdrawalStatus {
+            tx_hash: reported,
+            status,
+            resubmissions,
+            invalid_hereafter,
+            ..
+        } = response
+        else {
+            panic!("expected withdrawal status, got {response:?}");
+        };
+        assert_eq!(reported, tx_hash.to_ascii_lowercase());
+        assert_eq!(status, WithdrawalStatus::Failed);
+        assert_eq!(resubmissions, 0);
+        assert_eq!(invalid_hereafter, None);
+
+        let err = handle_withdrawal_status(
+            &WithdrawalStatusRequest {
+                tx_hash: "cd".repeat(32),
+            },
+            &ctx,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::WithdrawalNotFound);
+    }
+
+    #[test]
+    fn refunded_withdrawals_report_their_notes_and_are_final() {
+        let ctx = test_context();
+        let tx_hash = "ef".repeat(32);
+        seed_withdrawal_record(
+            &ctx,
+            &tx_hash,
+            mugraph_core::types::WithdrawalRecord::refunded(),
+        );
+        let refund_notes = vec![sample_change_output(3, b"refund")];
+        let submission = WithdrawalSubmission {
+            refund_outputs: refund_notes.clone(),
+            refund_notes: refund_notes.clone(),
+            ..Default::default()
+        };
+        let write_tx = ctx.database.write().unwrap();
+        {
+            let mut table = write_tx
+                .open_table(crate::database::WITHDRAWAL_SUBMISSIONS)
+                .unwrap();
+            table
+                .insert(
+                    withdrawal_key_from_hex(&tx_hash),
+                    submission.to_bytes().unwrap().as_slice(),
+                )
+                .unwrap();
+        }
+        write_tx.commit().unwrap();
+
+        let response = handle_withdrawal_status(
+            &WithdrawalStatusRequest {
+                tx_hash: tx_hash.clone(),
+            },
+            &ctx,
+        )
+        .unwrap();
+        let Response::WithdrawalStatus {
+            status,
+            refund_notes: reported,
+            ..
+        } = response
+        else {
+            panic!("expected withdrawal status, got {response:?}");
+        };
+        assert_eq!(status, WithdrawalStatus::Refunded);
+        assert_eq!(reported, refund_notes);
+
+        let err = mark_withdrawal_completed(&ctx, &tx_hash, &[]).unwrap_err();
+        assert!(format!("{err:?}").contains("Withdrawal already refunded"));
+        let err = check_idempotency(
+            &WithdrawRequest {
+                notes: vec![],
+                change_outputs: vec![],
+                refund_outputs: vec![],
+                tx_cbor: String::new(),
+                tx_hash,
+            },
+            &ctx,
+        )
+        .unwrap_err();
+        assert!(format!("{err:?}").contains("Withdrawal already refunded"));
+    }
+
     #[test]
     fn completion_rejects_already_completed_withdrawal_without_mutating_deposits()
      {
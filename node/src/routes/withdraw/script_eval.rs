@@ -0,0 +1,576 @@
+//! Local phase-two evaluation of withdrawal transactions.
+//!
+//! A transaction whose scripts fail on chain costs the user its collateral
+//! and, since notes are burned before submission, leaves the withdrawal
+//! `Failed`. Running the deposit validator here first turns that into a
+//! request error while nothing has changed yet. The transaction is evaluated
+//! exactly as submitted, so it must supply the validator and the datums of
+//! any inputs that hold theirs by hash, as the ledger requires.
+
+use std::collections::HashMap;
+
+use mugraph_core::{error::Error, types::CardanoWallet};
+use uplc::tx::{error::Error as EvalError, eval_phase_two_raw};
+use whisky_csl::csl;
+
+use super::ParsedWithdrawalTx;
+use crate::{
+    network::CardanoNetwork,
+    provider::{Provider, UtxoInfo},
+};
+
+/// Evaluate every redeemer of `parsed_tx` as submitted, resolving the inputs
+/// and reference inputs it spends through `provider`.
+///
+/// Fails if an input or reference input does not resolve, if an input at the
+/// script address has no spend redeemer, if the transaction neither carries
+/// the wallet's validator nor references it, if a script fails, or if a
+/// redeemer needs more execution units than it declares. Addresses locked by
+/// a key have nothing to evaluate.
+///
+/// Returns the size of the reference scripts on the resolved outputs, which
+/// the ledger charges for on top of the size and execution fee.
+pub(super) async fn evaluate_script_inputs(
+    parsed_tx: &ParsedWithdrawalTx,
+    wallet: &CardanoWallet,
+    provider: &Provider,
+) -> Result<u64, Error> {
+    let Some(script_hash) = script_credential(&wallet.script_address) else {
+        return reference_script_bytes(parsed_tx, provider).await;
+    };
+
+    let body = parsed_tx.tx.body();
+    // Spend redeemers point into the inputs in ledger order
+    let mut inputs: Vec<csl::TransactionInput> =
+        (&body.inputs()).into_iter().cloned().collect();
+    inputs.sort_by_key(|input| {
+        (input.transaction_id().to_bytes(), input.index())
+    });
+    let references: Vec<csl::TransactionInput> = body
+        .reference_inputs()
+        .map(|refs| (&refs).into_iter().cloned().collect())
+        .unwrap_or_default();
+
+    let declared = declared_budgets(&parsed_tx.tx);
+    let mut resolved = Vec::with_capacity(inputs.len() + references.len());
+    let mut script_bytes = 0u64;
+    let mut spends_script = false;
+    let mut supplied = witness_scripts(&parsed_tx.tx).contains(&script_hash);
+    for (i, input) in inputs.iter().enumerate() {
+        let utxo = resolve(provider, input).await?;
+        script_bytes = script_bytes.saturating_add(script_size(&utxo)?);
+        supplied |= script_hash_of(&utxo)?.as_ref() == Some(&script_hash);
+        let at_script =
+            script_credential(&utxo.address).as_ref() == Some(&script_hash);
+        spends_script |= at_script;
+        if at_script
+            && !declared.contains_key(&(csl::RedeemerTagKind::Spend, i as u64))
+        {
+            return Err(Error::ScriptEvaluationFailed {
+                input_index: Some(i as u32),
+                reason: format!(
+                    "script input {i} ({}) has no spend redeemer",
+                    outref(input)
+                ),
+            });
+        }
+        resolved.push((input.to_bytes(), ledger_output(&utxo)?.to_bytes()));
+    }
+    for input in &references {
+        let utxo = resolve(provider, input).await?;
+        script_bytes = script_bytes.saturating_add(script_size(&utxo)?);
+        supplied |= script_hash_of(&utxo)?.as_ref() == Some(&script_hash);
+        resolved.push((input.to_bytes(), ledger_output(&utxo)?.to_bytes()));
+    }
+
+    if spends_script && !supplied {
+        return Err(Error::ScriptEvaluationFailed {
+            input_index: None,
+            reason: format!(
+                "validator {} is neither in the witness set nor a reference script of an input",
+                script_hash.to_hex()
+            ),
+        });
+    }
+    if declared.is_empty() {
+        return Ok(script_bytes);
+    }
+
+    let tx_cbor = parsed_tx.tx_cbor.clone();
+    let total = declared.values().fold((0u64, 0u64), |(mem, steps), units| {
+        (mem.saturating_add(units.0), steps.saturating_add(units.1))
+    });
+    let slot_config = CardanoNetwork::parse(&wallet.network)
+        .map_err(|e| Error::Internal {
+            reason: e.to_string(),
+        })?
+        .slot_config();
+
+    let outrefs: Vec<String> = inputs.iter().map(outref).collect();
+    let evaluated = tokio::task::spawn_blocking(move || {
+        eval_phase_two_raw(
+            &tx_cbor,
+            &resolved,
+            None,
+            (total.1, total.0),
+            slot_config,
+            false,
+            |_| (),
+        )
+        .map_err(|e| evaluation_error(e, &outrefs))
+    })
+    .await
+    .map_err(|e| Error::ScriptEvaluationFailed {
+        input_index: None,
+        reason: format!("evaluator aborted: {e}"),
+    })??;
+
+    for bytes in evaluated {
+        let redeemer =
+            csl::Redeemer::from_bytes(bytes).map_err(|e| Error::Internal {
+                reason: format!("Evaluator returned an invalid redeemer: {e}"),
+            })?;
+        let kind = redeemer.tag().kind();
+        let index = u64::from(redeemer.index());
+        let used = redeemer.ex_units();
+        let (used_mem, used_steps) =
+            (u64::from(used.mem()), u64::from(used.steps()));
+        let Some(&(mem, steps)) = declared.get(&(kind, index)) else {
+            continue;
+        };
+
+        tracing::debug!(
+            "{kind:?} redeemer {index} uses {used_mem} memory and {used_steps} steps of {mem} and {steps}"
+        );
+        if used_mem > mem || used_steps > steps {
+            let spend = kind == csl::RedeemerTagKind::Spend;
+            return Err(Error::ScriptEvaluationFailed {
+                input_index: spend.then_some(index as u32),
+                reason: format!(
+                    "{kind:?} redeemer {index} needs {used_mem} memory and {used_steps} steps but declares {mem} and {steps}"
+                ),
+            });
+        }
+    }
+
+    Ok(script_bytes)
+}
+
+/// Size of the reference scripts on the outputs `parsed_tx` spends or
+/// references, for a wallet with no validator to run
+async fn reference_script_bytes(
+    parsed_tx: &ParsedWithdrawalTx,
+    provider: &Provider,
+) -> Result<u64, Error> {
+    let body = parsed_tx.tx.body();
+    let references = body
+        .reference_inputs()
+        .unwrap_or_else(csl::TransactionInputs::new);
+    let mut script_bytes = 0u64;
+    for input in (&body.inputs()).into_iter().chain(&references) {
+        let utxo = resolve(provider, input).await?;
+        script_bytes = script_bytes.saturating_add(script_size(&utxo)?);
+    }
+    Ok(script_bytes)
+}
+
+fn script_size(utxo: &UtxoInfo) -> Result<u64, Error> {
+    utxo.reference_script_size().map_err(|e| Error::Internal {
+        reason: e.to_string(),
+    })
+}
+
+fn script_hash_of(utxo: &UtxoInfo) -> Result<Option<csl::ScriptHash>, Error> {
+    utxo.reference_script_hash().map_err(|e| Error::Internal {
+        reason: e.to_string(),
+    })
+}
+
+/// Hashes of the Plutus scripts `tx` carries in its witness set
+fn witness_scripts(tx: &csl::Transaction) -> Vec<csl::ScriptHash> {
+    let Some(scripts) = tx.witness_set().plutus_scripts() else {
+        return Vec::new();
+    };
+    (0..scripts.len()).map(|i| scripts.get(i).hash()).collect()
+}
+
+/// The script hash locking `address`, if a script locks it
+fn script_credential(address: &str) -> Option<csl::ScriptHash> {
+    csl::Address::from_bech32(address)
+        .ok()?
+        .payment_cred()?
+        .to_scripthash()
+}
+
+/// Declared `(mem, steps)` of every redeemer, by tag and index
+fn declared_budgets(
+    tx: &csl::Transaction,
+) -> HashMap<(csl::RedeemerTagKind, u64), (u64, u64)> {
+    let Some(redeemers) = tx.witness_set().redeemers() else {
+        return HashMap::new();
+    };
+
+    (0..redeemers.len())
+        .map(|i| {
+            let redeemer = redeemers.get(i);
+            let units = redeemer.ex_units();
+            (
+                (redeemer.tag().kind(), u64::from(redeemer.index())),
+                (u64::from(units.mem()), u64::from(units.steps())),
+            )
+        })
+        .collect()
+}
+
+async fn resolve(
+    provider: &Provider,
+    input: &csl::TransactionInput,
+) -> Result<UtxoInfo, Error> {
+    let tx_hash = input.transaction_id().to_hex();
+    let index =
+        u16::try_from(input.index()).map_err(|_| Error::InvalidInput {
+            reason: format!("Input {} index exceeds u16::MAX", outref(input)),
+        })?;
+
+    match provider.get_utxo(&tx_hash, index).await {
+        Ok(Some(utxo)) => Ok(utxo),
+        Ok(None) => Err(Error::InvalidInput {
+            reason: format!("Input {} not found on chain", outref(input)),
+        }),
+        Err(e) => Err(Error::NetworkError {
+            reason: format!("Failed to resolve input {}: {e}", outref(input)),
+        }),
+    }
+}
+
+fn ledger_output(utxo: &UtxoInfo) -> Result<csl::TransactionOutput, Error> {
+    utxo.to_output().map_err(|e| Error::InvalidInput {
+        reason: e.to_string(),
+    })
+}
+
+/// `error` as a request error, naming the input among `outrefs` (in ledger
+/// order) whose script failed
+fn evaluation_error(error: EvalError, outrefs: &[String]) -> Error {
+    match error {
+        EvalError::RedeemerError { tag, index, err } => {
+            let spent = (tag == "Spend")
+                .then(|| outrefs.get(index as usize))
+                .flatten();
+            Error::ScriptEvaluationFailed {
+                input_index: spent.map(|_| index),
+                reason: match spent {
+                    Some(outref) => {
+                        format!("script input {index} ({outref}) failed: {err}")
+                    }
+                    None => format!("{tag} redeemer {index} failed: {err}"),
+                },
+            }
+        }
+        other => Error::ScriptEvaluationFailed {
+            input_index: None,
+            reason: other.to_string(),
+        },
+    }
+}
+
+fn outref(input: &csl::TransactionInput) -> String {
+    format!("{}#{}", input.transaction_id().to_hex(), input.index())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cardano::{
+            build_script_address, compile_validator, compute_script_hash,
+        },
+        provider::{AssetAmount, EmulatorProvider},
+    };
+
+    const UNLIMITED: (u64, u64) = (14_000_000, 10_000_000_000);
+
+    fn key_hash(seed: u8) -> csl::Ed25519KeyHash {
+        csl::PrivateKey::from_normal_bytes(&[seed; 32])
+            .unwrap()
+            .to_public()
+            .hash()
+    }
+
+    fn constr(fields: Vec<Vec<u8>>) -> csl::PlutusData {
+        let mut list = csl::PlutusList::new();
+        for field in fields {
+            list.add(&csl::PlutusData::new_bytes(field));
+        }
+        csl::PlutusData::new_constr_plutus_data(&csl::ConstrPlutusData::new(
+            &csl::BigNum::zero(),
+            &list,
+        ))
+    }
+
+    fn wallet() -> CardanoWallet {
+        let script_cbor = compile_validator().expect("validator artifacts");
+        let script_hash = compute_script_hash(&script_cbor);
+        let script_address =
+            build_script_address(&script_hash, "preprod").unwrap();
+        CardanoWallet::new(
+            vec![1u8; 32],
+            vec![2u8; 32],
+            script_cbor,
+            script_hash,
+            script_address,
+            "preprod".to_string(),
+        )
+    }
+
+    /// A deposit at the script address owned by `key_hash(1)`, in its own
+    /// emulated ledger
+    fn deposit(
+        name: &str,
+        wallet: &CardanoWallet,
+    ) -> (Provider, (String, u16)) {
+        let ledger = EmulatorProvider::shared(name, "preprod".to_string());
+        let datum = constr(vec![
+            key_hash(1).to_bytes(),
+            key_hash(2).to_bytes(),
+            vec![0u8; 32],
+        ]);
+        let funded = ledger
+            .fund(
+                &wallet.script_address,
+                vec![AssetAmount {
+                    unit: "lovelace".to_string(),
+                    quantity: "5000000".to_string(),
+                }],
+                Some(datum.to_hex()),
+            )
+            .unwrap();
+        ledger.advance(1);
+        (Provider::Emulator(ledger), funded)
+    }
+
+    fn validator(wallet: &CardanoWallet) -> csl::PlutusScript {
+        csl::PlutusScript::new_v3(wallet.script_cbor.clone())
+    }
+
+    /// An output holding `wallet`'s validator as a reference script, in the
+    /// ledger behind `provider`
+    fn deploy(provider: &Provider, wallet: &CardanoWallet) -> (String, u16) {
+        let Provider::Emulator(ledger) = provider else {
+            unreachable!("deposits are made on an emulated ledger");
+        };
+        let deployed = ledger
+            .deploy_script(
+                "addr_test1vru4e2un2tq50q4rv6qzk7t8w34gjdtw3y2uzuqxzj0ldrqqactxh",
+                vec![AssetAmount {
+                    unit: "lovelace".to_string(),
+                    quantity: "20000000".to_string(),
+                }],
+                &validator(wallet),
+            )
+            .unwrap();
+        ledger.advance(1);
+        deployed
+    }
+
+    /// How a withdrawal supplies the validator
+    enum Supplied<'a> {
+        Witness(csl::PlutusScript),
+        Reference(&'a (String, u16)),
+        Missing,
+    }
+
+    /// Spend `input`, signed for by `signers`, declaring `budget` for its
+    /// redeemer if any
+    fn withdrawal(
+        input: &(String, u16),
+        signers: &[csl::Ed25519KeyHash],
+        budget: Option<(u64, u64)>,
+        validator: Supplied,
+    ) -> ParsedWithdrawalTx {
+        let mut inputs = csl::TransactionInputs::new();
+        inputs.add(&csl::TransactionInput::new(
+            &csl::TransactionHash::from_hex(&input.0).unwrap(),
+            input.1 as u32,
+        ));
+        let mut outputs = csl::TransactionOutputs::new();
+        outputs.add(&csl::TransactionOutput::new(
+            &csl::Address::from_bech32(
+                "addr_test1vru4e2un2tq50q4rv6qzk7t8w34gjdtw3y2uzuqxzj0ldrqqactxh",
+            )
+            .unwrap(),
+            &csl::Value::new(&csl::BigNum::from(4_800_000u64)),
+        ));
+        let mut body = csl::TransactionBody::new_tx_body(
+            &inputs,
+            &outputs,
+            &csl::BigNum::from(200_000u64),
+        );
+        let mut required = csl::Ed25519KeyHashes::new();
+        for signer in signers {
+            required.add(signer);
+        }
+        body.set_required_signers(&required);
+
+        let mut witnesses = csl::TransactionWitnessSet::new();
+        match validator {
+            Supplied::Witness(script) => {
+                let mut scripts = csl::PlutusScripts::new();
+                scripts.add(&script);
+                witnesses.set_plutus_scripts(&scripts);
+            }
+            Supplied::Reference(reference) => {
+                let mut references = csl::TransactionInputs::new();
+                references.add(&csl::TransactionInput::new(
+                    &csl::TransactionHash::from_hex(&reference.0).unwrap(),
+                    reference.1 as u32,
+                ));
+                body.set_reference_inputs(&references);
+            }
+            Supplied::Missing => {}
+        }
+        if let Some((mem, steps)) = budget {
+            let mut redeemers = csl::Redeemers::new();
+            redeemers.add(&csl::Redeemer::new(
+                &csl::RedeemerTag::new_spend(),
+                &csl::BigNum::zero(),
+                &constr(Vec::new()),
+                &csl::ExUnits::new(
+                    &csl::BigNum::from(mem),
+                    &csl::BigNum::from(steps),
+                ),
+            ));
+            witnesses.set_redeemers(&redeemers);
+        }
+
+        let tx = csl::Transaction::new(&body, &witnesses, None);
+        ParsedWithdrawalTx::parse(&tx.to_hex()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn signed_withdrawals_pass_within_their_declared_budget() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-pass", &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            Some(UNLIMITED),
+            Supplied::Witness(validator(&wallet)),
+        );
+
+        evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .expect("validator accepts the user's signature");
+    }
+
+    #[tokio::test]
+    async fn failing_validators_are_reported_per_input() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-fail", &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(3)],
+            Some(UNLIMITED),
+            Supplied::Witness(validator(&wallet)),
+        );
+
+        match evaluate_script_inputs(&tx, &wallet, &provider).await {
+            Err(Error::ScriptEvaluationFailed {
+                input_index: Some(0),
+                reason,
+            }) => assert!(reason.contains("failed"), "{reason}"),
+            other => panic!("expected a failed script input, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn budgets_above_the_declared_ex_units_are_rejected() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-budget", &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            Some((1_000, 1_000)),
+            Supplied::Witness(validator(&wallet)),
+        );
+
+        let err = evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .unwrap_err();
+        assert_eq!(err.details()["input_index"], "0");
+        assert!(err.to_string().contains("declares 1000 and 1000"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn script_inputs_need_a_spend_redeemer() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-redeemer", &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            None,
+            Supplied::Witness(validator(&wallet)),
+        );
+
+        let err = evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has no spend redeemer"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn validators_can_be_supplied_by_reference() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-reference", &wallet);
+        let deployed = deploy(&provider, &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            Some(UNLIMITED),
+            Supplied::Reference(&deployed),
+        );
+
+        let script_bytes = evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .expect("the referenced validator accepts the user's signature");
+        assert_eq!(script_bytes, wallet.script_cbor.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn withdrawals_must_supply_the_validator() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-missing", &wallet);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            Some(UNLIMITED),
+            Supplied::Missing,
+        );
+
+        let err = evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("neither in the witness set"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reference_inputs_must_resolve() {
+        let wallet = wallet();
+        let (provider, input) = deposit("emulator://eval-unresolved", &wallet);
+        let missing = ("ee".repeat(32), 0);
+        let tx = withdrawal(
+            &input,
+            &[key_hash(1)],
+            Some(UNLIMITED),
+            Supplied::Reference(&missing),
+        );
+
+        let err = evaluate_script_inputs(&tx, &wallet, &provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found on chain"), "{err}");
+    }
+}
@@ -1,63 +1,111 @@
 use mugraph_core::{
     error::Error,
-    types::{Signature, WithdrawRequest, WithdrawalRecord, WithdrawalStatus},
+    types::{
+        EventKind, EventSubject, Signature, WithdrawRequest, WithdrawalRecord,
+        WithdrawalStatus,
+    },
 };
 use redb::ReadableTable;
 
 use crate::{
-    database::{DEPOSITS, NOTES, WITHDRAWALS},
+    database::{
+        NOTES, NOTES_BY_KEYSET, NOTES_SPENT_AT, WITHDRAWAL_SUBMISSIONS,
+        WITHDRAWALS,
+    },
     routes::Context,
+    withdrawal_monitor::{WithdrawalSubmission, complete_withdrawal},
 };
 
+/// Burn the request's notes and record the withdrawal as pending, with
+/// `submission` kept so it can be settled if the request never finishes
 pub(super) fn atomic_burn_and_record_pending(
     request: &WithdrawRequest,
     ctx: &Context,
     tx_hash: &str,
+    submission: &WithdrawalSubmission,
 ) -> Result<(), Error> {
+    let submission_bytes = submission.to_bytes()?;
     let network_byte = ctx.config.network_byte();
     let key = crate::tx_ids::parse_withdrawal_key(tx_hash, network_byte)?;
 
-    {
-        let read_tx = ctx.database.read()?;
-        let withdrawals = read_tx.open_table(WITHDRAWALS)?;
-        if let Some(existing) = withdrawals.get(&key)?
-            && existing.value().status == WithdrawalStatus::Failed
-        {
-            let write_tx = ctx.database.write()?;
-            {
-                let mut withdrawals_table = write_tx.open_table(WITHDRAWALS)?;
-                withdrawals_table.insert(&key, &WithdrawalRecord::pending())?;
-            }
-            write_tx.commit()?;
-            tracing::info!(
-                "Reused failed withdrawal state for retry without reburning notes: {}",
-                &tx_hash[..std::cmp::min(16, tx_hash.len())]
-            );
-            return Ok(());
-        }
-    }
-
+    let spent_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // The status is read in the transaction that changes it, so the monitor
+    // cannot settle the withdrawal between the check and the write
     let write_tx = ctx.database.write()?;
-    {
-        let mut notes_table = write_tx.open_table(NOTES)?;
-
-        for note in &request.notes {
-            let sig_bytes: &[u8; 32] = note.signature.0.as_ref();
-            let signature = Signature::from(*sig_bytes);
-
-            if notes_table.get(signature)?.is_some() {
-                return Err(Error::AlreadySpent { signature });
+    let retried = {
+        let mut withdrawals_table = write_tx.open_table(WITHDRAWALS)?;
+        let existing = withdrawals_table.get(&key)?.map(|v| v.value().status);
+        match existing {
+            Some(WithdrawalStatus::Completed) => {
+                return Err(Error::InvalidInput {
+                    reason: "Withdrawal already completed".to_string(),
+                });
+            }
+            // Refunded notes were reissued; the transaction cannot land
+            Some(WithdrawalStatus::Refunded) => {
+                return Err(Error::InvalidInput {
+                    reason: "Withdrawal already refunded".to_string(),
+                });
+            }
+            Some(WithdrawalStatus::Failed) => {
+                withdrawals_table.insert(&key, WithdrawalRecord::pending())?;
+
+                let mut submissions =
+                    write_tx.open_table(WITHDRAWAL_SUBMISSIONS)?;
+                if submissions.get(&key)?.is_none() {
+                    submissions.insert(&key, submission_bytes.as_slice())?;
+                }
+                true
+            }
+            Some(WithdrawalStatus::Pending) | None => {
+                let mut notes_table = write_tx.open_table(NOTES)?;
+                let mut spent_at_table = write_tx.open_table(NOTES_SPENT_AT)?;
+                let mut by_keyset_table =
+                    write_tx.open_table(NOTES_BY_KEYSET)?;
+                let keyset = ctx.keypair.public_key.0;
+
+                for note in &request.notes {
+                    let sig_bytes: &[u8; 32] = note.signature.0.as_ref();
+                    let signature = Signature::from(*sig_bytes);
+
+                    if notes_table.get(signature)?.is_some()
+                        || ctx.database.is_archived(&signature)?
+                    {
+                        return Err(Error::AlreadySpent { signature });
+                    }
+
+                    notes_table.insert(signature, true)?;
+                    spent_at_table.insert((spent_at, signature), keyset)?;
+                    by_keyset_table.insert((keyset, signature), spent_at)?;
+                }
+
+                withdrawals_table.insert(&key, WithdrawalRecord::pending())?;
+
+                let mut submissions =
+                    write_tx.open_table(WITHDRAWAL_SUBMISSIONS)?;
+                submissions.insert(&key, submission_bytes.as_slice())?;
+                false
             }
-
-            notes_table.insert(signature, true)?;
         }
-
-        let mut withdrawals_table = write_tx.open_table(WITHDRAWALS)?;
-        withdrawals_table.insert(&key, &WithdrawalRecord::pending())?;
-    }
+    };
+    write_tx.append_event(
+        EventSubject::withdrawal(tx_hash),
+        EventKind::WithdrawalPending,
+    )?;
 
     write_tx.commit()?;
 
+    if retried {
+        tracing::info!(
+            "Reused failed withdrawal state for retry without reburning notes: {}",
+            &tx_hash[..std::cmp::min(16, tx_hash.len())]
+        );
+        return Ok(());
+    }
+
     tracing::info!(
         "Burned {} notes and recorded pending withdrawal {}",
         request.notes.len(),
@@ -81,6 +129,10 @@ pub(super) fn mark_withdrawal_failed(
         let record = WithdrawalRecord::failed();
         withdrawals_table.insert(key, &record)?;
     }
+    write_tx.append_event(
+        EventSubject::withdrawal(tx_hash),
+        EventKind::WithdrawalFailed,
+    )?;
 
     write_tx.commit()?;
     Ok(())
@@ -91,44 +143,7 @@ pub(super) fn mark_withdrawal_completed(
     tx_hash: &str,
     consumed_deposits: &[mugraph_core::types::UtxoRef],
 ) -> Result<(), Error> {
-    let write_tx = ctx.database.write()?;
-
-    {
-        let mut withdrawals_table = write_tx.open_table(WITHDRAWALS)?;
-
-        let network_byte = ctx.config.network_byte();
-        let key = crate::tx_ids::parse_withdrawal_key(tx_hash, network_byte)?;
-
-        let existing = withdrawals_table.get(&key)?.map(|v| v.value());
-        let Some(existing) = existing else {
-            return Err(Error::InvalidInput {
-                reason: "Pending withdrawal not found for completion"
-                    .to_string(),
-            });
-        };
-
-        if existing.status == WithdrawalStatus::Completed {
-            return Err(Error::InvalidInput {
-                reason: "Withdrawal already completed".to_string(),
-            });
-        }
-
-        withdrawals_table.insert(key, &WithdrawalRecord::completed())?;
-
-        let mut deposits_table = write_tx.open_table(DEPOSITS)?;
-        for utxo_ref in consumed_deposits {
-            let existing_record =
-                deposits_table.get(utxo_ref)?.map(|v| v.value());
-            if let Some(mut record) = existing_record {
-                record.spent = true;
-                deposits_table.insert(utxo_ref, &record)?;
-            }
-        }
-    }
-
-    write_tx.commit()?;
-
-    tracing::info!("Marked withdrawal {} as completed", tx_hash);
-
-    Ok(())
+    let network_byte = ctx.config.network_byte();
+    let key = crate::tx_ids::parse_withdrawal_key(tx_hash, network_byte)?;
+    complete_withdrawal(&ctx.database, &key, tx_hash, consumed_deposits)
 }
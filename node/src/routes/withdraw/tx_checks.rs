@@ -5,7 +5,10 @@ use mugraph_core::{error::Error, types::BlindSignature};
 use whisky_csl::csl;
 
 use super::ParsedWithdrawalTx;
-use crate::network::CardanoNetwork;
+use crate::{
+    network::CardanoNetwork,
+    provider::{ProtocolParams, declared_ex_units},
+};
 
 pub(super) fn validate_parsed_fee(
     parsed_tx: &ParsedWithdrawalTx,
@@ -59,6 +62,72 @@ fn max_acceptable_fee(max_fee_lovelace: u64, tolerance_pct: u8) -> u64 {
     max_fee_lovelace.saturating_mul(tolerance_factor) / 100
 }
 
+/// Check the signed transaction against the current protocol parameters:
+/// its fee must cover the minimum for its size and declared execution
+/// budget, plus `ref_script_bytes` of reference scripts, without overpaying
+/// it by more than `tolerance_pct`, and every output must hold the minimum
+/// ADA for its size.
+pub(super) fn validate_fee_and_min_ada(
+    signed_cbor: &[u8],
+    params: &ProtocolParams,
+    ref_script_bytes: u64,
+    tolerance_pct: u8,
+) -> Result<u64, Error> {
+    let tx =
+        csl::Transaction::from_bytes(signed_cbor.to_vec()).map_err(|e| {
+            Error::Internal {
+                reason: format!("Failed to parse signed transaction: {}", e),
+            }
+        })?;
+
+    let fee = extract_transaction_fee_from_tx(&tx)?;
+    let size = signed_cbor.len() as u64;
+    let min_fee = params.min_fee(&tx, size, ref_script_bytes);
+    let (mem, steps) = declared_ex_units(&tx);
+    if fee < min_fee {
+        return Err(Error::InvalidInput {
+            reason: format!(
+                "Fee {} lovelace is below the minimum {} lovelace for {} bytes, {} mem / {} steps of execution and {} bytes of reference scripts",
+                fee, min_fee, size, mem, steps, ref_script_bytes
+            ),
+        });
+    }
+
+    let max_fee = max_acceptable_fee(min_fee, tolerance_pct);
+    if fee > max_fee {
+        return Err(Error::InvalidInput {
+            reason: format!(
+                "Fee {} lovelace overpays the minimum {} lovelace by more than {}% (at most {} lovelace)",
+                fee, min_fee, tolerance_pct, max_fee
+            ),
+        });
+    }
+
+    for (index, output) in (&tx.body().outputs()).into_iter().enumerate() {
+        let min_ada =
+            params.min_ada(output).map_err(|e| Error::InvalidInput {
+                reason: format!("Output {}: {}", index, e),
+            })?;
+        let coin = u64::from(output.amount().coin());
+        if coin < min_ada {
+            return Err(Error::InvalidInput {
+                reason: format!(
+                    "Output {} holds {} lovelace, below the minimum {} lovelace for its size",
+                    index, coin, min_ada
+                ),
+            });
+        }
+    }
+
+    tracing::info!(
+        "Transaction fee: {} lovelace (minimum: {}, tolerance: {}%)",
+        fee,
+        min_fee,
+        tolerance_pct
+    );
+    Ok(fee)
+}
+
 pub(super) fn validate_transaction_balance_with_parsed_tx(
     parsed_tx: &ParsedWithdrawalTx,
     input_totals: &HashMap<String, u128>,
@@ -301,7 +370,7 @@ fn validate_withdraw_intent_metadata_from_tx(
                 if let Ok(h_txt) = val.as_text() {
                     type Blake2b256 =
                         blake2::Blake2b<blake2::digest::consts::U32>;
-                    let h = Blake2b256::digest(&tx.body().to_bytes());
+                    let h = Blake2b256::digest(tx.body().to_bytes());
                     let mut h_arr = [0u8; 32];
                     h_arr.copy_from_slice(&h);
                     let expected_hex = hex::encode(h_arr);
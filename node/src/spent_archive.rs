@@ -0,0 +1,840 @@
+//! Immutable archive of spent note signatures.
+//!
+//! Old rows from the `NOTES` table, and rows signed by a keyset the node no
+//! longer uses, are moved into write-once segment files so the live database
+//! stays small. Each segment holds a sorted list of
+//! signatures split into fixed-size blocks, a sparse block index for exact
+//! lookups, and a Bloom filter that answers most negative lookups without
+//! touching the file. Each new segment is merged into the one before it
+//! while that one holds less than twice as many signatures, so a lookup
+//! probes a number of filters that grows with the log of the archive size.
+//!
+//! Blocks are front coded. Neighbouring signatures in a sorted segment of
+//! `n` share about `log2(n)` leading bits, so each is stored as the number
+//! of leading bytes it shares with the one before it followed by the rest.
+//! Beyond that the signatures are uniformly random, which leaves nothing
+//! for general-purpose compression to remove. Segments written before
+//! blocks were front coded (`MGSPENT2`) hold the signatures as-is and are
+//! still read, and merging rewrites them front coded.
+//!
+//! Layout (all integers little endian):
+//!
+//! ```text
+//! magic[8] | count u64 | created_at u64 | filter_hashes u32 | filter_bits u64
+//! | filter bytes | block_count u32 | (first_key[32], offset u64, len u32)*
+//! | blocks... | blake3(everything before)[32]
+//!
+//! block: (shared u8, signature[shared..32])*
+//! ```
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write as _,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use metrics::counter;
+use mugraph_core::{
+    error::Error,
+    types::{PublicKey, Signature},
+};
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::{database::Database, supervisor::Shutdown};
+
+const SEGMENT_MAGIC: &[u8; 8] = b"MGSPENT3";
+/// Segments whose blocks hold whole signatures
+const RAW_SEGMENT_MAGIC: &[u8; 8] = b"MGSPENT2";
+const SEGMENT_EXTENSION: &str = "seg";
+const BLOCK_ENTRIES: usize = 64;
+const FILTER_BITS_PER_ENTRY: u64 = 10;
+const FILTER_HASHES: u32 = 7;
+const SIGNATURE_LEN: usize = 32;
+const INDEX_ENTRY_LEN: usize = SIGNATURE_LEN + 8 + 4;
+const HEADER_LEN: usize = 8 + 8 + 8 + 4 + 8;
+/// Segments at least this large are left as they are, so merging never
+/// holds more than this many signatures in memory
+const MAX_MERGED_ENTRIES: u64 = 8 * 1024 * 1024;
+
+const METRIC_LOOKUP: &str = "mugraph.node.spent_archive.lookup";
+const METRIC_FILTER_NEGATIVE: &str =
+    "mugraph.node.spent_archive.filter_negative";
+const METRIC_FILTER_FALSE_POSITIVE: &str =
+    "mugraph.node.spent_archive.filter_false_positive";
+const METRIC_SEGMENTS_WRITTEN: &str =
+    "mugraph.node.spent_archive.segments_written";
+const METRIC_SEGMENTS_MERGED: &str =
+    "mugraph.node.spent_archive.segments_merged";
+
+fn archive_error(reason: impl Into<String>) -> Error {
+    Error::StorageError {
+        kind: "SpentArchive".to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Filter bit positions of one signature, from a single blake3 digest that
+/// every segment's filter shares.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    h1: u64,
+    h2: u64,
+}
+
+impl Probe {
+    fn new(signature: &Signature) -> Self {
+        let digest = blake3::hash(&signature.0);
+        let bytes = digest.as_bytes();
+        Self {
+            h1: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            h2: u64::from_le_bytes(bytes[8..16].try_into().unwrap()) | 1,
+        }
+    }
+}
+
+/// Probabilistic membership filter fronting a segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BloomFilter {
+    bits: Vec<u8>,
+    bit_count: u64,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    fn with_capacity(entries: usize) -> Self {
+        let bit_count = (entries as u64)
+            .saturating_mul(FILTER_BITS_PER_ENTRY)
+            .max(64);
+        Self {
+            bits: vec![0u8; bit_count.div_ceil(8) as usize],
+            bit_count,
+            hashes: FILTER_HASHES,
+        }
+    }
+
+    fn positions(&self, probe: Probe) -> impl Iterator<Item = u64> {
+        let bit_count = self.bit_count;
+
+        (0..self.hashes as u64).map(move |i| {
+            probe.h1.wrapping_add(i.wrapping_mul(probe.h2)) % bit_count
+        })
+    }
+
+    fn insert(&mut self, probe: Probe) {
+        let positions: Vec<u64> = self.positions(probe).collect();
+        for bit in positions {
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    fn may_contain(&self, probe: Probe) -> bool {
+        self.positions(probe)
+            .all(|bit| self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BlockHandle {
+    first_key: [u8; SIGNATURE_LEN],
+    offset: u64,
+    len: u32,
+}
+
+/// A single immutable segment file.
+#[derive(Debug)]
+pub struct Segment {
+    path: PathBuf,
+    file: File,
+    count: u64,
+    created_at: u64,
+    filter: BloomFilter,
+    blocks: Vec<BlockHandle>,
+    front_coded: bool,
+}
+
+impl Segment {
+    /// Write `signatures` into a new segment at `path`.
+    ///
+    /// The file is written to a temporary sibling and renamed into place, so a
+    /// crash never leaves a partially written segment behind.
+    pub fn write(
+        path: impl AsRef<Path>,
+        mut signatures: Vec<Signature>,
+        created_at: u64,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        signatures.sort_unstable();
+        signatures.dedup();
+
+        let mut filter = BloomFilter::with_capacity(signatures.len());
+        for signature in &signatures {
+            filter.insert(Probe::new(signature));
+        }
+
+        let mut encoded_blocks = Vec::new();
+        let mut handles = Vec::new();
+        for chunk in signatures.chunks(BLOCK_ENTRIES) {
+            let block = encode_block(chunk);
+            handles.push(BlockHandle {
+                first_key: chunk[0].0,
+                offset: 0,
+                len: block.len() as u32,
+            });
+            encoded_blocks.push(block);
+        }
+
+        let blocks_start = HEADER_LEN
+            + filter.bits.len()
+            + 4
+            + handles.len() * INDEX_ENTRY_LEN;
+        let mut offset = blocks_start as u64;
+        for handle in &mut handles {
+            handle.offset = offset;
+            offset += handle.len as u64;
+        }
+
+        let mut bytes = Vec::with_capacity(offset as usize + 32);
+        bytes.extend_from_slice(SEGMENT_MAGIC);
+        bytes.extend_from_slice(&(signatures.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&created_at.to_le_bytes());
+        bytes.extend_from_slice(&filter.hashes.to_le_bytes());
+        bytes.extend_from_slice(&filter.bit_count.to_le_bytes());
+        bytes.extend_from_slice(&filter.bits);
+        bytes.extend_from_slice(&(handles.len() as u32).to_le_bytes());
+        for handle in &handles {
+            bytes.extend_from_slice(&handle.first_key);
+            bytes.extend_from_slice(&handle.offset.to_le_bytes());
+            bytes.extend_from_slice(&handle.len.to_le_bytes());
+        }
+        for block in &encoded_blocks {
+            bytes.extend_from_slice(block);
+        }
+        let checksum = blake3::hash(&bytes);
+        bytes.extend_from_slice(checksum.as_bytes());
+
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut tmp = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&tmp_path)?;
+            tmp.write_all(&bytes)?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        if let Some(parent) = path.parent()
+            && let Ok(dir) = File::open(parent)
+        {
+            dir.sync_all()?;
+        }
+
+        counter!(METRIC_SEGMENTS_WRITTEN).increment(1);
+        Self::open(path)
+    }
+
+    /// Open an existing segment, verifying its checksum and loading the
+    /// filter and block index into memory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = fs::read(&path)?;
+        let corrupt = |what: &str| {
+            archive_error(format!(
+                "segment {} is corrupt: {what}",
+                path.display()
+            ))
+        };
+
+        if bytes.len() < HEADER_LEN + 4 + 32 {
+            return Err(corrupt("bad header"));
+        }
+        let front_coded = match &bytes[..8] {
+            magic if magic == SEGMENT_MAGIC => true,
+            magic if magic == RAW_SEGMENT_MAGIC => false,
+            _ => return Err(corrupt("bad header")),
+        };
+
+        let (body, checksum) = bytes.split_at(bytes.len() - 32);
+        if blake3::hash(body).as_bytes() != checksum {
+            return Err(corrupt("checksum mismatch"));
+        }
+
+        let mut cursor = Cursor::new(body, 8);
+        let count = cursor.u64().ok_or_else(|| corrupt("count"))?;
+        let created_at = cursor.u64().ok_or_else(|| corrupt("created_at"))?;
+        let hashes = cursor.u32().ok_or_else(|| corrupt("filter hashes"))?;
+        let bit_count = cursor.u64().ok_or_else(|| corrupt("filter size"))?;
+        if bit_count == 0 {
+            return Err(corrupt("empty filter"));
+        }
+        let filter_bits = cursor
+            .take(bit_count.div_ceil(8) as usize)
+            .ok_or_else(|| corrupt("filter bits"))?
+            .to_vec();
+        let block_count = cursor.u32().ok_or_else(|| corrupt("block count"))?;
+
+        let mut blocks = Vec::with_capacity(block_count as usize);
+        for _ in 0..block_count {
+            let first_key = cursor
+                .take(SIGNATURE_LEN)
+                .ok_or_else(|| corrupt("block index"))?
+                .try_into()
+                .unwrap();
+            let offset = cursor.u64().ok_or_else(|| corrupt("block offset"))?;
+            let len = cursor.u32().ok_or_else(|| corrupt("block length"))?;
+            if offset.saturating_add(len as u64) > body.len() as u64 {
+                return Err(corrupt("block out of bounds"));
+            }
+            blocks.push(BlockHandle {
+                first_key,
+                offset,
+                len,
+            });
+        }
+
+        Ok(Self {
+            file: File::open(&path)?,
+            path,
+            count,
+            created_at,
+            filter: BloomFilter {
+                bits: filter_bits,
+                bit_count,
+                hashes,
+            },
+            blocks,
+            front_coded,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Exact membership check, consulting the filter first.
+    pub fn contains(&self, signature: &Signature) -> Result<bool, Error> {
+        self.contains_probed(signature, Probe::new(signature))
+    }
+
+    fn contains_probed(
+        &self,
+        signature: &Signature,
+        probe: Probe,
+    ) -> Result<bool, Error> {
+        if !self.filter.may_contain(probe) {
+            counter!(METRIC_FILTER_NEGATIVE).increment(1);
+            return Ok(false);
+        }
+
+        let idx = self
+            .blocks
+            .partition_point(|block| block.first_key <= signature.0);
+        let Some(block) = idx.checked_sub(1).map(|i| self.blocks[i]) else {
+            counter!(METRIC_FILTER_FALSE_POSITIVE).increment(1);
+            return Ok(false);
+        };
+
+        let found = self.read_block(block)?.binary_search(&signature.0).is_ok();
+
+        if !found {
+            counter!(METRIC_FILTER_FALSE_POSITIVE).increment(1);
+        }
+
+        Ok(found)
+    }
+
+    /// Decode every signature stored in the segment, in sorted order.
+    pub fn signatures(&self) -> Result<Vec<Signature>, Error> {
+        let mut out = Vec::with_capacity(self.count as usize);
+        for block in &self.blocks {
+            out.extend(self.read_block(*block)?.into_iter().map(Signature));
+        }
+        Ok(out)
+    }
+
+    fn read_block(
+        &self,
+        block: BlockHandle,
+    ) -> Result<Vec<[u8; SIGNATURE_LEN]>, Error> {
+        let mut buf = vec![0u8; block.len as usize];
+        self.file.read_exact_at(&mut buf, block.offset)?;
+
+        let keys = if self.front_coded {
+            decode_block(&buf)
+        } else {
+            decode_raw_block(&buf)
+        };
+        keys.ok_or_else(|| {
+            archive_error(format!(
+                "segment {} has a corrupt block at offset {}",
+                self.path.display(),
+                block.offset
+            ))
+        })
+    }
+}
+
+/// Front code a sorted run of distinct signatures: each is stored as the
+/// number of leading bytes it shares with the one before it, then the rest.
+fn encode_block(signatures: &[Signature]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(signatures.len() * (SIGNATURE_LEN + 1));
+    let mut previous: Option<&[u8; SIGNATURE_LEN]> = None;
+    for signature in signatures {
+        let shared = previous.map_or(0, |previous| {
+            previous
+                .iter()
+                .zip(&signature.0)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        out.push(shared as u8);
+        out.extend_from_slice(&signature.0[shared..]);
+        previous = Some(&signature.0);
+    }
+    out
+}
+
+fn decode_block(bytes: &[u8]) -> Option<Vec<[u8; SIGNATURE_LEN]>> {
+    let mut keys: Vec<[u8; SIGNATURE_LEN]> = Vec::new();
+    let mut rest = bytes;
+    while let Some((&shared, tail)) = rest.split_first() {
+        let shared = shared as usize;
+        // Distinct signatures share at most all but one byte
+        if shared >= SIGNATURE_LEN || (keys.is_empty() && shared > 0) {
+            return None;
+        }
+        let (suffix, tail) = tail.split_at_checked(SIGNATURE_LEN - shared)?;
+
+        let mut key = keys.last().copied().unwrap_or([0; SIGNATURE_LEN]);
+        key[shared..].copy_from_slice(suffix);
+        keys.push(key);
+        rest = tail;
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+fn decode_raw_block(bytes: &[u8]) -> Option<Vec<[u8; SIGNATURE_LEN]>> {
+    let (keys, rest) = bytes.as_chunks::<SIGNATURE_LEN>();
+    (!keys.is_empty() && rest.is_empty()).then(|| keys.to_vec())
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8], pos: usize) -> Self {
+        Self { bytes, pos }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let slice = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(slice)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+/// Set of archived segments living in one directory.
+#[derive(Debug)]
+pub struct SpentArchive {
+    dir: PathBuf,
+    segments: RwLock<Vec<Segment>>,
+    next_segment: AtomicUsize,
+    /// Held while segments are added or merged, so only lookups share the
+    /// list with a writer
+    writer: Mutex<()>,
+}
+
+impl SpentArchive {
+    /// Open (creating if needed) the archive directory and load every segment
+    /// found in it.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(SEGMENT_EXTENSION) => paths.push(path),
+                // Leftover from a crash mid-write; the rows it would have held
+                // are still in the live table.
+                Some("tmp") => fs::remove_file(&path)?,
+                _ => {}
+            }
+        }
+        paths.sort();
+        let next_segment = paths
+            .iter()
+            .filter_map(|path| segment_number(path))
+            .max()
+            .map_or(0, |n| n + 1);
+
+        let segments = paths
+            .iter()
+            .map(Segment::open)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        tracing::info!(
+            dir = %dir.display(),
+            segments = segments.len(),
+            "loaded spent-set archive"
+        );
+
+        Ok(Self {
+            dir,
+            next_segment: AtomicUsize::new(next_segment),
+            segments: RwLock::new(segments),
+            writer: Mutex::new(()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn segment_count(&self) -> Result<usize, Error> {
+        Ok(self.segments.read()?.len())
+    }
+
+    /// Total number of archived signatures across all segments.
+    pub fn len(&self) -> Result<u64, Error> {
+        Ok(self.segments.read()?.iter().map(Segment::len).sum())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Whether `signature` has been archived as spent.
+    pub fn contains(&self, signature: &Signature) -> Result<bool, Error> {
+        counter!(METRIC_LOOKUP).increment(1);
+
+        let probe = Probe::new(signature);
+        for segment in self.segments.read()?.iter().rev() {
+            if segment.contains_probed(signature, probe)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Persist `signatures` as a new segment and make it visible to lookups.
+    ///
+    /// The segment is written and synced before the lock is taken, so
+    /// lookups only wait for it to be appended to the list. It is then
+    /// merged with the segments before it that are less than twice its
+    /// size.
+    pub fn add_segment(
+        &self,
+        signatures: Vec<Signature>,
+        created_at: u64,
+    ) -> Result<(), Error> {
+        if signatures.is_empty() {
+            return Ok(());
+        }
+
+        let _writer = self.writer.lock()?;
+        let segment = Segment::write(
+            self.dir.join(self.segment_name(created_at)),
+            signatures,
+            created_at,
+        )?;
+
+        tracing::info!(
+            path = %segment.path().display(),
+            signatures = segment.len(),
+            "wrote spent-set archive segment"
+        );
+
+        self.segments.write()?.push(segment);
+        self.compact()
+    }
+
+    /// Merge the newest segment into the one before it while that one holds
+    /// less than twice as many signatures.
+    ///
+    /// The merged segment is durable before it replaces the two, and their
+    /// files are removed only after that. A crash in between leaves the
+    /// signatures in more than one segment, which lookups do not mind.
+    fn compact(&self) -> Result<(), Error> {
+        loop {
+            let (signatures, created_at) = {
+                let segments = self.segments.read()?;
+                let [.., older, newer] = segments.as_slice() else {
+                    return Ok(());
+                };
+                if older.len() >= 2 * newer.len()
+                    || older.len() + newer.len() > MAX_MERGED_ENTRIES
+                {
+                    return Ok(());
+                }
+
+                let mut signatures = older.signatures()?;
+                signatures.extend(newer.signatures()?);
+                (signatures, newer.created_at())
+            };
+
+            let merged = Segment::write(
+                self.dir.join(self.segment_name(created_at)),
+                signatures,
+                created_at,
+            )?;
+            let replaced = {
+                let mut segments = self.segments.write()?;
+                let at = segments.len() - 2;
+                let replaced = segments.split_off(at);
+                segments.push(merged);
+                replaced
+            };
+            for segment in &replaced {
+                fs::remove_file(segment.path())?;
+            }
+
+            counter!(METRIC_SEGMENTS_MERGED).increment(1);
+            tracing::info!(
+                merged = replaced.len(),
+                signatures = replaced.iter().map(Segment::len).sum::<u64>(),
+                "merged spent-set archive segments"
+            );
+        }
+    }
+
+    /// File name of a new segment, ordered after every existing one
+    fn segment_name(&self, created_at: u64) -> String {
+        format!(
+            "{:020}-{:06}.{SEGMENT_EXTENSION}",
+            created_at,
+            self.next_segment.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+/// The sequence number in a segment's file name
+fn segment_number(path: &Path) -> Option<usize> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.rsplit_once('-')?.1.parse().ok()
+}
+
+/// Periodically move spent signatures older than `max_age`, or signed by a
+/// keyset other than `active_keyset`, out of the live `NOTES` table and into
+/// archive segments.
+pub async fn archiver_loop(
+    database: Arc<Database>,
+    tick: Duration,
+    max_age: Duration,
+    active_keyset: PublicKey,
+    shutdown: Shutdown,
+) {
+    let mut ticker = interval(tick);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown.wait() => break,
+            _ = ticker.tick() => {}
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let cutoff = now.saturating_sub(max_age.as_secs());
+
+        let db = database.clone();
+        match tokio::task::spawn_blocking(move || {
+            db.archive_spent_notes(cutoff, &active_keyset)
+        })
+        .await
+        {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::error!("spent-set archival failed: {}", e),
+            Err(e) => {
+                tracing::error!("spent-set archival task panicked: {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng, rngs::StdRng};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn random_signatures(count: usize, seed: u64) -> Vec<Signature> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                let mut bytes = [0u8; 32];
+                rng.fill_bytes(&mut bytes);
+                Signature(bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocks_roundtrip_and_reject_truncation() {
+        let mut signatures = random_signatures(BLOCK_ENTRIES, 1);
+        signatures.sort_unstable();
+
+        let encoded = encode_block(&signatures[..50]);
+        let expected: Vec<_> = signatures[..50].iter().map(|s| s.0).collect();
+        assert_eq!(decode_block(&encoded).unwrap(), expected);
+        assert!(decode_block(&encoded[..encoded.len() - 1]).is_none());
+        assert!(decode_block(&[]).is_none());
+    }
+
+    #[test]
+    fn blocks_store_only_what_neighbours_do_not_share() {
+        let mut first = [7u8; 32];
+        let mut second = first;
+        second[30] = 9;
+        first[31] = 1;
+        let signatures = [Signature(first), Signature(second)];
+
+        let encoded = encode_block(&signatures);
+        assert_eq!(encoded.len(), 1 + 32 + 1 + 2);
+        assert_eq!(
+            decode_block(&encoded).unwrap(),
+            vec![first, second],
+            "shared prefixes are restored from the previous signature"
+        );
+
+        // A sorted segment of random signatures comes out smaller than
+        // the signatures themselves
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.seg");
+        let segment =
+            Segment::write(&path, random_signatures(100_000, 7), 1).unwrap();
+        let stored: u64 = segment.blocks.iter().map(|b| b.len as u64).sum();
+        assert!(stored < 100_000 * 32, "{stored}");
+    }
+
+    #[test]
+    fn segment_answers_exact_membership() {
+        let dir = TempDir::new().unwrap();
+        let stored = random_signatures(1_000, 2);
+        let absent = random_signatures(1_000, 3);
+
+        let segment =
+            Segment::write(dir.path().join("a.seg"), stored.clone(), 42)
+                .unwrap();
+
+        assert_eq!(segment.len(), 1_000);
+        assert_eq!(segment.created_at(), 42);
+        for signature in &stored {
+            assert!(segment.contains(signature).unwrap());
+        }
+        for signature in &absent {
+            assert!(!segment.contains(signature).unwrap());
+        }
+
+        let mut expected = stored;
+        expected.sort_unstable();
+        assert_eq!(segment.signatures().unwrap(), expected);
+    }
+
+    #[test]
+    fn segment_open_rejects_tampered_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.seg");
+        Segment::write(&path, random_signatures(10, 4), 1).unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        let err = Segment::open(&path).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn archive_reloads_segments_and_discards_partial_writes() {
+        let dir = TempDir::new().unwrap();
+        let first = random_signatures(20, 5);
+        let second = random_signatures(20, 6);
+
+        {
+            let archive = SpentArchive::open(dir.path()).unwrap();
+            archive.add_segment(first.clone(), 10).unwrap();
+            archive.add_segment(second.clone(), 20).unwrap();
+            archive.add_segment(Vec::new(), 30).unwrap();
+            assert_eq!(archive.segment_count().unwrap(), 1);
+        }
+
+        fs::write(dir.path().join("partial.tmp"), b"junk").unwrap();
+
+        let archive = SpentArchive::open(dir.path()).unwrap();
+        assert_eq!(archive.segment_count().unwrap(), 1);
+        assert_eq!(archive.len().unwrap(), 40);
+        assert!(archive.contains(&first[3]).unwrap());
+        assert!(archive.contains(&second[17]).unwrap());
+        assert!(!archive.contains(&Signature([9u8; 32])).unwrap());
+        assert!(!dir.path().join("partial.tmp").exists());
+    }
+
+    #[test]
+    fn segments_merge_while_the_older_is_under_twice_the_size() {
+        let dir = TempDir::new().unwrap();
+        let batches: Vec<_> =
+            (0..5).map(|i| random_signatures(10, 10 + i)).collect();
+
+        {
+            let archive = SpentArchive::open(dir.path()).unwrap();
+            let mut counts = Vec::new();
+            for (i, batch) in batches.iter().enumerate() {
+                archive.add_segment(batch.clone(), i as u64).unwrap();
+                counts.push(archive.segment_count().unwrap());
+            }
+            // 10, 20, 20+10, 40, 40+10
+            assert_eq!(counts, [1, 1, 2, 1, 2]);
+        }
+
+        let archive = SpentArchive::open(dir.path()).unwrap();
+        assert_eq!(archive.segment_count().unwrap(), 2);
+        assert_eq!(archive.len().unwrap(), 50);
+        for batch in &batches {
+            assert!(archive.contains(&batch[7]).unwrap());
+        }
+
+        // New segments are named after every existing one
+        archive.add_segment(random_signatures(10, 20), 4).unwrap();
+        assert_eq!(archive.segment_count().unwrap(), 2);
+        assert_eq!(archive.len().unwrap(), 60);
+        let files = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(files, 2);
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use mugraph_core::{Signal, error::Error};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::{
+    sync::Notify,
+    task::{AbortHandle, JoinHandle},
+    time::{Instant, sleep, timeout},
+};
+
+/// Cooperative shutdown flag shared by the supervisor and its workers.
+///
+/// Workers check it between passes, so a pass that has already started is
+/// always allowed to finish.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    signal: Signal,
+    notify: Arc<Notify>,
+}
+
+impl Shutdown {
+    pub fn trigger(&self) {
+        self.signal.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.signal.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once [`Shutdown::trigger`] has been called.
+    pub async fn wait(&self) {
+        loop {
+            // Register before checking the flag so a concurrent trigger is
+            // never missed.
+            let notified = self.notify.notified();
+            if self.is_triggered() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Running,
+    /// Waiting out the backoff after a panic or unexpected exit
+    Restarting,
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TaskState {
+    pub status: TaskStatus,
+    pub restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Aborts the current attempt if the supervisor loop itself is aborted.
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+type ShutdownHook = Box<dyn FnOnce() -> Result<(), Error> + Send>;
+
+/// Owns the node's background workers: restarts them with exponential
+/// backoff when they panic, and stops them in order on shutdown.
+pub struct Supervisor {
+    shutdown: Shutdown,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    states: Arc<Mutex<BTreeMap<&'static str, TaskState>>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    hooks: Mutex<Vec<(&'static str, ShutdownHook)>>,
+    stopped: AtomicBool,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("shutdown", &self.shutdown.is_triggered())
+            .field("tasks", &self.states())
+            .finish()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::with_backoff(Duration::from_secs(1), Duration::from_secs(60))
+    }
+
+    pub fn with_backoff(
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        Self {
+            shutdown: Shutdown::default(),
+            initial_backoff,
+            max_backoff,
+            states: Default::default(),
+            handles: Default::default(),
+            hooks: Default::default(),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// The shutdown flag handed to every worker.
+    pub fn shutdown_signal(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_triggered()
+    }
+
+    /// Snapshot of every supervised task.
+    pub fn states(&self) -> BTreeMap<&'static str, TaskState> {
+        self.states.lock().expect("task states poisoned").clone()
+    }
+
+    /// Run `worker` under supervision.
+    ///
+    /// `worker` is called again to build a fresh future after every panic or
+    /// premature exit. A clean return after shutdown was triggered stops it.
+    pub fn spawn<F, Fut>(&self, name: &'static str, worker: F)
+    where
+        F: Fn(Shutdown) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        let states = self.states.clone();
+        let initial_backoff = self.initial_backoff;
+        let max_backoff = self.max_backoff;
+
+        let set_state = move |status, restarts, last_error| {
+            states.lock().expect("task states poisoned").insert(
+                name,
+                TaskState {
+                    status,
+                    restarts,
+                    last_error,
+                },
+            );
+        };
+        set_state(TaskStatus::Running, 0, None);
+
+        let handle = tokio::spawn(async move {
+            let mut restarts = 0u32;
+            let mut backoff = initial_backoff;
+
+            loop {
+                let started = Instant::now();
+                // Running each attempt as its own task turns a panic into a
+                // `JoinError` instead of tearing down the supervisor loop.
+                let attempt = tokio::spawn(worker(shutdown.clone()));
+                let _abort = AbortOnDrop(attempt.abort_handle());
+                let outcome = attempt.await;
+
+                if shutdown.is_triggered() {
+                    let last_error = outcome.err().map(|e| e.to_string());
+                    set_state(TaskStatus::Stopped, restarts, last_error);
+                    tracing::info!(task = name, "background task stopped");
+                    return;
+                }
+
+                let error = match outcome {
+                    Ok(()) => "exited unexpectedly".to_string(),
+                    Err(e) => e.to_string(),
+                };
+
+                // A worker that stayed up for a full backoff period was
+                // healthy; start counting from scratch.
+                if started.elapsed() >= max_backoff {
+                    backoff = initial_backoff;
+                }
+
+                restarts += 1;
+                metrics::counter!(
+                    "mugraph.node.task_restarts_total",
+                    "task" => name,
+                )
+                .increment(1);
+                tracing::error!(
+                    task = name,
+                    restarts,
+                    backoff_ms = backoff.as_millis() as u64,
+                    "background task failed: {error}; restarting"
+                );
+                set_state(
+                    TaskStatus::Restarting,
+                    restarts,
+                    Some(error.clone()),
+                );
+
+                tokio::select! {
+                    _ = sleep(backoff) => {}
+                    _ = shutdown.wait() => {
+                        set_state(TaskStatus::Stopped, restarts, Some(error));
+                        return;
+                    }
+                }
+
+                backoff = (backoff * 2).min(max_backoff);
+                set_state(TaskStatus::Running, restarts, Some(error));
+            }
+        });
+
+        self.handles
+            .lock()
+            .expect("task handles poisoned")
+            .push(handle);
+    }
+
+    /// Run `hook` once every worker has stopped, in registration order.
+    pub fn on_shutdown(
+        &self,
+        name: &'static str,
+        hook: impl FnOnce() -> Result<(), Error> + Send + 'static,
+    ) {
+        self.hooks
+            .lock()
+            .expect("shutdown hooks poisoned")
+            .push((name, Box::new(hook)));
+    }
+
+    /// Signal every worker, wait up to `grace` for their current pass to
+    /// finish, then run the shutdown hooks.
+    ///
+    /// Workers still running after `grace` are aborted. Calling this more
+    /// than once is a no-op.
+    pub async fn shutdown(&self, grace: Duration) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        self.shutdown.trigger();
+
+        let handles = std::mem::take(
+            &mut *self.handles.lock().expect("task handles poisoned"),
+        );
+        let deadline = Instant::now() + grace;
+        for mut handle in handles {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if timeout(remaining, &mut handle).await.is_err() {
+                tracing::warn!("background task ignored shutdown; aborting it");
+                handle.abort();
+            }
+        }
+
+        let hooks = std::mem::take(
+            &mut *self.hooks.lock().expect("shutdown hooks poisoned"),
+        );
+        for (name, hook) in hooks {
+            if let Err(e) = hook() {
+                tracing::error!(hook = name, "shutdown hook failed: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    fn fast_supervisor() -> Supervisor {
+        Supervisor::with_backoff(
+            Duration::from_millis(10),
+            Duration::from_millis(40),
+        )
+    }
+
+    #[tokio::test]
+    async fn panicking_worker_is_restarted_with_backoff() {
+        let supervisor = fast_supervisor();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        supervisor.spawn("flaky", move |shutdown| {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("boom");
+                }
+                shutdown.wait().await;
+            }
+        });
+
+        for _ in 0..100 {
+            if runs.load(Ordering::SeqCst) >= 3 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let state = &supervisor.states()["flaky"];
+        assert_eq!(state.status, TaskStatus::Running);
+        assert_eq!(state.restarts, 2);
+        assert!(state.last_error.as_deref().unwrap().contains("panic"));
+
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(supervisor.states()["flaky"].status, TaskStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn shutdown_lets_the_current_pass_finish_before_hooks_run() {
+        let supervisor = fast_supervisor();
+        let finished_pass = Arc::new(AtomicBool::new(false));
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let started_tx = Mutex::new(Some(started_tx));
+
+        let finished = finished_pass.clone();
+        supervisor.spawn("worker", move |shutdown| {
+            let finished = finished.clone();
+            let started = started_tx.lock().unwrap().take();
+            async move {
+                if let Some(started) = started {
+                    let _ = started.send(());
+                }
+                // One pass of work that must not be cut short
+                sleep(Duration::from_millis(100)).await;
+                finished.store(true, Ordering::SeqCst);
+                shutdown.wait().await;
+            }
+        });
+
+        let hook_saw_finished = Arc::new(AtomicBool::new(false));
+        let (finished, saw) =
+            (finished_pass.clone(), hook_saw_finished.clone());
+        supervisor.on_shutdown("check", move || {
+            saw.store(finished.load(Ordering::SeqCst), Ordering::SeqCst);
+            Ok(())
+        });
+
+        started_rx.await.unwrap();
+        supervisor.shutdown(Duration::from_secs(5)).await;
+
+        assert!(finished_pass.load(Ordering::SeqCst));
+        assert!(hook_saw_finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn workers_ignoring_shutdown_are_aborted_after_grace() {
+        let supervisor = fast_supervisor();
+        supervisor.spawn("stubborn", |_| async {
+            sleep(Duration::from_secs(3600)).await;
+        });
+
+        let started = Instant::now();
+        supervisor.shutdown(Duration::from_millis(50)).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn wait_resolves_after_trigger() {
+        let shutdown = Shutdown::default();
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+
+        shutdown.trigger();
+        timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        // Already triggered: resolves immediately
+        shutdown.wait().await;
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::HashMap, future::Future, sync::OnceLock, time::Instant,
+};
+
+use axum::{Router, http::header, response::IntoResponse, routing::get};
+use metrics_exporter_prometheus::{
+    Matcher, PrometheusBuilder, PrometheusHandle, PrometheusRecorder,
+};
+use mugraph_core::error::Error;
+use opentelemetry::{propagation::TextMapPropagator, trace::TracerProvider};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider,
+};
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+const SERVICE_NAME: &str = "mugraph-node";
+const TRACEPARENT: &str = "traceparent";
+
+/// Bucket boundaries, in seconds, for every `*_seconds` histogram.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+    10.0, 30.0,
+];
+
+static HANDLE: OnceLock<Result<PrometheusHandle, String>> = OnceLock::new();
+
+fn builder() -> PrometheusBuilder {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )
+        .expect("latency buckets are not empty")
+}
+
+/// Build a recorder that is not installed globally, for tests.
+pub fn build_recorder() -> PrometheusRecorder {
+    builder().build_recorder()
+}
+
+/// Install the process-wide Prometheus recorder.
+///
+/// Only one global recorder can exist, so repeated calls return the handle
+/// created by the first one.
+pub fn install_recorder() -> Result<PrometheusHandle, Error> {
+    HANDLE
+        .get_or_init(|| builder().install_recorder().map_err(|e| e.to_string()))
+        .clone()
+        .map_err(|reason| Error::Internal {
+            reason: format!("failed to install metrics recorder: {reason}"),
+        })
+}
+
+/// Router serving `/metrics` in the Prometheus text format.
+pub fn metrics_router(handle: PrometheusHandle) -> Router {
+    Router::new().route(
+        "/metrics",
+        get(move || async move {
+            handle.run_upkeep();
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                handle.render(),
+            )
+                .into_response()
+        }),
+    )
+}
+
+/// Flushes and shuts down the OTLP exporter when dropped.
+#[must_use = "dropping the guard stops span export"]
+pub struct TracingGuard(Option<SdkTracerProvider>);
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.0.take()
+            && let Err(e) = provider.shutdown()
+        {
+            tracing::warn!("failed to flush OTLP spans: {e}");
+        }
+    }
+}
+
+/// Collector URL for trace export. `endpoint` is the collector base URL,
+/// e.g. `http://localhost:4318`.
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}/v1/traces")
+    }
+}
+
+/// Tracer provider that batches spans to an OTLP/HTTP collector.
+pub fn otlp_tracer_provider(
+    endpoint: &str,
+) -> Result<SdkTracerProvider, Error> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(endpoint))
+        .build()
+        .map_err(|e| Error::Internal {
+            reason: format!("failed to build OTLP exporter: {e}"),
+        })?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder().with_service_name(SERVICE_NAME).build(),
+        )
+        .build())
+}
+
+/// Install the global `tracing` subscriber: formatted logs, plus span export
+/// to `otlp_endpoint` when one is configured.
+pub fn init_tracing(
+    otlp_endpoint: Option<&str>,
+) -> Result<TracingGuard, Error> {
+    let registry = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer());
+
+    let Some(endpoint) = otlp_endpoint else {
+        registry.init();
+        return Ok(TracingGuard(None));
+    };
+
+    let provider = otlp_tracer_provider(endpoint)?;
+    registry
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer(SERVICE_NAME)),
+        )
+        .init();
+
+    Ok(TracingGuard(Some(provider)))
+}
+
+/// W3C `traceparent` for the current span, if it belongs to an exported trace.
+pub fn current_trace_context() -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new()
+        .inject_context(&Span::current().context(), &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Make `span` a child of the remote span described by `traceparent`.
+///
+/// Malformed or missing trace context leaves `span` where it is; tracing must
+/// never reject a message.
+pub fn set_remote_parent(span: &Span, traceparent: Option<&str>) {
+    let Some(traceparent) = traceparent else {
+        return;
+    };
+
+    let carrier =
+        HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let parent = TraceContextPropagator::new().extract(&carrier);
+    if let Err(e) = span.set_parent(parent) {
+        tracing::debug!("ignoring remote trace context: {e}");
+    }
+}
+
+/// Time a provider call and count its failures.
+pub async fn observe_provider_call<T, E, F>(
+    provider: &'static str,
+    operation: &'static str,
+    call: F,
+) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    let result = call
+        .instrument(tracing::info_span!("provider_call", provider, operation))
+        .await;
+
+    metrics::histogram!(
+        "mugraph.node.provider.request_duration_seconds",
+        "provider" => provider,
+        "operation" => operation,
+    )
+    .record(started.elapsed().as_secs_f64());
+
+    if result.is_err() {
+        metrics::counter!(
+            "mugraph.node.provider.errors_total",
+            "provider" => provider,
+            "operation" => operation,
+        )
+        .increment(1);
+    }
+
+    result
+}
+
+/// Count quorum backends answering `operation` differently.
+pub fn record_provider_disagreement(operation: &'static str) {
+    metrics::counter!(
+        "mugraph.node.provider.disagreements_total",
+        "operation" => operation,
+    )
+    .increment(1);
+}
+
+/// Count a cacheable provider call by whether the cache answered it.
+pub fn record_provider_cache(operation: &'static str, hit: bool) {
+    metrics::counter!(
+        "mugraph.node.provider.cache_requests_total",
+        "operation" => operation,
+        "result" => if hit { "hit" } else { "miss" },
+    )
+    .increment(1);
+}
+
+/// Count a provider call refused by the per-minute request budget.
+pub fn record_provider_shed(operation: &'static str, priority: &'static str) {
+    metrics::counter!(
+        "mugraph.node.provider.requests_shed_total",
+        "operation" => operation,
+        "priority" => priority,
+    )
+    .increment(1);
+}
+
+/// Publish the chain index's tip and how many outputs it tracks.
+pub fn record_chain_index_tip(height: u64, outputs: u64) {
+    metrics::gauge!("mugraph.node.chain_index.height").set(height as f64);
+    metrics::gauge!("mugraph.node.chain_index.outputs").set(outputs as f64);
+}
+
+/// Count a rollback undone by the chain index.
+pub fn record_chain_index_rollback() {
+    metrics::counter!("mugraph.node.chain_index.rollbacks_total").increment(1);
+}
+
+/// Count a settled deposit or withdrawal and its value for each asset unit.
+///
+/// Units missing from `tracked` share the `other` label, so the number of
+/// series stays bounded however many tokens pass through the node.
+pub fn record_asset_flow<'a>(
+    direction: &'static str,
+    tracked: &[String],
+    amounts: impl IntoIterator<Item = (&'a str, u128)>,
+) {
+    for (unit, amount) in amounts {
+        let asset = if tracked.iter().any(|t| t == unit) {
+            unit.to_string()
+        } else {
+            "other".to_string()
+        };
+        metrics::counter!(
+            format!("mugraph.node.{direction}s_total"),
+            "asset" => asset.clone(),
+        )
+        .increment(1);
+        metrics::counter!(
+            format!("mugraph.node.{direction}_amount_total"),
+            "asset" => asset,
+        )
+        .increment(u64::try_from(amount).unwrap_or(u64::MAX));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn provider_calls_record_latency_and_errors() {
+        let recorder = build_recorder();
+        let handle = recorder.handle();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        observe_provider_call("blockfrost", "get_tip", async {
+            Ok::<_, ()>(())
+        })
+        .await
+        .unwrap();
+        observe_provider_call("blockfrost", "get_tip", async {
+            Err::<(), _>(())
+        })
+        .await
+        .unwrap_err();
+
+        let rendered = handle.render();
+        assert!(rendered.contains(
+            "mugraph_node_provider_request_duration_seconds_count{provider=\"blockfrost\",operation=\"get_tip\"} 2"
+        ), "{rendered}");
+        assert!(rendered.contains(
+            "mugraph_node_provider_errors_total{provider=\"blockfrost\",operation=\"get_tip\"} 1"
+        ), "{rendered}");
+    }
+
+    fn otel_subscriber(
+        provider: &SdkTracerProvider,
+    ) -> impl tracing::Subscriber + Send + Sync {
+        tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("test")),
+        )
+    }
+
+    #[test]
+    fn trace_context_round_trips_through_remote_parent() {
+        let provider = SdkTracerProvider::builder().build();
+        let _guard =
+            tracing::subscriber::set_default(otel_subscriber(&provider));
+
+        let remote = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let span = tracing::info_span!("child");
+        set_remote_parent(&span, Some(remote));
+
+        let propagated = span.in_scope(current_trace_context).unwrap();
+        assert!(propagated.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert_ne!(propagated, remote);
+    }
+
+    #[test]
+    fn malformed_trace_context_is_ignored() {
+        let provider = SdkTracerProvider::builder().build();
+        let _guard =
+            tracing::subscriber::set_default(otel_subscriber(&provider));
+
+        let span = tracing::info_span!("child");
+        set_remote_parent(&span, Some("not-a-traceparent"));
+
+        assert!(span.in_scope(current_trace_context).is_some());
+    }
+
+    #[test]
+    fn trace_context_is_absent_without_an_exporter() {
+        let span = tracing::info_span!("untraced");
+        assert_eq!(span.in_scope(current_trace_context), None);
+    }
+
+    #[test]
+    fn traces_url_appends_signal_path_once() {
+        assert_eq!(
+            traces_url("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://localhost:4318/v1/traces/"),
+            "http://localhost:4318/v1/traces"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_are_exported_to_otlp_collector() {
+        use axum::{body::Bytes, extract::State, routing::post};
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Bytes>();
+        let collector = Router::new()
+            .route(
+                "/v1/traces",
+                post(
+                    |State(tx): State<
+                        tokio::sync::mpsc::UnboundedSender<Bytes>,
+                    >,
+                     body: Bytes| async move {
+                        let _ = tx.send(body);
+                    },
+                ),
+            )
+            .with_state(tx);
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, collector).await });
+
+        let provider = otlp_tracer_provider(&endpoint).unwrap();
+        tracing::subscriber::with_default(otel_subscriber(&provider), || {
+            tracing::info_span!("xnode_message", transfer_id = "tr-otlp")
+                .in_scope(|| {});
+        });
+        tokio::task::spawn_blocking(move || provider.force_flush())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let body =
+            tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        let contains =
+            |needle: &[u8]| body.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"xnode_message"));
+        assert!(contains(b"tr-otlp"));
+        assert!(contains(SERVICE_NAME.as_bytes()));
+    }
+
+    #[test]
+    fn asset_flow_counts_tracked_units_and_collapses_the_rest() {
+        let recorder = build_recorder();
+        let handle = recorder.handle();
+        let tracked = vec!["lovelace".to_string()];
+
+        metrics::with_local_recorder(&recorder, || {
+            record_asset_flow(
+                "deposit",
+                &tracked,
+                [("lovelace", 2_000_000), ("policytoken", 5)],
+            );
+            record_asset_flow(
+                "deposit",
+                &tracked,
+                [("lovelace", 1_000_000), ("othertoken", 7)],
+            );
+        });
+
+        let rendered = handle.render();
+        assert!(
+            rendered
+                .contains("mugraph_node_deposits_total{asset=\"lovelace\"} 2")
+        );
+        assert!(rendered.contains(
+            "mugraph_node_deposit_amount_total{asset=\"lovelace\"} 3000000"
+        ));
+        assert!(
+            rendered.contains(
+                "mugraph_node_deposit_amount_total{asset=\"other\"} 12"
+            )
+        );
+        assert!(!rendered.contains("policytoken"), "{rendered}");
+    }
+}
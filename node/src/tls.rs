@@ -0,0 +1,473 @@
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use axum::{extract::connect_info::Connected, serve::IncomingStream};
+use mugraph_core::error::Error;
+use rustls::{
+    RootCertStore, ServerConfig, crypto::ring::default_provider,
+    server::WebPkiClientVerifier,
+};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+use crate::{config::Config, supervisor::Shutdown};
+
+/// Connections that have not finished the handshake by now are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often certificate files are checked for changes.
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Handshaken connections waiting for the server to accept them.
+const ACCEPT_BACKLOG: usize = 256;
+
+fn tls_error(reason: impl Into<String>) -> Error {
+    Error::Internal {
+        reason: reason.into(),
+    }
+}
+
+/// Files backing the listener's TLS configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    /// CA bundle for optional client certificates (mutual TLS)
+    pub client_ca_file: Option<PathBuf>,
+}
+
+impl TlsSettings {
+    /// TLS settings from `config`, or `None` when TLS is disabled.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, Error> {
+        match (
+            config.tls_cert_file(),
+            config.tls_key_file(),
+            config.xnode_client_ca_file(),
+        ) {
+            (Some(cert_file), Some(key_file), client_ca_file) => {
+                Ok(Some(Self {
+                    cert_file: cert_file.into(),
+                    key_file: key_file.into(),
+                    client_ca_file: client_ca_file.map(PathBuf::from),
+                }))
+            }
+            (None, None, None) => Ok(None),
+            (None, None, Some(_)) => Err(Error::InvalidInput {
+                reason: "xnode client CA requires a TLS certificate and key"
+                    .to_string(),
+            }),
+            _ => Err(Error::InvalidInput {
+                reason: "TLS needs both a certificate and a private key"
+                    .to_string(),
+            }),
+        }
+    }
+
+    fn files(&self) -> impl Iterator<Item = &Path> {
+        [self.cert_file.as_path(), self.key_file.as_path()]
+            .into_iter()
+            .chain(self.client_ca_file.as_deref())
+    }
+
+    /// Latest modification time across every file, for change detection.
+    fn modified(&self) -> Option<SystemTime> {
+        self.files()
+            .filter_map(|path| std::fs::metadata(path).ok()?.modified().ok())
+            .max()
+    }
+
+    /// Read every file and build a rustls server configuration.
+    pub fn load(&self) -> Result<ServerConfig, Error> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_file)
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|e| {
+                tls_error(format!(
+                    "failed to read certificate {}: {e}",
+                    self.cert_file.display()
+                ))
+            })?;
+        if certs.is_empty() {
+            return Err(tls_error(format!(
+                "no certificates in {}",
+                self.cert_file.display()
+            )));
+        }
+
+        let key =
+            PrivateKeyDer::from_pem_file(&self.key_file).map_err(|e| {
+                tls_error(format!(
+                    "failed to read private key {}: {e}",
+                    self.key_file.display()
+                ))
+            })?;
+
+        let provider = Arc::new(default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| tls_error(e.to_string()))?;
+
+        let builder = match &self.client_ca_file {
+            None => builder.with_no_client_auth(),
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for ca in CertificateDer::pem_file_iter(path)
+                    .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+                    .map_err(|e| {
+                        tls_error(format!(
+                            "failed to read client CA {}: {e}",
+                            path.display()
+                        ))
+                    })?
+                {
+                    roots.add(ca).map_err(|e| {
+                        tls_error(format!(
+                            "invalid client CA {}: {e}",
+                            path.display()
+                        ))
+                    })?;
+                }
+
+                // Wallets connect without a certificate; xnode routes
+                // require one, checked per request
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(roots),
+                    provider,
+                )
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| tls_error(e.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| tls_error(format!("invalid certificate/key: {e}")))?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+}
+
+/// Server configuration that can be swapped while the listener runs.
+#[derive(Debug)]
+pub struct ReloadableTls {
+    settings: TlsSettings,
+    current: RwLock<(Arc<ServerConfig>, Option<SystemTime>)>,
+}
+
+impl ReloadableTls {
+    pub fn new(settings: TlsSettings) -> Result<Self, Error> {
+        let modified = settings.modified();
+        let config = Arc::new(settings.load()?);
+
+        Ok(Self {
+            settings,
+            current: RwLock::new((config, modified)),
+        })
+    }
+
+    pub fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().expect("TLS config poisoned").0.clone()
+    }
+
+    /// Reload from disk if any file changed since the last load.
+    ///
+    /// A failed reload keeps serving the previous configuration. Returns
+    /// whether a new configuration was installed.
+    pub fn reload_if_changed(&self) -> Result<bool, Error> {
+        let modified = self.settings.modified();
+        if modified == self.current.read().expect("TLS config poisoned").1 {
+            return Ok(false);
+        }
+
+        let config = Arc::new(self.settings.load()?);
+        *self.current.write().expect("TLS config poisoned") =
+            (config, modified);
+        tracing::info!(
+            cert = %self.settings.cert_file.display(),
+            "reloaded TLS certificate"
+        );
+        Ok(true)
+    }
+
+    /// Poll the certificate files every `interval` until `shutdown`.
+    pub async fn watch(
+        self: Arc<Self>,
+        interval: Duration,
+        shutdown: Shutdown,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => {}
+            }
+
+            let tls = self.clone();
+            match tokio::task::spawn_blocking(move || tls.reload_if_changed())
+                .await
+            {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    tracing::error!("TLS reload failed, keeping old one: {e}")
+                }
+                Err(e) => tracing::error!("TLS reload task panicked: {e}"),
+            }
+        }
+    }
+}
+
+/// TCP listener that terminates TLS before handing connections to axum.
+///
+/// Handshakes run on their own tasks so a slow client cannot stall accepts.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(
+        listener: TcpListener,
+        tls: Arc<ReloadableTls>,
+    ) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    // The server dropped the listener
+                    _ = tx.closed() => return,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!("TLS listener accept failed: {e}");
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            continue;
+                        }
+                    },
+                };
+
+                let acceptor = TlsAcceptor::from(tls.current());
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(
+                        HANDSHAKE_TIMEOUT,
+                        acceptor.accept(stream),
+                    )
+                    .await
+                    {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::debug!(%addr, "TLS handshake failed: {e}")
+                        }
+                        Err(_) => {
+                            tracing::debug!(%addr, "TLS handshake timed out")
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Peer address and, over mutual TLS, the verified client certificate chain.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub addr: SocketAddr,
+    pub certificates: Option<Arc<[CertificateDer<'static>]>>,
+}
+
+impl ClientInfo {
+    /// Whether the client's leaf certificate lists `uri` as a URI SAN.
+    pub fn has_uri_identity(&self, uri: &str) -> bool {
+        let Some(leaf) = self.certificates.as_deref().and_then(<[_]>::first)
+        else {
+            return false;
+        };
+
+        certificate_uris(leaf).iter().any(|name| name == uri)
+    }
+}
+
+/// URI subject alternative names of a DER certificate.
+pub fn certificate_uris(cert: &CertificateDer<'_>) -> Vec<String> {
+    use x509_parser::{extensions::GeneralName, prelude::FromDer};
+
+    let Ok((_, cert)) =
+        x509_parser::certificate::X509Certificate::from_der(cert)
+    else {
+        return Vec::new();
+    };
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::URI(uri) => Some(uri.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for ClientInfo {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self {
+            addr: *stream.remote_addr(),
+            certificates: None,
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for ClientInfo {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        let (_, session) = stream.io().get_ref();
+        Self {
+            addr: *stream.remote_addr(),
+            certificates: session.peer_certificates().map(|certs| {
+                certs.iter().map(|c| c.clone().into_owned()).collect()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{CertificateParams, KeyPair, SanType};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn write_self_signed(dir: &TempDir, name: &str, san: &str) -> TlsSettings {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec![san.to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+
+        let cert_file = dir.path().join(format!("{name}.pem"));
+        let key_file = dir.path().join(format!("{name}.key"));
+        std::fs::write(&cert_file, cert.pem()).unwrap();
+        std::fs::write(&key_file, key.serialize_pem()).unwrap();
+
+        TlsSettings {
+            cert_file,
+            key_file,
+            client_ca_file: None,
+        }
+    }
+
+    #[test]
+    fn reload_picks_up_changed_files_and_keeps_old_config_on_error() {
+        let dir = TempDir::new().unwrap();
+        let settings = write_self_signed(&dir, "node", "localhost");
+        let tls = ReloadableTls::new(settings.clone()).unwrap();
+        let first = tls.current();
+
+        assert!(!tls.reload_if_changed().unwrap());
+        assert!(Arc::ptr_eq(&first, &tls.current()));
+
+        // Rotate the certificate; bump the mtime in case the filesystem
+        // clock is coarse
+        std::thread::sleep(Duration::from_millis(20));
+        write_self_signed(&dir, "node", "localhost");
+        let later = SystemTime::now() + Duration::from_secs(5);
+        for path in [&settings.cert_file, &settings.key_file] {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+        }
+
+        assert!(tls.reload_if_changed().unwrap());
+        let rotated = tls.current();
+        assert!(!Arc::ptr_eq(&first, &rotated));
+
+        // A broken key on disk must not take the listener down
+        std::fs::write(&settings.key_file, "not a key").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&settings.key_file)
+            .unwrap()
+            .set_modified(later + Duration::from_secs(5))
+            .unwrap();
+        assert!(tls.reload_if_changed().is_err());
+        assert!(Arc::ptr_eq(&rotated, &tls.current()));
+    }
+
+    #[test]
+    fn certificate_uris_lists_only_uri_names() {
+        let key = KeyPair::generate().unwrap();
+        let mut params =
+            CertificateParams::new(vec!["peer.example".to_string()]).unwrap();
+        params
+            .subject_alt_names
+            .push(SanType::URI("node://a".try_into().unwrap()));
+        let cert = params.self_signed(&key).unwrap();
+
+        assert_eq!(certificate_uris(cert.der()), vec!["node://a".to_string()]);
+
+        let client = ClientInfo {
+            addr: "127.0.0.1:1".parse().unwrap(),
+            certificates: Some(Arc::from(vec![cert.der().clone()])),
+        };
+        assert!(client.has_uri_identity("node://a"));
+        assert!(!client.has_uri_identity("node://b"));
+
+        let anonymous = ClientInfo {
+            certificates: None,
+            ..client
+        };
+        assert!(!anonymous.has_uri_identity("node://a"));
+    }
+
+    #[test]
+    fn missing_or_partial_files_fail_to_load() {
+        let dir = TempDir::new().unwrap();
+        let mut settings = write_self_signed(&dir, "node", "localhost");
+        settings.key_file = dir.path().join("missing.key");
+        let err = settings.load().unwrap_err().to_string();
+        assert!(err.contains("missing.key"), "{err}");
+    }
+}
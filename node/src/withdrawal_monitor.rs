@@ -0,0 +1,621 @@
+//! Settlement of withdrawals the request path left unfinished.
+//!
+//! A withdrawal is recorded `Pending` when its notes are burned and
+//! `Completed` once the provider accepts its transaction. A crash between
+//! the two leaves it `Pending`, and a submission that times out after the
+//! relay took it leaves it `Failed` although it may still land. The
+//! monitor looks each of these up on chain: one that made it into a block
+//! is completed and its deposits marked spent, and one the chain has not
+//! seen is submitted again for as long as its TTL allows.
+//!
+//! Once the chain proves the transaction can no longer be included, its
+//! TTL passed or one of its inputs spent by another transaction, and the
+//! block that proved it is buried by `confirm_depth` blocks, the monitor
+//! signs the refund outputs the request carried in place of the burned
+//! notes and records the withdrawal `Refunded`. Only their number was
+//! checked against the notes: what they are worth is hidden by their
+//! blinding and trusted like change outputs.
+
+use std::sync::Arc;
+
+use mugraph_core::{
+    crypto,
+    error::Error,
+    types::{
+        BlindSignature, EventKind, EventSubject, Keypair, UtxoRef,
+        WithdrawalKey, WithdrawalRecord, WithdrawalStatus,
+    },
+};
+use redb::ReadableTable;
+use serde::{Deserialize, Serialize};
+use whisky_csl::csl;
+
+use crate::{
+    database::{
+        DEPOSIT_CONFIRMATIONS, DEPOSITS, Database, WITHDRAWAL_SUBMISSIONS,
+        WITHDRAWALS,
+    },
+    provider::{ChainTip, Provider, TxSettlementState},
+    supervisor::Shutdown,
+};
+
+/// Configuration for withdrawal reconciliation
+#[derive(Debug, Clone)]
+pub struct WithdrawalMonitorConfig {
+    /// Network byte of the withdrawal keys to settle
+    pub network_byte: u8,
+    /// Seconds between passes
+    pub interval: u64,
+    /// Seconds a pending withdrawal is left to the request that burned its
+    /// notes before the monitor takes it over
+    pub pending_grace: u64,
+    /// Blocks that must bury the proof a withdrawal can no longer land
+    /// before its refund is signed
+    pub confirm_depth: u64,
+}
+
+impl Default for WithdrawalMonitorConfig {
+    fn default() -> Self {
+        Self {
+            network_byte: 0,
+            interval: 30,
+            pending_grace: 120,
+            confirm_depth: 15,
+        }
+    }
+}
+
+/// What the node needs to settle a withdrawal without the request that
+/// burned its notes, stored alongside its [`WithdrawalRecord`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalSubmission {
+    /// Signed transaction CBOR (hex encoded), node witness included
+    pub signed_tx_cbor: String,
+    /// Deposits the transaction spends, marked spent once it lands
+    pub consumed_deposits: Vec<UtxoRef>,
+    /// Slot from which the transaction can no longer be included
+    pub invalid_hereafter: Option<u64>,
+    /// Times the monitor has submitted the transaction again
+    pub resubmissions: u32,
+    /// Blinded outputs to sign in place of the burned notes if the
+    /// transaction can no longer land
+    #[serde(default)]
+    pub refund_outputs: Vec<BlindSignature>,
+    /// Signatures over `refund_outputs`, once refunded
+    #[serde(default)]
+    pub refund_notes: Vec<BlindSignature>,
+    /// Block height of the tip when the transaction was first seen unable
+    /// to land, while its refund waits for that block to settle
+    #[serde(default)]
+    pub unlandable_since: Option<u64>,
+}
+
+impl WithdrawalSubmission {
+    pub fn new(signed_tx_cbor: &[u8], consumed_deposits: Vec<UtxoRef>) -> Self {
+        let invalid_hereafter =
+            csl::Transaction::from_bytes(signed_tx_cbor.to_vec())
+                .ok()
+                .and_then(|tx| tx.body().ttl_bignum())
+                .map(u64::from);
+
+        Self {
+            signed_tx_cbor: hex::encode(signed_tx_cbor),
+            consumed_deposits,
+            invalid_hereafter,
+            resubmissions: 0,
+            refund_outputs: Vec::new(),
+            refund_notes: Vec::new(),
+            unlandable_since: None,
+        }
+    }
+
+    pub fn with_refund_outputs(
+        mut self,
+        refund_outputs: Vec<BlindSignature>,
+    ) -> Self {
+        self.refund_outputs = refund_outputs;
+        self
+    }
+
+    /// Whether a chain at `slot` can no longer include the transaction
+    pub fn is_expired(&self, slot: u64) -> bool {
+        self.invalid_hereafter.is_some_and(|ttl| ttl <= slot)
+    }
+
+    /// Outputs the transaction spends, as `(tx_hash, index)`
+    pub fn inputs(&self) -> Result<Vec<(String, u16)>, Error> {
+        let tx = hex::decode(&self.signed_tx_cbor)
+            .ok()
+            .and_then(|bytes| csl::Transaction::from_bytes(bytes).ok())
+            .ok_or_else(|| Error::Internal {
+                reason: "Invalid stored transaction CBOR".to_string(),
+            })?;
+        let inputs = tx.body().inputs();
+
+        (0..inputs.len())
+            .map(|i| {
+                let input = inputs.get(i);
+                let index = u16::try_from(input.index()).map_err(|_| {
+                    Error::Internal {
+                        reason: format!(
+                            "Input index {} out of range",
+                            input.index()
+                        ),
+                    }
+                })?;
+                Ok((input.transaction_id().to_hex(), index))
+            })
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec(self).map_err(|e| Error::Internal {
+            reason: format!("failed to encode withdrawal submission: {e}"),
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        serde_json::from_slice(bytes).map_err(|e| Error::Internal {
+            reason: format!("failed to decode withdrawal submission: {e}"),
+        })
+    }
+}
+
+/// The record for `key` and, for withdrawals recorded since submissions
+/// were kept, what is needed to settle it
+pub fn load_withdrawal(
+    database: &Database,
+    key: &WithdrawalKey,
+) -> Result<Option<(WithdrawalRecord, Option<WithdrawalSubmission>)>, Error> {
+    let read_tx = database.read()?;
+    let withdrawals = read_tx.open_table(WITHDRAWALS)?;
+    let Some(record) = withdrawals.get(key)?.map(|v| v.value()) else {
+        return Ok(None);
+    };
+
+    let submissions = read_tx.open_table(WITHDRAWAL_SUBMISSIONS)?;
+    let submission = submissions
+        .get(key)?
+        .map(|bytes| WithdrawalSubmission::from_bytes(bytes.value()))
+        .transpose()?;
+
+    Ok(Some((record, submission)))
+}
+
+/// Mark the withdrawal `tx_hash` completed and the deposits it consumed
+/// spent, refusing one that is already completed or refunded
+pub fn complete_withdrawal(
+    database: &Database,
+    key: &WithdrawalKey,
+    tx_hash: &str,
+    consumed_deposits: &[UtxoRef],
+) -> Result<(), Error> {
+    let write_tx = database.write()?;
+
+    {
+        let mut withdrawals_table = write_tx.open_table(WITHDRAWALS)?;
+
+        let existing = withdrawals_table.get(key)?.map(|v| v.value());
+        let Some(existing) = existing else {
+            return Err(Error::InvalidInput {
+                reason: "Pending withdrawal not found for completion"
+                    .to_string(),
+            });
+        };
+
+        if existing.status == WithdrawalStatus::Completed {
+            return Err(Error::InvalidInput {
+                reason: "Withdrawal already completed".to_string(),
+            });
+        }
+        if existing.status == WithdrawalStatus::Refunded {
+            return Err(Error::InvalidInput {
+                reason: "Withdrawal already refunded".to_string(),
+            });
+        }
+
+        withdrawals_table.insert(key, WithdrawalRecord::completed())?;
+
+        let mut deposits_table = write_tx.open_table(DEPOSITS)?;
+        let mut confirmations_table =
+            write_tx.open_table(DEPOSIT_CONFIRMATIONS)?;
+        for utxo_ref in consumed_deposits {
+            let existing_record =
+                deposits_table.get(utxo_ref)?.map(|v| v.value());
+            if let Some(mut record) = existing_record {
+                record.spent = true;
+                deposits_table.insert(utxo_ref, &record)?;
+                confirmations_table.remove(utxo_ref)?;
+                write_tx.append_event(
+                    EventSubject::deposit(&utxo_ref.tx_hash, utxo_ref.index),
+                    EventKind::DepositSpent {
+                        withdrawal_tx_hash: tx_hash.to_ascii_lowercase(),
+                    },
+                )?;
+            }
+        }
+    }
+    write_tx.append_event(
+        EventSubject::withdrawal(tx_hash),
+        EventKind::WithdrawalSubmitted,
+    )?;
+
+    write_tx.commit()?;
+
+    tracing::info!("Marked withdrawal {} as completed", tx_hash);
+
+    Ok(())
+}
+
+/// Background worker settling pending and failed withdrawals
+#[derive(Clone)]
+pub struct WithdrawalMonitor {
+    config: WithdrawalMonitorConfig,
+    database: Arc<Database>,
+    provider: Provider,
+    keypair: Keypair,
+}
+
+impl WithdrawalMonitor {
+    pub fn new(
+        config: WithdrawalMonitorConfig,
+        database: Arc<Database>,
+        provider: Provider,
+        keypair: Keypair,
+    ) -> Self {
+        Self {
+            config,
+            database,
+            provider,
+            keypair,
+        }
+    }
+
+    /// Run the reconciliation loop until `shutdown` is triggered
+    ///
+    /// A pass that is already in progress finishes before the loop exits.
+    pub async fn run(self, shutdown: Shutdown) {
+        tracing::info!(
+            "Starting withdrawal monitor every {}s",
+            self.config.interval
+        );
+
+        let mut interval = tokio::time::interval(
+            tokio::time::Duration::from_secs(self.config.interval),
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = interval.tick() => {}
+            }
+
+            if let Err(e) = self.reconcile().await {
+                tracing::error!("Error reconciling withdrawals: {}", e);
+            }
+        }
+
+        tracing::info!("Withdrawal monitor stopped");
+    }
+
+    /// Settle every pending or failed withdrawal against the chain once
+    pub async fn reconcile(&self) -> Result<(), Error> {
+        let tip =
+            self.provider
+                .get_tip()
+                .await
+                .map_err(|e| Error::NetworkError {
+                    reason: format!("Failed to get chain tip: {}", e),
+                })?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        for (key, record) in self.unsettled_withdrawals()? {
+            if record.status == WithdrawalStatus::Pending
+                && now < record.timestamp + self.config.pending_grace
+            {
+                continue;
+            }
+
+            let tx_hash = hex::encode(key.tx_hash);
+            if let Err(e) = self.settle(&key, &tx_hash, &record, &tip).await {
+                // One withdrawal must not hold up the rest of the pass
+                tracing::warn!(
+                    "Failed to settle withdrawal {}: {}",
+                    &tx_hash[..16],
+                    e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn settle(
+        &self,
+        key: &WithdrawalKey,
+        tx_hash: &str,
+        record: &WithdrawalRecord,
+        tip: &ChainTip,
+    ) -> Result<(), Error> {
+        let observation = self
+            .provider
+            .observe_tx_status(tx_hash, 1, false)
+            .await
+            .map_err(|e| Error::NetworkError {
+                reason: format!("Failed to observe transaction: {}", e),
+            })?;
+        let submission = load_withdrawal(&self.database, key)?
+            .and_then(|(_, submission)| submission);
+
+        match observation.state {
+            TxSettlementState::Confirmed | TxSettlementState::Confirming => {
+                let consumed =
+                    submission.map(|s| s.consumed_deposits).unwrap_or_default();
+                complete_withdrawal(&self.database, key, tx_hash, &consumed)
+            }
+            TxSettlementState::NotFound | TxSettlementState::Invalidated => {
+                // Withdrawals recorded before submissions were kept cannot
+                // be sent again; they settle once seen on chain
+                let Some(mut submission) = submission else {
+                    return Ok(());
+                };
+
+                let reason = if submission.is_expired(tip.slot) {
+                    "expired"
+                } else if self.spends_missing_input(&submission).await? {
+                    // It may have spent them itself since it was looked up
+                    let observation = self
+                        .provider
+                        .observe_tx_status(tx_hash, 1, false)
+                        .await
+                        .map_err(|e| Error::NetworkError {
+                            reason: format!(
+                                "Failed to observe transaction: {}",
+                                e
+                            ),
+                        })?;
+                    if observation.tx_block_height.is_some() {
+                        return complete_withdrawal(
+                            &self.database,
+                            key,
+                            tx_hash,
+                            &submission.consumed_deposits,
+                        );
+                    }
+                    "lost its inputs"
+                } else {
+                    submission.unlandable_since = None;
+                    return self.resubmit(key, tx_hash, submission).await;
+                };
+
+                if !submission.refund_outputs.is_empty() {
+                    // The block that ruled it out may still be rolled back,
+                    // and a rollback may yet let it land. Once that block
+                    // is buried, the lookups above prove that no
+                    // settled block holds the transaction, so whatever
+                    // spent its inputs was another one
+                    let since = match submission.unlandable_since {
+                        Some(since) => since,
+                        None => {
+                            submission.unlandable_since =
+                                Some(tip.block_height);
+                            self.save_submission(key, &submission)?;
+                            tip.block_height
+                        }
+                    };
+                    if tip.block_height < since + self.config.confirm_depth {
+                        return Ok(());
+                    }
+                    return self.refund(key, tx_hash, submission, reason);
+                }
+                if record.status == WithdrawalStatus::Pending {
+                    tracing::warn!(
+                        "Withdrawal {} {} before reaching the chain",
+                        &tx_hash[..16],
+                        reason
+                    );
+                    self.update(key, tx_hash, WithdrawalStatus::Failed, None)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether an output the transaction spends is gone from the chain
+    async fn spends_missing_input(
+        &self,
+        submission: &WithdrawalSubmission,
+    ) -> Result<bool, Error> {
+        for (input_hash, index) in submission.inputs()? {
+            let utxo =
+                self.provider.get_utxo(&input_hash, index).await.map_err(
+                    |e| Error::NetworkError {
+                        reason: format!("Failed to fetch UTxO: {}", e),
+                    },
+                )?;
+            if utxo.is_none() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Sign the refund outputs in place of the burned notes and record the
+    /// withdrawal refunded, for a transaction that can no longer land
+    fn refund(
+        &self,
+        key: &WithdrawalKey,
+        tx_hash: &str,
+        mut submission: WithdrawalSubmission,
+        reason: &str,
+    ) -> Result<(), Error> {
+        let mut rng = rand::rng();
+        submission.refund_notes = submission
+            .refund_outputs
+            .iter()
+            .map(|output| {
+                let blinded_point = output.signature.0.to_point()?;
+                Ok(crypto::sign_blinded(
+                    &mut rng,
+                    &self.keypair.secret_key,
+                    &blinded_point,
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        tracing::info!(
+            "Withdrawal {} {}; signed {} refund notes",
+            &tx_hash[..16],
+            reason,
+            submission.refund_notes.len()
+        );
+        self.update(key, tx_hash, WithdrawalStatus::Refunded, Some(&submission))
+    }
+
+    /// Submit the transaction again, leaving the withdrawal pending while
+    /// the provider holds it and failed when it refuses it
+    async fn resubmit(
+        &self,
+        key: &WithdrawalKey,
+        tx_hash: &str,
+        mut submission: WithdrawalSubmission,
+    ) -> Result<(), Error> {
+        let tx_bytes =
+            hex::decode(&submission.signed_tx_cbor).map_err(|e| {
+                Error::Internal {
+                    reason: format!("Invalid stored transaction CBOR: {}", e),
+                }
+            })?;
+        let result = self.provider.submit_tx(&tx_bytes).await;
+        submission.resubmissions += 1;
+
+        let status = match result {
+            Ok(response) if response.tx_hash == tx_hash => {
+                tracing::info!(
+                    "Resubmitted withdrawal {} (attempt {})",
+                    &tx_hash[..16],
+                    submission.resubmissions
+                );
+                WithdrawalStatus::Pending
+            }
+            Ok(response) => {
+                tracing::error!(
+                    "Provider returned mismatched tx hash on resubmission: expected {}, got {}",
+                    tx_hash,
+                    response.tx_hash
+                );
+                WithdrawalStatus::Failed
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Resubmission of withdrawal {} failed: {}",
+                    &tx_hash[..16],
+                    e
+                );
+                WithdrawalStatus::Failed
+            }
+        };
+
+        self.update(key, tx_hash, status, Some(&submission))
+    }
+
+    /// Store `submission` for a withdrawal that is still unsettled,
+    /// leaving its record as it is
+    fn save_submission(
+        &self,
+        key: &WithdrawalKey,
+        submission: &WithdrawalSubmission,
+    ) -> Result<(), Error> {
+        let write_tx = self.database.write()?;
+        {
+            let withdrawals = write_tx.open_table(WITHDRAWALS)?;
+            let status = withdrawals.get(key)?.map(|v| v.value().status);
+            if !matches!(
+                status,
+                Some(WithdrawalStatus::Pending | WithdrawalStatus::Failed)
+            ) {
+                return Ok(());
+            }
+
+            let mut submissions =
+                write_tx.open_table(WITHDRAWAL_SUBMISSIONS)?;
+            submissions.insert(key, submission.to_bytes()?.as_slice())?;
+        }
+        write_tx.commit()
+    }
+
+    /// Record `status` for the withdrawal, and `submission` if given,
+    /// publishing an event when the status changes
+    fn update(
+        &self,
+        key: &WithdrawalKey,
+        tx_hash: &str,
+        status: WithdrawalStatus,
+        submission: Option<&WithdrawalSubmission>,
+    ) -> Result<(), Error> {
+        let write_tx = self.database.write()?;
+        let changed = {
+            let mut withdrawals = write_tx.open_table(WITHDRAWALS)?;
+            let previous = withdrawals.get(key)?.map(|v| v.value().status);
+            // The request path may have settled it since the pass began
+            if matches!(
+                previous,
+                None | Some(
+                    WithdrawalStatus::Completed | WithdrawalStatus::Refunded
+                )
+            ) {
+                return Ok(());
+            }
+            withdrawals.insert(key, WithdrawalRecord::new(status.clone()))?;
+
+            if let Some(submission) = submission {
+                let mut submissions =
+                    write_tx.open_table(WITHDRAWAL_SUBMISSIONS)?;
+                submissions.insert(key, submission.to_bytes()?.as_slice())?;
+            }
+
+            previous != Some(status.clone())
+        };
+        if changed {
+            let event = match status {
+                WithdrawalStatus::Pending => EventKind::WithdrawalPending,
+                WithdrawalStatus::Failed => EventKind::WithdrawalFailed,
+                WithdrawalStatus::Completed => EventKind::WithdrawalSubmitted,
+                WithdrawalStatus::Refunded => EventKind::WithdrawalRefunded,
+            };
+            write_tx.append_event(EventSubject::withdrawal(tx_hash), event)?;
+        }
+        write_tx.commit()
+    }
+
+    /// Pending and failed withdrawals on the configured network
+    fn unsettled_withdrawals(
+        &self,
+    ) -> Result<Vec<(WithdrawalKey, WithdrawalRecord)>, Error> {
+        let read_tx = self.database.read()?;
+        let table = read_tx.open_table(WITHDRAWALS)?;
+
+        let mut unsettled = Vec::new();
+        for item in table.iter()? {
+            let (k, v) = item?;
+            let key = k.value();
+            let record = v.value();
+
+            if key.network == self.config.network_byte
+                && matches!(
+                    record.status,
+                    WithdrawalStatus::Pending | WithdrawalStatus::Failed
+                )
+            {
+                unsettled.push((key, record));
+            }
+        }
+
+        Ok(unsettled)
+    }
+}
@@ -32,6 +32,13 @@ fn test_script_address_building() {
     // Test testnet address
     let testnet_addr = build_script_address(&script_hash, "preprod").unwrap();
     assert!(testnet_addr.starts_with("addr_test1"));
+
+    // Outputs can only sit at payment addresses, so the hash must be the
+    // payment credential rather than a stake credential
+    let address = whisky_csl::csl::Address::from_bech32(&testnet_addr).unwrap();
+    assert!(
+        whisky_csl::csl::EnterpriseAddress::from_address(&address).is_some()
+    );
 }
 
 #[test]
@@ -57,6 +64,7 @@ fn test_utxo_info_serialization() {
         }],
         datum_hash: None,
         datum: None,
+        inline_datum: false,
         script_ref: None,
         block_height: Some(12345),
     };
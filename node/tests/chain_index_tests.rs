@@ -0,0 +1,284 @@
+//! Chain index following an emulated ledger
+
+use std::{sync::Arc, time::Duration};
+
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use mugraph_core::types::{CardanoWallet, UtxoRef};
+use mugraph_node::{
+    chain_index::{self, ChainIndex},
+    database::{CARDANO_WALLET, Database},
+    provider::{AssetAmount, EmulatorProvider, Provider},
+    telemetry::build_recorder,
+};
+use whisky_csl::csl;
+
+const POLL: Duration = Duration::from_secs(20);
+
+fn key(seed: u8) -> csl::PrivateKey {
+    csl::PrivateKey::from_normal_bytes(&[seed; 32]).unwrap()
+}
+
+fn address(key: &csl::PrivateKey) -> String {
+    let credential = csl::Credential::from_keyhash(&key.to_public().hash());
+    csl::EnterpriseAddress::new(0, &credential)
+        .to_address()
+        .to_bech32(None)
+        .unwrap()
+}
+
+fn lovelace(quantity: u64) -> Vec<AssetAmount> {
+    vec![AssetAmount {
+        unit: "lovelace".to_string(),
+        quantity: quantity.to_string(),
+    }]
+}
+
+/// Spend all of `input` to `to`, less a 0.2 ADA fee, signed by `signer`
+fn spend(
+    input: &(String, u16),
+    amount: u64,
+    to: &str,
+    signer: &csl::PrivateKey,
+) -> Vec<u8> {
+    let mut inputs = csl::TransactionInputs::new();
+    inputs.add(&csl::TransactionInput::new(
+        &csl::TransactionHash::from_hex(&input.0).unwrap(),
+        input.1 as u32,
+    ));
+    let mut outputs = csl::TransactionOutputs::new();
+    outputs.add(&csl::TransactionOutput::new(
+        &csl::Address::from_bech32(to).unwrap(),
+        &csl::Value::new(&csl::BigNum::from(amount - 200_000)),
+    ));
+    let body = csl::TransactionBody::new_tx_body(
+        &inputs,
+        &outputs,
+        &csl::BigNum::from(200_000u64),
+    );
+
+    let hash = csl::TransactionHash::from_bytes(
+        Blake2b::<U32>::digest(body.to_bytes()).to_vec(),
+    )
+    .unwrap();
+    let mut vkeys = csl::Vkeywitnesses::new();
+    vkeys.add(&csl::make_vkey_witness(&hash, signer));
+    let mut witnesses = csl::TransactionWitnessSet::new();
+    witnesses.set_vkeys(&vkeys);
+    csl::Transaction::new(&body, &witnesses, None).to_bytes()
+}
+
+/// A migrated database whose wallet's script address is `script_address`
+fn database(script_address: &str) -> Arc<Database> {
+    let dir = tempfile::tempdir().unwrap().keep();
+    let database = Arc::new(Database::setup(dir.join("node.db")).unwrap());
+    database.migrate().unwrap();
+
+    let w = database.write().unwrap();
+    {
+        let mut table = w.open_table(CARDANO_WALLET).unwrap();
+        table
+            .insert(
+                "wallet",
+                CardanoWallet::new(
+                    vec![1u8; 32],
+                    vec![2u8; 32],
+                    vec![],
+                    vec![],
+                    script_address.to_string(),
+                    "preprod".to_string(),
+                ),
+            )
+            .unwrap();
+    }
+    w.commit().unwrap();
+    database
+}
+
+fn emulator(name: &str) -> EmulatorProvider {
+    EmulatorProvider::shared(name, "preprod".to_string())
+}
+
+fn utxo_ref((tx_hash, index): &(String, u16)) -> UtxoRef {
+    UtxoRef::new(hex::decode(tx_hash).unwrap().try_into().unwrap(), *index)
+}
+
+#[tokio::test]
+async fn script_outputs_are_served_from_the_index() {
+    let ledger = emulator("emulator://index-reads");
+    let script = address(&key(1));
+    let database = database(&script);
+    let datum = hex::encode(
+        csl::PlutusData::new_integer(&csl::BigInt::from(7)).to_bytes(),
+    );
+    ledger
+        .fund(&script, lovelace(5_000_000), Some(datum.clone()))
+        .unwrap();
+    ledger
+        .fund(&address(&key(2)), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    let index = ChainIndex::new(
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        POLL,
+    );
+    let state = index.sync().await.unwrap().expect("wallet exists");
+    assert_eq!(state.tip.block_height, 1);
+    assert_eq!(state.script_address, script);
+
+    let indexed = chain_index::unspent_utxos(&database).unwrap();
+    assert_eq!(indexed.len(), 1);
+    assert_eq!(indexed[0].datum.as_deref(), Some(datum.as_str()));
+    assert_eq!(indexed[0].block_height, Some(1));
+
+    // Outputs landing after the sync stay invisible until the next one
+    ledger.fund(&script, lovelace(3_000_000), None).unwrap();
+    ledger.advance(1);
+    let provider = Provider::Emulator(ledger.clone()).indexed(database.clone());
+    assert_eq!(provider.get_address_utxos(&script).await.unwrap().len(), 1);
+    assert_eq!(provider.get_tip().await.unwrap().block_height, 1);
+
+    index.sync().await.unwrap();
+    assert_eq!(provider.get_address_utxos(&script).await.unwrap().len(), 2);
+    assert_eq!(provider.get_tip().await.unwrap().block_height, 2);
+}
+
+#[tokio::test]
+async fn stale_indexes_fall_back_to_the_provider() {
+    let ledger = emulator("emulator://index-stale");
+    let script = address(&key(1));
+    let database = database(&script);
+    ledger.fund(&script, lovelace(5_000_000), None).unwrap();
+    ledger.advance(1);
+
+    ChainIndex::new(
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        Duration::ZERO,
+    )
+    .sync()
+    .await
+    .unwrap();
+    assert!(chain_index::fresh_state(&database).unwrap().is_none());
+
+    ledger.fund(&script, lovelace(3_000_000), None).unwrap();
+    ledger.advance(1);
+    let provider = Provider::Emulator(ledger).indexed(database);
+    assert_eq!(provider.get_address_utxos(&script).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn submitted_spends_are_recorded_then_confirmed() {
+    let ledger = emulator("emulator://index-spends");
+    let owner = key(1);
+    let script = address(&owner);
+    let database = database(&script);
+    let funded = ledger.fund(&script, lovelace(5_000_000), None).unwrap();
+    ledger.advance(1);
+
+    let index = ChainIndex::new(
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        POLL,
+    );
+    index.sync().await.unwrap();
+
+    let provider = Provider::Emulator(ledger.clone()).indexed(database.clone());
+    let tx = spend(&funded, 5_000_000, &address(&key(2)), &owner);
+    let submitted = provider.submit_tx(&tx).await.unwrap();
+
+    let indexed = chain_index::indexed_utxo(&database, &utxo_ref(&funded))
+        .unwrap()
+        .expect("indexed");
+    assert_eq!(
+        indexed.spent_by.as_deref(),
+        Some(submitted.tx_hash.as_str())
+    );
+    assert!(indexed.is_unspent(), "not on chain yet");
+
+    ledger.advance(1);
+    index.sync().await.unwrap();
+    let indexed = chain_index::indexed_utxo(&database, &utxo_ref(&funded))
+        .unwrap()
+        .expect("spent outputs are kept");
+    assert_eq!(indexed.spent_at, Some(2));
+    assert_eq!(
+        indexed.spent_by.as_deref(),
+        Some(submitted.tx_hash.as_str())
+    );
+    assert!(chain_index::unspent_utxos(&database).unwrap().is_empty());
+    assert!(
+        provider
+            .get_utxo(&funded.0, funded.1)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn rollbacks_undo_outputs_and_spends_above_the_fork() {
+    let ledger = emulator("emulator://index-rollback");
+    let owner = key(1);
+    let script = address(&owner);
+    let database = database(&script);
+    let kept = ledger.fund(&script, lovelace(5_000_000), None).unwrap();
+    ledger.advance(2);
+    let dropped = ledger.fund(&script, lovelace(3_000_000), None).unwrap();
+    let replaced = ledger.advance(1);
+
+    let recorder = build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(&recorder);
+
+    let index = ChainIndex::new(
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        POLL,
+    );
+    index.sync().await.unwrap();
+    assert_eq!(chain_index::unspent_utxos(&database).unwrap().len(), 2);
+
+    // The block holding `dropped` is replaced by an empty one
+    ledger.rollback(1).unwrap();
+    assert_ne!(ledger.advance(1).hash, replaced.hash);
+    index.sync().await.unwrap();
+
+    let unspent = chain_index::unspent_utxos(&database).unwrap();
+    assert_eq!(unspent.len(), 1);
+    assert_eq!(unspent[0].tx_hash, kept.0);
+    assert!(
+        chain_index::indexed_utxo(&database, &utxo_ref(&dropped))
+            .unwrap()
+            .is_none()
+    );
+
+    // A spend seen at the tip comes back once its block is rolled back
+    let tx = spend(&kept, 5_000_000, &address(&key(2)), &owner);
+    Provider::Emulator(ledger.clone())
+        .indexed(database.clone())
+        .submit_tx(&tx)
+        .await
+        .unwrap();
+    ledger.advance(1);
+    index.sync().await.unwrap();
+    assert!(chain_index::unspent_utxos(&database).unwrap().is_empty());
+
+    ledger.rollback(2).unwrap();
+    index.sync().await.unwrap();
+    let revived = chain_index::indexed_utxo(&database, &utxo_ref(&kept))
+        .unwrap()
+        .expect("indexed");
+    assert!(revived.is_unspent());
+
+    let rendered = handle.render();
+    assert!(
+        rendered.contains("mugraph_node_chain_index_rollbacks_total 2"),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains("mugraph_node_chain_index_height 2"),
+        "{rendered}"
+    );
+}
@@ -1,7 +1,12 @@
 //! Tests for node configuration
 
+use std::time::Duration;
+
 use clap::Parser;
-use mugraph_node::config::Config;
+use mugraph_node::{
+    config::Config,
+    provider::{Provider, RequestPriority},
+};
 
 fn parse_server(args: &[&str]) -> Config {
     Config::try_parse_from(
@@ -131,6 +136,7 @@ fn direct_server_config_getters_preserve_assigned_values() {
     // We can't easily test Config::new() since it parses CLI args,
     // but we can test the struct directly
     let config = Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -148,6 +154,34 @@ fn direct_server_config_getters_preserve_assigned_values() {
         max_withdrawal_fee: 2000000,
         fee_tolerance_pct: 5,
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     assert_eq!(config.network(), "preprod");
@@ -166,6 +200,7 @@ fn direct_server_config_getters_preserve_assigned_values() {
 #[test]
 fn direct_server_config_getters_preserve_explicit_overrides() {
     let config = Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -183,6 +218,34 @@ fn direct_server_config_getters_preserve_explicit_overrides() {
         max_withdrawal_fee: 3000000,
         fee_tolerance_pct: 10,
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     assert_eq!(config.network(), "mainnet");
@@ -209,6 +272,7 @@ fn direct_server_config_getters_preserve_explicit_overrides() {
 fn network_getter_preserves_exact_lowercase_strings() {
     for network in ["mainnet", "preprod", "preview", "testnet"] {
         let config = Config::Server {
+            config_file: None,
             addr: "0.0.0.0:9999".parse().unwrap(),
             seed: None,
             secret_key: None,
@@ -226,6 +290,34 @@ fn network_getter_preserves_exact_lowercase_strings() {
             max_withdrawal_fee: 2000000,
             fee_tolerance_pct: 5,
             dev_mode: false,
+            spent_archive_after_secs: None,
+            spent_archive_interval_secs: 3600,
+            event_retention_secs: 0,
+            event_max_streams: 0,
+            metrics_addr: None,
+            metrics_assets: vec!["lovelace".to_string()],
+            otlp_endpoint: None,
+            rpc_max_body_bytes: 2 * 1024 * 1024,
+            rpc_rate_limit: 0,
+            rpc_method_rate_limits: Vec::new(),
+            rpc_max_concurrent_expensive: 0,
+            tls_cert_file: None,
+            tls_key_file: None,
+            xnode_client_ca_file: None,
+            rpc_max_batch_size: 100,
+            rpc_trusted_proxies: Vec::new(),
+            node_info_ttl_secs: 3600,
+            operator_contact: Vec::new(),
+            cardano_emulator_block_secs: 20,
+            cardano_ogmios_url: None,
+            cardano_backends: Vec::new(),
+            cardano_quorum: 1,
+            cardano_cache_immutable_secs: 3600,
+            cardano_cache_volatile_secs: 5,
+            cardano_cache_negative_secs: 2,
+            cardano_request_budget: None,
+            cardano_index_poll_secs: 0,
+            withdrawal_reconcile_secs: 0,
         };
         assert_eq!(config.network(), network);
     }
@@ -237,6 +329,7 @@ fn test_network_bytes() {
     let cases = [("preprod", 0u8), ("preview", 2u8), ("testnet", 3u8)];
     for (network, expected) in cases {
         let config = Config::Server {
+            config_file: None,
             addr: "0.0.0.0:9999".parse().unwrap(),
             seed: None,
             secret_key: None,
@@ -254,6 +347,34 @@ fn test_network_bytes() {
             max_withdrawal_fee: 2000000,
             fee_tolerance_pct: 5,
             dev_mode: false,
+            spent_archive_after_secs: None,
+            spent_archive_interval_secs: 3600,
+            event_retention_secs: 0,
+            event_max_streams: 0,
+            metrics_addr: None,
+            metrics_assets: vec!["lovelace".to_string()],
+            otlp_endpoint: None,
+            rpc_max_body_bytes: 2 * 1024 * 1024,
+            rpc_rate_limit: 0,
+            rpc_method_rate_limits: Vec::new(),
+            rpc_max_concurrent_expensive: 0,
+            tls_cert_file: None,
+            tls_key_file: None,
+            xnode_client_ca_file: None,
+            rpc_max_batch_size: 100,
+            rpc_trusted_proxies: Vec::new(),
+            node_info_ttl_secs: 3600,
+            operator_contact: Vec::new(),
+            cardano_emulator_block_secs: 20,
+            cardano_ogmios_url: None,
+            cardano_backends: Vec::new(),
+            cardano_quorum: 1,
+            cardano_cache_immutable_secs: 3600,
+            cardano_cache_volatile_secs: 5,
+            cardano_cache_negative_secs: 2,
+            cardano_request_budget: None,
+            cardano_index_poll_secs: 0,
+            withdrawal_reconcile_secs: 0,
         };
         assert_eq!(
             config.network_byte(),
@@ -266,6 +387,7 @@ fn test_network_bytes() {
 #[test]
 fn test_testnets_have_distinct_network_bytes_for_db_namespacing() {
     let make = |network: &str| Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -283,6 +405,34 @@ fn test_testnets_have_distinct_network_bytes_for_db_namespacing() {
         max_withdrawal_fee: 2000000,
         fee_tolerance_pct: 5,
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     let preprod = make("preprod").network_byte();
@@ -298,6 +448,7 @@ fn test_testnets_have_distinct_network_bytes_for_db_namespacing() {
 #[test]
 fn direct_server_config_getters_apply_fallbacks_for_missing_optionals() {
     let config = Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -315,6 +466,34 @@ fn direct_server_config_getters_apply_fallbacks_for_missing_optionals() {
         max_withdrawal_fee: 2000000,
         fee_tolerance_pct: 5,
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     // API key should not silently default to a fake key
@@ -335,6 +514,7 @@ fn direct_server_config_getters_apply_fallbacks_for_missing_optionals() {
 fn test_fee_tolerance_bounds() {
     // Test that values over 100 are clamped
     let config = Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -352,12 +532,41 @@ fn test_fee_tolerance_bounds() {
         max_withdrawal_fee: 2000000,
         fee_tolerance_pct: 150, // Over 100
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     assert_eq!(config.fee_tolerance_pct(), 100);
 
     // Test zero tolerance
     let config_zero = Config::Server {
+        config_file: None,
         addr: "0.0.0.0:9999".parse().unwrap(),
         seed: None,
         secret_key: None,
@@ -375,7 +584,503 @@ fn test_fee_tolerance_bounds() {
         max_withdrawal_fee: 2000000,
         fee_tolerance_pct: 0,
         dev_mode: false,
+        spent_archive_after_secs: None,
+        spent_archive_interval_secs: 3600,
+        event_retention_secs: 0,
+        event_max_streams: 0,
+        metrics_addr: None,
+        metrics_assets: vec!["lovelace".to_string()],
+        otlp_endpoint: None,
+        rpc_max_body_bytes: 2 * 1024 * 1024,
+        rpc_rate_limit: 0,
+        rpc_method_rate_limits: Vec::new(),
+        rpc_max_concurrent_expensive: 0,
+        tls_cert_file: None,
+        tls_key_file: None,
+        xnode_client_ca_file: None,
+        rpc_max_batch_size: 100,
+        rpc_trusted_proxies: Vec::new(),
+        node_info_ttl_secs: 3600,
+        operator_contact: Vec::new(),
+        cardano_emulator_block_secs: 20,
+        cardano_ogmios_url: None,
+        cardano_backends: Vec::new(),
+        cardano_quorum: 1,
+        cardano_cache_immutable_secs: 3600,
+        cardano_cache_volatile_secs: 5,
+        cardano_cache_negative_secs: 2,
+        cardano_request_budget: None,
+        cardano_index_poll_secs: 0,
+        withdrawal_reconcile_secs: 0,
     };
 
     assert_eq!(config_zero.fee_tolerance_pct(), 0);
 }
+
+fn write_config_file(contents: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+    file
+}
+
+fn load_server(args: &[&str]) -> Result<Config, clap::Error> {
+    Config::try_load_from(
+        std::iter::once("mugraph-node")
+            .chain(std::iter::once("server"))
+            .chain(args.iter().copied()),
+    )
+}
+
+#[test]
+fn config_file_fills_settings_not_given_on_the_command_line() {
+    let file = write_config_file(
+        r#"
+        [cardano]
+        network = "preview"
+        provider = "maestro"
+
+        [deposit]
+        confirm_depth = 3
+        min_value = 5000000
+
+        [withdraw]
+        fee_tolerance_pct = 12
+
+        [xnode]
+        node_id = "node://from-file"
+        "#,
+    );
+    let path = file.path().display().to_string();
+
+    let config = load_server(&["--config", &path]).unwrap();
+
+    assert_eq!(config.config_file(), Some(path));
+    assert_eq!(config.network(), "preview");
+    assert_eq!(config.provider_type(), "maestro");
+    assert_eq!(config.deposit_confirm_depth(), 3);
+    assert_eq!(config.min_deposit_value(), 5_000_000);
+    assert_eq!(config.fee_tolerance_pct(), 12);
+    assert_eq!(config.xnode_node_id(), "node://from-file");
+    // untouched settings keep their CLI defaults
+    assert_eq!(config.deposit_expiration_blocks(), 1440);
+    assert_eq!(config.max_tx_size(), 16_384);
+}
+
+#[test]
+fn command_line_flags_override_config_file() {
+    let file = write_config_file(
+        "[cardano]\nnetwork = \"preview\"\n[deposit]\nconfirm_depth = 3\n",
+    );
+    let path = file.path().display().to_string();
+
+    let config = load_server(&[
+        "--config",
+        &path,
+        "--cardano-network",
+        "mainnet",
+        "--deposit-confirm-depth",
+        "30",
+    ])
+    .unwrap();
+
+    assert_eq!(config.network(), "mainnet");
+    assert_eq!(config.deposit_confirm_depth(), 30);
+}
+
+#[test]
+fn load_without_config_file_matches_plain_parse() {
+    let loaded = load_server(&["--seed", "7", "--max-tx-size", "100"]).unwrap();
+    let parsed = parse_server(&["--seed", "7", "--max-tx-size", "100"]);
+
+    assert_eq!(loaded.effective_file(), parsed.effective_file());
+}
+
+#[test]
+fn invalid_config_file_reports_offending_key() {
+    let file = write_config_file("[withdraw]\nfee_tolerance_pct = 250\n");
+    let path = file.path().display().to_string();
+
+    let err = load_server(&["--config", &path]).unwrap_err();
+
+    assert_eq!(err.kind(), clap::error::ErrorKind::InvalidValue);
+    assert!(err.to_string().contains("withdraw.fee_tolerance_pct"));
+    assert!(err.to_string().contains(&path));
+}
+
+#[test]
+fn paired_settings_are_checked_once_merged() {
+    let file = write_config_file("[tls]\nkey_file = \"node.key\"\n");
+    let path = file.path().display().to_string();
+
+    let config =
+        load_server(&["--config", &path, "--tls-cert-file", "node.pem"])
+            .unwrap();
+    assert_eq!(config.tls_cert_file(), Some("node.pem".to_string()));
+    assert_eq!(config.tls_key_file(), Some("node.key".to_string()));
+
+    let file = write_config_file("[xnode]\nclient_ca_file = \"ca.pem\"\n");
+    let path = file.path().display().to_string();
+    let err = load_server(&["--config", &path]).unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    assert!(err.to_string().contains("xnode.client_ca_file"), "{err}");
+
+    let err = load_server(&["--tls-cert-file", "node.pem"]).unwrap_err();
+    assert!(err.to_string().contains("tls.key_file"), "{err}");
+}
+
+#[test]
+fn missing_config_file_is_an_error() {
+    let err =
+        load_server(&["--config", "/nonexistent/mugraph.toml"]).unwrap_err();
+    assert!(err.to_string().contains("failed to read config file"));
+}
+
+#[test]
+fn check_config_resolves_server_flags_and_redacts_secrets() {
+    let file = write_config_file(
+        "[cardano]\napi_key = \"project-secret\"\nnetwork = \"preview\"\n",
+    );
+    let path = file.path().display().to_string();
+
+    let check = Config::try_load_from([
+        "mugraph-node",
+        "check-config",
+        "--config",
+        &path,
+        "--cardano-payment-sk",
+        "deadbeef",
+    ])
+    .unwrap();
+    assert!(matches!(check, Config::CheckConfig { .. }));
+
+    let effective = check.resolve_check_config().unwrap();
+    assert_eq!(effective.network(), "preview");
+    assert_eq!(effective.payment_sk(), Some("deadbeef".to_string()));
+
+    let rendered = effective.effective_file().redacted().to_toml().unwrap();
+    assert!(rendered.contains("network = \"preview\""));
+    assert!(!rendered.contains("project-secret"));
+    assert!(!rendered.contains("deadbeef"));
+}
+
+#[test]
+fn rpc_limits_layer_from_file_and_flags() {
+    let defaults = parse_server(&[]);
+    assert_eq!(defaults.rpc_max_body_bytes(), 2 * 1024 * 1024);
+    assert_eq!(defaults.rpc_rate_limit(), 50);
+    assert_eq!(
+        defaults
+            .rpc_method_rate_limits()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["refresh=20", "deposit=2", "withdraw=2"]
+    );
+    assert_eq!(defaults.rpc_max_concurrent_expensive(), 16);
+    assert_eq!(defaults.event_max_streams(), 256);
+    assert_eq!(defaults.rpc_max_batch_size(), 100);
+    assert!(defaults.rpc_trusted_proxies().is_empty());
+
+    let file = write_config_file(
+        r#"
+        [rpc]
+        max_body_bytes = 4096
+        rate_limit = 5
+        method_rate_limits = { withdraw = 1 }
+        max_batch_size = 10
+        trusted_proxies = ["10.0.0.1", "::1"]
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+
+    let config = load_server(&["--config", path, "--rpc-rate-limit", "7"])
+        .expect("config should load");
+    assert_eq!(config.rpc_max_body_bytes(), 4096);
+    assert_eq!(config.rpc_rate_limit(), 7);
+    assert_eq!(config.rpc_max_batch_size(), 10);
+    assert_eq!(
+        config.rpc_trusted_proxies(),
+        [
+            "10.0.0.1".parse::<std::net::IpAddr>().unwrap(),
+            "::1".parse().unwrap()
+        ]
+    );
+    assert_eq!(
+        config.rpc_method_rate_limits(),
+        vec!["withdraw=1".parse().unwrap()]
+    );
+
+    let err = Config::try_parse_from([
+        "mugraph-node",
+        "server",
+        "--rpc-method-rate-limits",
+        "refesh=1",
+    ])
+    .unwrap_err();
+    assert!(err.to_string().contains("unknown RPC method"), "{err}");
+}
+
+#[test]
+fn node_info_settings_layer_from_file_and_flags() {
+    let defaults = parse_server(&[]);
+    assert_eq!(defaults.node_info_ttl_secs(), 3600);
+    assert!(defaults.operator_contact().is_empty());
+
+    let file = write_config_file(
+        r#"
+        [info]
+        ttl_secs = 600
+        contact = ["mailto:ops@example.com"]
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+
+    let config = load_server(&["--config", path]).expect("config should load");
+    assert_eq!(config.node_info_ttl_secs(), 600);
+    assert_eq!(config.operator_contact(), ["mailto:ops@example.com"]);
+
+    let config = load_server(&[
+        "--config",
+        path,
+        "--operator-contact",
+        "https://example.com/support,mailto:a@example.com",
+    ])
+    .expect("config should load");
+    assert_eq!(
+        config.operator_contact(),
+        ["https://example.com/support", "mailto:a@example.com"]
+    );
+
+    let file = write_config_file("[info]\nttl_secs = 0\n");
+    let err =
+        load_server(&["--config", file.path().to_str().unwrap()]).unwrap_err();
+    assert!(err.to_string().contains("info.ttl_secs"), "{err}");
+}
+
+#[test]
+fn emulator_settings_layer_from_file_and_flags() {
+    let defaults = parse_server(&[]);
+    assert_eq!(defaults.emulator_block_secs(), 20);
+    assert!(!defaults.emulated_ledger());
+
+    let file = write_config_file(
+        r#"
+        [server]
+        dev_mode = true
+
+        [cardano]
+        provider = "emulator"
+        provider_url = "emulator://alice"
+        emulator_block_secs = 5
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+
+    let config = load_server(&["--config", path]).expect("config should load");
+    assert_eq!(config.emulator_block_secs(), 5);
+    assert_eq!(config.provider_url().as_deref(), Some("emulator://alice"));
+    assert!(config.emulated_ledger());
+
+    let config =
+        load_server(&["--config", path, "--cardano-emulator-block-secs", "0"])
+            .expect("config should load");
+    assert_eq!(config.emulator_block_secs(), 0);
+}
+
+/// The backend under the cache `Provider::from_config` puts in front of it
+fn uncached(provider: Provider) -> Provider {
+    match provider {
+        Provider::Cached(provider) => *provider.inner,
+        provider => provider,
+    }
+}
+
+#[test]
+fn ogmios_url_layers_from_file_and_reaches_provider() {
+    let file = write_config_file(
+        r#"
+        [cardano]
+        provider = "ogmios"
+        provider_url = "http://kupo.internal:1442"
+        ogmios_url = "ws://ogmios.internal:1337"
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+
+    let config = load_server(&["--config", path]).expect("config should load");
+    assert_eq!(
+        config.ogmios_url().as_deref(),
+        Some("ws://ogmios.internal:1337")
+    );
+
+    match uncached(Provider::from_config(&config).expect("provider")) {
+        Provider::Ogmios(provider) => {
+            assert_eq!(provider.kupo_url, "http://kupo.internal:1442");
+            assert_eq!(provider.ogmios_url, "ws://ogmios.internal:1337");
+        }
+        _ => panic!("Expected Ogmios provider"),
+    }
+
+    let invalid = write_config_file(
+        r#"
+        [cardano]
+        ogmios_url = "not a url"
+        "#,
+    );
+    let err = load_server(&["--config", invalid.path().to_str().unwrap()])
+        .expect_err("invalid ogmios_url must be rejected");
+    assert!(format!("{err:?}").contains("cardano.ogmios_url"));
+}
+
+#[test]
+fn provider_backends_parse_from_flags_and_file() {
+    let config = parse_server(&[
+        "--cardano-api-key",
+        "primary",
+        "--cardano-backends",
+        "koios,maestro:mkey@https://maestro.example/v1",
+        "--cardano-quorum",
+        "2",
+    ]);
+    let backends = config.provider_backends();
+    assert_eq!(backends.len(), 2);
+    assert_eq!(backends[0].provider, "koios");
+    assert_eq!(backends[0].api_key, None);
+    assert_eq!(backends[1].api_key.as_deref(), Some("mkey"));
+    assert_eq!(
+        backends[1].provider_url.as_deref(),
+        Some("https://maestro.example/v1")
+    );
+
+    match uncached(Provider::from_config(&config).expect("provider")) {
+        Provider::Quorum(provider) => {
+            assert_eq!(provider.quorum, 2);
+            let names = provider
+                .backends
+                .iter()
+                .map(Provider::name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["blockfrost", "koios", "maestro"]);
+        }
+        _ => panic!("Expected quorum provider"),
+    }
+
+    let file = write_config_file(
+        r#"
+        [cardano]
+        api_key = "primary"
+        quorum = 3
+        backends = [
+            { provider = "koios" },
+            { provider = "blockfrost", api_key = "second", provider_url = "https://bf.example/api/v0" },
+        ]
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+    let config = load_server(&["--config", path]).expect("config should load");
+    assert_eq!(config.provider_quorum(), 3);
+    assert_eq!(config.provider_backends().len(), 2);
+    assert!(matches!(
+        Provider::from_config(&config).map(uncached),
+        Ok(Provider::Quorum(_))
+    ));
+
+    let config = parse_server(&["--cardano-api-key", "primary"]);
+    assert!(matches!(
+        Provider::from_config(&config).map(uncached),
+        Ok(Provider::Blockfrost(_))
+    ));
+}
+
+#[test]
+fn provider_quorum_cannot_exceed_backends() {
+    let config = parse_server(&[
+        "--cardano-api-key",
+        "primary",
+        "--cardano-quorum",
+        "2",
+    ]);
+    let err = Provider::from_config(&config).expect_err("one backend");
+    assert!(format!("{err}").contains("provider quorum 2"));
+
+    assert!(
+        Config::try_parse_from([
+            "mugraph-node",
+            "server",
+            "--cardano-backends",
+            "acme",
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn provider_cache_settings_layer_from_file_and_flags() {
+    let defaults = parse_server(&["--cardano-api-key", "primary"]);
+    let policy = defaults.provider_cache();
+    assert_eq!(policy.immutable, Duration::from_secs(3600));
+    assert_eq!(policy.volatile, Duration::from_secs(5));
+    assert_eq!(policy.negative, Duration::from_secs(2));
+    assert_eq!(policy.settled_depth, 15);
+    assert_eq!(policy.budget_per_minute, None);
+
+    match Provider::from_config(&defaults).expect("provider") {
+        Provider::Cached(provider) => {
+            assert_eq!(provider.policy, policy);
+            assert_eq!(provider.priority, RequestPriority::Critical);
+            assert!(matches!(*provider.inner, Provider::Blockfrost(_)));
+        }
+        _ => panic!("Expected cached provider"),
+    }
+
+    let file = write_config_file(
+        r#"
+        [cardano]
+        api_key = "primary"
+        cache_immutable_secs = 600
+        cache_negative_secs = 0
+        request_budget = 120
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+    let config =
+        load_server(&["--config", path, "--cardano-cache-volatile-secs", "1"])
+            .expect("config should load");
+    let policy = config.provider_cache();
+    assert_eq!(policy.immutable, Duration::from_secs(600));
+    assert_eq!(policy.volatile, Duration::from_secs(1));
+    assert_eq!(policy.negative, Duration::ZERO);
+    assert_eq!(policy.budget_per_minute, Some(120));
+
+    let provider = Provider::from_config(&config)
+        .expect("provider")
+        .with_priority(RequestPriority::Background);
+    match provider {
+        Provider::Cached(provider) => {
+            assert_eq!(provider.priority, RequestPriority::Background)
+        }
+        _ => panic!("Expected cached provider"),
+    }
+}
+
+#[test]
+fn chain_index_poll_interval_layers_from_file_and_flags() {
+    let defaults = parse_server(&["--cardano-api-key", "primary"]);
+    assert_eq!(defaults.index_poll_secs(), 20);
+
+    let file = write_config_file(
+        r#"
+        [cardano]
+        api_key = "primary"
+        index_poll_secs = 5
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+    let config = load_server(&["--config", path]).expect("config should load");
+    assert_eq!(config.index_poll_secs(), 5);
+
+    let config =
+        load_server(&["--config", path, "--cardano-index-poll-secs", "0"])
+            .expect("config should load");
+    assert_eq!(config.index_poll_secs(), 0);
+}
@@ -1,4 +1,8 @@
-use std::{future::Future, path::Path, sync::OnceLock};
+use std::{
+    future::Future,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 use axum::{
     Router,
@@ -6,16 +10,20 @@ use axum::{
     http::{Request as HttpRequest, StatusCode},
 };
 use ed25519_dalek::{Signer, SigningKey};
-use mugraph_core::types::{
-    CardanoWallet, Request, Response, TransferAckPayload, TransferAckStatus,
-    TransferInitPayload, TransferNoticePayload, TransferNoticeStage,
-    TransferQueryType, TransferStatusQueryPayload, XNodeAuth, XNodeEnvelope,
-    XNodeMessageType,
+use mugraph_core::{
+    error::ErrorCode,
+    types::{
+        CardanoWallet, Request, Response, TransferAckPayload,
+        TransferAckStatus, TransferInitPayload, TransferNoticePayload,
+        TransferNoticeStage, TransferQueryType, TransferStatusQueryPayload,
+        XNodeAuth, XNodeEnvelope, XNodeMessageType,
+    },
 };
 use mugraph_node::{
     config::Config,
     database::{CARDANO_WALLET, Database},
     routes::router,
+    serve,
 };
 use tempfile::TempDir;
 use tower::util::ServiceExt;
@@ -43,25 +51,17 @@ where
 }
 
 fn test_config(peer_registry_file: String) -> Config {
-    Config::Server {
-        addr: "127.0.0.1:9999".parse().unwrap(),
-        seed: Some(42),
-        secret_key: None,
-        cardano_network: "preprod".to_string(),
-        cardano_provider: "blockfrost".to_string(),
-        cardano_api_key: None,
-        cardano_provider_url: None,
-        cardano_payment_sk: None,
-        xnode_peer_registry_file: Some(peer_registry_file),
-        xnode_node_id: "node://b".to_string(),
-        deposit_confirm_depth: 15,
-        deposit_expiration_blocks: 1440,
-        min_deposit_value: Some(1_000_000),
-        max_tx_size: 16_384,
-        max_withdrawal_fee: 2_000_000,
-        fee_tolerance_pct: 5,
-        dev_mode: true,
+    let mut config = Config::test_server();
+    if let Config::Server {
+        xnode_peer_registry_file,
+        xnode_node_id,
+        ..
+    } = &mut config
+    {
+        *xnode_peer_registry_file = Some(peer_registry_file);
+        *xnode_node_id = "node://b".to_string();
     }
+    config
 }
 
 fn write_registry(dir: &TempDir, pk: &SigningKey) -> String {
@@ -83,7 +83,7 @@ fn seed_wallet(path: &Path) {
         let mut t = w.open_table(CARDANO_WALLET).unwrap();
         t.insert(
             "wallet",
-            &CardanoWallet::new(
+            CardanoWallet::new(
                 vec![7u8; 32],
                 vec![8u8; 32],
                 vec![],
@@ -162,6 +162,7 @@ async fn cross_node_rpc_smoke_preserves_create_notify_status_ack_flow() {
             destination_node_id: "node://b".to_string(),
             sent_at: now_rfc3339_offset(0),
             expires_at: Some(now_rfc3339_offset(120)),
+            trace_context: None,
             payload: TransferInitPayload {
                 asset: "lovelace".to_string(),
                 amount: "1000000".to_string(),
@@ -200,6 +201,7 @@ async fn cross_node_rpc_smoke_preserves_create_notify_status_ack_flow() {
             destination_node_id: "node://b".to_string(),
             sent_at: now_rfc3339_offset(0),
             expires_at: Some(now_rfc3339_offset(120)),
+            trace_context: None,
             payload: TransferNoticePayload {
                 notice_stage: TransferNoticeStage::Submitted,
                 tx_hash: notice_tx_hash.clone(),
@@ -233,6 +235,7 @@ async fn cross_node_rpc_smoke_preserves_create_notify_status_ack_flow() {
             destination_node_id: "node://b".to_string(),
             sent_at: now_rfc3339_offset(0),
             expires_at: None,
+            trace_context: None,
             payload: TransferStatusQueryPayload {
                 query_type: TransferQueryType::Current,
             },
@@ -274,6 +277,7 @@ async fn cross_node_rpc_smoke_preserves_create_notify_status_ack_flow() {
             destination_node_id: "node://b".to_string(),
             sent_at: now_rfc3339_offset(0),
             expires_at: Some(now_rfc3339_offset(120)),
+            trace_context: None,
             payload: TransferAckPayload {
                 ack_for_message_id: "mid-notify".to_string(),
                 ack_status: TransferAckStatus::Processed,
@@ -294,3 +298,209 @@ async fn cross_node_rpc_smoke_preserves_create_notify_status_ack_flow() {
         Response::CrossNodeTransferAck { accepted: true }
     ));
 }
+
+struct TestPki {
+    ca: rcgen::Certificate,
+    ca_key: rcgen::KeyPair,
+}
+
+impl TestPki {
+    fn new() -> Self {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key).unwrap();
+        Self { ca, ca_key }
+    }
+
+    fn issue(
+        &self,
+        sans: Vec<rcgen::SanType>,
+        usage: rcgen::ExtendedKeyUsagePurpose,
+    ) -> (rcgen::Certificate, rcgen::KeyPair) {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+        params.subject_alt_names = sans;
+        params.extended_key_usages = vec![usage];
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        (cert, key)
+    }
+
+    fn client(&self, node_id: &str) -> (rcgen::Certificate, rcgen::KeyPair) {
+        self.issue(
+            vec![rcgen::SanType::URI(node_id.try_into().unwrap())],
+            rcgen::ExtendedKeyUsagePurpose::ClientAuth,
+        )
+    }
+}
+
+async fn send_rpc_over_tls(
+    addr: std::net::SocketAddr,
+    ca: &rcgen::Certificate,
+    client_cert: Option<&(rcgen::Certificate, rcgen::KeyPair)>,
+    request: &Request,
+) -> (StatusCode, Response) {
+    use rustls_pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca.der().clone()).unwrap();
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots);
+    let config = match client_cert {
+        Some((cert, key)) => builder
+            .with_client_auth_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                    key.serialize_der(),
+                )),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+
+    let body = serde_json::to_vec(request).unwrap();
+    let head = format!(
+        "POST /rpc HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&body).await.unwrap();
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await.unwrap();
+    let raw = String::from_utf8(raw).unwrap();
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse::<u16>().unwrap();
+    (
+        StatusCode::from_u16(status).unwrap(),
+        serde_json::from_str(body).unwrap(),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn xnode_rpc_over_mutual_tls_requires_the_origin_nodes_certificate() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("cross-node-mtls.redb");
+    seed_wallet(&db_path);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    let registry_path = write_registry(&dir, &signer);
+
+    let pki = TestPki::new();
+    let (server_cert, server_key) = pki.issue(
+        vec![rcgen::SanType::DnsName("localhost".try_into().unwrap())],
+        rcgen::ExtendedKeyUsagePurpose::ServerAuth,
+    );
+    let write = |name: &str, contents: String| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    };
+    let mut config = test_config(registry_path);
+    if let Config::Server {
+        tls_cert_file,
+        tls_key_file,
+        xnode_client_ca_file,
+        ..
+    } = &mut config
+    {
+        *tls_cert_file = Some(write("server.pem", server_cert.pem()));
+        *tls_key_file = Some(write("server.key", server_key.serialize_pem()));
+        *xnode_client_ca_file = Some(write("ca.pem", pki.ca.pem()));
+    }
+
+    let origin_cert = pki.client("node://a");
+    let other_cert = pki.client("node://c");
+    let create = Request::CrossNodeTransferCreate(sign_envelope(
+        XNodeEnvelope {
+            m: "xnode".to_string(),
+            version: "3.0".to_string(),
+            message_type: XNodeMessageType::TransferInit,
+            message_id: "mid-mtls".to_string(),
+            transfer_id: "tr-mtls".to_string(),
+            idempotency_key: "ik-mtls".to_string(),
+            correlation_id: "corr-mtls".to_string(),
+            origin_node_id: "node://a".to_string(),
+            destination_node_id: "node://b".to_string(),
+            sent_at: now_rfc3339_offset(0),
+            expires_at: Some(now_rfc3339_offset(120)),
+            trace_context: None,
+            payload: TransferInitPayload {
+                asset: "lovelace".to_string(),
+                amount: "1000000".to_string(),
+                destination_account_ref: "acct-1".to_string(),
+                source_intent_hash: "ab".repeat(32),
+            },
+            auth: XNodeAuth {
+                alg: "Ed25519".to_string(),
+                kid: "k1".to_string(),
+                sig: String::new(),
+            },
+        },
+        &signer,
+    ));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+
+    with_db_path(&db_path, || async {
+        let keypair = config.keypair().unwrap();
+        let server = tokio::spawn(serve(listener, config, keypair, async {
+            let _ = stop_rx.await;
+        }));
+
+        // Wallet traffic needs no client certificate
+        let (status, info) =
+            send_rpc_over_tls(addr, &pki.ca, None, &Request::Info).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(matches!(info, Response::Info { .. }), "{info:?}");
+
+        let (status, anonymous) =
+            send_rpc_over_tls(addr, &pki.ca, None, &create).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(
+            matches!(&anonymous, Response::Error {
+                code: ErrorCode::AuthzDenied,
+                reason,
+                ..
+            } if reason.contains("client certificate")),
+            "{anonymous:?}"
+        );
+
+        let (status, impostor) =
+            send_rpc_over_tls(addr, &pki.ca, Some(&other_cert), &create).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(
+            matches!(&impostor, Response::Error { reason, .. }
+                if reason.contains("does not identify node://a")),
+            "{impostor:?}"
+        );
+
+        let (status, created) =
+            send_rpc_over_tls(addr, &pki.ca, Some(&origin_cert), &create).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            matches!(
+                &created,
+                Response::CrossNodeTransferCreate { accepted: true, .. }
+            ),
+            "{created:?}"
+        );
+
+        stop_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    })
+    .await;
+}
@@ -3,8 +3,8 @@ use mugraph_core::types::{
     TransferAuditEvent,
 };
 use mugraph_node::database::{
-    CROSS_NODE_MESSAGES, CROSS_NODE_TRANSFERS, Database, IDEMPOTENCY_KEYS,
-    TRANSFER_AUDIT_LOG,
+    CROSS_NODE_MESSAGES, CROSS_NODE_TRANSFERS, CURRENT_SCHEMA_VERSION,
+    Database, IDEMPOTENCY_KEYS, TRANSFER_AUDIT_LOG,
 };
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
@@ -20,10 +20,10 @@ fn temp_db_path() -> std::path::PathBuf {
 }
 
 #[test]
-fn migrates_schema_to_v3() -> TestResult {
+fn migrates_schema_to_the_current_version() -> TestResult {
     let db = Database::setup(temp_db_path())?;
     db.migrate()?;
-    assert_eq!(db.schema_version()?, 3);
+    assert_eq!(db.schema_version()?, CURRENT_SCHEMA_VERSION);
     Ok(())
 }
 
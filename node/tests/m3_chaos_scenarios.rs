@@ -82,6 +82,7 @@ fn mixed_version_scenario_rejects_unsupported_major() {
         destination_node_id: "node://b".to_string(),
         sent_at: "2026-02-26T18:00:00Z".to_string(),
         expires_at: Some("2026-02-26T18:05:00Z".to_string()),
+        trace_context: None,
         payload: (),
         auth: mugraph_core::types::XNodeAuth {
             alg: "Ed25519".to_string(),
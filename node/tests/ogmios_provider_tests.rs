@@ -0,0 +1,445 @@
+//! Ogmios + Kupo provider against local stand-ins replaying recorded
+//! responses
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    extract::{
+        Path, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::IntoResponse,
+    routing::get,
+};
+use mugraph_node::provider::{
+    Provider, Ratio, RedeemerBudget, TxSettlementState,
+};
+use serde_json::{Value, json};
+
+const OUTPUT_TX: &str =
+    "5f2ab3b0d4d3a1d7c5a0c8bd2e7e4a4c7f0e9f0a3b8f3b1d2c6a9e8d7f6e5d4c";
+const SCRIPT_ADDRESS: &str =
+    "addr_test1wzq3v3kfuzqhl4ffm2n2y6rj4mu6ttjw3m6zhtyy5kyzxpqccnrd2";
+const CREATED_AT_HEADER: &str =
+    "a1f3c1b4e54fbe9d6d2a0a3e59d3c0f14e1f54e8c0f8c6f46cc2c93b2d17c1a2";
+
+fn recorded(json: &str) -> Value {
+    serde_json::from_str(json).expect("recorded fixture")
+}
+
+/// Recorded Ogmios responses by method, replayed in order on each
+/// connection; the last one repeats.
+fn ogmios_replies(
+    overrides: &[(&'static str, Vec<Value>)],
+) -> HashMap<&'static str, Vec<Value>> {
+    let mut replies = HashMap::from([
+        (
+            "queryNetwork/tip",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/query_network_tip.json"
+            ))],
+        ),
+        (
+            "queryNetwork/blockHeight",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/query_network_block_height.json"
+            ))],
+        ),
+        (
+            "queryLedgerState/protocolParameters",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/query_ledger_state_protocol_parameters.json"
+            ))],
+        ),
+        (
+            "submitTransaction",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/submit_transaction.json"
+            ))],
+        ),
+        (
+            "evaluateTransaction",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/evaluate_transaction.json"
+            ))],
+        ),
+        (
+            "findIntersection",
+            vec![recorded(include_str!(
+                "fixtures/ogmios/find_intersection.json"
+            ))],
+        ),
+        (
+            "nextBlock",
+            vec![
+                recorded(include_str!(
+                    "fixtures/ogmios/next_block_backward.json"
+                )),
+                recorded(include_str!(
+                    "fixtures/ogmios/next_block_forward.json"
+                )),
+            ],
+        ),
+    ]);
+    for (method, responses) in overrides {
+        replies.insert(method, responses.clone());
+    }
+    replies
+}
+
+#[derive(Clone)]
+struct OgmiosState {
+    replies: Arc<HashMap<&'static str, Vec<Value>>>,
+    requests: Arc<Mutex<Vec<Value>>>,
+}
+
+async fn ogmios_socket(
+    ws: WebSocketUpgrade,
+    State(state): State<OgmiosState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| replay_ogmios(socket, state))
+}
+
+async fn replay_ogmios(mut socket: WebSocket, state: OgmiosState) {
+    let mut served: HashMap<String, usize> = HashMap::new();
+    while let Some(Ok(message)) = socket.recv().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let request: Value = serde_json::from_str(&text).expect("json-rpc");
+        let method = request["method"].as_str().expect("method").to_string();
+        state.requests.lock().unwrap().push(request.clone());
+
+        let responses = &state.replies[method.as_str()];
+        let count = served.entry(method).or_default();
+        let mut reply = responses[(*count).min(responses.len() - 1)].clone();
+        *count += 1;
+        reply["id"] = request["id"].clone();
+
+        if socket
+            .send(Message::Text(reply.to_string().into()))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+async fn kupo_matches(Path(pattern): Path<String>) -> impl IntoResponse {
+    let output = format!("0@{OUTPUT_TX}");
+    let any_output = format!("*@{OUTPUT_TX}");
+    let body = if pattern == output || pattern == any_output {
+        recorded(include_str!("fixtures/kupo/matches_output.json"))
+    } else if pattern == SCRIPT_ADDRESS {
+        recorded(include_str!("fixtures/kupo/matches_address.json"))
+    } else {
+        json!([])
+    };
+
+    axum::Json(body)
+}
+
+async fn kupo_datum(Path(_hash): Path<String>) -> impl IntoResponse {
+    axum::Json(recorded(include_str!("fixtures/kupo/datum.json")))
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    addr.to_string()
+}
+
+async fn spawn_stand_ins(
+    overrides: &[(&'static str, Vec<Value>)],
+) -> (Provider, Arc<Mutex<Vec<Value>>>) {
+    let kupo = serve(
+        Router::new()
+            .route("/matches/{pattern}", get(kupo_matches))
+            .route("/datums/{hash}", get(kupo_datum)),
+    )
+    .await;
+
+    let state = OgmiosState {
+        replies: Arc::new(ogmios_replies(overrides)),
+        requests: Arc::new(Mutex::new(Vec::new())),
+    };
+    let ogmios = serve(
+        Router::new()
+            .route("/", get(ogmios_socket))
+            .with_state(state.clone()),
+    )
+    .await;
+
+    let provider = match Provider::new(
+        "ogmios",
+        String::new(),
+        "preprod".to_string(),
+        Some(format!("http://{kupo}")),
+    )
+    .expect("provider")
+    {
+        Provider::Ogmios(mut provider) => {
+            provider.ogmios_url = format!("ws://{ogmios}");
+            Provider::Ogmios(provider)
+        }
+        _ => panic!("Expected Ogmios provider"),
+    };
+
+    (provider, state.requests)
+}
+
+fn methods(requests: &Mutex<Vec<Value>>) -> Vec<String> {
+    requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|request| request["method"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn ogmios_get_tip_combines_tip_point_and_block_height() {
+    let (provider, _) = spawn_stand_ins(&[]).await;
+
+    let tip = provider.get_tip().await.expect("tip");
+    assert_eq!(tip.slot, 71_284_210);
+    assert_eq!(
+        tip.hash,
+        "f2d0e9c4b7a6d5e3c1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8"
+    );
+    assert_eq!(tip.block_height, 2_893_410);
+}
+
+#[tokio::test]
+async fn ogmios_get_protocol_params_converts_fractions_and_nested_units() {
+    let (provider, _) = spawn_stand_ins(&[]).await;
+
+    let params = provider
+        .get_protocol_params()
+        .await
+        .expect("protocol params");
+    assert_eq!(params.min_fee_a, 44);
+    assert_eq!(params.min_fee_b, 155_381);
+    assert_eq!(params.max_tx_size, 16_384);
+    assert_eq!(params.max_val_size, 5_000);
+    assert_eq!(params.key_deposit, 2_000_000);
+    assert_eq!(params.coins_per_utxo_byte, 4_310);
+    assert_eq!(params.max_tx_ex_mem, 14_000_000);
+    assert_eq!(params.max_tx_ex_steps, 10_000_000_000);
+    assert_eq!(params.price_mem, Ratio::new(577, 10_000));
+    assert_eq!(params.price_step, Ratio::new(721, 10_000_000));
+}
+
+#[tokio::test]
+async fn ogmios_submit_tx_sends_hex_cbor_and_returns_id() {
+    let (provider, requests) = spawn_stand_ins(&[]).await;
+
+    let response = provider
+        .submit_tx(&[0xde, 0xad, 0xbe, 0xef])
+        .await
+        .expect("submit ok");
+    assert_eq!(
+        response.tx_hash,
+        "cdc5a7d0f2b3e4a5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9"
+    );
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0]["params"]["transaction"]["cbor"], "deadbeef");
+}
+
+#[tokio::test]
+async fn ogmios_submit_tx_surfaces_ledger_rejection() {
+    let (provider, _) = spawn_stand_ins(&[(
+        "submitTransaction",
+        vec![recorded(include_str!(
+            "fixtures/ogmios/submit_transaction_rejected.json"
+        ))],
+    )])
+    .await;
+
+    let err = provider
+        .submit_tx(&[0xca, 0xfe])
+        .await
+        .expect_err("rejected submission must fail");
+    let msg = format!("{err:?}");
+    assert!(msg.contains("Failed to submit transaction to Ogmios"));
+    assert!(msg.contains("Ogmios error 3117"));
+    assert!(msg.contains("unknownOutputReferences"));
+}
+
+#[tokio::test]
+async fn ogmios_evaluate_tx_returns_budget_per_redeemer() {
+    let (provider, _) = spawn_stand_ins(&[]).await;
+
+    let budgets = provider.evaluate_tx(&[0x84]).await.expect("evaluation");
+    assert_eq!(
+        budgets,
+        vec![
+            RedeemerBudget {
+                purpose: "spend".to_string(),
+                index: 0,
+                memory: 1_765_011,
+                cpu: 503_871_230,
+            },
+            RedeemerBudget {
+                purpose: "mint".to_string(),
+                index: 0,
+                memory: 2_301,
+                cpu: 586_883,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn evaluate_tx_is_unsupported_by_hosted_providers() {
+    let provider = Provider::new(
+        "blockfrost",
+        "test-key".to_string(),
+        "preprod".to_string(),
+        Some("http://127.0.0.1:9".to_string()),
+    )
+    .expect("provider");
+
+    let err = provider
+        .evaluate_tx(&[0x84])
+        .await
+        .expect_err("blockfrost cannot evaluate");
+    assert!(format!("{err}").contains("blockfrost cannot evaluate"));
+}
+
+#[tokio::test]
+async fn kupo_get_utxo_resolves_datum_assets_and_block_height() {
+    let (provider, requests) = spawn_stand_ins(&[]).await;
+
+    let utxo = provider
+        .get_utxo(OUTPUT_TX, 0)
+        .await
+        .expect("get utxo")
+        .expect("utxo should exist");
+    assert_eq!(utxo.address, SCRIPT_ADDRESS);
+    assert_eq!(utxo.amount.len(), 2);
+    assert_eq!(utxo.amount[0].unit, "lovelace");
+    assert_eq!(utxo.amount[0].quantity, "5000000");
+    assert_eq!(
+        utxo.amount[1].unit,
+        "2b8a7e1c9d4f6a3b5c8e0d2f4a6b8c0e2d4f6a8b0c2e4d6f8a0b2c4d4d554752"
+    );
+    assert_eq!(utxo.amount[1].quantity, "25");
+    assert!(utxo.datum.as_deref().unwrap().starts_with("d8799f"));
+    // The block after the creating one is recorded at height 2893401
+    assert_eq!(utxo.block_height, Some(2_893_400));
+
+    assert_eq!(
+        methods(&requests),
+        ["findIntersection", "nextBlock", "nextBlock"]
+    );
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        requests[0]["params"]["points"][0],
+        json!({"slot": 71_283_944, "id": CREATED_AT_HEADER})
+    );
+}
+
+#[tokio::test]
+async fn kupo_get_utxo_returns_none_for_spent_or_unknown_output() {
+    let (provider, requests) = spawn_stand_ins(&[]).await;
+
+    let missing = provider
+        .get_utxo(OUTPUT_TX, 1)
+        .await
+        .expect("missing lookup should succeed");
+    assert!(missing.is_none());
+    assert!(methods(&requests).is_empty());
+}
+
+#[tokio::test]
+async fn kupo_get_address_utxos_maps_coins_assets_and_datum_hashes() {
+    let (provider, _) = spawn_stand_ins(&[]).await;
+
+    let utxos = provider
+        .get_address_utxos(SCRIPT_ADDRESS)
+        .await
+        .expect("address utxos");
+    assert_eq!(utxos.len(), 2);
+    assert_eq!(utxos[0].output_index, 1);
+    assert_eq!(utxos[0].amount.len(), 1);
+    assert_eq!(utxos[0].datum_hash, None);
+    assert_eq!(utxos[0].block_height, None);
+    assert_eq!(utxos[1].amount[1].quantity, "1");
+    assert_eq!(
+        utxos[1].script_ref.as_deref(),
+        Some("e1317b152faac13426e6a83e06ff88a4d62cce3c1634ab0a5ec13309")
+    );
+    assert!(utxos[1].datum_hash.is_some());
+
+    let none = provider
+        .get_address_utxos("addr_test1unknown")
+        .await
+        .expect("empty address");
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn observe_tx_status_counts_confirmations_from_chain_sync_height() {
+    let (provider, _) = spawn_stand_ins(&[]).await;
+
+    let observation = provider
+        .observe_tx_status(OUTPUT_TX, 12, false)
+        .await
+        .expect("observation");
+    assert_eq!(observation.tx_block_height, Some(2_893_400));
+    assert_eq!(observation.tip_height, 2_893_410);
+    assert_eq!(observation.confirmations, 11);
+    assert_eq!(observation.state, TxSettlementState::Confirming);
+
+    let unknown = provider
+        .observe_tx_status(&"ee".repeat(32), 12, false)
+        .await
+        .expect("unknown observation");
+    assert_eq!(unknown.state, TxSettlementState::NotFound);
+}
+
+#[tokio::test]
+async fn observe_tx_status_invalidates_rolled_back_block() {
+    let (provider, _) = spawn_stand_ins(&[(
+        "findIntersection",
+        vec![recorded(include_str!(
+            "fixtures/ogmios/find_intersection_not_found.json"
+        ))],
+    )])
+    .await;
+
+    let observation = provider
+        .observe_tx_status(OUTPUT_TX, 12, true)
+        .await
+        .expect("observation");
+    assert_eq!(observation.tx_block_height, None);
+    assert_eq!(observation.state, TxSettlementState::Invalidated);
+}
+
+#[tokio::test]
+async fn block_at_tip_uses_tip_height_without_reading_blocks() {
+    let mut at_tip =
+        recorded(include_str!("fixtures/ogmios/find_intersection.json"));
+    at_tip["result"]["tip"]["id"] = json!(CREATED_AT_HEADER);
+    let (provider, requests) =
+        spawn_stand_ins(&[("findIntersection", vec![at_tip])]).await;
+
+    let utxo = provider
+        .get_utxo(OUTPUT_TX, 0)
+        .await
+        .expect("get utxo")
+        .expect("utxo should exist");
+    assert_eq!(utxo.block_height, Some(2_893_410));
+    assert_eq!(methods(&requests), ["findIntersection"]);
+}
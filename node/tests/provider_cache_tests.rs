@@ -0,0 +1,283 @@
+//! Cached provider in front of a Koios-shaped stand-in that counts requests
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use mugraph_node::{
+    provider::{
+        CachePolicy, CachedProvider, Provider, ProviderBudgetExhausted,
+        RequestPriority,
+    },
+    telemetry::build_recorder,
+};
+use serde_json::{Value, json};
+
+const TIP: u64 = 1_010;
+
+#[derive(Clone, Default)]
+struct Backend {
+    requests: Arc<AtomicU64>,
+    /// Block height of every output, or 0 when no output exists
+    utxo_height: Arc<AtomicU64>,
+}
+
+impl Backend {
+    fn requests(&self) -> u64 {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+async fn utxo_info(
+    State(backend): State<Backend>,
+    axum::Json(body): axum::Json<Value>,
+) -> impl IntoResponse {
+    backend.requests.fetch_add(1, Ordering::SeqCst);
+    let height = backend.utxo_height.load(Ordering::SeqCst);
+    if height == 0 {
+        return axum::Json(json!([]));
+    }
+
+    let reference = body["_utxo_refs"][0].as_str().unwrap();
+    let (tx_hash, _) = reference.split_once('#').unwrap();
+    axum::Json(json!([{
+        "tx_hash": tx_hash,
+        "tx_index": 0,
+        "address": "addr_test1cache",
+        "value": "5000000",
+        "block_height": height,
+        "datum_hash": null,
+        "inline_datum": null,
+        "reference_script": null,
+        "asset_list": [],
+        "is_spent": false
+    }]))
+}
+
+async fn tip(State(backend): State<Backend>) -> impl IntoResponse {
+    backend.requests.fetch_add(1, Ordering::SeqCst);
+    axum::Json(json!([{
+        "hash": "ab".repeat(32),
+        "abs_slot": TIP * 20,
+        "block_no": TIP
+    }]))
+}
+
+async fn protocol_params(State(backend): State<Backend>) -> impl IntoResponse {
+    backend.requests.fetch_add(1, Ordering::SeqCst);
+    axum::Json(json!({
+        "txFeePerByte": 44,
+        "txFeeFixed": 155381,
+        "maxTxSize": 16384,
+        "maxValueSize": 5000,
+        "stakeAddressDeposit": 2000000,
+        "stakePoolDeposit": 500000000,
+        "executionUnitPrices": {"priceMemory": 0.0577, "priceSteps": 0.0000721},
+        "maxTxExecutionUnits": {"memory": 14000000, "steps": 10000000000u64},
+        "utxoCostPerByte": 4310
+    }))
+}
+
+async fn submit(State(backend): State<Backend>) -> impl IntoResponse {
+    backend.requests.fetch_add(1, Ordering::SeqCst);
+    (StatusCode::ACCEPTED, axum::Json(json!("cd".repeat(32))))
+}
+
+async fn spawn_backend(utxo_height: u64) -> (Backend, Provider) {
+    let backend = Backend::default();
+    backend.utxo_height.store(utxo_height, Ordering::SeqCst);
+    let app = Router::new()
+        .route("/utxo_info", post(utxo_info))
+        .route("/tip", get(tip))
+        .route("/cli_protocol_params", get(protocol_params))
+        .route("/submittx", post(submit))
+        .with_state(backend.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let provider = Provider::new(
+        "koios",
+        String::new(),
+        "preprod".to_string(),
+        Some(format!("http://{addr}")),
+    )
+    .expect("provider");
+    (backend, provider)
+}
+
+fn policy() -> CachePolicy {
+    CachePolicy {
+        immutable: Duration::from_secs(3600),
+        volatile: Duration::from_secs(3600),
+        negative: Duration::from_secs(3600),
+        settled_depth: 5,
+        budget_per_minute: None,
+    }
+}
+
+#[tokio::test]
+async fn repeated_tips_are_answered_from_the_cache() {
+    let (backend, inner) = spawn_backend(1_000).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy()));
+
+    let recorder = build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(&recorder);
+
+    for _ in 0..3 {
+        assert_eq!(provider.get_tip().await.unwrap().block_height, TIP);
+    }
+    assert_eq!(backend.requests(), 1);
+
+    let rendered = handle.render();
+    for (result, count) in [("hit", 2), ("miss", 1)] {
+        let line = format!(
+            "mugraph_node_provider_cache_requests_total{{operation=\"get_tip\",result=\"{result}\"}} {count}"
+        );
+        assert!(rendered.contains(&line), "{rendered}");
+    }
+}
+
+#[tokio::test]
+async fn outputs_are_rechecked_after_the_volatile_ttl_however_deep() {
+    let uncached = CachePolicy {
+        volatile: Duration::ZERO,
+        ..policy()
+    };
+    let tx_hash = "11".repeat(32);
+
+    // Ten blocks deep with a settled depth of five, but it can still be
+    // spent, so it is not kept
+    let (backend, inner) = spawn_backend(TIP - 10).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, uncached));
+    provider.get_tip().await.unwrap();
+    provider.get_utxo(&tx_hash, 0).await.unwrap().expect("utxo");
+    assert_eq!(backend.requests(), 2);
+
+    backend.utxo_height.store(0, Ordering::SeqCst);
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_none());
+    assert_eq!(backend.requests(), 3);
+
+    // Within the volatile TTL the answer is reused
+    let (backend, inner) = spawn_backend(TIP - 10).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy()));
+    provider.get_utxo(&tx_hash, 0).await.unwrap().expect("utxo");
+    provider.get_utxo(&tx_hash, 0).await.unwrap().expect("utxo");
+    assert_eq!(backend.requests(), 1);
+}
+
+#[tokio::test]
+async fn protocol_params_are_kept_for_the_rest_of_the_epoch() {
+    let policy = CachePolicy {
+        volatile: Duration::ZERO,
+        ..policy()
+    };
+    let (backend, inner) = spawn_backend(1_000).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy));
+
+    for _ in 0..3 {
+        let params = provider.get_protocol_params().await.unwrap();
+        assert_eq!(params.coins_per_utxo_byte, 4_310);
+    }
+    assert_eq!(backend.requests(), 1);
+
+    // The tip is volatile and still reaches the backend every time
+    provider.get_tip().await.unwrap();
+    provider.get_tip().await.unwrap();
+    assert_eq!(backend.requests(), 3);
+}
+
+#[tokio::test]
+async fn missing_outputs_use_the_negative_ttl() {
+    let tx_hash = "22".repeat(32);
+
+    let (backend, inner) = spawn_backend(0).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy()));
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_none());
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_none());
+    assert_eq!(backend.requests(), 1);
+
+    let policy = CachePolicy {
+        negative: Duration::ZERO,
+        ..policy()
+    };
+    let (backend, inner) = spawn_backend(0).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy));
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_none());
+    backend.utxo_height.store(TIP - 10, Ordering::SeqCst);
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn submitting_drops_cached_outputs() {
+    let tx_hash = "33".repeat(32);
+    let (backend, inner) = spawn_backend(TIP - 10).await;
+    let provider = Provider::Cached(CachedProvider::new(inner, policy()));
+
+    provider.get_utxo(&tx_hash, 0).await.unwrap().expect("utxo");
+    provider.get_utxo(&tx_hash, 0).await.unwrap().expect("utxo");
+    assert_eq!(backend.requests(), 1);
+
+    provider.submit_tx(&[0x84, 0xa0]).await.expect("submitted");
+    backend.utxo_height.store(0, Ordering::SeqCst);
+    assert!(provider.get_utxo(&tx_hash, 0).await.unwrap().is_none());
+    assert_eq!(backend.requests(), 3);
+}
+
+#[tokio::test]
+async fn background_calls_are_shed_before_critical_ones() {
+    let policy = CachePolicy {
+        volatile: Duration::ZERO,
+        budget_per_minute: Some(4),
+        ..policy()
+    };
+    let (backend, inner) = spawn_backend(1_000).await;
+    let critical = Provider::Cached(CachedProvider::new(inner, policy));
+    let background =
+        critical.clone().with_priority(RequestPriority::Background);
+
+    let recorder = build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(&recorder);
+
+    for _ in 0..3 {
+        background.get_tip().await.expect("within budget");
+    }
+    let err = background
+        .get_tip()
+        .await
+        .expect_err("reserved for critical");
+    let exhausted = err
+        .downcast_ref::<ProviderBudgetExhausted>()
+        .unwrap_or_else(|| panic!("expected a shed call, got {err:?}"));
+    assert_eq!(exhausted.priority, RequestPriority::Background);
+    assert_eq!(exhausted.budget, 4);
+
+    critical.get_tip().await.expect("critical reserve");
+    let err = critical.get_tip().await.expect_err("budget spent");
+    assert!(format!("{err}").contains("shedding critical get_tip"));
+    assert_eq!(backend.requests(), 4);
+
+    let rendered = handle.render();
+    assert!(
+        rendered.contains(
+            "mugraph_node_provider_requests_shed_total{operation=\"get_tip\",priority=\"background\"} 1"
+        ),
+        "{rendered}"
+    );
+}
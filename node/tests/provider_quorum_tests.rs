@@ -0,0 +1,434 @@
+//! Quorum provider over several Koios-shaped stand-ins giving chosen answers
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use mugraph_core::types::CardanoWallet;
+use mugraph_node::{
+    chain_index::{self, ChainIndex},
+    database::{CARDANO_WALLET, Database},
+    provider::{Provider, ProviderDisagreement, QuorumProvider},
+    telemetry::build_recorder,
+};
+use serde_json::{Value, json};
+
+const ADDRESS: &str = "addr_test1quorum";
+
+/// What one stand-in reports for every output and transaction
+#[derive(Clone, Copy)]
+struct Answer {
+    up: bool,
+    /// Lovelace in the output, or `None` when the output does not exist
+    lovelace: Option<u64>,
+    block_height: u64,
+    tip: u64,
+}
+
+impl Answer {
+    fn honest() -> Self {
+        Self {
+            up: true,
+            lovelace: Some(5_000_000),
+            block_height: 1_000,
+            tip: 1_010,
+        }
+    }
+
+    fn down() -> Self {
+        Self {
+            up: false,
+            ..Self::honest()
+        }
+    }
+}
+
+fn utxo(answer: Answer, tx_hash: &str) -> Value {
+    json!({
+        "tx_hash": tx_hash,
+        "tx_index": 0,
+        "address": ADDRESS,
+        "value": answer.lovelace.unwrap_or_default().to_string(),
+        "block_height": answer.block_height,
+        "datum_hash": null,
+        "inline_datum": {"bytes": "D8799F01FF"},
+        "reference_script": null,
+        "asset_list": [],
+        "is_spent": false
+    })
+}
+
+async fn utxo_info(
+    State(answer): State<Answer>,
+    axum::Json(body): axum::Json<Value>,
+) -> impl IntoResponse {
+    if !answer.up {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let reference = body["_utxo_refs"][0].as_str().unwrap();
+    let (tx_hash, _) = reference.split_once('#').unwrap();
+    match answer.lovelace {
+        Some(_) => axum::Json(json!([utxo(answer, tx_hash)])).into_response(),
+        None => axum::Json(json!([])).into_response(),
+    }
+}
+
+async fn address_utxos(State(answer): State<Answer>) -> impl IntoResponse {
+    if !answer.up {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    axum::Json(json!([utxo(answer, &"11".repeat(32))])).into_response()
+}
+
+async fn tx_info(
+    State(answer): State<Answer>,
+    axum::Json(body): axum::Json<Value>,
+) -> impl IntoResponse {
+    if !answer.up {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    axum::Json(json!([{
+        "tx_hash": body["_tx_hashes"][0],
+        "block_height": answer.block_height
+    }]))
+    .into_response()
+}
+
+async fn tip(State(answer): State<Answer>) -> impl IntoResponse {
+    if !answer.up {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    axum::Json(json!([{
+        "hash": format!("{:064x}", answer.tip),
+        "abs_slot": answer.tip * 20,
+        "block_no": answer.tip
+    }]))
+    .into_response()
+}
+
+async fn submit(State(answer): State<Answer>) -> impl IntoResponse {
+    if !answer.up {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (StatusCode::ACCEPTED, axum::Json(json!("ab".repeat(32)))).into_response()
+}
+
+async fn spawn_backend(answer: Answer) -> Provider {
+    let app = Router::new()
+        .route("/utxo_info", post(utxo_info))
+        .route("/address_utxos", post(address_utxos))
+        .route("/tx_info", post(tx_info))
+        .route("/tip", get(tip))
+        .route("/submittx", post(submit))
+        .with_state(answer);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    Provider::new(
+        "koios",
+        String::new(),
+        "preprod".to_string(),
+        Some(format!("http://{addr}")),
+    )
+    .expect("provider")
+}
+
+async fn quorum(quorum: usize, answers: &[Answer]) -> Provider {
+    let mut backends = Vec::new();
+    for answer in answers {
+        backends.push(spawn_backend(*answer).await);
+    }
+
+    Provider::Quorum(QuorumProvider { backends, quorum })
+}
+
+/// A migrated database whose wallet's script address is [`ADDRESS`]
+fn database() -> Arc<Database> {
+    let dir = tempfile::tempdir().unwrap().keep();
+    let database = Arc::new(Database::setup(dir.join("node.db")).unwrap());
+    database.migrate().unwrap();
+
+    let w = database.write().unwrap();
+    {
+        let mut table = w.open_table(CARDANO_WALLET).unwrap();
+        table
+            .insert(
+                "wallet",
+                CardanoWallet::new(
+                    vec![1u8; 32],
+                    vec![2u8; 32],
+                    vec![],
+                    vec![],
+                    ADDRESS.to_string(),
+                    "preprod".to_string(),
+                ),
+            )
+            .unwrap();
+    }
+    w.commit().unwrap();
+    database
+}
+
+/// A [`database`] after one chain index pass over `provider`
+async fn synced(provider: &Provider) -> Arc<Database> {
+    let database = database();
+    ChainIndex::new(
+        database.clone(),
+        provider.clone(),
+        Duration::from_secs(20),
+    )
+    .sync()
+    .await
+    .unwrap()
+    .expect("wallet exists");
+    database
+}
+
+fn disagreement(err: &color_eyre::eyre::Report) -> &ProviderDisagreement {
+    err.downcast_ref::<ProviderDisagreement>()
+        .unwrap_or_else(|| panic!("expected a disagreement, got {err:?}"))
+}
+
+#[tokio::test]
+async fn get_utxo_returns_the_answer_a_quorum_agrees_on() {
+    let inflated = Answer {
+        lovelace: Some(500_000_000),
+        ..Answer::honest()
+    };
+    let provider =
+        quorum(2, &[inflated, Answer::honest(), Answer::honest()]).await;
+
+    let recorder = build_recorder();
+    let handle = recorder.handle();
+    let _guard = metrics::set_default_local_recorder(&recorder);
+
+    let utxo = provider
+        .get_utxo(&"22".repeat(32), 0)
+        .await
+        .expect("quorum answer")
+        .expect("utxo exists");
+    assert_eq!(utxo.amount[0].quantity, "5000000");
+
+    let rendered = handle.render();
+    assert!(
+        rendered.contains(
+            "mugraph_node_provider_disagreements_total{operation=\"get_utxo\"} 1"
+        ),
+        "{rendered}"
+    );
+}
+
+#[tokio::test]
+async fn get_utxo_reports_disagreement_without_a_quorum() {
+    let inflated = Answer {
+        lovelace: Some(500_000_000),
+        ..Answer::honest()
+    };
+    let missing = Answer {
+        lovelace: None,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[inflated, Answer::honest(), missing]).await;
+
+    let err = provider
+        .get_utxo(&"22".repeat(32), 0)
+        .await
+        .expect_err("no two backends agree");
+    let disagreement = disagreement(&err);
+    assert_eq!(disagreement.operation, "get_utxo");
+    assert_eq!(disagreement.quorum, 2);
+    assert_eq!(disagreement.answers.len(), 3);
+    assert!(format!("{err}").contains("koios[0] vs koios[1] vs koios[2]"));
+}
+
+#[tokio::test]
+async fn get_utxo_existence_needs_a_quorum() {
+    let missing = Answer {
+        lovelace: None,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[Answer::honest(), missing]).await;
+
+    let err = provider
+        .get_utxo(&"22".repeat(32), 0)
+        .await
+        .expect_err("one backend alone cannot prove a deposit");
+    assert_eq!(disagreement(&err).answers.len(), 2);
+}
+
+#[tokio::test]
+async fn get_utxo_fails_when_too_few_backends_answer() {
+    let provider =
+        quorum(2, &[Answer::down(), Answer::honest(), Answer::down()]).await;
+
+    let err = provider
+        .get_utxo(&"22".repeat(32), 0)
+        .await
+        .expect_err("a single answer is below quorum");
+    assert!(err.downcast_ref::<ProviderDisagreement>().is_none());
+    assert!(format!("{err}").contains("fewer than 2 of 3"));
+
+    let provider =
+        quorum(2, &[Answer::down(), Answer::honest(), Answer::honest()]).await;
+    assert!(
+        provider
+            .get_utxo(&"22".repeat(32), 0)
+            .await
+            .expect("two backends agree")
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn submissions_fail_over_to_the_next_backend() {
+    let provider = quorum(2, &[Answer::down(), Answer::honest()]).await;
+
+    let submitted = provider
+        .submit_tx(&[0x84, 0xa0])
+        .await
+        .expect("second backend accepts");
+    assert_eq!(submitted.tx_hash, "ab".repeat(32));
+
+    let provider = quorum(1, &[Answer::down(), Answer::down()]).await;
+    let err = provider
+        .submit_tx(&[0x84, 0xa0])
+        .await
+        .expect_err("every backend is down");
+    assert!(format!("{err}").contains("all 2 provider backends failed"));
+}
+
+#[tokio::test]
+async fn tip_is_the_highest_a_quorum_has_reached() {
+    let runaway = Answer {
+        tip: 9_999,
+        ..Answer::honest()
+    };
+    let lagging = Answer {
+        tip: 1_005,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[runaway, lagging, Answer::honest()]).await;
+
+    let tip = provider.get_tip().await.expect("tip");
+    assert_eq!(tip.block_height, 1_010);
+}
+
+#[tokio::test]
+async fn confirmations_need_agreement_on_the_block_height() {
+    let provider = quorum(2, &[Answer::honest(), Answer::honest()]).await;
+    let observation = provider
+        .observe_tx_status(&"33".repeat(32), 5, false)
+        .await
+        .expect("observation");
+    assert_eq!(observation.tx_block_height, Some(1_000));
+    assert_eq!(observation.confirmations, 11);
+
+    let early = Answer {
+        block_height: 900,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[early, Answer::honest()]).await;
+    let err = provider
+        .observe_tx_status(&"33".repeat(32), 5, false)
+        .await
+        .expect_err("heights disagree");
+    assert_eq!(disagreement(&err).operation, "get_tx_block_height");
+}
+
+#[tokio::test]
+async fn address_outputs_need_a_quorum() {
+    let inflated = Answer {
+        lovelace: Some(500_000_000),
+        ..Answer::honest()
+    };
+    let provider =
+        quorum(2, &[inflated, Answer::honest(), Answer::honest()]).await;
+    let utxos = provider.get_address_utxos(ADDRESS).await.expect("listing");
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(utxos[0].amount[0].quantity, "5000000");
+    assert_eq!(utxos[0].block_height, Some(1_000));
+
+    // Without a second backend to back it, the output is left out
+    let provider = quorum(2, &[inflated, Answer::honest()]).await;
+    let utxos = provider.get_address_utxos(ADDRESS).await.expect("listing");
+    assert!(utxos.is_empty());
+
+    // and a listing needs `quorum` backends to answer at all
+    let provider = quorum(2, &[Answer::down(), Answer::honest()]).await;
+    let err = provider
+        .get_address_utxos(ADDRESS)
+        .await
+        .expect_err("a single listing is below quorum");
+    assert!(format!("{err}").contains("fewer than 2 of 2"));
+}
+
+#[tokio::test]
+async fn address_outputs_keep_only_the_details_a_quorum_agrees_on() {
+    let early = Answer {
+        block_height: 900,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[early, Answer::honest(), early]).await;
+    let utxos = provider.get_address_utxos(ADDRESS).await.expect("listing");
+    assert_eq!(utxos[0].block_height, Some(900));
+
+    let late = Answer {
+        block_height: 1_005,
+        ..Answer::honest()
+    };
+    let provider = quorum(2, &[early, Answer::honest(), late]).await;
+    let utxos = provider.get_address_utxos(ADDRESS).await.expect("listing");
+    assert_eq!(utxos[0].amount[0].quantity, "5000000");
+    assert_eq!(utxos[0].block_height, None);
+    assert_eq!(utxos[0].datum, None);
+}
+
+#[tokio::test]
+async fn the_chain_index_does_not_trust_a_lying_backend() {
+    let inflated = Answer {
+        lovelace: Some(500_000_000),
+        block_height: 1_009,
+        ..Answer::honest()
+    };
+    let deposit = "11".repeat(32);
+
+    // The liar answers first, where failover would have taken its word
+    let provider =
+        quorum(2, &[inflated, Answer::honest(), Answer::honest()]).await;
+    let database = synced(&provider).await;
+
+    let indexed = chain_index::unspent_utxos(&database).unwrap();
+    assert_eq!(indexed.len(), 1);
+    assert_eq!(indexed[0].amount[0].quantity, "5000000");
+    assert_eq!(indexed[0].block_height, Some(1_000));
+
+    let utxo = provider
+        .indexed(database.clone())
+        .get_utxo(&deposit, 0)
+        .await
+        .unwrap()
+        .expect("deposit exists");
+    assert_eq!(utxo.amount[0].quantity, "5000000");
+    assert_eq!(utxo.block_height, Some(1_000));
+
+    // With only one honest backend nothing is indexed, and reads through
+    // the index still need the quorum
+    let provider = quorum(2, &[inflated, Answer::honest()]).await;
+    let database = synced(&provider).await;
+    assert!(chain_index::unspent_utxos(&database).unwrap().is_empty());
+    let err = provider
+        .indexed(database)
+        .get_utxo(&deposit, 0)
+        .await
+        .expect_err("no quorum for the deposit");
+    assert_eq!(disagreement(&err).operation, "get_utxo");
+}
@@ -11,7 +11,7 @@ use axum::{
     response::IntoResponse,
     routing::{get, post},
 };
-use mugraph_node::provider::{Provider, TxSettlementState};
+use mugraph_node::provider::{Provider, Ratio, TxSettlementState};
 use serde::Deserialize;
 use serde_json::json;
 use tokio::sync::Mutex;
@@ -453,6 +453,167 @@ async fn spawn_maestro_mock(
     format!("http://{}", addr)
 }
 
+async fn koios_tip(headers: HeaderMap) -> impl IntoResponse {
+    assert_eq!(
+        headers.get("authorization").and_then(|v| v.to_str().ok()),
+        Some("Bearer koios-token")
+    );
+    axum::Json(json!([{
+        "hash": "ef".repeat(32),
+        "epoch_no": 190,
+        "abs_slot": 71284210,
+        "epoch_slot": 55410,
+        "block_no": 2893410,
+        "block_time": 1726110610
+    }]))
+}
+
+async fn koios_protocol_params() -> impl IntoResponse {
+    axum::Json(json!({
+        "txFeePerByte": 44,
+        "txFeeFixed": 155381,
+        "maxTxSize": 16384,
+        "maxValueSize": 5000,
+        "stakeAddressDeposit": 2000000,
+        "stakePoolDeposit": 500000000,
+        "executionUnitPrices": {"priceMemory": 0.0577, "priceSteps": 0.0000721},
+        "maxTxExecutionUnits": {"memory": 14000000, "steps": 10000000000u64},
+        "utxoCostPerByte": 4310
+    }))
+}
+
+async fn koios_submit(headers: HeaderMap, body: Bytes) -> impl IntoResponse {
+    assert_eq!(
+        headers.get("content-type").and_then(|v| v.to_str().ok()),
+        Some("application/cbor")
+    );
+    assert!(!body.is_empty(), "submitted tx body should not be empty");
+    (StatusCode::ACCEPTED, axum::Json(json!("ee".repeat(32))))
+}
+
+fn koios_utxo(tx_hash: &str, tx_index: u16, spent: bool) -> serde_json::Value {
+    json!({
+        "tx_hash": tx_hash,
+        "tx_index": tx_index,
+        "address": "addr_test1koios",
+        "value": "4000000",
+        "stake_address": null,
+        "payment_cred": "11".repeat(28),
+        "epoch_no": 190,
+        "block_height": 2893400,
+        "block_time": 1726110000,
+        "datum_hash": "dd".repeat(32),
+        "inline_datum": if tx_index == 0 {
+            json!({"bytes": "d8799f01ff", "value": {"constructor": 0, "fields": [{"int": 1}]}})
+        } else {
+            serde_json::Value::Null
+        },
+        "reference_script": null,
+        "asset_list": [{
+            "policy_id": "ab".repeat(28),
+            "asset_name": "4d554752",
+            "fingerprint": "asset1xyz",
+            "decimals": 0,
+            "quantity": "7"
+        }],
+        "is_spent": spent
+    })
+}
+
+async fn koios_utxo_info(
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> impl IntoResponse {
+    assert_eq!(body["_extended"], true);
+    let utxos = body["_utxo_refs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|reference| {
+            let (tx_hash, index) =
+                reference.as_str().unwrap().split_once('#').unwrap();
+            match tx_hash.get(..2) {
+                Some("ff") => None,
+                Some("99") => {
+                    Some(koios_utxo(tx_hash, index.parse().unwrap(), true))
+                }
+                _ => Some(koios_utxo(tx_hash, index.parse().unwrap(), false)),
+            }
+        })
+        .collect::<Vec<_>>();
+    axum::Json(serde_json::Value::Array(utxos))
+}
+
+async fn koios_datum_info(
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> impl IntoResponse {
+    assert_eq!(body["_datum_hashes"][0], "dd".repeat(32));
+    axum::Json(json!([{
+        "datum_hash": "dd".repeat(32),
+        "creation_tx_hash": "12".repeat(32),
+        "value": {"constructor": 0, "fields": []},
+        "bytes": "d87980"
+    }]))
+}
+
+#[derive(Deserialize)]
+struct KoiosRange {
+    order: String,
+    offset: usize,
+    limit: usize,
+}
+
+async fn koios_address_utxos(
+    Query(range): Query<KoiosRange>,
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> impl IntoResponse {
+    assert_eq!(body["_addresses"][0], "addr_test1koios");
+    assert_eq!(range.order, "tx_hash.asc,tx_index.asc");
+    let total = 130;
+    let utxos = (range.offset..total.min(range.offset + range.limit))
+        .map(|idx| koios_utxo(&format!("{:064x}", idx), 1, false))
+        .collect::<Vec<_>>();
+    axum::Json(serde_json::Value::Array(utxos))
+}
+
+async fn koios_tx_info(
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let tx_hash = body["_tx_hashes"][0].as_str().unwrap();
+    if tx_hash.starts_with("ff") {
+        return axum::Json(json!([]));
+    }
+    axum::Json(json!([{"tx_hash": tx_hash, "block_height": 2893400}]))
+}
+
+async fn spawn_koios_mock() -> String {
+    let app = Router::new()
+        .route("/tip", get(koios_tip))
+        .route("/cli_protocol_params", get(koios_protocol_params))
+        .route("/submittx", post(koios_submit))
+        .route("/utxo_info", post(koios_utxo_info))
+        .route("/datum_info", post(koios_datum_info))
+        .route("/address_utxos", post(koios_address_utxos))
+        .route("/tx_info", post(koios_tx_info));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+fn koios_provider(url: String) -> Provider {
+    Provider::new(
+        "koios",
+        "koios-token".to_string(),
+        "preprod".to_string(),
+        Some(url),
+    )
+    .expect("provider")
+}
+
 #[tokio::test]
 async fn get_tip_retries_transient_errors_and_recovers() {
     let (url, hits) = spawn_mock(vec![
@@ -818,3 +979,119 @@ async fn observe_tx_status_combines_tip_and_tx_height_consistently() {
     assert_eq!(invalidated.confirmations, 0);
     assert_eq!(invalidated.state, TxSettlementState::Invalidated);
 }
+
+#[tokio::test]
+async fn koios_get_tip_sends_bearer_token_and_parses_response() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let tip = provider.get_tip().await.expect("tip");
+    assert_eq!(tip.slot, 71_284_210);
+    assert_eq!(tip.hash, "ef".repeat(32));
+    assert_eq!(tip.block_height, 2_893_410);
+}
+
+#[tokio::test]
+async fn koios_get_protocol_params_maps_cli_fields() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let params = provider
+        .get_protocol_params()
+        .await
+        .expect("protocol params");
+    assert_eq!(params.min_fee_a, 44);
+    assert_eq!(params.min_fee_b, 155_381);
+    assert_eq!(params.max_tx_ex_steps, 10_000_000_000);
+    assert_eq!(params.coins_per_utxo_byte, 4_310);
+    assert_eq!(params.price_mem, Ratio::new(577, 10_000));
+}
+
+#[tokio::test]
+async fn koios_submit_tx_accepts_202_hash_response() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let response = provider
+        .submit_tx(&[0xde, 0xad, 0xbe, 0xef])
+        .await
+        .expect("submit ok");
+    assert_eq!(response.tx_hash, "ee".repeat(32));
+}
+
+#[tokio::test]
+async fn koios_get_utxo_reads_inline_and_hashed_datums() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let inline = provider
+        .get_utxo(&"11".repeat(32), 0)
+        .await
+        .expect("get utxo")
+        .expect("utxo should exist");
+    assert_eq!(inline.address, "addr_test1koios");
+    assert_eq!(inline.datum.as_deref(), Some("d8799f01ff"));
+    assert_eq!(inline.block_height, Some(2_893_400));
+    assert_eq!(inline.amount[0].unit, "lovelace");
+    assert_eq!(inline.amount[0].quantity, "4000000");
+    assert_eq!(
+        inline.amount[1].unit,
+        format!("{}4d554752", "ab".repeat(28))
+    );
+    assert_eq!(inline.amount[1].quantity, "7");
+
+    let hashed = provider
+        .get_utxo(&"11".repeat(32), 1)
+        .await
+        .expect("get utxo")
+        .expect("utxo should exist");
+    assert_eq!(hashed.datum.as_deref(), Some("d87980"));
+}
+
+#[tokio::test]
+async fn koios_get_utxo_treats_spent_and_unknown_outputs_as_missing() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let spent = provider
+        .get_utxo(&"99".repeat(32), 0)
+        .await
+        .expect("spent lookup should succeed");
+    assert!(spent.is_none());
+
+    let unknown = provider
+        .get_utxo(&"ff".repeat(32), 0)
+        .await
+        .expect("unknown lookup should succeed");
+    assert!(unknown.is_none());
+}
+
+#[tokio::test]
+async fn koios_get_address_utxos_pages_with_offset_and_limit() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let utxos = provider
+        .get_address_utxos("addr_test1koios")
+        .await
+        .expect("address utxos");
+    assert_eq!(utxos.len(), 130);
+    assert_eq!(utxos[0].tx_hash, format!("{:064x}", 0));
+    assert_eq!(utxos[129].tx_hash, format!("{:064x}", 129));
+    assert_eq!(utxos[129].block_height, Some(2_893_400));
+    // Hash-only datums are resolved like a single UTxO lookup resolves them
+    assert!(utxos.iter().all(|u| u.datum.as_deref() == Some("d87980")));
+}
+
+#[tokio::test]
+async fn koios_observe_tx_status_uses_tx_info_block_height() {
+    let provider = koios_provider(spawn_koios_mock().await);
+
+    let confirming = provider
+        .observe_tx_status(&"11".repeat(32), 12, false)
+        .await
+        .expect("observation");
+    assert_eq!(confirming.tx_block_height, Some(2_893_400));
+    assert_eq!(confirming.confirmations, 11);
+    assert_eq!(confirming.state, TxSettlementState::Confirming);
+
+    let rolled_back = provider
+        .observe_tx_status(&"ff".repeat(32), 12, true)
+        .await
+        .expect("observation");
+    assert_eq!(rolled_back.state, TxSettlementState::Invalidated);
+}
@@ -47,6 +47,54 @@ fn test_maestro_provider_creation() {
     }
 }
 
+/// Test Koios provider creation with and without a bearer token
+#[test]
+fn test_koios_provider_creation() {
+    let provider =
+        Provider::new("koios", String::new(), "preview".to_string(), None)
+            .expect("anonymous koios provider");
+
+    match provider {
+        Provider::Koios(p) => {
+            assert_eq!(p.base_url, "https://preview.koios.rest/api/v1");
+            assert!(p.api_key.is_none());
+        }
+        _ => panic!("Expected Koios provider"),
+    }
+
+    let provider = Provider::new(
+        "koios",
+        "token".to_string(),
+        "mainnet".to_string(),
+        None,
+    )
+    .expect("koios provider");
+
+    match provider {
+        Provider::Koios(p) => {
+            assert_eq!(p.base_url, "https://api.koios.rest/api/v1");
+            assert_eq!(p.api_key.as_deref(), Some("token"));
+        }
+        _ => panic!("Expected Koios provider"),
+    }
+}
+
+/// Test Ogmios provider creation needs no API key
+#[test]
+fn test_ogmios_provider_creation() {
+    let provider =
+        Provider::new("ogmios", String::new(), "preprod".to_string(), None)
+            .expect("ogmios provider");
+
+    match provider {
+        Provider::Ogmios(p) => {
+            assert_eq!(p.kupo_url, "http://127.0.0.1:1442");
+            assert_eq!(p.ogmios_url, "ws://127.0.0.1:1337");
+        }
+        _ => panic!("Expected Ogmios provider"),
+    }
+}
+
 /// Test provider with custom URL
 #[test]
 fn test_provider_custom_url() {
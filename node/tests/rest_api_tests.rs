@@ -0,0 +1,352 @@
+use std::{future::Future, path::Path, sync::OnceLock};
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Method, Request as HttpRequest, StatusCode},
+};
+use mugraph_core::types::{ProtocolVersion, SignedNodeInfo};
+use mugraph_node::{
+    config::Config,
+    routes::{OPERATIONS, document, router},
+};
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::util::ServiceExt;
+
+fn env_lock() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+fn test_config() -> Config {
+    Config::test_server()
+}
+
+async fn with_db_path<T, Fut>(path: &Path, f: impl FnOnce() -> Fut) -> T
+where
+    Fut: Future<Output = T>,
+{
+    let _guard = env_lock().lock().await;
+    let previous = std::env::var_os("MUGRAPH_DB_PATH");
+    // SAFETY: tests in this file serialize environment mutation through a
+    // process-wide mutex and hold it across the async startup call.
+    unsafe {
+        std::env::set_var("MUGRAPH_DB_PATH", path);
+    }
+    let result = f().await;
+    match previous {
+        Some(value) => unsafe { std::env::set_var("MUGRAPH_DB_PATH", value) },
+        None => unsafe { std::env::remove_var("MUGRAPH_DB_PATH") },
+    }
+    result
+}
+
+async fn dev_router(dir: &TempDir) -> Router {
+    let db_path = dir.path().join("rest-api.redb");
+    with_db_path(&db_path, || async {
+        let config = test_config();
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await
+}
+
+async fn send(
+    app: &Router,
+    method: Method,
+    path: &str,
+    body: Option<&str>,
+) -> (StatusCode, Vec<u8>) {
+    let mut request = HttpRequest::builder().method(method).uri(path);
+    if body.is_some() {
+        request = request.header("content-type", "application/json");
+    }
+    let request = request
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    // Event streams stay open; their status is all that is needed here
+    if response
+        .headers()
+        .get("content-type")
+        .is_some_and(|value| value.as_bytes().starts_with(b"text/event-stream"))
+    {
+        return (status, Vec::new());
+    }
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, body.to_vec())
+}
+
+fn concrete(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.starts_with('{') {
+            true => "tr-1",
+            false => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn every_documented_operation_is_served() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    for op in OPERATIONS {
+        let path = concrete(op.path);
+        let body = op.request.map(|_| "{}");
+        let (status, body) = send(&app, op.method.clone(), &path, body).await;
+
+        // The router's own 404 has no body; handler 404s are JSON errors
+        assert!(
+            status != StatusCode::METHOD_NOT_ALLOWED
+                && !(status == StatusCode::NOT_FOUND && body.is_empty()),
+            "{} {} is documented but not served ({status})",
+            op.method,
+            op.path,
+        );
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn undocumented_methods_on_documented_paths_are_not_served() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    for op in OPERATIONS {
+        let documented: Vec<&Method> = OPERATIONS
+            .iter()
+            .filter(|other| other.path == op.path)
+            .map(|other| &other.method)
+            .collect();
+
+        for method in [Method::GET, Method::POST, Method::PUT, Method::DELETE] {
+            if documented.contains(&&method) {
+                continue;
+            }
+            let (status, _) =
+                send(&app, method.clone(), &concrete(op.path), None).await;
+            assert_eq!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {} is served but not documented",
+                op.path,
+            );
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn committed_openapi_document_matches_the_generated_one() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../docs/openapi.json");
+    let generated = serde_json::to_string_pretty(&document()).unwrap() + "\n";
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(path, &generated).unwrap();
+    }
+
+    let committed = std::fs::read_to_string(path).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "docs/openapi.json is stale; regenerate it with \
+         `UPDATE_OPENAPI=1 cargo test -p mugraph-node --test rest_api_tests`"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn served_document_lists_every_operation() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    let (status, body) =
+        send(&app, Method::GET, "/v1/openapi.json", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let served: Value = serde_json::from_slice(&body).unwrap();
+
+    for op in OPERATIONS {
+        let method = op.method.as_str().to_ascii_lowercase();
+        assert!(
+            served["paths"][op.path][&method].is_object(),
+            "{method} {} missing from the served document",
+            op.path,
+        );
+    }
+    assert!(served["components"]["schemas"]["Request"].is_object());
+    assert!(served["components"]["schemas"]["ErrorCode"].is_object());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn rest_routes_share_the_rpc_handlers() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    let (status, rest) = send(&app, Method::GET, "/v1/info", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, rpc) =
+        send(&app, Method::POST, "/rpc", Some(r#"{"m":"public_key"}"#)).await;
+    assert_eq!(
+        serde_json::from_slice::<Value>(&rest).unwrap(),
+        serde_json::from_slice::<Value>(&rpc).unwrap(),
+    );
+
+    let withdraw =
+        r#"{"notes":[],"change_outputs":[],"tx_cbor":"","tx_hash":"bb"}"#;
+    let (status, body) =
+        send(&app, Method::PUT, "/v1/withdrawals/aa", Some(withdraw)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["r"]["code"], "INVALID_INPUT");
+
+    let (status, body) =
+        send(&app, Method::POST, "/v1/refresh", Some("{")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["r"]["code"], "MALFORMED_JSON");
+}
+
+async fn send_versioned(
+    app: &Router,
+    version: &str,
+    body: &str,
+) -> (StatusCode, Option<String>, Value) {
+    let request = HttpRequest::post("/rpc")
+        .header("content-type", "application/json")
+        .header("mugraph-protocol-version", version)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let answered = response
+        .headers()
+        .get("mugraph-protocol-version")
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, answered, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn clients_negotiate_their_protocol_version() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+    let info = r#"{"m":"public_key"}"#;
+
+    let (status, answered, body) = send_versioned(&app, "1.0", info).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answered.as_deref(), Some("1.0"));
+    assert_eq!(body["r"]["protocol"]["version"], "1.4");
+    assert_eq!(body["r"]["protocol"]["min_version"], "1.0");
+    assert!(
+        body["r"]["protocol"]["features"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("batch"))
+    );
+
+    // Newer minors are answered at the node's version
+    let (status, answered, _) = send_versioned(&app, "1.9", info).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answered.as_deref(), Some("1.4"));
+
+    for version in ["2.0", "0.9", "latest"] {
+        let (status, answered, body) =
+            send_versioned(&app, version, info).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{version}");
+        assert_eq!(answered, None);
+        assert_eq!(body["r"]["code"], "UNSUPPORTED_VERSION");
+    }
+
+    // A 1.0 client cannot send features it does not know about
+    let batch = r#"{"m":"batch","p":{"requests":[{"m":"public_key"}]}}"#;
+    let (status, _, body) = send_versioned(&app, "1.0", batch).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["r"]["code"], "UNSUPPORTED_VERSION");
+    let (status, _, body) = send_versioned(&app, "1.1", batch).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["m"], "batch");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn unversioned_legacy_requests_are_still_served() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    // A 1.0 refresh payload as old wallets send it, with no version header
+    let (status, body) = send(
+        &app,
+        Method::POST,
+        "/rpc",
+        Some(r#"{"m":"refresh","p":{"m":0,"a":[],"a_":[],"s":[]}}"#),
+    )
+    .await;
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_ne!(body["r"]["code"], "UNSUPPORTED_VERSION", "{status}");
+    assert_ne!(body["r"]["code"], "MALFORMED_JSON", "{status}");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn well_known_node_info_is_signed_and_cacheable() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+
+    let request = HttpRequest::get("/.well-known/mugraph-node.json")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["cache-control"], "public, max-age=3600");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let info: SignedNodeInfo = serde_json::from_slice(&body).unwrap();
+    let delegate = test_config().keypair().unwrap().public_key;
+    let issued_at = info.document().unwrap().issued_at;
+    let document = info.verify(&delegate, issued_at).unwrap();
+    assert_eq!(document.protocol.version, ProtocolVersion::CURRENT);
+
+    // The RPC method carries the same document inside the envelope
+    let (status, body) =
+        send(&app, Method::POST, "/rpc", Some(r#"{"m":"node_info"}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["m"], "node_info");
+    let signed: SignedNodeInfo =
+        serde_json::from_value(body["r"].clone()).unwrap();
+    let document = signed.verify(&delegate, issued_at).unwrap();
+    assert_eq!(document.delegate_pk, delegate);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn withdrawal_status_reports_unknown_withdrawals() {
+    let dir = TempDir::new().unwrap();
+    let app = dev_router(&dir).await;
+    let tx_hash = "ab".repeat(32);
+
+    let (status, body) = send(
+        &app,
+        Method::GET,
+        &format!("/v1/withdrawals/{tx_hash}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["r"]["code"], "WITHDRAWAL_NOT_FOUND");
+
+    let (status, body) =
+        send(&app, Method::GET, "/v1/withdrawals/zz", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["r"]["code"], "INVALID_INPUT");
+
+    // Status queries arrived in 1.3
+    let query =
+        format!(r#"{{"m":"withdrawal_status","p":{{"tx_hash":"{tx_hash}"}}}}"#);
+    let (status, _, body) = send_versioned(&app, "1.2", &query).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["r"]["code"], "UNSUPPORTED_VERSION");
+    let (status, _, body) = send_versioned(&app, "1.3", &query).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["r"]["code"], "WITHDRAWAL_NOT_FOUND");
+}
@@ -4,7 +4,12 @@ use std::{
     sync::OnceLock,
 };
 
-use mugraph_node::{config::Config, database::Database, routes::router};
+use mugraph_node::{
+    config::Config,
+    database::{CURRENT_SCHEMA_VERSION, Database},
+    routes::router,
+    serve,
+};
 use tempfile::TempDir;
 
 fn env_lock() -> &'static tokio::sync::Mutex<()> {
@@ -13,25 +18,17 @@ fn env_lock() -> &'static tokio::sync::Mutex<()> {
 }
 
 fn test_config(dev_mode: bool, peer_registry_file: Option<String>) -> Config {
-    Config::Server {
-        addr: "127.0.0.1:9999".parse().unwrap(),
-        seed: Some(42),
-        secret_key: None,
-        cardano_network: "preprod".to_string(),
-        cardano_provider: "blockfrost".to_string(),
-        cardano_api_key: None,
-        cardano_provider_url: None,
-        cardano_payment_sk: None,
-        xnode_peer_registry_file: peer_registry_file,
-        xnode_node_id: "node://local".to_string(),
-        deposit_confirm_depth: 15,
-        deposit_expiration_blocks: 1440,
-        min_deposit_value: Some(1_000_000),
-        max_tx_size: 16_384,
-        max_withdrawal_fee: 2_000_000,
-        fee_tolerance_pct: 5,
-        dev_mode,
+    let mut config = Config::test_server();
+    if let Config::Server {
+        xnode_peer_registry_file,
+        dev_mode: config_dev_mode,
+        ..
+    } = &mut config
+    {
+        *xnode_peer_registry_file = peer_registry_file;
+        *config_dev_mode = dev_mode;
     }
+    config
 }
 
 async fn with_db_path<T, Fut>(path: &Path, f: impl FnOnce() -> Fut) -> T
@@ -73,7 +70,7 @@ async fn router_starts_in_dev_mode_and_creates_migrated_database() {
     );
 
     let reopened = Database::setup(PathBuf::from(&db_path)).unwrap();
-    assert_eq!(reopened.schema_version().unwrap(), 3);
+    assert_eq!(reopened.schema_version().unwrap(), CURRENT_SCHEMA_VERSION);
 }
 
 #[tokio::test(flavor = "current_thread")]
@@ -95,3 +92,341 @@ async fn router_rejects_missing_peer_registry_at_startup() {
         "unexpected startup error: {err}"
     );
 }
+
+async fn get_metrics(app: axum::Router) -> (axum::http::StatusCode, String) {
+    use tower::util::ServiceExt;
+
+    let response = app
+        .oneshot(
+            axum::http::Request::get("/metrics")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn router_serves_prometheus_metrics_with_rpc_labels() {
+    use tower::util::ServiceExt;
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-metrics.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let config = test_config(true, None);
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::post("/rpc")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(r#"{"m":"public_key"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let (status, body) = get_metrics(app).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    assert!(
+        body.contains(
+            "mugraph_node_rpc_requests_total{method=\"public_key\",outcome=\"ok\"}"
+        ),
+        "{body}"
+    );
+    assert!(
+        body.contains(
+            "mugraph_node_rpc_duration_seconds_bucket{method=\"public_key\""
+        ),
+        "{body}"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn router_rejects_oversized_and_rate_limited_rpc_requests() {
+    use tower::util::ServiceExt;
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-limits.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let mut config = test_config(true, None);
+        if let Config::Server {
+            rpc_max_body_bytes,
+            rpc_method_rate_limits,
+            ..
+        } = &mut config
+        {
+            *rpc_max_body_bytes = 64;
+            *rpc_method_rate_limits = vec!["public_key=1".parse().unwrap()];
+        }
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let post = |body: String| {
+        app.clone().oneshot(
+            axum::http::Request::post("/rpc")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(body))
+                .unwrap(),
+        )
+    };
+
+    let oversized =
+        format!(r#"{{"m":"public_key","pad":"{}"}}"#, "x".repeat(64));
+    let response = post(oversized).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Burst of twice the configured rate, then throttled
+    for _ in 0..2 {
+        let response = post(r#"{"m":"public_key"}"#.to_string()).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+    let response = post(r#"{"m":"public_key"}"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "1");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(
+        error["r"]["reason"]
+            .as_str()
+            .unwrap()
+            .contains("Rate limited (method)"),
+        "{error}"
+    );
+    assert_eq!(error["r"]["code"], "RATE_LIMITED");
+    assert_eq!(error["r"]["retryable"], true);
+    assert_eq!(error["r"]["details"]["scope"], "method");
+
+    let (_, metrics) = get_metrics(app).await;
+    assert!(
+        metrics.contains(
+            "mugraph_node_rpc_rejections_total{method=\"public_key\",reason=\"method\"} 1"
+        ),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains(
+            "mugraph_node_rpc_method_rate_limit{method=\"public_key\"} 1"
+        ),
+        "{metrics}"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn router_leaves_metrics_to_admin_listener_when_configured() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-metrics-admin.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let mut config = test_config(true, None);
+        if let Config::Server { metrics_addr, .. } = &mut config {
+            *metrics_addr = Some("127.0.0.1:0".parse().unwrap());
+        }
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let (status, _) = get_metrics(app).await;
+    assert_eq!(status, axum::http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn dev_mode_router_is_live_and_ready_without_chain_checks() {
+    use tower::util::ServiceExt;
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-health.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let config = test_config(true, None);
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    for path in ["/livez", "/readyz"] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::get(path)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK, "{path}");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["status"], "ok", "{path}: {report}");
+        assert_eq!(report["checks"]["database"]["status"], "ok");
+        if path == "/readyz" {
+            assert_eq!(
+                report["checks"]["schema"]["schema_version"],
+                CURRENT_SCHEMA_VERSION
+            );
+            assert_eq!(report["checks"]["wallet"]["status"], "skipped");
+            assert_eq!(report["checks"]["provider"]["status"], "skipped");
+        }
+    }
+}
+
+fn emulator_config(dev_mode: bool, ledger: &str) -> Config {
+    let mut config = test_config(dev_mode, None);
+    if let Config::Server {
+        cardano_provider,
+        cardano_provider_url,
+        cardano_emulator_block_secs,
+        ..
+    } = &mut config
+    {
+        *cardano_provider = "emulator".to_string();
+        *cardano_provider_url = Some(ledger.to_string());
+        *cardano_emulator_block_secs = 0;
+    }
+    config
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn emulator_provider_is_refused_outside_dev_mode() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-emulator-prod.redb");
+
+    let err = with_db_path(&db_path, || async {
+        let config = emulator_config(false, "emulator://router-prod");
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap_err()
+    })
+    .await;
+
+    assert!(err.to_string().contains("dev mode"), "{err}");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn dev_mode_with_the_emulator_sets_up_the_wallet_and_checks_the_chain() {
+    use tower::util::ServiceExt;
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-emulator.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let config = emulator_config(true, "emulator://router-dev");
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let response = app
+        .oneshot(
+            axum::http::Request::get("/readyz")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["checks"]["wallet"]["status"], "ok", "{report}");
+    assert_eq!(report["checks"]["provider"]["status"], "ok", "{report}");
+    assert_ne!(report["checks"]["deposit_monitor"]["status"], "skipped");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_drains_requests_and_flushes_database_on_shutdown() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-shutdown.redb");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/readyz", listener.local_addr().unwrap());
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+
+    let served = with_db_path(&db_path, || async {
+        let config = test_config(true, None);
+        let keypair = config.keypair().unwrap();
+        let server = tokio::spawn(serve(listener, config, keypair, async {
+            let _ = stop_rx.await;
+        }));
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let report: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(report["checks"]["tasks"]["status"], "ok");
+
+        stop_tx.send(()).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(10), server)
+            .await
+            .expect("serve should return after the shutdown signal")
+            .unwrap()
+    })
+    .await;
+
+    served.expect("graceful shutdown should succeed");
+    // The database lock is released and its contents are intact
+    let reopened = Database::setup(PathBuf::from(&db_path)).unwrap();
+    assert_eq!(reopened.schema_version().unwrap(), CURRENT_SCHEMA_VERSION);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn rpc_errors_carry_a_code_and_matching_http_status() {
+    use tower::util::ServiceExt;
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("router-error-codes.redb");
+
+    let app = with_db_path(&db_path, || async {
+        let mut config = test_config(false, None);
+        if let Config::Server {
+            cardano_api_key, ..
+        } = &mut config
+        {
+            *cardano_api_key = Some("test-project".to_string());
+        }
+        let keypair = config.keypair().unwrap();
+        router(config, keypair).await.unwrap()
+    })
+    .await;
+
+    let emit = r#"{"m":"emit","p":{"policy_id":"00000000000000000000000000000000000000000000000000000000","asset_name":"","amount":1}}"#;
+    let response = app
+        .oneshot(
+            axum::http::Request::post("/rpc")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(emit))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["m"], "error");
+    assert_eq!(error["r"]["code"], "METHOD_DISABLED");
+    assert_eq!(error["r"]["retryable"], false);
+    assert!(
+        error["r"]["reason"].as_str().unwrap().contains("dev mode"),
+        "{error}"
+    );
+}
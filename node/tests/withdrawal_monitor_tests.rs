@@ -0,0 +1,474 @@
+//! Withdrawal monitor settling withdrawals against an emulated ledger
+
+use std::sync::Arc;
+
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use mugraph_core::{
+    crypto,
+    types::{
+        BlindSignature, Blinded, DepositRecord, Keypair, SecretKey, UtxoRef,
+        WithdrawalKey, WithdrawalRecord, WithdrawalStatus,
+    },
+};
+use mugraph_node::{
+    database::{DEPOSITS, Database, WITHDRAWAL_SUBMISSIONS, WITHDRAWALS},
+    provider::{AssetAmount, EmulatorProvider, Provider},
+    withdrawal_monitor::{
+        WithdrawalMonitor, WithdrawalMonitorConfig, WithdrawalSubmission,
+        load_withdrawal,
+    },
+};
+use rand::{SeedableRng, rngs::StdRng};
+use whisky_csl::csl;
+
+fn key(seed: u8) -> csl::PrivateKey {
+    csl::PrivateKey::from_normal_bytes(&[seed; 32]).unwrap()
+}
+
+fn address(key: &csl::PrivateKey) -> String {
+    let credential = csl::Credential::from_keyhash(&key.to_public().hash());
+    csl::EnterpriseAddress::new(0, &credential)
+        .to_address()
+        .to_bech32(None)
+        .unwrap()
+}
+
+fn lovelace(quantity: u64) -> Vec<AssetAmount> {
+    vec![AssetAmount {
+        unit: "lovelace".to_string(),
+        quantity: quantity.to_string(),
+    }]
+}
+
+/// Spend all of `input` to a fresh address, less a 0.2 ADA fee, signed by
+/// `signer` and valid before slot `ttl` if given
+fn spend(
+    input: &(String, u16),
+    amount: u64,
+    signer: &csl::PrivateKey,
+    ttl: Option<u64>,
+) -> Vec<u8> {
+    let mut inputs = csl::TransactionInputs::new();
+    inputs.add(&csl::TransactionInput::new(
+        &csl::TransactionHash::from_hex(&input.0).unwrap(),
+        input.1 as u32,
+    ));
+    let mut outputs = csl::TransactionOutputs::new();
+    outputs.add(&csl::TransactionOutput::new(
+        &csl::Address::from_bech32(&address(&key(9))).unwrap(),
+        &csl::Value::new(&csl::BigNum::from(amount - 200_000)),
+    ));
+    let mut body = csl::TransactionBody::new_tx_body(
+        &inputs,
+        &outputs,
+        &csl::BigNum::from(200_000u64),
+    );
+    if let Some(ttl) = ttl {
+        body.set_ttl(&csl::BigNum::from(ttl));
+    }
+
+    let hash = csl::TransactionHash::from_bytes(
+        Blake2b::<U32>::digest(body.to_bytes()).to_vec(),
+    )
+    .unwrap();
+    let mut vkeys = csl::Vkeywitnesses::new();
+    vkeys.add(&csl::make_vkey_witness(&hash, signer));
+    let mut witnesses = csl::TransactionWitnessSet::new();
+    witnesses.set_vkeys(&vkeys);
+    csl::Transaction::new(&body, &witnesses, None).to_bytes()
+}
+
+fn tx_hash(tx: &[u8]) -> String {
+    let tx = csl::Transaction::from_bytes(tx.to_vec()).unwrap();
+    hex::encode(Blake2b::<U32>::digest(tx.body().to_bytes()))
+}
+
+fn database() -> Arc<Database> {
+    let dir = tempfile::tempdir().unwrap().keep();
+    let database = Arc::new(Database::setup(dir.join("node.db")).unwrap());
+    database.migrate().unwrap();
+    database
+}
+
+fn withdrawal_key(tx_hash: &str) -> WithdrawalKey {
+    WithdrawalKey::new(0, hex::decode(tx_hash).unwrap().try_into().unwrap())
+}
+
+fn node_keypair() -> Keypair {
+    let secret_key = SecretKey::from([7u8; 32]);
+    Keypair {
+        public_key: secret_key.public(),
+        secret_key,
+    }
+}
+
+fn refund_outputs(count: u64) -> Vec<BlindSignature> {
+    (0..count)
+        .map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let blind = crypto::blind(&mut rng, b"refund");
+            BlindSignature {
+                signature: Blinded(blind.point.into()),
+                proof: Default::default(),
+            }
+        })
+        .collect()
+}
+
+/// Record the withdrawal of `tx` as a crashed or failed request leaves it,
+/// spending a tracked deposit
+fn record(
+    database: &Database,
+    tx: &[u8],
+    status: WithdrawalStatus,
+    timestamp: u64,
+    refund_outputs: Vec<BlindSignature>,
+) -> UtxoRef {
+    let deposit = UtxoRef::new([0xd0; 32], 0);
+    let submission = WithdrawalSubmission::new(tx, vec![deposit.clone()])
+        .with_refund_outputs(refund_outputs);
+    let key = withdrawal_key(&tx_hash(tx));
+
+    let w = database.write().unwrap();
+    {
+        let mut deposits = w.open_table(DEPOSITS).unwrap();
+        deposits
+            .insert(&deposit, DepositRecord::new(1, 0, u64::MAX))
+            .unwrap();
+        let mut withdrawals = w.open_table(WITHDRAWALS).unwrap();
+        withdrawals
+            .insert(&key, WithdrawalRecord { status, timestamp })
+            .unwrap();
+        let mut submissions = w.open_table(WITHDRAWAL_SUBMISSIONS).unwrap();
+        submissions
+            .insert(&key, submission.to_bytes().unwrap().as_slice())
+            .unwrap();
+    }
+    w.commit().unwrap();
+    deposit
+}
+
+fn monitor(
+    database: &Arc<Database>,
+    ledger: &EmulatorProvider,
+) -> WithdrawalMonitor {
+    WithdrawalMonitor::new(
+        WithdrawalMonitorConfig::default(),
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        node_keypair(),
+    )
+}
+
+fn state(
+    database: &Database,
+    tx: &[u8],
+) -> (WithdrawalRecord, WithdrawalSubmission) {
+    let (record, submission) =
+        load_withdrawal(database, &withdrawal_key(&tx_hash(tx)))
+            .unwrap()
+            .expect("recorded");
+    (record, submission.expect("submission kept"))
+}
+
+fn deposit_spent(database: &Database, deposit: &UtxoRef) -> bool {
+    let r = database.read().unwrap();
+    let deposits = r.open_table(DEPOSITS).unwrap();
+    deposits.get(deposit).unwrap().unwrap().value().spent
+}
+
+#[tokio::test]
+async fn crashed_withdrawals_are_resubmitted_then_completed() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-resubmit",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    let deposit = record(&database, &tx, WithdrawalStatus::Pending, 0, vec![]);
+    let monitor = monitor(&database, &ledger);
+
+    // Never submitted: the monitor sends it and waits for a block
+    monitor.reconcile().await.unwrap();
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Pending);
+    assert_eq!(submission.resubmissions, 1);
+    assert!(!deposit_spent(&database, &deposit));
+
+    ledger.advance(1);
+    let later = WithdrawalMonitor::new(
+        WithdrawalMonitorConfig {
+            pending_grace: 0,
+            ..Default::default()
+        },
+        database.clone(),
+        Provider::Emulator(ledger.clone()),
+        node_keypair(),
+    );
+    later.reconcile().await.unwrap();
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Completed);
+    assert_eq!(submission.resubmissions, 1);
+    assert!(deposit_spent(&database, &deposit));
+}
+
+#[tokio::test]
+async fn failed_withdrawals_that_landed_are_completed() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-landed",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    // The submit timed out, but the relay had taken the transaction
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    Provider::Emulator(ledger.clone())
+        .submit_tx(&tx)
+        .await
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    let deposit = record(&database, &tx, WithdrawalStatus::Failed, 0, vec![]);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Completed);
+    assert_eq!(submission.resubmissions, 0);
+    assert!(deposit_spent(&database, &deposit));
+}
+
+#[tokio::test]
+async fn expired_withdrawals_are_not_resubmitted() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-expired",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    let tip = ledger.advance(2);
+
+    let database = database();
+    let tx = spend(&funded, 5_000_000, &owner, Some(tip.slot));
+    let deposit = record(&database, &tx, WithdrawalStatus::Pending, 0, vec![]);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Failed);
+    assert_eq!(submission.invalid_hereafter, Some(tip.slot));
+    assert_eq!(submission.resubmissions, 0);
+    assert!(!deposit_spent(&database, &deposit));
+
+    // Failed and expired withdrawals stay as they are
+    monitor(&database, &ledger).reconcile().await.unwrap();
+    assert_eq!(state(&database, &tx).1.resubmissions, 0);
+}
+
+#[tokio::test]
+async fn fresh_pending_withdrawals_are_left_to_their_request() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-fresh",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    record(&database, &tx, WithdrawalStatus::Pending, now, vec![]);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Pending);
+    assert_eq!(submission.resubmissions, 0);
+}
+
+#[tokio::test]
+async fn expired_withdrawals_with_refund_outputs_are_refunded() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-refund-expired",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    let tip = ledger.advance(2);
+
+    let database = database();
+    let tx = spend(&funded, 5_000_000, &owner, Some(tip.slot));
+    let outputs = refund_outputs(2);
+    let deposit =
+        record(&database, &tx, WithdrawalStatus::Failed, 0, outputs.clone());
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    // Nothing is signed until the expiry is buried
+    let (record, waiting) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Failed);
+    assert!(waiting.refund_notes.is_empty());
+    assert_eq!(waiting.unlandable_since, Some(ledger.tip().block_height));
+
+    ledger.advance(WithdrawalMonitorConfig::default().confirm_depth);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Refunded);
+    assert_eq!(submission.resubmissions, 0);
+    assert!(!deposit_spent(&database, &deposit));
+    assert_eq!(submission.refund_notes.len(), outputs.len());
+    let public_key = node_keypair().public_key;
+    for (output, note) in outputs.iter().zip(&submission.refund_notes) {
+        assert!(
+            crypto::verify_dleq_signature(
+                &public_key,
+                &output.signature.0.to_point().unwrap(),
+                &note.signature,
+                &note.proof,
+            )
+            .unwrap()
+        );
+    }
+
+    // Refunds are final: later passes neither re-sign nor resubmit
+    monitor(&database, &ledger).reconcile().await.unwrap();
+    let (record, again) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Refunded);
+    assert_eq!(again, submission);
+}
+
+#[tokio::test]
+async fn withdrawals_whose_inputs_were_spent_elsewhere_are_refunded() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-refund-conflict",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    // Another transaction spends the withdrawal's input first
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    let conflicting = spend(&funded, 5_000_000, &owner, Some(1_000_000));
+    Provider::Emulator(ledger.clone())
+        .submit_tx(&conflicting)
+        .await
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    record(
+        &database,
+        &tx,
+        WithdrawalStatus::Pending,
+        0,
+        refund_outputs(1),
+    );
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, waiting) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Pending);
+    assert!(waiting.refund_notes.is_empty());
+
+    ledger.advance(WithdrawalMonitorConfig::default().confirm_depth);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Refunded);
+    assert_eq!(submission.resubmissions, 0);
+    assert_eq!(submission.refund_notes.len(), 1);
+}
+
+#[tokio::test]
+async fn withdrawals_whose_inputs_were_spent_elsewhere_without_refunds_fail() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-conflict",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    let conflicting = spend(&funded, 5_000_000, &owner, Some(1_000_000));
+    Provider::Emulator(ledger.clone())
+        .submit_tx(&conflicting)
+        .await
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    record(&database, &tx, WithdrawalStatus::Pending, 0, vec![]);
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Failed);
+    assert_eq!(submission.resubmissions, 0);
+    assert!(submission.refund_notes.is_empty());
+}
+
+#[tokio::test]
+async fn refunds_wait_out_rollbacks_of_the_conflicting_spend() {
+    let ledger = EmulatorProvider::shared(
+        "emulator://withdrawal-refund-rollback",
+        "preprod".to_string(),
+    );
+    let owner = key(1);
+    let funded = ledger
+        .fund(&address(&owner), lovelace(5_000_000), None)
+        .unwrap();
+    ledger.advance(1);
+
+    let tx = spend(&funded, 5_000_000, &owner, None);
+    let conflicting = spend(&funded, 5_000_000, &owner, Some(1_000_000));
+    Provider::Emulator(ledger.clone())
+        .submit_tx(&conflicting)
+        .await
+        .unwrap();
+    ledger.advance(1);
+
+    let database = database();
+    record(
+        &database,
+        &tx,
+        WithdrawalStatus::Pending,
+        0,
+        refund_outputs(1),
+    );
+    monitor(&database, &ledger).reconcile().await.unwrap();
+    let (_, waiting) = state(&database, &tx);
+    assert!(waiting.unlandable_since.is_some());
+
+    // The conflicting spend is rolled back before it settles, so the
+    // withdrawal is sent again rather than refunded
+    ledger.rollback(1).unwrap();
+    monitor(&database, &ledger).reconcile().await.unwrap();
+
+    let (record, submission) = state(&database, &tx);
+    assert_eq!(record.status, WithdrawalStatus::Pending);
+    assert_eq!(submission.resubmissions, 1);
+    assert_eq!(submission.unlandable_since, None);
+    assert!(submission.refund_notes.is_empty());
+}
@@ -10,6 +10,7 @@ fn withdraw_request_serde_roundtrip_preserves_change_outputs() {
             BlindSignature::default(),
             BlindSignature::default(),
         ],
+        refund_outputs: vec![BlindSignature::default()],
         tx_cbor: "abcdef".to_string(),
         tx_hash: "hash123".to_string(),
     };
@@ -21,4 +22,14 @@ fn withdraw_request_serde_roundtrip_preserves_change_outputs() {
     assert_eq!(decoded.tx_cbor, request.tx_cbor);
     assert_eq!(decoded.notes.len(), request.notes.len());
     assert_eq!(decoded.change_outputs.len(), request.change_outputs.len());
+    assert_eq!(decoded.refund_outputs.len(), request.refund_outputs.len());
+}
+
+#[test]
+fn withdraw_requests_without_refund_outputs_still_decode() {
+    let json = r#"{"notes":[],"change_outputs":[],"tx_cbor":"","tx_hash":""}"#;
+    let decoded: WithdrawRequest = serde_json::from_str(json).unwrap();
+
+    assert!(decoded.refund_outputs.is_empty());
+    assert!(!serde_json::to_string(&decoded).unwrap().contains("refund"));
 }
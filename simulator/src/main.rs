@@ -1,5 +1,4 @@
 mod assets;
-mod client;
 mod simulation;
 mod types;
 mod ui;
@@ -8,13 +7,13 @@ use std::time::Duration;
 
 use clap::Parser;
 use color_eyre::eyre::{Result, WrapErr};
+use mugraph_client::Client;
 use rand::{SeedableRng, rngs::StdRng};
 use tokio::sync::{mpsc, watch};
 use tracing::{error, info};
 
 use crate::{
     assets::generate_assets,
-    client::NodeClient,
     simulation::{bootstrap_wallets, simulation_owner_loop},
     types::{AppState, Args, SimChannels, SimCommand, SimConfig, SimNode},
     ui::ui_loop,
@@ -36,13 +35,13 @@ async fn main() -> Result<()> {
 
     let mut nodes = Vec::new();
     for url in &args.node_urls {
-        let client = NodeClient::new(url)?;
+        let client = Client::new(url.clone())?;
         client
             .health()
             .await
             .wrap_err_with(|| format!("health check failed for {url}"))?;
         let delegate_pk = client
-            .public_key()
+            .delegate()
             .await
             .wrap_err_with(|| format!("fetch public key from {url}"))?;
         info!("connected to node {url} (delegate pk {delegate_pk})");
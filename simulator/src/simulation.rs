@@ -514,6 +514,7 @@ fn handle_cross_node_completion(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_simulation_tick(
     nodes: &[SimNode],
     state: &mut AppState,
@@ -4,12 +4,10 @@ use std::{
 };
 
 use clap::Parser;
+use mugraph_client::{Client, Url};
 use mugraph_core::types::{
     Asset, BlindSignature, Note, PolicyId, PublicKey, Refresh,
 };
-use reqwest::Url;
-
-use crate::client::NodeClient;
 
 #[derive(Debug, Parser)]
 pub struct Args {
@@ -54,7 +52,7 @@ pub struct Wallet {
 
 #[derive(Clone)]
 pub struct SimNode {
-    pub client: NodeClient,
+    pub client: Client,
     pub delegate_pk: PublicKey,
 }
 